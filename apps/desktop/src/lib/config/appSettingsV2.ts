@@ -144,6 +144,8 @@ export type AppSettings = {
 	fetch: Fetch;
 	/** Settings related to Claude Code */
 	claude: Claude;
+	/** Settings related to the MCP server */
+	mcp: Mcp;
 };
 
 export type TelemetrySettings = {
@@ -191,3 +193,8 @@ export type Claude = {
 	/** Whether to use the configured model in .claude/settings.json instead of passing --model. */
 	useConfiguredModel: boolean;
 };
+
+export type Mcp = {
+	/** The tools that rewrite history or discard work which each MCP client may call, by client name. */
+	allowedDestructiveTools: Record<string, string[]>;
+};
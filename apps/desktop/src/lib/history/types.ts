@@ -26,7 +26,9 @@ export type Operation =
 	| 'UpdateCommitMessage'
 	| 'MoveCommit'
 	| 'RestoreFromSnapshot'
+	| 'RestoreStackFromSnapshot'
 	| 'ReorderCommit'
+	| 'RebaseStack'
 	| 'InsertBlankCommit'
 	| 'MoveCommitFile'
 	| 'FileChanges'
@@ -39,7 +41,8 @@ export type Operation =
 	| 'UpdateDependentBranchPrNumber'
 	| 'AutoHandleChangesBefore'
 	| 'AutoHandleChangesAfter'
-	| 'SplitBranch';
+	| 'SplitBranch'
+	| 'ApplyWorkspaceRule';
 
 export class Trailer {
 	key!: string;
@@ -6,6 +6,7 @@ import type { DiffSpec } from '$lib/hunks/hunk';
 export type HookStatus =
 	| {
 			status: 'success';
+			output?: string;
 	  }
 	| {
 			status: 'notconfigured';
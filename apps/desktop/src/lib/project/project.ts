@@ -30,8 +30,15 @@ export type Project = {
 	// Produced just for the frontend to determine if the project is open in any window.
 	is_open: boolean;
 	forge_override: ForgeName | undefined;
+	llm_provider: LlmProviderSettings | undefined;
 };
 
+export type LlmProviderSettings =
+	| { kind: 'openAi' }
+	| { kind: 'openAiCompatible'; baseUrl: string; model: string; apiKeyEnv?: string }
+	| { kind: 'anthropic'; model?: string }
+	| { kind: 'mock'; responses: unknown[] };
+
 export function vscodePath(path: string) {
 	return path.includes('\\') ? '/' + path.replace('\\', '/') : path;
 }
@@ -26,8 +26,22 @@ export interface WorkspaceRule {
 	filters: RuleFilter[];
 	/** The action determines what happens to the files or changes that matched the filters. */
 	action: RuleAction;
+	/**
+	 * Where the rule is defined. Repository rules are read from `.gitbutler/rules.toml`
+	 * and can only be changed by editing that file.
+	 */
+	source?: RuleSource;
 }
 
+/**
+ * Where a rule is defined.
+ */
+export type RuleSource =
+	/** Created by the user and stored in the per-user database. */
+	| 'personal'
+	/** Checked into the repository. */
+	| 'repository';
+
 export type AiRule = WorkspaceRule & {
 	trigger: 'claudeCodeHook';
 	action: {
@@ -52,7 +66,13 @@ export type Trigger =
 	/** When a file is added, removed or modified in the Git worktree. */
 	| 'fileSytemChange'
 	/** Whenever a Claude Code hook is invoked. */
-	| 'claudeCodeHook';
+	| 'claudeCodeHook'
+	/** When a commit was created, be it by GitButler or by Git directly. */
+	| 'commitCreated'
+	/** When remote tracking branches may have moved, which happens after every fetch. */
+	| 'upstreamChange'
+	/** Right before a branch is pushed. */
+	| 'prePush';
 
 /**
  * A filter is a condition that determines what files or changes the rule applies to.
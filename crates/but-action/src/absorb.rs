@@ -6,9 +6,9 @@ use but_tools::{
 use but_workspace::StackId;
 use gitbutler_command_context::CommandContext;
 use gitbutler_oxidize::ObjectIdExt;
-use gix::hashtable::hash_map::HashMap;
+use gix::{bstr::BString, hashtable::hash_map::HashMap};
 
-use crate::OpenAiProvider;
+use crate::llm::LlmProvider;
 
 /// Absorb file changes into existing commits in the project.
 ///
@@ -29,7 +29,7 @@ use crate::OpenAiProvider;
 pub fn absorb(
     emitter: std::sync::Arc<Emitter>,
     ctx: &mut CommandContext,
-    openai: &OpenAiProvider,
+    llm: &dyn LlmProvider,
     changes: Vec<but_core::TreeChange>,
 ) -> anyhow::Result<()> {
     let repo = ctx.gix_repo()?;
@@ -101,17 +101,24 @@ pub fn absorb(
     ");
 
     // Now we trigger the tool calling loop to absorb the remaining changes.
-    crate::openai::tool_calling_loop(
-        openai,
-        system_message,
-        vec![prompt.into()],
-        &mut toolset,
-        None,
-    )?;
+    crate::openai::tool_calling_loop(llm, system_message, vec![prompt.into()], &mut toolset, None)?;
 
     Ok(())
 }
 
+/// Absorb the changes to `paths` that are locked to exactly one commit into that commit.
+///
+/// As opposed to [`absorb()`], this works offline and leaves all other changes untouched.
+pub fn absorb_locked(
+    emitter: std::sync::Arc<Emitter>,
+    ctx: &mut CommandContext,
+    paths: Vec<BString>,
+) -> anyhow::Result<()> {
+    let repo = ctx.gix_repo()?;
+    let project_status = but_tools::workspace::get_project_status(ctx, &repo, Some(paths))?;
+    absorb_locked_changes(emitter, ctx, &project_status).context("Failed to absorb locked changes")
+}
+
 struct AbsorbGroup {
     commit_id: gix::ObjectId,
     stack_id: StackId,
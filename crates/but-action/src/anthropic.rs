@@ -0,0 +1,332 @@
+use anyhow::{Context, Result, bail};
+use futures::future::BoxFuture;
+use gitbutler_secret::{Sensitive, secret};
+use serde_json::{Value, json};
+
+use crate::{
+    ChatMessage, ToolCallContent,
+    llm::{ChatRequest, ChatResponse, LlmProvider},
+};
+
+const API_URL: &str = "https://api.anthropic.com/v1/messages";
+const API_VERSION: &str = "2023-06-01";
+const DEFAULT_MODEL: &str = "claude-sonnet-4-0";
+const MAX_TOKENS: u32 = 8192;
+/// The tool the model is forced to call when the response must conform to a schema,
+/// as the Messages API has no other way to request structured output.
+const STRUCTURED_OUTPUT_TOOL: &str = "structured_response";
+
+/// Anthropic's Messages API, with the key configured in the app or read from `ANTHROPIC_API_KEY`.
+///
+/// Responses aren't streamed, so `on_token` receives the whole text at once.
+#[derive(Debug, Clone)]
+pub struct AnthropicProvider {
+    api_key: Sensitive<String>,
+    model: String,
+}
+
+impl AnthropicProvider {
+    pub fn new(model: Option<String>) -> Result<Self> {
+        let api_key = match secret::retrieve("aiAnthropicKey", secret::Namespace::Global)? {
+            Some(key) => key,
+            None => Sensitive(std::env::var("ANTHROPIC_API_KEY").context(
+                "No Anthropic key configured. Add it through the GitButler settings or set ANTHROPIC_API_KEY",
+            )?),
+        };
+        Ok(AnthropicProvider {
+            api_key,
+            model: model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+        })
+    }
+
+    fn request_body(&self, request: &ChatRequest) -> Value {
+        let mut tools: Vec<Value> = request
+            .tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "input_schema": tool.parameters,
+                })
+            })
+            .collect();
+        let mut body = json!({
+            "model": self.model,
+            "max_tokens": MAX_TOKENS,
+            "system": request.system_message,
+            "messages": messages(&request.messages),
+        });
+        if let Some(schema) = &request.response_schema {
+            tools.push(json!({
+                "name": STRUCTURED_OUTPUT_TOOL,
+                "description": format!("Respond with the {} as the input of this tool.", schema.name),
+                "input_schema": schema.schema,
+            }));
+            body["tool_choice"] = json!({ "type": "tool", "name": STRUCTURED_OUTPUT_TOOL });
+        }
+        if !tools.is_empty() {
+            body["tools"] = Value::Array(tools);
+        }
+        body
+    }
+}
+
+impl LlmProvider for AnthropicProvider {
+    fn chat<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: Option<&'a (dyn Fn(&str) + Send + Sync)>,
+    ) -> BoxFuture<'a, Result<ChatResponse>> {
+        Box::pin(async move {
+            let response = reqwest::Client::new()
+                .post(API_URL)
+                .header("x-api-key", &self.api_key.0)
+                .header("anthropic-version", API_VERSION)
+                .json(&self.request_body(request))
+                .send()
+                .await
+                .context("Failed to send request to Anthropic")?;
+            let status = response.status();
+            let body: Value = response.json().await?;
+            if !status.is_success() {
+                bail!(
+                    "Anthropic responded with {status}: {}",
+                    body["error"]["message"].as_str().unwrap_or("unknown error")
+                );
+            }
+
+            let response = parse_response(&body);
+            if let (Some(on_token), Some(text)) = (on_token, &response.text) {
+                on_token(text);
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Convert the `body` of a successful response of the Messages API into a response,
+/// using the input of the structured output tool as text if it was called.
+fn parse_response(body: &Value) -> ChatResponse {
+    let mut text = None::<String>;
+    let mut tool_calls = Vec::new();
+    for block in body["content"].as_array().into_iter().flatten() {
+        match block["type"].as_str() {
+            Some("text") => {
+                let block_text = block["text"].as_str().unwrap_or_default();
+                text.get_or_insert_default().push_str(block_text);
+            }
+            Some("tool_use") if block["name"] == STRUCTURED_OUTPUT_TOOL => {
+                text = Some(block["input"].to_string());
+            }
+            Some("tool_use") => tool_calls.push(ToolCallContent {
+                id: block["id"].as_str().unwrap_or_default().to_owned(),
+                name: block["name"].as_str().unwrap_or_default().to_owned(),
+                arguments: block["input"].to_string(),
+            }),
+            _ => {}
+        }
+    }
+    ChatResponse { text, tool_calls }
+}
+
+/// Convert `messages` into the messages of the Messages API, which must alternate between the user and the assistant,
+/// with tool results being sent by the user.
+fn messages(messages: &[ChatMessage]) -> Vec<Value> {
+    let mut out: Vec<(&str, Vec<Value>)> = Vec::new();
+    for message in messages {
+        let (role, block) = match message {
+            ChatMessage::User(text) => ("user", json!({ "type": "text", "text": text })),
+            ChatMessage::Assistant(text) => ("assistant", json!({ "type": "text", "text": text })),
+            ChatMessage::ToolCall(call) => (
+                "assistant",
+                json!({
+                    "type": "tool_use",
+                    "id": call.id,
+                    "name": call.name,
+                    "input": serde_json::from_str::<Value>(&call.arguments)
+                        .ok()
+                        .filter(Value::is_object)
+                        .unwrap_or_else(|| json!({})),
+                }),
+            ),
+            ChatMessage::ToolResponse(response) => (
+                "user",
+                json!({
+                    "type": "tool_result",
+                    "tool_use_id": response.id,
+                    "content": response.result,
+                }),
+            ),
+        };
+        match out.last_mut() {
+            Some((last_role, blocks)) if *last_role == role => blocks.push(block),
+            _ => out.push((role, vec![block])),
+        }
+    }
+    out.into_iter()
+        .map(|(role, content)| json!({ "role": role, "content": content }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ToolResponseContent,
+        llm::{ResponseSchema, ToolDefinition},
+    };
+
+    fn provider() -> AnthropicProvider {
+        AnthropicProvider {
+            api_key: Sensitive(String::new()),
+            model: "model".into(),
+        }
+    }
+
+    fn request(messages: Vec<ChatMessage>) -> ChatRequest {
+        ChatRequest {
+            system_message: "system".into(),
+            messages,
+            tools: vec![],
+            response_schema: None,
+            model: None,
+        }
+    }
+
+    #[test]
+    fn messages_alternate_and_tool_results_are_sent_by_the_user() {
+        let body = provider().request_body(&request(vec![
+            ChatMessage::User("first".into()),
+            ChatMessage::User("second".into()),
+            ChatMessage::Assistant("thinking".into()),
+            ChatMessage::ToolCall(ToolCallContent {
+                id: "call_1".into(),
+                name: "get_status".into(),
+                arguments: r#"{"verbose":true}"#.into(),
+            }),
+            ChatMessage::ToolCall(ToolCallContent {
+                id: "call_2".into(),
+                name: "get_status".into(),
+                arguments: "not json".into(),
+            }),
+            ChatMessage::ToolResponse(ToolResponseContent {
+                id: "call_1".into(),
+                result: "clean".into(),
+            }),
+        ]));
+        assert_eq!(
+            body,
+            json!({
+                "model": "model",
+                "max_tokens": MAX_TOKENS,
+                "system": "system",
+                "messages": [
+                    {
+                        "role": "user",
+                        "content": [
+                            { "type": "text", "text": "first" },
+                            { "type": "text", "text": "second" },
+                        ],
+                    },
+                    {
+                        "role": "assistant",
+                        "content": [
+                            { "type": "text", "text": "thinking" },
+                            { "type": "tool_use", "id": "call_1", "name": "get_status", "input": { "verbose": true } },
+                            { "type": "tool_use", "id": "call_2", "name": "get_status", "input": {} },
+                        ],
+                    },
+                    {
+                        "role": "user",
+                        "content": [
+                            { "type": "tool_result", "tool_use_id": "call_1", "content": "clean" },
+                        ],
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn structured_output_is_requested_as_forced_tool_call() {
+        let mut request = request(vec![ChatMessage::User("classify".into())]);
+        request.tools = vec![ToolDefinition {
+            name: "get_status".into(),
+            description: "Get the status".into(),
+            parameters: json!({ "type": "object" }),
+        }];
+        request.response_schema = Some(ResponseSchema {
+            name: "classification".into(),
+            schema: json!({ "type": "object", "properties": { "kind": { "type": "string" } } }),
+            strict: true,
+        });
+        let body = provider().request_body(&request);
+        assert_eq!(
+            body["tools"],
+            json!([
+                {
+                    "name": "get_status",
+                    "description": "Get the status",
+                    "input_schema": { "type": "object" },
+                },
+                {
+                    "name": STRUCTURED_OUTPUT_TOOL,
+                    "description": "Respond with the classification as the input of this tool.",
+                    "input_schema": { "type": "object", "properties": { "kind": { "type": "string" } } },
+                },
+            ])
+        );
+        assert_eq!(
+            body["tool_choice"],
+            json!({ "type": "tool", "name": STRUCTURED_OUTPUT_TOOL })
+        );
+
+        let body = provider().request_body(&self::request(vec![]));
+        assert!(
+            body.get("tools").is_none() && body.get("tool_choice").is_none(),
+            "without tools, none are sent"
+        );
+    }
+
+    #[test]
+    fn responses_are_parsed_into_text_and_tool_calls() {
+        let response = parse_response(&json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [
+                { "type": "text", "text": "Let me " },
+                { "type": "text", "text": "check." },
+                { "type": "tool_use", "id": "toolu_1", "name": "get_status", "input": { "verbose": true } },
+            ],
+            "stop_reason": "tool_use",
+        }));
+        assert_eq!(response.text.as_deref(), Some("Let me check."));
+        assert_eq!(
+            response.tool_calls,
+            [ToolCallContent {
+                id: "toolu_1".into(),
+                name: "get_status".into(),
+                arguments: r#"{"verbose":true}"#.into(),
+            }]
+        );
+
+        let response = parse_response(&json!({
+            "content": [
+                { "type": "tool_use", "id": "toolu_2", "name": STRUCTURED_OUTPUT_TOOL, "input": { "kind": "fix" } },
+            ],
+        }));
+        assert_eq!(
+            response.text.as_deref(),
+            Some(r#"{"kind":"fix"}"#),
+            "the input of the structured output tool is the response"
+        );
+        assert!(response.tool_calls.is_empty());
+
+        assert_eq!(
+            parse_response(&json!({ "content": [] })),
+            ChatResponse::default()
+        );
+    }
+}
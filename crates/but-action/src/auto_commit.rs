@@ -2,12 +2,12 @@ use anyhow::Context;
 use but_tools::{emit::Emitter, workspace::commit_toolset};
 use gitbutler_command_context::CommandContext;
 
-use crate::OpenAiProvider;
+use crate::llm::LlmProvider;
 
 pub fn auto_commit(
     emitter: std::sync::Arc<Emitter>,
     ctx: &mut CommandContext,
-    openai: &OpenAiProvider,
+    llm: &dyn LlmProvider,
     changes: Vec<but_core::TreeChange>,
 ) -> anyhow::Result<()> {
     let repo = ctx.gix_repo()?;
@@ -54,13 +54,7 @@ pub fn auto_commit(
         </project_status>
     ");
 
-    crate::openai::tool_calling_loop(
-        openai,
-        system_message,
-        vec![prompt.into()],
-        &mut toolset,
-        None,
-    )?;
+    crate::openai::tool_calling_loop(llm, system_message, vec![prompt.into()], &mut toolset, None)?;
 
     Ok(())
 }
@@ -0,0 +1,286 @@
+//! Record the chats of LLM-driven actions to JSON, and replay them deterministically, to test them without a model.
+//!
+//! A [`Cassette`] holds the request of each chat and the response to it, which includes the tool calls of the model
+//! along with their arguments, while the results of the tool calls are part of the next request.
+//! When replaying with a [`Player`], the actual requests are compared to the recorded ones, after
+//! object ids and UUIDs were replaced with placeholders as they differ between runs. The ids are
+//! then mapped back, so tool calls in replayed responses refer to the objects of the current run.
+//!
+//! Set `GITBUTLER_LLM_RECORD` or `GITBUTLER_LLM_REPLAY` to the path of a cassette to record or
+//! replay all chats of [`crate::llm::for_project()`].
+
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, bail};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::llm::{ChatRequest, ChatResponse, LlmProvider};
+
+/// The environment variable with the path of a cassette to record all chats to.
+pub const RECORD_ENV: &str = "GITBUTLER_LLM_RECORD";
+/// The environment variable with the path of a cassette to replay all chats from.
+pub const REPLAY_ENV: &str = "GITBUTLER_LLM_REPLAY";
+
+/// The chats of one or more actions, in the order they happened.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+/// A single request to a model and its response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Interaction {
+    /// The request as it was recorded, or `None` to accept any request when replaying,
+    /// which is useful for cassettes that are written by hand.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<ChatRequest>,
+    pub response: ChatResponse,
+}
+
+impl Cassette {
+    /// Read the cassette at `path`.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read cassette at {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid cassette at {}", path.display()))
+    }
+
+    /// Write the cassette to `path`, creating the directories leading to it.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Could not write cassette to {}", path.display()))
+    }
+}
+
+/// A provider that passes all chats to another provider, and records them to a cassette as they happen.
+#[derive(Debug)]
+pub struct Recorder {
+    inner: Box<dyn LlmProvider>,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl Recorder {
+    /// Record the chats with `inner` to the cassette at `path`, replacing what was recorded there before.
+    pub fn new(inner: Box<dyn LlmProvider>, path: impl Into<PathBuf>) -> Self {
+        Recorder {
+            inner,
+            path: path.into(),
+            cassette: Mutex::default(),
+        }
+    }
+
+    /// Return everything that was recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.lock().expect("poisoned").clone()
+    }
+}
+
+impl LlmProvider for Recorder {
+    fn chat<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: Option<&'a (dyn Fn(&str) + Send + Sync)>,
+    ) -> BoxFuture<'a, anyhow::Result<ChatResponse>> {
+        Box::pin(async move {
+            let response = self.inner.chat(request, on_token).await?;
+            let mut cassette = self.cassette.lock().expect("poisoned");
+            cassette.interactions.push(Interaction {
+                request: Some(request.clone()),
+                response: response.clone(),
+            });
+            // Save after each chat so nothing is lost if the action fails later.
+            cassette.save(&self.path)?;
+            Ok(response)
+        })
+    }
+}
+
+/// A provider that replays the responses of a cassette in order, and fails if a request differs from the recorded one.
+#[derive(Debug)]
+pub struct Player {
+    name: String,
+    interactions: Mutex<VecDeque<Interaction>>,
+    state: Mutex<PlayerState>,
+}
+
+#[derive(Debug, Default)]
+struct PlayerState {
+    requests: Vec<ChatRequest>,
+    /// The ids of the recording, mapped to the ids of the current run.
+    ids: HashMap<String, String>,
+}
+
+impl Player {
+    /// Replay the cassette at `path`.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self::new(Cassette::load(path)?, path.display().to_string()))
+    }
+
+    /// Replay `cassette`, which is called `name` in errors.
+    pub fn new(cassette: Cassette, name: impl Into<String>) -> Self {
+        Player {
+            name: name.into(),
+            interactions: Mutex::new(cassette.interactions.into()),
+            state: Mutex::default(),
+        }
+    }
+
+    /// Return all requests received so far.
+    pub fn requests(&self) -> Vec<ChatRequest> {
+        self.state.lock().expect("poisoned").requests.clone()
+    }
+
+    /// Return how many interactions weren't replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.interactions.lock().expect("poisoned").len()
+    }
+
+    fn replay(&self, request: &ChatRequest) -> anyhow::Result<ChatResponse> {
+        let mut state = self.state.lock().expect("poisoned");
+        state.requests.push(request.clone());
+        let number = state.requests.len();
+        let interaction = self
+            .interactions
+            .lock()
+            .expect("poisoned")
+            .pop_front()
+            .with_context(|| {
+                format!(
+                    "Cassette {} has no response for request {number}",
+                    self.name
+                )
+            })?;
+
+        if let Some(recorded) = &interaction.request {
+            let actual = serde_json::to_string(request)?;
+            let recorded = serde_json::to_string(recorded)?;
+            let (actual_normalized, actual_ids) = normalize(&actual);
+            let (recorded_normalized, recorded_ids) = normalize(&recorded);
+            if actual_normalized != recorded_normalized {
+                bail!(
+                    "Request {number} differs from the one recorded in cassette {}:\n{}",
+                    self.name,
+                    first_difference(&recorded_normalized, &actual_normalized)
+                );
+            }
+            for (recorded, actual) in recorded_ids.into_iter().zip(actual_ids) {
+                state.ids.insert(recorded.to_owned(), actual.to_owned());
+            }
+        }
+
+        let response = serde_json::to_string(&interaction.response)?;
+        Ok(serde_json::from_str(&substitute(&response, &state.ids))?)
+    }
+}
+
+impl LlmProvider for Player {
+    fn chat<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: Option<&'a (dyn Fn(&str) + Send + Sync)>,
+    ) -> BoxFuture<'a, anyhow::Result<ChatResponse>> {
+        Box::pin(async move {
+            let response = self.replay(request)?;
+            if let (Some(on_token), Some(text)) = (on_token, &response.text) {
+                on_token(text);
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Split `text` into words of ASCII alphanumerics and dashes, and return their byte ranges.
+fn words(text: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'-';
+    let bytes = text.as_bytes();
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        while pos < bytes.len() && !is_word(bytes[pos]) {
+            pos += 1;
+        }
+        let start = pos;
+        while pos < bytes.len() && is_word(bytes[pos]) {
+            pos += 1;
+        }
+        (start < pos).then_some((start, pos))
+    })
+}
+
+/// Return `true` if `word` is a full hexadecimal object id or a UUID.
+fn is_id(word: &str) -> bool {
+    let is_hex = |s: &str| s.bytes().all(|b| b.is_ascii_hexdigit());
+    match word.len() {
+        40 => is_hex(word),
+        36 => {
+            let parts: Vec<_> = word.split('-').collect();
+            parts.iter().map(|p| p.len()).eq([8, 4, 4, 4, 12]) && parts.into_iter().all(is_hex)
+        }
+        _ => false,
+    }
+}
+
+/// Replace all ids in `text` with a placeholder, and return them in order.
+fn normalize(text: &str) -> (String, Vec<&str>) {
+    let mut out = String::with_capacity(text.len());
+    let mut ids = Vec::new();
+    let mut last = 0;
+    for (start, end) in words(text) {
+        let word = &text[start..end];
+        if is_id(word) {
+            out.push_str(&text[last..start]);
+            out.push_str("<id>");
+            ids.push(word);
+            last = end;
+        }
+    }
+    out.push_str(&text[last..]);
+    (out, ids)
+}
+
+/// Replace all ids in `text` that are keys of `ids` with their values.
+fn substitute(text: &str, ids: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end) in words(text) {
+        if let Some(id) = ids.get(&text[start..end]) {
+            out.push_str(&text[last..start]);
+            out.push_str(id);
+            last = end;
+        }
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// Show where `actual` starts to differ from `expected`.
+fn first_difference(expected: &str, actual: &str) -> String {
+    const CONTEXT: usize = 80;
+    let pos = expected
+        .chars()
+        .zip(actual.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let excerpt = |s: &str| -> String {
+        s.chars()
+            .skip(pos.saturating_sub(CONTEXT))
+            .take(2 * CONTEXT)
+            .collect()
+    };
+    format!(
+        "recorded: …{}…\nactual:   …{}…",
+        excerpt(expected),
+        excerpt(actual)
+    )
+}
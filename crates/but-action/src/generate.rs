@@ -1,44 +1,36 @@
-use async_openai::{
-    Client,
-    config::OpenAIConfig,
-    types::{
-        ChatCompletionRequestSystemMessage, ChatCompletionRequestUserMessage,
-        CreateChatCompletionRequestArgs, ResponseFormat, ResponseFormatJsonSchema,
-    },
-};
+use anyhow::Context;
 use schemars::{JsonSchema, schema_for};
 
-use crate::OpenAiProvider;
+use crate::{
+    ChatMessage,
+    llm::{ChatRequest, LlmProvider, ResponseSchema},
+    openai::parse_structured_output,
+};
 
 #[expect(dead_code)]
 pub fn commit_message_blocking(
-    openai: &OpenAiProvider,
+    llm: &dyn LlmProvider,
     external_summary: &str,
     external_prompt: &str,
     diff: &str,
 ) -> anyhow::Result<String> {
-    let change_summary_owned = external_summary.to_string();
-    let external_prompt_owned = external_prompt.to_string();
-    let diff_owned = diff.to_string();
-    let client = openai.client()?;
-
-    std::thread::spawn(move || {
-        tokio::runtime::Runtime::new()
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                tokio::runtime::Runtime::new()?.block_on(commit_message(
+                    llm,
+                    external_summary,
+                    external_prompt,
+                    diff,
+                ))
+            })
+            .join()
             .unwrap()
-            .block_on(commit_message(
-                &client,
-                &change_summary_owned,
-                &external_prompt_owned,
-                &diff_owned,
-            ))
     })
-    .join()
-    .unwrap()
 }
 
 pub async fn commit_message(
-    // openai_provider: OpenAiProvider,
-    client: &Client<OpenAIConfig>,
+    llm: &dyn LlmProvider,
     external_summary: &str,
     external_prompt: &str,
     diff: &str,
@@ -50,37 +42,21 @@ pub async fn commit_message(
     );
 
     let schema = schema_for!(StructuredOutput);
-    let schema_json = serde_json::to_value(schema).unwrap();
-    let response_format = ResponseFormat::JsonSchema {
-        json_schema: ResponseFormatJsonSchema {
-            description: None,
+    let request = ChatRequest {
+        system_message,
+        messages: vec![ChatMessage::User(user_message)],
+        tools: vec![],
+        response_schema: Some(ResponseSchema {
             name: "commit_message".into(),
-            schema: Some(schema_json),
-            strict: Some(true),
-        },
+            schema: serde_json::to_value(schema)?,
+            strict: true,
+        }),
+        model: None,
     };
 
-    let request = CreateChatCompletionRequestArgs::default()
-        .model("gpt-5-mini")
-        .messages([
-            ChatCompletionRequestSystemMessage::from(system_message).into(),
-            ChatCompletionRequestUserMessage::from(user_message).into(),
-        ])
-        .response_format(response_format)
-        .build()?;
-
-    let response = client.chat().create(request).await?;
-    let response_string = response
-        .choices
-        .first()
-        .unwrap()
-        .message
-        .content
-        .as_ref()
-        .unwrap();
-
-    let structured_output: StructuredOutput = serde_json::from_str(response_string)
-        .map_err(|e| anyhow::anyhow!("Failed to parse response: {}", e))?;
+    let response = llm.chat(&request, None).await?;
+    let structured_output: StructuredOutput =
+        parse_structured_output(response)?.context("No content in LLM response")?;
 
     Ok(structured_output.commit_message)
 }
@@ -93,7 +69,7 @@ pub struct StructuredOutput {
 }
 
 pub async fn branch_name(
-    client: &Client<OpenAIConfig>,
+    llm: &dyn LlmProvider,
     commit_messages: &[String],
     diffs: &[String],
     existing_branch_names: &[String],
@@ -131,39 +107,21 @@ pub async fn branch_name(
     );
 
     let schema = schema_for!(GenerateBranchNameOutput);
-    let schema_json = serde_json::to_value(schema)?;
-    let response_format = ResponseFormat::JsonSchema {
-        json_schema: ResponseFormatJsonSchema {
-            description: None,
+    let request = ChatRequest {
+        system_message,
+        messages: vec![ChatMessage::User(user_message)],
+        tools: vec![],
+        response_schema: Some(ResponseSchema {
             name: "branch_name".into(),
-            schema: Some(schema_json),
-            strict: Some(false),
-        },
+            schema: serde_json::to_value(schema)?,
+            strict: false,
+        }),
+        model: None,
     };
 
-    let request = CreateChatCompletionRequestArgs::default()
-        .model("gpt-5-mini")
-        .messages([
-            ChatCompletionRequestSystemMessage::from(system_message).into(),
-            ChatCompletionRequestUserMessage::from(user_message).into(),
-        ])
-        .response_format(response_format)
-        .build()?;
-
-    let response = client.chat().create(request).await?;
-    let choice = response
-        .choices
-        .first()
-        .ok_or_else(|| anyhow::anyhow!("No choices returned from OpenAI response"))?;
-
-    let response_string = choice
-        .message
-        .content
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("No content in OpenAI response message"))?;
-
-    let structured_output: GenerateBranchNameOutput = serde_json::from_str(response_string)
-        .map_err(|e| anyhow::anyhow!("Failed to parse response: {}", e))?;
+    let response = llm.chat(&request, None).await?;
+    let structured_output: GenerateBranchNameOutput =
+        parse_structured_output(response)?.context("No content in LLM response")?;
 
     Ok(structured_output.branch_name)
 }
@@ -4,7 +4,7 @@ use but_tools::workspace::ProjectStatus;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{ChatMessage, OpenAiProvider, openai};
+use crate::{ChatMessage, llm::LlmProvider, openai};
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub enum BranchSuggestion {
@@ -64,7 +64,7 @@ pub struct Grouping {
 }
 
 #[expect(dead_code)]
-pub fn group(openai: &OpenAiProvider, project_status: &ProjectStatus) -> anyhow::Result<Grouping> {
+pub fn group(llm: &dyn LlmProvider, project_status: &ProjectStatus) -> anyhow::Result<Grouping> {
     let system_message ="
         You are an expert in grouping file changes into logical units for version control.
         When given the status of a project, you should be able to identify related changes and suggest how they should be grouped into commits.
@@ -97,9 +97,8 @@ pub fn group(openai: &OpenAiProvider, project_status: &ProjectStatus) -> anyhow:
 
     let messages = vec![ChatMessage::User(user_message)];
 
-    let grouping =
-        openai::structured_output_blocking::<Grouping>(openai, system_message, messages)?
-            .ok_or_else(|| anyhow::anyhow!("Failed to get grouping from OpenAI"))?;
+    let grouping = openai::structured_output_blocking::<Grouping>(llm, system_message, messages)?
+        .ok_or_else(|| anyhow::anyhow!("Failed to get grouping from OpenAI"))?;
 
     Ok(grouping)
 }
@@ -14,16 +14,20 @@ use gitbutler_command_context::CommandContext;
 use gitbutler_oxidize::ObjectIdExt;
 use gitbutler_project::{Project, ProjectId, access::WorktreeWritePermission};
 use gitbutler_stack::{Target, VirtualBranchesHandle};
-pub use openai::{CredentialsKind, OpenAiProvider};
+use llm::LlmProvider;
+pub use openai::{CredentialsKind, OpenAiCompatibleProvider, OpenAiProvider};
 use serde::{Deserialize, Serialize};
 
 mod absorb;
 mod action;
+mod anthropic;
 mod auto_commit;
 mod branch_changes;
+pub mod cassette;
 pub mod cli;
 mod generate;
 mod grouping;
+pub mod llm;
 mod openai;
 pub mod rename_branch;
 pub mod reword;
@@ -33,10 +37,11 @@ mod workflow;
 pub use action::ActionListing;
 pub use action::Source;
 pub use action::list_actions;
+pub use anthropic::AnthropicProvider;
 use but_graph::VirtualBranchesTomlMetadata;
 pub use openai::{
-    ChatMessage, ToolCallContent, ToolResponseContent, structured_output_blocking,
-    tool_calling_loop, tool_calling_loop_stream,
+    ChatMessage, ToolCallContent, ToolResponseContent, structured_output,
+    structured_output_blocking, tool_calling_loop, tool_calling_loop_stream,
 };
 use strum::EnumString;
 use uuid::Uuid;
@@ -48,7 +53,7 @@ pub fn freestyle(
     message_id: String,
     emitter: Arc<Emitter>,
     ctx: &mut CommandContext,
-    openai: &OpenAiProvider,
+    llm: &dyn LlmProvider,
     chat_messages: Vec<openai::ChatMessage>,
     model: Option<String>,
 ) -> anyhow::Result<String> {
@@ -133,7 +138,7 @@ pub fn freestyle(
         }
     });
     let (response, _) = crate::openai::tool_calling_loop_stream(
-        openai,
+        llm,
         system_message,
         internal_chat_messages,
         &mut toolset,
@@ -147,28 +152,36 @@ pub fn freestyle(
 pub fn absorb(
     emitter: Arc<Emitter>,
     ctx: &mut CommandContext,
-    openai: &OpenAiProvider,
+    llm: &dyn LlmProvider,
     changes: Vec<TreeChange>,
 ) -> anyhow::Result<()> {
-    absorb::absorb(emitter, ctx, openai, changes)
+    absorb::absorb(emitter, ctx, llm, changes)
+}
+
+pub fn absorb_locked(
+    emitter: Arc<Emitter>,
+    ctx: &mut CommandContext,
+    paths: Vec<gix::bstr::BString>,
+) -> anyhow::Result<()> {
+    absorb::absorb_locked(emitter, ctx, paths)
 }
 
 pub fn branch_changes(
     emitter: Arc<Emitter>,
     ctx: &mut CommandContext,
-    openai: &OpenAiProvider,
+    llm: &dyn LlmProvider,
     changes: Vec<TreeChange>,
 ) -> anyhow::Result<()> {
-    branch_changes::branch_changes(emitter, ctx, openai, changes)
+    branch_changes::branch_changes(emitter, ctx, llm, changes)
 }
 
 pub fn auto_commit(
     emitter: Arc<Emitter>,
     ctx: &mut CommandContext,
-    openai: &OpenAiProvider,
+    llm: &dyn LlmProvider,
     changes: Vec<TreeChange>,
 ) -> anyhow::Result<()> {
-    auto_commit::auto_commit(emitter, ctx, openai, changes)
+    auto_commit::auto_commit(emitter, ctx, llm, changes)
 }
 
 pub fn handle_changes(
@@ -0,0 +1,234 @@
+//! A provider-agnostic interface to the LLMs that drive actions, so a project can use OpenAI,
+//! any server with an OpenAI-compatible API, Anthropic, or a scripted mock in tests.
+
+use std::{collections::VecDeque, fmt::Debug, path::Path, sync::Mutex};
+
+use anyhow::Context;
+use but_tools::tool::Tool;
+use futures::future::BoxFuture;
+use gitbutler_project::{LlmProviderSettings, Project};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ChatMessage, CredentialsKind, OpenAiProvider, ToolCallContent, anthropic::AnthropicProvider,
+    cassette, openai::OpenAiCompatibleProvider,
+};
+
+/// Something that can complete a chat, optionally calling tools.
+pub trait LlmProvider: Debug + Send + Sync {
+    /// Complete the chat in `request`, calling `on_token` with text as it is generated if the provider can stream.
+    fn chat<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: Option<&'a (dyn Fn(&str) + Send + Sync)>,
+    ) -> BoxFuture<'a, anyhow::Result<ChatResponse>>;
+}
+
+/// A chat to complete.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatRequest {
+    pub system_message: String,
+    pub messages: Vec<ChatMessage>,
+    /// The tools the model may call.
+    #[serde(default)]
+    pub tools: Vec<ToolDefinition>,
+    /// The schema the response text has to conform to, if it must be JSON.
+    #[serde(default)]
+    pub response_schema: Option<ResponseSchema>,
+    /// The model to use instead of the default one. Only honoured by providers that don't have a configured model.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// A tool the model may call, as listed by a [`but_tools::tool::Toolset`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    /// The JSON schema of the arguments.
+    pub parameters: serde_json::Value,
+}
+
+impl From<&dyn Tool> for ToolDefinition {
+    fn from(tool: &dyn Tool) -> Self {
+        ToolDefinition {
+            name: tool.name(),
+            description: tool.description(),
+            parameters: tool.parameters(),
+        }
+    }
+}
+
+/// A JSON schema for structured output.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseSchema {
+    pub name: String,
+    pub schema: serde_json::Value,
+    /// Whether the provider should enforce the schema strictly, if it supports that.
+    #[serde(default)]
+    pub strict: bool,
+}
+
+/// The completion of a chat.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatResponse {
+    /// The text the model answered with, if any.
+    #[serde(default)]
+    pub text: Option<String>,
+    /// The tools the model wants to call before it continues.
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCallContent>,
+}
+
+/// Return the provider configured for `project`, or OpenAI with `preferred_creds` if it doesn't configure one.
+///
+/// If [`cassette::REPLAY_ENV`] is set, the cassette it points to is replayed instead, and if
+/// [`cassette::RECORD_ENV`] is set, all chats with the provider are recorded to it.
+pub fn for_project(
+    project: &Project,
+    preferred_creds: Option<CredentialsKind>,
+) -> anyhow::Result<Box<dyn LlmProvider>> {
+    if let Some(path) = std::env::var_os(cassette::REPLAY_ENV) {
+        return Ok(Box::new(cassette::Player::open(Path::new(&path))?));
+    }
+    let settings = project
+        .llm_provider
+        .clone()
+        .or_else(|| stored_settings(&project.path))
+        .unwrap_or_default();
+    let provider = from_settings(&settings, preferred_creds)?;
+    Ok(match std::env::var_os(cassette::RECORD_ENV) {
+        Some(path) => Box::new(cassette::Recorder::new(provider, path)),
+        None => provider,
+    })
+}
+
+/// Projects that were opened by path instead of being read from storage don't have settings,
+/// so look up the ones of the stored project with the same worktree.
+fn stored_settings(worktree_dir: &Path) -> Option<LlmProviderSettings> {
+    let worktree_dir = worktree_dir.canonicalize().ok()?;
+    gitbutler_project::list()
+        .ok()?
+        .into_iter()
+        .find(|project| {
+            project
+                .path
+                .canonicalize()
+                .is_ok_and(|path| path == worktree_dir)
+        })?
+        .llm_provider
+}
+
+/// Create the provider described by `settings`, using `preferred_creds` if it is OpenAI.
+pub fn from_settings(
+    settings: &LlmProviderSettings,
+    preferred_creds: Option<CredentialsKind>,
+) -> anyhow::Result<Box<dyn LlmProvider>> {
+    Ok(match settings {
+        LlmProviderSettings::OpenAi => Box::new(OpenAiProvider::with(preferred_creds).context(
+            "No valid credentials found for OpenAI. Please configure your GitButler account credentials or an OpenAI key.",
+        )?),
+        LlmProviderSettings::OpenAiCompatible {
+            base_url,
+            model,
+            api_key_env,
+        } => Box::new(OpenAiCompatibleProvider::new(
+            base_url,
+            model,
+            api_key_env.as_deref(),
+        )?),
+        LlmProviderSettings::Anthropic { model } => Box::new(AnthropicProvider::new(model.clone())?),
+        LlmProviderSettings::Mock { responses } => Box::new(MockProvider::from_values(responses)?),
+    })
+}
+
+/// Complete `request` with `provider` from synchronous code, blocking until the response is complete.
+pub fn chat_blocking(
+    provider: &dyn LlmProvider,
+    request: &ChatRequest,
+    on_token: Option<&(dyn Fn(&str) + Send + Sync)>,
+) -> anyhow::Result<ChatResponse> {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| tokio::runtime::Runtime::new()?.block_on(provider.chat(request, on_token)))
+            .join()
+            .expect("the chat thread doesn't panic")
+    })
+}
+
+/// A provider that replies with scripted responses in order, and remembers all requests, for tests.
+#[derive(Debug, Default)]
+pub struct MockProvider {
+    responses: Mutex<VecDeque<ChatResponse>>,
+    requests: Mutex<Vec<ChatRequest>>,
+}
+
+impl MockProvider {
+    /// Create a provider that replies with `responses`, one per request.
+    pub fn new(responses: impl IntoIterator<Item = ChatResponse>) -> Self {
+        MockProvider {
+            responses: Mutex::new(responses.into_iter().collect()),
+            requests: Mutex::default(),
+        }
+    }
+
+    /// Create a provider from responses that are either a [`ChatResponse`], or a string for a text-only response.
+    pub fn from_values(responses: &[serde_json::Value]) -> anyhow::Result<Self> {
+        let responses = responses
+            .iter()
+            .map(|value| match value {
+                serde_json::Value::String(text) => Ok(ChatResponse {
+                    text: Some(text.clone()),
+                    tool_calls: vec![],
+                }),
+                value => serde_json::from_value(value.clone())
+                    .context("Mock responses must be strings or chat responses"),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self::new(responses))
+    }
+
+    /// Return all requests received so far.
+    pub fn requests(&self) -> Vec<ChatRequest> {
+        self.requests.lock().expect("poisoned").clone()
+    }
+}
+
+impl LlmProvider for MockProvider {
+    fn chat<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: Option<&'a (dyn Fn(&str) + Send + Sync)>,
+    ) -> BoxFuture<'a, anyhow::Result<ChatResponse>> {
+        Box::pin(async move {
+            self.requests
+                .lock()
+                .expect("poisoned")
+                .push(request.clone());
+            let response = self
+                .responses
+                .lock()
+                .expect("poisoned")
+                .pop_front()
+                .context("The mock provider has no more responses")?;
+            if let (Some(on_token), Some(text)) = (on_token, &response.text) {
+                on_token(text);
+            }
+            Ok(response)
+        })
+    }
+}
+
+impl<T: LlmProvider + ?Sized> LlmProvider for Box<T> {
+    fn chat<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: Option<&'a (dyn Fn(&str) + Send + Sync)>,
+    ) -> BoxFuture<'a, anyhow::Result<ChatResponse>> {
+        (**self).chat(request, on_token)
+    }
+}
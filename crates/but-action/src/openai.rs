@@ -1,23 +1,25 @@
-use std::{collections::HashMap, fmt::Display, ops::Deref, sync::Arc};
+use std::{collections::BTreeMap, fmt::Display, ops::Deref, sync::Arc};
 
 use anyhow::{Context, Result};
 use async_openai::{
     Client,
     config::OpenAIConfig,
     types::{
-        ChatCompletionRequestAssistantMessageContent, ChatCompletionRequestMessage,
-        ChatCompletionRequestSystemMessage, ChatCompletionRequestUserMessageContent,
-        CreateChatCompletionRequestArgs, ResponseFormat, ResponseFormatJsonSchema,
+        ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage, ChatCompletionTool,
+        ChatCompletionToolType, CreateChatCompletionRequest, CreateChatCompletionRequestArgs,
+        CreateChatCompletionResponse, CreateChatCompletionStreamResponse, FunctionObject,
+        ResponseFormat, ResponseFormatJsonSchema,
     },
 };
 
 use but_tools::tool::Toolset;
-use futures::StreamExt;
+use futures::{StreamExt, future::BoxFuture};
 use gitbutler_secret::{Sensitive, secret};
 use reqwest::header::{HeaderMap, HeaderValue};
 use schemars::{JsonSchema, schema_for};
 use serde::de::DeserializeOwned;
-use tokio::sync::Mutex;
+
+use crate::llm::{self, ChatRequest, ChatResponse, LlmProvider, ResponseSchema, ToolDefinition};
 
 #[derive(Debug, Clone, serde::Serialize, strum::Display)]
 pub enum CredentialsKind {
@@ -27,6 +29,7 @@ pub enum CredentialsKind {
 }
 
 pub const GB_OPENAI_API_BASE: &str = "https://app.gitbutler.com/api/proxy/openai";
+const DEFAULT_MODEL: &str = "gpt-5-mini";
 
 #[derive(Debug, Clone)]
 pub struct OpenAiProvider {
@@ -115,233 +118,267 @@ impl OpenAiProvider {
     }
 }
 
-pub fn structured_output_blocking<
-    T: serde::Serialize + DeserializeOwned + JsonSchema + std::marker::Send + 'static,
->(
-    openai: &OpenAiProvider,
-    system_message: &str,
-    chat_messages: Vec<ChatMessage>,
-) -> anyhow::Result<Option<T>> {
-    let client = openai.client()?;
-    let mut messages: Vec<ChatCompletionRequestMessage> =
-        vec![ChatCompletionRequestSystemMessage::from(system_message).into()];
-
-    messages.extend(
-        chat_messages
-            .into_iter()
-            .map(ChatCompletionRequestMessage::from)
-            .collect::<Vec<_>>(),
-    );
-
-    std::thread::spawn(move || {
-        tokio::runtime::Runtime::new()
-            .unwrap()
-            .block_on(structured_output::<T>(&client, messages))
-    })
-    .join()
-    .unwrap()
+impl LlmProvider for OpenAiProvider {
+    fn chat<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: Option<&'a (dyn Fn(&str) + Send + Sync)>,
+    ) -> BoxFuture<'a, anyhow::Result<ChatResponse>> {
+        Box::pin(async move {
+            let model = request.model.as_deref().unwrap_or(DEFAULT_MODEL);
+            chat(&self.client()?, model, request, on_token).await
+        })
+    }
 }
 
-pub async fn structured_output<T: serde::Serialize + DeserializeOwned + JsonSchema>(
-    client: &Client<OpenAIConfig>,
-    messages: Vec<ChatCompletionRequestMessage>,
-) -> anyhow::Result<Option<T>> {
-    let schema = schema_for!(T);
-    let schema_value = serde_json::to_value(&schema)?;
-    let response_format = ResponseFormat::JsonSchema {
-        json_schema: ResponseFormatJsonSchema {
-            description: None,
-            name: "structured_response".into(),
-            schema: Some(schema_value),
-            strict: Some(false),
-        },
-    };
-
-    let request = CreateChatCompletionRequestArgs::default()
-        .model("gpt-5-mini")
-        .messages(messages)
-        .response_format(response_format)
-        .build()?;
-
-    let response = client.chat().create(request).await?;
+/// A server with an OpenAI-compatible API, like vLLM, the llama.cpp server or Ollama.
+#[derive(Debug, Clone)]
+pub struct OpenAiCompatibleProvider {
+    base_url: String,
+    model: String,
+    api_key: Option<Sensitive<String>>,
+}
 
-    for choice in response.choices {
-        if let Some(content) = choice.message.content {
-            return Ok(Some(serde_json::from_str::<T>(&content)?));
-        }
+impl OpenAiCompatibleProvider {
+    /// Use `model` on the server at `base_url`, with the key in the environment variable `api_key_env` if it needs one.
+    pub fn new(base_url: &str, model: &str, api_key_env: Option<&str>) -> Result<Self> {
+        let api_key = api_key_env
+            .map(|name| {
+                std::env::var(name)
+                    .map(Sensitive)
+                    .with_context(|| format!("Environment variable {name} is not set"))
+            })
+            .transpose()?;
+        Ok(OpenAiCompatibleProvider {
+            base_url: base_url.to_owned(),
+            model: model.to_owned(),
+            api_key,
+        })
     }
 
-    Ok(None)
+    fn client(&self) -> Client<OpenAIConfig> {
+        // Servers without authentication ignore the key, but the client would read `OPENAI_API_KEY` without one,
+        // which must never be sent elsewhere.
+        let api_key = self.api_key.as_ref().map_or("", |key| key.0.as_str());
+        Client::with_config(
+            OpenAIConfig::new()
+                .with_api_base(&self.base_url)
+                .with_api_key(api_key),
+        )
+    }
 }
 
-pub fn tool_calling_blocking(
-    client: &OpenAiProvider,
-    messages: Vec<ChatCompletionRequestMessage>,
-    tools: Vec<async_openai::types::ChatCompletionTool>,
-    model: Option<String>,
-) -> anyhow::Result<async_openai::types::CreateChatCompletionResponse> {
-    let client = client.client()?;
-    let messages_owned = messages.clone();
-    let tools_owned = tools.clone();
-
-    std::thread::spawn(move || {
-        tokio::runtime::Runtime::new()
-            .unwrap()
-            .block_on(tool_calling(&client, messages_owned, tools_owned, model))
-    })
-    .join()
-    .unwrap()
+impl LlmProvider for OpenAiCompatibleProvider {
+    fn chat<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: Option<&'a (dyn Fn(&str) + Send + Sync)>,
+    ) -> BoxFuture<'a, anyhow::Result<ChatResponse>> {
+        Box::pin(async move { chat(&self.client(), &self.model, request, on_token).await })
+    }
 }
 
-pub async fn tool_calling(
+/// Complete `request` with `model` through the chat completions API of `client`, streaming the response
+/// if there is an `on_token` callback.
+async fn chat(
     client: &Client<OpenAIConfig>,
-    messages: Vec<ChatCompletionRequestMessage>,
-    tools: Vec<async_openai::types::ChatCompletionTool>,
-    model: Option<String>,
-) -> anyhow::Result<async_openai::types::CreateChatCompletionResponse> {
-    let model = model.unwrap_or_else(|| "gpt-5-mini".to_string());
-    let request = CreateChatCompletionRequestArgs::default()
-        .model(model)
-        .messages(messages.clone())
-        .tools(tools)
-        .build()?;
-
-    let response = client.chat().create(request).await?;
+    model: &str,
+    request: &ChatRequest,
+    on_token: Option<&(dyn Fn(&str) + Send + Sync)>,
+) -> anyhow::Result<ChatResponse> {
+    let openai_request = chat_completion_request(model, request)?;
+
+    let Some(on_token) = on_token else {
+        let response = client.chat().create(openai_request).await?;
+        return Ok(chat_response(response));
+    };
 
-    Ok(response)
+    let mut stream = client.chat().create_stream(openai_request).await?;
+    let mut streamed = StreamedResponse::default();
+    while let Some(result) = stream.next().await {
+        let chunk = result.context("Failed to receive response from OpenAI stream")?;
+        if let Some(response) = streamed.push(&chunk, on_token) {
+            return Ok(response);
+        }
+    }
+    Ok(streamed.into_response())
 }
 
-pub struct ToolCall {
-    pub id: String,
-    pub name: String,
-    pub arguments: String,
+/// The request of the chat completions API to complete `request` with `model`.
+fn chat_completion_request(
+    model: &str,
+    request: &ChatRequest,
+) -> anyhow::Result<CreateChatCompletionRequest> {
+    let mut args = CreateChatCompletionRequestArgs::default();
+    args.model(model).messages(to_openai_messages(
+        &request.system_message,
+        &request.messages,
+    ));
+    if !request.tools.is_empty() {
+        args.tools(
+            request
+                .tools
+                .iter()
+                .map(|tool| ChatCompletionTool {
+                    r#type: ChatCompletionToolType::Function,
+                    function: FunctionObject {
+                        name: tool.name.clone(),
+                        description: Some(tool.description.clone()),
+                        parameters: Some(tool.parameters.clone()),
+                        strict: Some(false),
+                    },
+                })
+                .collect::<Vec<_>>(),
+        );
+    }
+    if let Some(schema) = &request.response_schema {
+        args.response_format(ResponseFormat::JsonSchema {
+            json_schema: ResponseFormatJsonSchema {
+                description: None,
+                name: schema.name.clone(),
+                schema: Some(schema.schema.clone()),
+                strict: Some(schema.strict),
+            },
+        });
+    }
+    Ok(args.build()?)
 }
 
-type StreamToolCallResult = (Option<Vec<ToolCall>>, Option<String>);
-
-pub fn tool_calling_stream_blocking(
-    client: &OpenAiProvider,
-    messages: Vec<ChatCompletionRequestMessage>,
-    tools: Vec<async_openai::types::ChatCompletionTool>,
-    model: Option<String>,
-    on_token: impl Fn(&str) + Send + Sync + 'static,
-) -> anyhow::Result<StreamToolCallResult> {
-    let client = client.client()?;
-    let messages_owned = messages.clone();
-    let tools_owned = tools.clone();
-
-    std::thread::spawn(move || {
-        tokio::runtime::Runtime::new()
-            .unwrap()
-            .block_on(tool_calling_stream(
-                &client,
-                messages_owned,
-                tools_owned,
-                model,
-                on_token,
-            ))
-    })
-    .join()
-    .unwrap()
+/// Convert a response that wasn't streamed, using its first choice.
+fn chat_response(response: CreateChatCompletionResponse) -> ChatResponse {
+    let Some(choice) = response.choices.into_iter().next() else {
+        return ChatResponse::default();
+    };
+    ChatResponse {
+        text: choice.message.content,
+        tool_calls: choice
+            .message
+            .tool_calls
+            .unwrap_or_default()
+            .into_iter()
+            .map(|call| ToolCallContent {
+                id: call.id,
+                name: call.function.name,
+                arguments: call.function.arguments,
+            })
+            .collect(),
+    }
 }
 
-pub async fn tool_calling_stream(
-    client: &Client<OpenAIConfig>,
-    messages: Vec<ChatCompletionRequestMessage>,
-    tools: Vec<async_openai::types::ChatCompletionTool>,
-    model: Option<String>,
-    on_token: impl Fn(&str) + Send + Sync + 'static,
-) -> anyhow::Result<StreamToolCallResult> {
-    let model = model.unwrap_or_else(|| "gpt-5-mini".to_string());
-    let request = CreateChatCompletionRequestArgs::default()
-        .model(model)
-        .messages(messages.clone())
-        .tools(tools)
-        .build()?;
-
-    let mut stream = client.chat().create_stream(request).await?;
-
-    let tool_call_states: Arc<Mutex<HashMap<(u32, u32), ToolCall>>> =
-        Arc::new(Mutex::new(HashMap::new()));
-
-    let mut response_text: Option<String> = None;
+/// A response that is assembled from the chunks of a stream.
+#[derive(Default)]
+struct StreamedResponse {
+    /// The tool calls by the index of their choice and their own index, as their arguments arrive in pieces.
+    tool_calls: BTreeMap<(u32, u32), ToolCallContent>,
+    text: Option<String>,
+}
 
-    while let Some(result) = stream.next().await {
-        let response = result.context("Failed to receive response from OpenAI stream")?;
-        if let Some(chat_choice) = response.choices.first() {
-            // Keep track of tool call states
-            if let Some(tool_calls) = &chat_choice.delta.tool_calls {
-                for tool_call_chunk in tool_calls.iter() {
-                    let key = (chat_choice.index, tool_call_chunk.index);
-                    let states = tool_call_states.clone();
-                    let tool_call_data = tool_call_chunk.clone();
-
-                    let mut states_lock = states.lock().await;
-                    let state = states_lock.entry(key).or_insert_with(|| ToolCall {
-                        id: tool_call_data.id.clone().unwrap_or_default(),
-                        name: tool_call_data
-                            .function
-                            .as_ref()
-                            .and_then(|f| f.name.clone())
-                            .unwrap_or_default(),
-                        arguments: tool_call_data
-                            .function
-                            .as_ref()
-                            .and_then(|f| f.arguments.clone())
-                            .unwrap_or_default(),
+impl StreamedResponse {
+    /// Add `chunk`, passing its text to `on_token`, and return the response once the model is done calling tools.
+    fn push(
+        &mut self,
+        chunk: &CreateChatCompletionStreamResponse,
+        on_token: &(dyn Fn(&str) + Send + Sync),
+    ) -> Option<ChatResponse> {
+        let chat_choice = chunk.choices.first()?;
+        // Keep track of tool call states
+        if let Some(tool_calls) = &chat_choice.delta.tool_calls {
+            for tool_call_chunk in tool_calls.iter() {
+                let key = (chat_choice.index, tool_call_chunk.index);
+                let function = tool_call_chunk.function.as_ref();
+                let state = self
+                    .tool_calls
+                    .entry(key)
+                    .or_insert_with(|| ToolCallContent {
+                        id: tool_call_chunk.id.clone().unwrap_or_default(),
+                        name: function.and_then(|f| f.name.clone()).unwrap_or_default(),
+                        arguments: String::new(),
                     });
 
-                    if let Some(arguments) = tool_call_chunk
-                        .function
-                        .as_ref()
-                        .and_then(|f| f.arguments.as_ref())
-                    {
-                        state.arguments.push_str(arguments);
-                    }
+                if let Some(arguments) = function.and_then(|f| f.arguments.as_ref()) {
+                    state.arguments.push_str(arguments);
                 }
             }
+        }
 
-            // If finished streaming the tool calls, return them.
-            if let Some(finish_reason) = &chat_choice.finish_reason
-                && matches!(finish_reason, async_openai::types::FinishReason::ToolCalls)
-            {
-                let tool_call_states_clone = tool_call_states.clone();
-
-                let tool_calls_to_process = {
-                    let states_lock = tool_call_states_clone.lock().await;
-                    states_lock
-                        .values()
-                        .map(|state| ToolCall {
-                            id: state.id.clone(),
-                            name: state.name.clone(),
-                            arguments: state.arguments.clone(),
-                        })
-                        .collect::<Vec<ToolCall>>()
-                };
-
-                return Ok((Some(tool_calls_to_process), response_text));
-            }
-
-            // If there is any text content in the response, call the on_token callback
-            if let Some(content) = &chat_choice.delta.content {
-                if response_text.is_none() {
-                    response_text = Some(String::new());
-                }
+        // If finished streaming the tool calls, return them.
+        if let Some(finish_reason) = &chat_choice.finish_reason
+            && matches!(finish_reason, async_openai::types::FinishReason::ToolCalls)
+        {
+            return Some(ChatResponse {
+                text: self.text.take(),
+                tool_calls: std::mem::take(&mut self.tool_calls).into_values().collect(),
+            });
+        }
 
-                if let Some(text) = response_text.as_mut() {
-                    text.push_str(content);
-                }
+        // If there is any text content in the response, call the on_token callback
+        if let Some(content) = &chat_choice.delta.content {
+            self.text.get_or_insert_default().push_str(content);
+            on_token(content);
+        }
+        None
+    }
 
-                let content_str = content.as_str();
-                on_token(content_str);
-            }
+    /// The response once the stream ended without the model calling tools.
+    fn into_response(self) -> ChatResponse {
+        ChatResponse {
+            text: self.text,
+            tool_calls: vec![],
         }
     }
+}
+
+/// Ask `provider` for a response of type `T` to `chat_messages`.
+pub async fn structured_output<T: serde::Serialize + DeserializeOwned + JsonSchema>(
+    provider: &dyn LlmProvider,
+    system_message: &str,
+    chat_messages: Vec<ChatMessage>,
+) -> anyhow::Result<Option<T>> {
+    let response = provider
+        .chat(
+            &structured_output_request::<T>(system_message, chat_messages)?,
+            None,
+        )
+        .await?;
+    parse_structured_output(response)
+}
 
-    Ok((None, response_text))
+pub fn structured_output_blocking<T: serde::Serialize + DeserializeOwned + JsonSchema>(
+    provider: &dyn LlmProvider,
+    system_message: &str,
+    chat_messages: Vec<ChatMessage>,
+) -> anyhow::Result<Option<T>> {
+    let request = structured_output_request::<T>(system_message, chat_messages)?;
+    parse_structured_output(llm::chat_blocking(provider, &request, None)?)
+}
+
+fn structured_output_request<T: JsonSchema>(
+    system_message: &str,
+    chat_messages: Vec<ChatMessage>,
+) -> anyhow::Result<ChatRequest> {
+    Ok(ChatRequest {
+        system_message: system_message.to_owned(),
+        messages: chat_messages,
+        tools: vec![],
+        response_schema: Some(ResponseSchema {
+            name: "structured_response".into(),
+            schema: serde_json::to_value(schema_for!(T))?,
+            strict: false,
+        }),
+        model: None,
+    })
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) fn parse_structured_output<T: DeserializeOwned>(
+    response: ChatResponse,
+) -> anyhow::Result<Option<T>> {
+    response
+        .text
+        .map(|text| serde_json::from_str(&text).context("Failed to parse structured output"))
+        .transpose()
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolCallContent {
     pub id: String,
@@ -349,14 +386,14 @@ pub struct ToolCallContent {
     pub arguments: String,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolResponseContent {
     pub id: String,
     pub result: String,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", content = "content", rename_all = "camelCase")]
 pub enum ChatMessage {
     User(String),
@@ -378,14 +415,7 @@ impl From<ChatMessage> for ChatCompletionRequestMessage {
             ChatMessage::ToolCall(content) => ChatCompletionRequestMessage::Assistant(
                 async_openai::types::ChatCompletionRequestAssistantMessage {
                     content: None,
-                    tool_calls: Some(vec![async_openai::types::ChatCompletionMessageToolCall {
-                        id: content.id,
-                        r#type: async_openai::types::ChatCompletionToolType::Function,
-                        function: async_openai::types::FunctionCall {
-                            name: content.name,
-                            arguments: content.arguments,
-                        },
-                    }]),
+                    tool_calls: Some(vec![openai_tool_call(content)]),
                     ..Default::default()
                 },
             ),
@@ -401,48 +431,43 @@ impl From<ChatMessage> for ChatCompletionRequestMessage {
     }
 }
 
-fn from_openai_chat_messages(messages: Vec<ChatCompletionRequestMessage>) -> Vec<ChatMessage> {
-    let mut chat_messages = Vec::new();
-
-    for m in messages.into_iter() {
-        match m {
-            ChatCompletionRequestMessage::User(content) => {
-                if let ChatCompletionRequestUserMessageContent::Text(text) = content.content {
-                    chat_messages.push(ChatMessage::User(text));
-                }
-            }
-            ChatCompletionRequestMessage::Assistant(assistant_msg) => {
-                if let Some(tool_calls) = &assistant_msg.tool_calls {
-                    for tool_call in tool_calls {
-                        chat_messages.push(ChatMessage::ToolCall(ToolCallContent {
-                            id: tool_call.id.clone(),
-                            name: tool_call.function.name.clone(),
-                            arguments: tool_call.function.arguments.clone(),
-                        }));
-                    }
-                }
+fn openai_tool_call(call: ToolCallContent) -> async_openai::types::ChatCompletionMessageToolCall {
+    async_openai::types::ChatCompletionMessageToolCall {
+        id: call.id,
+        r#type: ChatCompletionToolType::Function,
+        function: async_openai::types::FunctionCall {
+            name: call.name,
+            arguments: call.arguments,
+        },
+    }
+}
 
-                if let Some(ChatCompletionRequestAssistantMessageContent::Text(text)) =
-                    assistant_msg.content
-                {
-                    chat_messages.push(ChatMessage::Assistant(text));
-                }
-            }
-            ChatCompletionRequestMessage::Tool(tool_msg) => {
-                if let async_openai::types::ChatCompletionRequestToolMessageContent::Text(text) =
-                    tool_msg.content
-                {
-                    chat_messages.push(ChatMessage::ToolResponse(ToolResponseContent {
-                        id: tool_msg.tool_call_id.clone(),
-                        result: text,
-                    }));
-                }
-            }
-            _ => (),
+/// Convert `messages` into the messages of the chat completions API, preceded by `system_message`.
+///
+/// Consecutive tool calls become a single assistant message, as the responses to all tool calls
+/// must follow the message that made them.
+fn to_openai_messages(
+    system_message: &str,
+    messages: &[ChatMessage],
+) -> Vec<ChatCompletionRequestMessage> {
+    let mut openai_messages: Vec<ChatCompletionRequestMessage> =
+        vec![ChatCompletionRequestSystemMessage::from(system_message).into()];
+    for message in messages {
+        match (message, openai_messages.last_mut()) {
+            (
+                ChatMessage::ToolCall(call),
+                Some(ChatCompletionRequestMessage::Assistant(
+                    async_openai::types::ChatCompletionRequestAssistantMessage {
+                        content: None,
+                        tool_calls: Some(tool_calls),
+                        ..
+                    },
+                )),
+            ) => tool_calls.push(openai_tool_call(call.clone())),
+            _ => openai_messages.push(message.clone().into()),
         }
     }
-
-    chat_messages
+    openai_messages
 }
 
 fn clamp_result_content(result: &ToolResponseContent) -> String {
@@ -495,263 +520,264 @@ impl From<String> for ChatMessage {
 }
 
 pub fn tool_calling_loop(
-    provider: &OpenAiProvider,
+    provider: &dyn LlmProvider,
     system_message: &str,
     chat_messages: Vec<ChatMessage>,
     tool_set: &mut impl Toolset,
     model: Option<String>,
 ) -> anyhow::Result<String> {
-    let mut messages: Vec<ChatCompletionRequestMessage> =
-        vec![ChatCompletionRequestSystemMessage::from(system_message).into()];
-
-    messages.extend(
-        chat_messages
-            .into_iter()
-            .map(ChatCompletionRequestMessage::from)
-            .collect::<Vec<_>>(),
-    );
-
-    let open_ai_tools = tool_set
-        .list()
-        .iter()
-        .map(|t| t.deref().try_into())
-        .collect::<Result<Vec<async_openai::types::ChatCompletionTool>, _>>()?;
-
-    let mut response = crate::openai::tool_calling_blocking(
+    run_tool_calling_loop(
         provider,
-        messages.clone(),
-        open_ai_tools.clone(),
-        model.clone(),
-    )?;
-
-    let mut text_response_buffer = vec![];
-    if let Some(text_response) = response
-        .choices
-        .first()
-        .and_then(|choice| choice.message.content.clone())
-    {
-        text_response_buffer.push(text_response.clone());
-        messages.push(ChatCompletionRequestMessage::Assistant(
-            async_openai::types::ChatCompletionRequestAssistantMessage {
-                content: Some(ChatCompletionRequestAssistantMessageContent::Text(
-                    text_response,
-                )),
-                ..Default::default()
-            },
-        ));
-    }
-
-    while let Some(tool_calls) = response
-        .choices
-        .first()
-        .and_then(|choice| choice.message.tool_calls.as_ref())
-    {
-        let mut tool_calls_messages: Vec<async_openai::types::ChatCompletionMessageToolCall> =
-            vec![];
-        let mut tool_response_messages: Vec<async_openai::types::ChatCompletionRequestMessage> =
-            vec![];
-
-        for call in tool_calls {
-            let function_name = call.function.name.clone();
-            let function_args = call.function.arguments.clone();
-
-            let tool_response = tool_set.call_tool(&function_name, &function_args);
-
-            let tool_response_str = serde_json::to_string(&tool_response)
-                .context("Failed to serialize tool response")?;
-
-            tool_calls_messages.push(async_openai::types::ChatCompletionMessageToolCall {
-                id: call.id.clone(),
-                r#type: async_openai::types::ChatCompletionToolType::Function,
-                function: async_openai::types::FunctionCall {
-                    name: function_name,
-                    arguments: function_args,
-                },
-            });
-
-            tool_response_messages.push(async_openai::types::ChatCompletionRequestMessage::Tool(
-                async_openai::types::ChatCompletionRequestToolMessage {
-                    tool_call_id: call.id.clone(),
-                    content: async_openai::types::ChatCompletionRequestToolMessageContent::Text(
-                        tool_response_str,
-                    ),
-                },
-            ));
-        }
-
-        messages.push(
-            async_openai::types::ChatCompletionRequestMessage::Assistant(
-                async_openai::types::ChatCompletionRequestAssistantMessage {
-                    tool_calls: Some(tool_calls_messages),
-                    ..Default::default()
-                },
-            ),
-        );
-
-        messages.extend(tool_response_messages);
-
-        response = crate::openai::tool_calling_blocking(
-            provider,
-            messages.clone(),
-            open_ai_tools.clone(),
-            model.clone(),
-        )?;
-
-        if let Some(text_response) = response
-            .choices
-            .first()
-            .and_then(|choice| choice.message.content.clone())
-        {
-            text_response_buffer.push(text_response.clone());
-            messages.push(ChatCompletionRequestMessage::Assistant(
-                async_openai::types::ChatCompletionRequestAssistantMessage {
-                    content: Some(ChatCompletionRequestAssistantMessageContent::Text(
-                        text_response,
-                    )),
-                    ..Default::default()
-                },
-            ));
-        }
-    }
-
-    let response = text_response_buffer
-        .into_iter()
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<String>>()
-        .join("\n\n");
-
-    Ok(response)
+        system_message,
+        chat_messages,
+        tool_set,
+        model,
+        None,
+    )
+    .map(|(response, _)| response)
 }
 
 pub fn tool_calling_loop_stream(
-    provider: &OpenAiProvider,
+    provider: &dyn LlmProvider,
     system_message: &str,
     chat_messages: Vec<ChatMessage>,
     tool_set: &mut impl Toolset,
     model: Option<String>,
     on_token: Arc<dyn Fn(&str) + Send + Sync + 'static>,
 ) -> anyhow::Result<(String, Vec<ChatMessage>)> {
-    let mut messages: Vec<ChatCompletionRequestMessage> =
-        vec![ChatCompletionRequestSystemMessage::from(system_message).into()];
+    run_tool_calling_loop(
+        provider,
+        system_message,
+        chat_messages,
+        tool_set,
+        model,
+        Some(&*on_token),
+    )
+}
 
-    messages.extend(
-        chat_messages
-            .into_iter()
-            .map(ChatCompletionRequestMessage::from)
-            .collect::<Vec<_>>(),
-    );
-
-    let open_ai_tools = tool_set
-        .list()
-        .iter()
-        .map(|t| t.deref().try_into())
-        .collect::<Result<Vec<async_openai::types::ChatCompletionTool>, _>>()?;
-
-    let on_token_cb = {
-        let on_token = on_token.clone();
-        Box::new(move |token: &str| on_token(token)) as Box<dyn Fn(&str) + Send + Sync + 'static>
+/// Let the model call tools of `tool_set` until it answers without calling any, and return all of its text responses
+/// along with the whole conversation.
+fn run_tool_calling_loop(
+    provider: &dyn LlmProvider,
+    system_message: &str,
+    chat_messages: Vec<ChatMessage>,
+    tool_set: &mut impl Toolset,
+    model: Option<String>,
+    on_token: Option<&(dyn Fn(&str) + Send + Sync)>,
+) -> anyhow::Result<(String, Vec<ChatMessage>)> {
+    let mut request = ChatRequest {
+        system_message: system_message.to_owned(),
+        messages: chat_messages,
+        tools: tool_set
+            .list()
+            .iter()
+            .map(|t| ToolDefinition::from(t.deref()))
+            .collect(),
+        response_schema: None,
+        model,
     };
 
-    let mut response = tool_calling_stream_blocking(
-        provider,
-        messages.clone(),
-        open_ai_tools.clone(),
-        model.clone(),
-        on_token_cb,
-    )?;
-
     let mut text_response_buffer = vec![];
-    if let Some(text_response) = response.1.clone() {
-        text_response_buffer.push(text_response.clone());
-        messages.push(ChatCompletionRequestMessage::Assistant(
-            async_openai::types::ChatCompletionRequestAssistantMessage {
-                content: Some(ChatCompletionRequestAssistantMessageContent::Text(
-                    text_response,
-                )),
-                ..Default::default()
-            },
-        ));
-    }
-
-    while let Some(tool_calls) = response.0 {
-        let mut tool_calls_messages: Vec<async_openai::types::ChatCompletionMessageToolCall> =
-            vec![];
-        let mut tool_response_messages: Vec<async_openai::types::ChatCompletionRequestMessage> =
-            vec![];
-
-        for call in tool_calls {
-            let ToolCall {
-                id,
-                name: function_name,
-                arguments: function_args,
-            } = call;
-
-            let tool_response = tool_set.call_tool(&function_name, &function_args);
+    loop {
+        let response = llm::chat_blocking(provider, &request, on_token)?;
+        if let Some(text_response) = response.text {
+            text_response_buffer.push(text_response.clone());
+            request.messages.push(ChatMessage::Assistant(text_response));
+        }
+        if response.tool_calls.is_empty() {
+            break;
+        }
 
+        let mut tool_response_messages = vec![];
+        for call in response.tool_calls {
+            let tool_response = tool_set.call_tool(&call.name, &call.arguments);
             let tool_response_str = serde_json::to_string(&tool_response)
                 .context("Failed to serialize tool response")?;
+            tool_response_messages.push(ChatMessage::ToolResponse(ToolResponseContent {
+                id: call.id.clone(),
+                result: tool_response_str,
+            }));
+            request.messages.push(ChatMessage::ToolCall(call));
+        }
+        request.messages.extend(tool_response_messages);
+    }
 
-            tool_calls_messages.push(async_openai::types::ChatCompletionMessageToolCall {
-                id: id.clone(),
-                r#type: async_openai::types::ChatCompletionToolType::Function,
-                function: async_openai::types::FunctionCall {
-                    name: function_name,
-                    arguments: function_args,
-                },
-            });
+    let text_response = text_response_buffer
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n\n");
+    Ok((text_response, request.messages))
+}
 
-            tool_response_messages.push(async_openai::types::ChatCompletionRequestMessage::Tool(
-                async_openai::types::ChatCompletionRequestToolMessage {
-                    tool_call_id: id.clone(),
-                    content: async_openai::types::ChatCompletionRequestToolMessageContent::Text(
-                        tool_response_str,
-                    ),
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn requests_serialize_with_tools_schema_and_grouped_tool_calls() -> anyhow::Result<()> {
+        let request = ChatRequest {
+            system_message: "system".into(),
+            messages: vec![
+                ChatMessage::User("commit".into()),
+                ChatMessage::ToolCall(ToolCallContent {
+                    id: "call_1".into(),
+                    name: "get_status".into(),
+                    arguments: "{}".into(),
+                }),
+                ChatMessage::ToolCall(ToolCallContent {
+                    id: "call_2".into(),
+                    name: "get_diff".into(),
+                    arguments: r#"{"path":"a"}"#.into(),
+                }),
+                ChatMessage::ToolResponse(ToolResponseContent {
+                    id: "call_1".into(),
+                    result: "clean".into(),
+                }),
+            ],
+            tools: vec![ToolDefinition {
+                name: "get_status".into(),
+                description: "Get the status".into(),
+                parameters: json!({ "type": "object" }),
+            }],
+            response_schema: Some(ResponseSchema {
+                name: "summary".into(),
+                schema: json!({ "type": "object" }),
+                strict: true,
+            }),
+            model: None,
+        };
+        let body = serde_json::to_value(chat_completion_request("model", &request)?)?;
+
+        assert_eq!(body["model"], "model");
+        assert_eq!(
+            body["messages"],
+            json!([
+                { "role": "system", "content": "system" },
+                { "role": "user", "content": "commit" },
+                {
+                    "role": "assistant",
+                    "tool_calls": [
+                        { "id": "call_1", "type": "function", "function": { "name": "get_status", "arguments": "{}" } },
+                        { "id": "call_2", "type": "function", "function": { "name": "get_diff", "arguments": r#"{"path":"a"}"# } },
+                    ],
                 },
-            ));
-        }
+                { "role": "tool", "tool_call_id": "call_1", "content": "clean" },
+            ]),
+            "consecutive tool calls are made by a single assistant message"
+        );
+        assert_eq!(
+            body["tools"],
+            json!([{
+                "type": "function",
+                "function": {
+                    "name": "get_status",
+                    "description": "Get the status",
+                    "parameters": { "type": "object" },
+                    "strict": false,
+                },
+            }])
+        );
+        assert_eq!(
+            body["response_format"],
+            json!({
+                "type": "json_schema",
+                "json_schema": { "name": "summary", "schema": { "type": "object" }, "strict": true },
+            })
+        );
+        Ok(())
+    }
 
-        messages.push(
-            async_openai::types::ChatCompletionRequestMessage::Assistant(
-                async_openai::types::ChatCompletionRequestAssistantMessage {
-                    tool_calls: Some(tool_calls_messages),
-                    ..Default::default()
+    #[test]
+    fn responses_deserialize_into_text_and_tool_calls() -> anyhow::Result<()> {
+        let response: CreateChatCompletionResponse = serde_json::from_value(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1,
+            "model": "model",
+            "choices": [{
+                "index": 0,
+                "finish_reason": "tool_calls",
+                "message": {
+                    "role": "assistant",
+                    "content": "Checking.",
+                    "tool_calls": [
+                        { "id": "call_1", "type": "function", "function": { "name": "get_status", "arguments": "{}" } },
+                    ],
                 },
-            ),
+            }],
+        }))?;
+        assert_eq!(
+            chat_response(response),
+            ChatResponse {
+                text: Some("Checking.".into()),
+                tool_calls: vec![ToolCallContent {
+                    id: "call_1".into(),
+                    name: "get_status".into(),
+                    arguments: "{}".into(),
+                }],
+            }
         );
 
-        messages.extend(tool_response_messages);
+        let response: CreateChatCompletionResponse = serde_json::from_value(json!({
+            "id": "chatcmpl-2",
+            "object": "chat.completion",
+            "created": 1,
+            "model": "model",
+            "choices": [],
+        }))?;
+        assert_eq!(chat_response(response), ChatResponse::default());
+        Ok(())
+    }
 
-        let on_token_cb = {
-            let on_token = on_token.clone();
-            Box::new(move |token: &str| on_token(token))
-                as Box<dyn Fn(&str) + Send + Sync + 'static>
+    #[test]
+    fn streamed_tool_calls_are_assembled_from_chunks() -> anyhow::Result<()> {
+        let chunk = |delta: serde_json::Value, finish_reason: Option<&str>| {
+            serde_json::from_value::<CreateChatCompletionStreamResponse>(json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion.chunk",
+                "created": 1,
+                "model": "model",
+                "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+            }))
         };
-        response = tool_calling_stream_blocking(
-            provider,
-            messages.clone(),
-            open_ai_tools.clone(),
-            model.clone(),
-            on_token_cb,
-        )?;
-
-        if let Some(text_response) = response.1.clone() {
-            text_response_buffer.push(text_response.clone());
-            messages.push(ChatCompletionRequestMessage::Assistant(
-                async_openai::types::ChatCompletionRequestAssistantMessage {
-                    content: Some(ChatCompletionRequestAssistantMessageContent::Text(
-                        text_response,
-                    )),
-                    ..Default::default()
-                },
-            ));
+        let tokens = Mutex::new(Vec::new());
+        let on_token = |token: &str| tokens.lock().unwrap().push(token.to_owned());
+        let mut streamed = StreamedResponse::default();
+
+        for chunk in [
+            chunk(json!({ "role": "assistant", "content": "Let me " }), None)?,
+            chunk(json!({ "content": "check." }), None)?,
+            chunk(
+                json!({ "tool_calls": [{ "index": 0, "id": "call_1", "type": "function", "function": { "name": "get_diff", "arguments": "{\"pa" } }] }),
+                None,
+            )?,
+            chunk(
+                json!({ "tool_calls": [{ "index": 0, "function": { "arguments": "th\":\"a\"}" } }] }),
+                None,
+            )?,
+        ] {
+            assert_eq!(streamed.push(&chunk, &on_token), None);
         }
+        let response = streamed
+            .push(&chunk(json!({}), Some("tool_calls"))?, &on_token)
+            .expect("the response is complete once the model is done calling tools");
+        assert_eq!(
+            response,
+            ChatResponse {
+                text: Some("Let me check.".into()),
+                tool_calls: vec![ToolCallContent {
+                    id: "call_1".into(),
+                    name: "get_diff".into(),
+                    arguments: r#"{"path":"a"}"#.into(),
+                }],
+            }
+        );
+        assert_eq!(*tokens.lock().unwrap(), ["Let me ", "check."]);
+        Ok(())
     }
-
-    let chat_messages = from_openai_chat_messages(messages);
-    let text_response = text_response_buffer
-        .into_iter()
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<String>>()
-        .join("\n\n");
-    Ok((text_response, chat_messages))
 }
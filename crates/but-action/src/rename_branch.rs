@@ -1,10 +1,12 @@
 use std::vec;
 
-use async_openai::{Client, config::OpenAIConfig};
 use but_workspace::StackId;
 use gitbutler_command_context::CommandContext;
 
-use crate::workflow::{self, Workflow};
+use crate::{
+    llm::LlmProvider,
+    workflow::{self, Workflow},
+};
 
 pub struct RenameBranchParams {
     pub commit_id: gix::ObjectId,
@@ -15,7 +17,7 @@ pub struct RenameBranchParams {
 
 pub async fn rename_branch(
     ctx: &mut CommandContext,
-    client: &Client<OpenAIConfig>,
+    llm: &dyn LlmProvider,
     parameters: RenameBranchParams,
     trigger_id: uuid::Uuid,
 ) -> anyhow::Result<()> {
@@ -38,8 +40,7 @@ pub async fn rename_branch(
 
     let commit_messages = vec![commit_message];
     let branch_name =
-        crate::generate::branch_name(client, &commit_messages, &diffs, &existing_branch_names)
-            .await?;
+        crate::generate::branch_name(llm, &commit_messages, &diffs, &existing_branch_names).await?;
     let normalized_branch_name = gitbutler_reference::normalize_branch_name(&branch_name)?;
 
     let update = gitbutler_branch_actions::stack::update_branch_name(
@@ -1,4 +1,3 @@
-use async_openai::{Client, config::OpenAIConfig};
 use but_graph::VirtualBranchesTomlMetadata;
 use but_settings::AppSettings;
 use but_workspace::{StacksFilter, ui::StackEntry};
@@ -7,7 +6,10 @@ use gitbutler_oxidize::{ObjectIdExt, OidExt};
 use gitbutler_project::Project;
 use uuid::Uuid;
 
-use crate::workflow::{self, Workflow};
+use crate::{
+    llm::LlmProvider,
+    workflow::{self, Workflow},
+};
 
 #[derive(Debug, Clone)]
 pub struct CommitEvent {
@@ -21,7 +23,7 @@ pub struct CommitEvent {
 }
 
 pub async fn commit(
-    client: &Client<OpenAIConfig>,
+    llm: &dyn LlmProvider,
     event: CommitEvent,
 ) -> anyhow::Result<Option<(gix::ObjectId, String)>> {
     let ctx = &mut CommandContext::open(
@@ -32,7 +34,7 @@ pub async fn commit(
     let changes = but_core::diff::ui::commit_changes_by_worktree_dir(repo, event.commit_id)?;
     let diff = changes.try_as_unidiff_string(repo, ctx.app_settings().context_lines)?;
     let message = crate::generate::commit_message(
-        client,
+        llm,
         &event.external_summary,
         &event.external_prompt,
         &diff,
@@ -0,0 +1,32 @@
+#!/usr/bin/env bash
+set -eu -o pipefail
+CLI=${1:?The first argument is the GitButler CLI}
+
+
+git init remote
+(cd remote
+  echo a > file
+  git add . && git commit -m "init"
+)
+
+export GITBUTLER_CLI_DATA_DIR=../user/gitbutler/app-data
+
+# Scenario:
+# - commit 2 (my_stack)
+# - commit 1
+# - uncommitted: new-file
+git clone remote workspace
+(cd workspace
+  git config user.name "Author"
+  git config user.email "author@example.com"
+
+  $CLI project add --switch-to-workspace "$(git rev-parse --symbolic-full-name @{u})"
+  $CLI branch create --set-default my_stack
+
+  echo change1 >> file1
+  $CLI branch commit my_stack -m "commit 1"
+  echo change2 >> file2
+  $CLI branch commit my_stack -m "commit 2"
+
+  echo new > new-file
+)
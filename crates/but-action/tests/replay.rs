@@ -0,0 +1,201 @@
+use std::{path::Path, sync::Arc};
+
+use anyhow::Result;
+use but_action::{
+    ChatMessage, ToolCallContent, ToolResponseContent,
+    cassette::{Player, Recorder},
+    llm::{ChatResponse, MockProvider},
+};
+use but_tools::{emit::Emitter, workspace::ProjectStatus};
+use gitbutler_command_context::CommandContext;
+use tempfile::TempDir;
+
+// The model commits the uncommitted file to the existing branch, and then summarizes what it did.
+#[test]
+fn freestyle_replays_a_hand_written_cassette() -> Result<()> {
+    let (mut ctx, _temp_dir) = command_ctx()?;
+    let llm = Player::open(Path::new("tests/fixtures/cassettes/freestyle-commit.json"))?;
+
+    let response = but_action::freestyle(
+        ctx.project().id,
+        "message".into(),
+        emitter(),
+        &mut ctx,
+        &llm,
+        vec![ChatMessage::User("Commit the new file".into())],
+        None,
+    )?;
+    assert_eq!(response, "Committed new-file to my_stack.");
+    assert_eq!(llm.remaining(), 0, "all interactions were replayed");
+
+    let requests = llm.requests();
+    let Some(ChatMessage::ToolResponse(commit)) = requests[1].messages.last() else {
+        panic!("the result of the commit is passed to the model");
+    };
+    assert!(commit.result.contains("newCommit"), "{}", commit.result);
+
+    let status = status(&mut ctx)?;
+    assert!(status.file_changes.is_empty(), "the new file was committed");
+    let commits = &status.stacks[0].branches[0].commits;
+    assert_eq!(commits.len(), 3);
+    assert_eq!(commits[0].message_title, "Add new-file");
+    assert_eq!(commits[0].message_body, "Add an empty placeholder file.");
+    Ok(())
+}
+
+// The stack and commit ids differ between the recording and the replay, as each runs the fixture script anew.
+#[test]
+fn recorded_squash_replays_against_a_fresh_workspace() -> Result<()> {
+    let tmp = TempDir::new()?;
+    let cassette = tmp.path().join("squash.json");
+    let prompt = "Squash the commits of my_stack";
+
+    let (mut ctx, _temp_dir) = command_ctx()?;
+    let status = status(&mut ctx)?;
+    let stack = &status.stacks[0];
+    let commits = &stack.branches[0].commits;
+    let squash = ToolCallContent {
+        id: "call_1".into(),
+        name: "squash_commits".into(),
+        arguments: serde_json::json!({
+            "stackId": stack.id.to_string(),
+            "sourceCommitIds": [commits[0].id.to_string()],
+            "destinationCommitId": commits[1].id.to_string(),
+            "messageTitle": "Change both files",
+            "messageBody": "",
+        })
+        .to_string(),
+    };
+    let llm = Recorder::new(
+        Box::new(MockProvider::new([
+            ChatResponse {
+                text: None,
+                tool_calls: vec![squash],
+            },
+            ChatResponse {
+                text: Some("Squashed the commits.".into()),
+                tool_calls: vec![],
+            },
+        ])),
+        &cassette,
+    );
+    let response = squash_with(&mut ctx, &llm, prompt)?;
+    assert_eq!(response, "Squashed the commits.");
+    assert_eq!(llm.cassette().interactions.len(), 2);
+    assert_eq!(status_titles(&mut ctx)?, ["Change both files"]);
+
+    let (mut ctx, _temp_dir) = command_ctx()?;
+    let llm = Player::open(&cassette)?;
+    let response = squash_with(&mut ctx, &llm, prompt)?;
+    assert_eq!(response, "Squashed the commits.");
+    assert_eq!(
+        status_titles(&mut ctx)?,
+        ["Change both files"],
+        "the recorded ids were mapped to the ones of this workspace"
+    );
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct Title {
+    title: String,
+}
+
+#[test]
+fn replay_fails_if_a_request_differs_from_the_recording() -> Result<()> {
+    let tmp = TempDir::new()?;
+    let cassette = tmp.path().join("title.json");
+    let recorder = Recorder::new(
+        Box::new(MockProvider::from_values(&[
+            r#"{"title":"Fix the login"}"#.into(),
+        ])?),
+        &cassette,
+    );
+    let title = but_action::structured_output_blocking::<Title>(
+        &recorder,
+        "Suggest a title",
+        vec![ChatMessage::User("The login is broken".into())],
+    )?;
+    assert_eq!(
+        title,
+        Some(Title {
+            title: "Fix the login".into()
+        })
+    );
+
+    let player = Player::open(&cassette)?;
+    let replayed = but_action::structured_output_blocking::<Title>(
+        &player,
+        "Suggest a title",
+        vec![ChatMessage::User("The login is broken".into())],
+    )?;
+    assert_eq!(replayed, title);
+    let err = but_action::structured_output_blocking::<Title>(
+        &player,
+        "Suggest a title",
+        vec![ChatMessage::User("The login is broken".into())],
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("has no response for request 2"),
+        "{err}"
+    );
+
+    let player = Player::open(&cassette)?;
+    let err = but_action::structured_output_blocking::<Title>(
+        &player,
+        "Suggest a title",
+        vec![ChatMessage::User("The signup is broken".into())],
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Request 1 differs from the one recorded"),
+        "{err}"
+    );
+    Ok(())
+}
+
+fn squash_with(
+    ctx: &mut CommandContext,
+    llm: &dyn but_action::llm::LlmProvider,
+    prompt: &str,
+) -> Result<String> {
+    // Like `freestyle()`, pass the status so the model knows the ids of the workspace.
+    let status = serde_json::to_string_pretty(&status(ctx)?)?;
+    let messages = vec![
+        ChatMessage::User(prompt.into()),
+        ChatMessage::ToolCall(ToolCallContent {
+            id: "project_status".into(),
+            name: "get_project_status".into(),
+            arguments: "{\"filterChanges\": null}".into(),
+        }),
+        ChatMessage::ToolResponse(ToolResponseContent {
+            id: "project_status".into(),
+            result: status,
+        }),
+    ];
+    let mut toolset = but_tools::workspace::workspace_toolset(ctx, emitter(), "message".into());
+    but_action::tool_calling_loop(llm, "Squash commits", messages, &mut toolset, None)
+}
+
+fn status(ctx: &mut CommandContext) -> Result<ProjectStatus> {
+    let repo = ctx.gix_repo()?;
+    but_tools::workspace::get_project_status(ctx, &repo, None)
+}
+
+fn status_titles(ctx: &mut CommandContext) -> Result<Vec<String>> {
+    Ok(status(ctx)?.stacks[0].branches[0]
+        .commits
+        .iter()
+        .map(|commit| commit.message_title.clone())
+        .collect())
+}
+
+fn emitter() -> Arc<Emitter> {
+    Arc::new(|_, _| {})
+}
+
+fn command_ctx() -> Result<(CommandContext, TempDir)> {
+    gitbutler_testsupport::writable::fixture("workspace.sh", "workspace")
+}
@@ -1,13 +1,20 @@
 //! In place of commands.rs
+use std::sync::Arc;
+
 use anyhow::Context;
 use anyhow::anyhow;
 use but_api_macros::api_cmd;
+use but_broadcaster::{Broadcaster, FrontendEvent};
 use but_settings::AppSettings;
 use gitbutler_command_context::CommandContext;
+use gitbutler_id::id::Id;
 use gitbutler_project::ProjectId;
 use gitbutler_reference::RemoteRefname;
 use gitbutler_repo::RepositoryExt as _;
 use gitbutler_repo_actions::RepoActionsExt as _;
+use gitbutler_repo_actions::progress::{self, Operation};
+use gitbutler_secret::Sensitive;
+use tokio::sync::Mutex;
 use tracing::instrument;
 
 use crate::error::Error;
@@ -113,3 +120,44 @@ pub fn git_get_global_config(key: String) -> Result<Option<String>, Error> {
         }
     }
 }
+
+/// Store `token` as the access token for HTTP(S) remotes on `host`, which is used when fetching and pushing
+/// without prompting. An empty `token` deletes it.
+#[api_cmd]
+#[tauri::command(async)]
+#[instrument(skip(token), err(Debug))]
+pub fn git_store_token(host: String, token: String) -> Result<(), Error> {
+    Ok(gitbutler_repo_actions::credentials::store_token(
+        &host,
+        Sensitive(token),
+    )?)
+}
+
+/// Cancel the fetch or push with `operation_id`, as seen in its progress events.
+/// Return `true` if it was still running.
+#[api_cmd]
+#[tauri::command(async)]
+#[instrument(err(Debug))]
+pub fn git_cancel_operation(operation_id: Id<Operation>) -> Result<bool, Error> {
+    Ok(progress::cancel(operation_id))
+}
+
+/// Send the progress of all fetches and pushes to the frontend,
+/// as `project://<project-id>/git/progress` events.
+///
+/// Must be called from within a Tokio runtime.
+pub fn broadcast_git_progress(broadcaster: Arc<Mutex<Broadcaster>>) {
+    // Progress is reported from threads that block on their own runtime, so it can't lock the broadcaster itself.
+    let (send, mut recv) = tokio::sync::mpsc::unbounded_channel();
+    progress::init(move |event| {
+        send.send(event).ok();
+    });
+    tokio::spawn(async move {
+        while let Some(event) = recv.recv().await {
+            broadcaster.lock().await.send(FrontendEvent {
+                name: format!("project://{}/git/progress", event.project_id),
+                payload: serde_json::json!(event),
+            });
+        }
+    });
+}
@@ -1,11 +1,10 @@
-use anyhow::{Context as _, Result};
+use anyhow::Result;
 use but_api_macros::api_cmd;
 use but_graph::virtual_branches_legacy_types::BranchOwnershipClaims;
 use but_settings::AppSettings;
 use but_workspace::DiffSpec;
 use gitbutler_branch_actions::{RemoteBranchFile, hooks};
 use gitbutler_command_context::CommandContext;
-use gitbutler_oxidize::ObjectIdExt;
 use gitbutler_project::ProjectId;
 use gitbutler_repo::hooks::{HookResult, MessageHookResult};
 use gitbutler_repo::{FileInfo, RepoCommands};
@@ -116,23 +115,7 @@ pub fn pre_commit_hook_diffspecs(
     let project = gitbutler_project::get(project_id)?;
     let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
 
-    let repository = ctx.gix_repo()?;
-    let head = repository
-        .head_tree_id_or_empty()
-        .context("Failed to get head tree")?;
-
-    let context_lines = ctx.app_settings().context_lines;
-
-    let mut changes = changes.into_iter().map(Ok).collect::<Vec<_>>();
-
-    let (new_tree, ..) = but_workspace::commit_engine::apply_worktree_changes(
-        head.detach(),
-        &repository,
-        &mut changes,
-        context_lines,
-    )?;
-
-    Ok(hooks::pre_commit_with_tree(&ctx, new_tree.to_git2())?)
+    Ok(hooks::pre_commit_with_diff_specs(&ctx, None, &changes)?)
 }
 
 #[api_cmd]
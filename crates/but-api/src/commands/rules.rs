@@ -60,3 +60,26 @@ pub fn list_workspace_rules(project_id: ProjectId) -> Result<Vec<WorkspaceRule>,
     )?;
     list_rules(ctx).map_err(Into::into)
 }
+
+/// Report what the workspace rules with `trigger` would do with the current worktree changes, without changing anything.
+#[api_cmd]
+#[tauri::command(async)]
+#[instrument(err(Debug))]
+pub fn dry_run_workspace_rules(
+    project_id: ProjectId,
+    trigger: but_rules::Trigger,
+) -> Result<Vec<but_rules::handler::RuleOutcome>, Error> {
+    let ctx = &mut CommandContext::open(
+        &gitbutler_project::get(project_id)?,
+        AppSettings::load_from_default_path_creating()?,
+    )?;
+    but_rules::evaluate_rules_for_trigger(ctx, trigger, true).map_err(Into::into)
+}
+
+/// Evaluate the workspace rules with `trigger` if rules are enabled.
+/// Failures are ignored, as rules must never prevent the operation that triggered them.
+pub(crate) fn process_rules_for_trigger(ctx: &mut CommandContext, trigger: but_rules::Trigger) {
+    if ctx.app_settings().feature_flags.rules {
+        but_rules::process_rules_for_trigger(ctx, trigger).ok();
+    }
+}
@@ -259,7 +259,8 @@ pub fn push_stack(
     run_hooks: bool,
 ) -> Result<PushResult, Error> {
     let project = gitbutler_project::get(project_id)?;
-    let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let mut ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    super::rules::process_rules_for_trigger(&mut ctx, but_rules::Trigger::PrePush);
     gitbutler_branch_actions::stack::push_stack(
         &ctx,
         stack_id,
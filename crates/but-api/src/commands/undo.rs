@@ -3,8 +3,9 @@ use but_api_macros::api_cmd;
 use but_settings::AppSettings;
 use gitbutler_command_context::CommandContext;
 use gitbutler_oplog::entry::OperationKind;
-use gitbutler_oplog::{OplogExt, entry::Snapshot};
+use gitbutler_oplog::{OplogExt, StackRestoreOutcome, UndoNode, UndoTree, entry::Snapshot};
 use gitbutler_project::ProjectId;
+use gitbutler_stack::StackId;
 use tracing::instrument;
 
 use crate::error::Error;
@@ -43,6 +44,53 @@ pub fn restore_snapshot(project_id: ProjectId, sha: String) -> Result<(), Error>
     Ok(())
 }
 
+#[api_cmd]
+#[tauri::command(async)]
+#[instrument(err(Debug))]
+pub fn restore_stack_snapshot(
+    project_id: ProjectId,
+    sha: String,
+    stack_id: StackId,
+) -> Result<StackRestoreOutcome, Error> {
+    let project = gitbutler_project::get(project_id).context("failed to get project")?;
+    let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let mut guard = project.exclusive_worktree_access();
+    Ok(ctx.restore_stack_snapshot(
+        sha.parse().map_err(anyhow::Error::from)?,
+        stack_id,
+        guard.write_permission(),
+    )?)
+}
+
+#[api_cmd]
+#[tauri::command(async)]
+#[instrument(err(Debug))]
+pub fn undo_tree(project_id: ProjectId) -> Result<UndoTree, Error> {
+    let project = gitbutler_project::get(project_id).context("failed to get project")?;
+    let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    Ok(ctx.undo_tree()?)
+}
+
+#[api_cmd]
+#[tauri::command(async)]
+#[instrument(err(Debug))]
+pub fn undo_operation(project_id: ProjectId) -> Result<Option<UndoNode>, Error> {
+    let project = gitbutler_project::get(project_id).context("failed to get project")?;
+    let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let mut guard = project.exclusive_worktree_access();
+    Ok(ctx.undo(guard.write_permission())?)
+}
+
+#[api_cmd]
+#[tauri::command(async)]
+#[instrument(err(Debug))]
+pub fn redo_operation(project_id: ProjectId) -> Result<Option<UndoNode>, Error> {
+    let project = gitbutler_project::get(project_id).context("failed to get project")?;
+    let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let mut guard = project.exclusive_worktree_access();
+    Ok(ctx.redo(guard.write_permission())?)
+}
+
 #[api_cmd]
 #[tauri::command(async)]
 #[instrument(err(Debug))]
@@ -5,6 +5,7 @@ use but_workspace::DiffSpec;
 use but_workspace::ui::{StackEntryNoOpt, StackHeadInfo};
 use gitbutler_branch::{BranchCreateRequest, BranchUpdateRequest};
 use gitbutler_branch_actions::branch_upstream_integration::IntegrationStrategy;
+use gitbutler_branch_actions::sync::{SyncApproach, SyncOptions, SyncReport};
 use gitbutler_branch_actions::upstream_integration::{
     BaseBranchResolution, BaseBranchResolutionApproach, IntegrationOutcome, Resolution,
     StackStatuses,
@@ -229,7 +230,8 @@ pub fn set_base_branch(
 #[instrument(err(Debug))]
 pub fn push_base_branch(project_id: ProjectId, with_force: bool) -> Result<(), Error> {
     let project = gitbutler_project::get(project_id)?;
-    let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let mut ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    super::rules::process_rules_for_trigger(&mut ctx, but_rules::Trigger::PrePush);
     gitbutler_branch_actions::push_base_branch(&ctx, with_force)?;
     Ok(())
 }
@@ -543,6 +545,24 @@ pub fn integrate_upstream(
     Ok(outcome)
 }
 
+#[api_cmd]
+#[tauri::command(async)]
+#[instrument(err(Debug))]
+pub fn sync_workspace(
+    project_id: ProjectId,
+    approach: Option<SyncApproach>,
+    push: bool,
+) -> Result<SyncReport, Error> {
+    let project = gitbutler_project::get(project_id)?;
+    let mut ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let report =
+        gitbutler_branch_actions::sync::sync(&mut ctx, SyncOptions { approach, push }, |ctx| {
+            super::rules::process_rules_for_trigger(ctx, but_rules::Trigger::PrePush);
+            Ok(())
+        })?;
+    Ok(report)
+}
+
 #[api_cmd]
 #[tauri::command(async)]
 #[instrument(err(Debug))]
@@ -197,7 +197,7 @@ pub fn create_commit_from_worktree_changes(
     stack_branch_name: String,
 ) -> Result<commit_engine::ui::CreateCommitOutcome, Error> {
     let project = gitbutler_project::get(project_id)?;
-    let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let mut ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
     let mut guard = project.exclusive_worktree_access();
     let snapshot_tree = ctx.prepare_snapshot(guard.read_permission());
 
@@ -220,8 +220,12 @@ pub fn create_commit_from_worktree_changes(
             guard.write_permission(),
         )
     });
+    drop(guard);
 
     let outcome = outcome?;
+    if outcome.new_commit.is_some() {
+        super::rules::process_rules_for_trigger(&mut ctx, but_rules::Trigger::CommitCreated);
+    }
     Ok(outcome.into())
 }
 
@@ -5,7 +5,7 @@ use std::str::FromStr;
 
 use anyhow::{Context, Result, anyhow};
 use but_action::rename_branch::RenameBranchParams;
-use but_action::{ActionHandler, OpenAiProvider, Source, reword::CommitEvent};
+use but_action::{ActionHandler, Source, reword::CommitEvent};
 use but_graph::VirtualBranchesTomlMetadata;
 use but_hunk_assignment::HunkAssignmentRequest;
 use but_settings::AppSettings;
@@ -165,9 +165,7 @@ pub async fn handle_stop() -> anyhow::Result<ClaudeHookOutput> {
     // TODO: Maybe this can be done in the main app process i.e. the GitButler GUI, if avaialbe
     // Alternatively, and probably better - we could spawn a new process to do this
 
-    if let Some(openai_client) =
-        OpenAiProvider::with(None).and_then(|provider| provider.client().ok())
-    {
+    if let Ok(llm) = but_action::llm::for_project(&project, None) {
         for branch in &outcome.updated_branches {
             let mut commit_message_mapping = HashMap::new();
 
@@ -184,7 +182,7 @@ pub async fn handle_stop() -> anyhow::Result<ClaudeHookOutput> {
                         app_settings: defer.ctx.app_settings().clone(),
                         trigger: id,
                     };
-                    let reword_result = but_action::reword::commit(&openai_client, commit_event)
+                    let reword_result = but_action::reword::commit(llm.as_ref(), commit_event)
                         .await
                         .ok()
                         .unwrap_or_default();
@@ -209,7 +207,7 @@ pub async fn handle_stop() -> anyhow::Result<ClaudeHookOutput> {
                         };
                         but_action::rename_branch::rename_branch(
                             defer.ctx,
-                            &openai_client,
+                            llm.as_ref(),
                             params,
                             id,
                         )
@@ -434,6 +432,30 @@ pub fn handle_post_tool_call() -> anyhow::Result<ClaudeHookOutput> {
 
     let _rejections = but_hunk_assignment::assign(defer.ctx, assignment_reqs, None)?;
 
+    if defer.ctx.app_settings().feature_flags.rules {
+        // Rules filtering on this session may assign its changes elsewhere.
+        let (assignments, _assignments_error) =
+            but_hunk_assignment::assignments_with_fallback(defer.ctx, false, Some(changes), None)?;
+        let session_assignments: Vec<_> = assignments
+            .into_iter()
+            .filter(|a| {
+                a.stack_id == Some(stack_id)
+                    && a.path.to_lowercase() == relative_file_path.to_lowercase()
+            })
+            .collect();
+        but_rules::handler::process_workspace_rules_in_context(
+            defer.ctx,
+            &but_rules::Trigger::FileSytemChange,
+            &session_assignments,
+            &None,
+            &but_rules::handler::MatchContext {
+                session_id: Some(&session_id),
+                classifier: None,
+            },
+        )
+        .ok();
+    }
+
     Ok(ClaudeHookOutput {
         do_continue: true,
         stop_reason: String::default(),
@@ -1,5 +1,4 @@
 use but_action::ActionHandler;
-use but_action::OpenAiProvider;
 use but_action::Source;
 use but_action::reword::CommitEvent;
 use but_graph::VirtualBranchesTomlMetadata;
@@ -226,9 +225,7 @@ pub async fn handle_stop(nightly: bool) -> anyhow::Result<CursorHookOutput> {
     // TODO: Maybe this can be done in the main app process i.e. the GitButler GUI, if avaialbe
     // Alternatively, and probably better - we could spawn a new process to do this
 
-    if let Some(openai_client) =
-        OpenAiProvider::with(None).and_then(|provider| provider.client().ok())
-    {
+    if let Ok(llm) = but_action::llm::for_project(&project, None) {
         for branch in &outcome.updated_branches {
             let mut commit_message_mapping = HashMap::new();
 
@@ -246,7 +243,7 @@ pub async fn handle_stop(nightly: bool) -> anyhow::Result<CursorHookOutput> {
                         app_settings: ctx.app_settings().clone(),
                         trigger: id,
                     };
-                    let reword_result = but_action::reword::commit(&openai_client, commit_event)
+                    let reword_result = but_action::reword::commit(llm.as_ref(), commit_event)
                         .await
                         .ok()
                         .unwrap_or_default();
@@ -269,7 +266,7 @@ pub async fn handle_stop(nightly: bool) -> anyhow::Result<CursorHookOutput> {
                             stack_id: branch.stack_id,
                             current_branch_name: branch.branch_name.clone(),
                         };
-                        but_action::rename_branch::rename_branch(ctx, &openai_client, params, id)
+                        but_action::rename_branch::rename_branch(ctx, llm.as_ref(), params, id)
                             .await
                             .ok();
                     }
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE `cli_ids`(
+	`generation` TEXT NOT NULL,
+	`short_id` TEXT NOT NULL,
+	`kind` TEXT NOT NULL,
+	`target` TEXT NOT NULL,
+	`created_at` TIMESTAMP NOT NULL,
+	PRIMARY KEY(`generation`, `short_id`)
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS `idx_rule_evaluations_rule_id`;
+DROP TABLE IF EXISTS `rule_evaluations`;
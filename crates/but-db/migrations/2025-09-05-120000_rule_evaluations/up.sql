@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE `rule_evaluations`(
+	`id` TEXT NOT NULL PRIMARY KEY,
+	`created_at` TIMESTAMP NOT NULL,
+	`rule_id` TEXT NOT NULL,
+	`trigger` TEXT NOT NULL,
+	`action` TEXT NOT NULL,
+	`changes` TEXT NOT NULL,
+	`description` TEXT NOT NULL,
+	`executed` BOOL NOT NULL,
+	`snapshot_id` TEXT,
+	`error` TEXT
+);
+
+CREATE INDEX `idx_rule_evaluations_rule_id` ON `rule_evaluations`(`rule_id`);
@@ -0,0 +1,61 @@
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use crate::DbHandle;
+use crate::schema::cli_ids::dsl::cli_ids;
+
+use diesel::prelude::{Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+/// A short id handed out by the CLI, pointing to a file, branch or commit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::cli_ids)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct CliId {
+    /// The workspace generation the id is valid in. Ids of other generations are stale.
+    pub generation: String,
+    /// The short id as shown to the user, unique within its `generation`.
+    pub short_id: String,
+    /// The kind of entity the id refers to.
+    pub kind: String,
+    /// The serialized identity of the entity, interpreted according to `kind`.
+    pub target: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl DbHandle {
+    pub fn cli_ids(&mut self) -> CliIdsHandle<'_> {
+        CliIdsHandle { db: self }
+    }
+}
+
+pub struct CliIdsHandle<'a> {
+    db: &'a mut DbHandle,
+}
+
+impl CliIdsHandle<'_> {
+    /// List all ids that were handed out in `generation`.
+    pub fn list(&mut self, generation: &str) -> Result<Vec<CliId>, diesel::result::Error> {
+        let ids = cli_ids
+            .filter(crate::schema::cli_ids::generation.eq(generation))
+            .load::<CliId>(&mut self.db.conn)?;
+        Ok(ids)
+    }
+
+    /// Insert all `ids` at once, failing if any of them is already taken.
+    pub fn insert(&mut self, ids: Vec<CliId>) -> Result<(), diesel::result::Error> {
+        use diesel::Connection;
+        self.db.conn.transaction(|conn| {
+            for id in ids {
+                diesel::insert_into(cli_ids).values(id).execute(conn)?;
+            }
+            diesel::result::QueryResult::Ok(())
+        })
+    }
+
+    /// Delete all ids that don't belong to `generation`, as they can never be valid again.
+    pub fn delete_all_but(&mut self, generation: &str) -> Result<(), diesel::result::Error> {
+        diesel::delete(cli_ids.filter(crate::schema::cli_ids::generation.ne(generation)))
+            .execute(&mut self.db.conn)?;
+        Ok(())
+    }
+}
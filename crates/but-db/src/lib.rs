@@ -19,6 +19,10 @@ mod file_write_locks;
 pub use file_write_locks::FileWriteLock;
 mod workspace_rules;
 pub use workspace_rules::WorkspaceRule;
+mod cli_ids;
+pub use cli_ids::CliId;
+mod rule_evaluations;
+pub use rule_evaluations::{EVALUATIONS_PER_RULE, RuleEvaluation};
 
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations");
@@ -0,0 +1,91 @@
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+
+use crate::DbHandle;
+use crate::schema::rule_evaluations::dsl::rule_evaluations;
+
+use diesel::prelude::{Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+/// The amount of evaluations that are kept per rule. Older ones are deleted whenever a new one is inserted.
+pub const EVALUATIONS_PER_RULE: i64 = 100;
+
+/// A record of a workspace rule that matched changes, and what its action did with them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::rule_evaluations)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RuleEvaluation {
+    pub id: String,
+    pub created_at: chrono::NaiveDateTime,
+    /// The ID of the rule that was evaluated.
+    pub rule_id: String,
+    /// The serialized trigger that caused the evaluation.
+    pub trigger: String,
+    /// The serialized action of the rule at the time of the evaluation.
+    pub action: String,
+    /// The serialized list of changes the rule matched.
+    pub changes: String,
+    /// A human readable description of what the action did.
+    pub description: String,
+    /// Whether the action was performed.
+    pub executed: bool,
+    /// The oplog snapshot taken before the action was performed, if any.
+    pub snapshot_id: Option<String>,
+    /// The error the action failed with, if any.
+    pub error: Option<String>,
+}
+
+impl DbHandle {
+    pub fn rule_evaluations(&mut self) -> RuleEvaluationsHandle<'_> {
+        RuleEvaluationsHandle { db: self }
+    }
+}
+
+pub struct RuleEvaluationsHandle<'a> {
+    db: &'a mut DbHandle,
+}
+
+impl RuleEvaluationsHandle<'_> {
+    /// Insert `evaluation`, and delete the oldest evaluations of the same rule so no more than
+    /// [`EVALUATIONS_PER_RULE`] are kept.
+    pub fn insert(&mut self, evaluation: RuleEvaluation) -> Result<(), diesel::result::Error> {
+        use crate::schema::rule_evaluations::{created_at, rule_id};
+        let evaluated_rule_id = evaluation.rule_id.clone();
+        self.db.conn.transaction(|conn| {
+            diesel::insert_into(rule_evaluations)
+                .values(evaluation)
+                .execute(conn)?;
+            let newest_to_delete = rule_evaluations
+                .filter(rule_id.eq(&evaluated_rule_id))
+                .order(created_at.desc())
+                .offset(EVALUATIONS_PER_RULE)
+                .select(created_at)
+                .first::<chrono::NaiveDateTime>(conn)
+                .optional()?;
+            if let Some(newest_to_delete) = newest_to_delete {
+                diesel::delete(
+                    rule_evaluations
+                        .filter(rule_id.eq(&evaluated_rule_id))
+                        .filter(created_at.le(newest_to_delete)),
+                )
+                .execute(conn)?;
+            }
+            diesel::result::QueryResult::Ok(())
+        })
+    }
+
+    /// List the `limit` most recent evaluations, newest first, optionally only those of the rule with `rule_id`.
+    pub fn list(
+        &mut self,
+        rule_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<RuleEvaluation>, diesel::result::Error> {
+        let mut query = rule_evaluations
+            .order(crate::schema::rule_evaluations::created_at.desc())
+            .limit(limit)
+            .into_boxed();
+        if let Some(rule_id) = rule_id {
+            query = query.filter(crate::schema::rule_evaluations::rule_id.eq(rule_id.to_owned()));
+        }
+        query.load::<RuleEvaluation>(&mut self.db.conn)
+    }
+}
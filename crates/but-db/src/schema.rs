@@ -86,3 +86,28 @@ diesel::table! {
         approved -> Nullable<Bool>,
     }
 }
+
+diesel::table! {
+    cli_ids (generation, short_id) {
+        generation -> Text,
+        short_id -> Text,
+        kind -> Text,
+        target -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    rule_evaluations (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        rule_id -> Text,
+        trigger -> Text,
+        action -> Text,
+        changes -> Text,
+        description -> Text,
+        executed -> Bool,
+        snapshot_id -> Nullable<Text>,
+        error -> Nullable<Text>,
+    }
+}
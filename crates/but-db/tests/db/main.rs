@@ -47,3 +47,5 @@ fn init_in_parallel() -> anyhow::Result<()> {
     });
     Ok(())
 }
+
+mod rule_evaluations;
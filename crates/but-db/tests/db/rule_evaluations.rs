@@ -0,0 +1,48 @@
+use but_db::{DbHandle, EVALUATIONS_PER_RULE, RuleEvaluation};
+
+fn evaluation(rule_id: &str, n: i64) -> RuleEvaluation {
+    RuleEvaluation {
+        id: format!("{rule_id}-{n}"),
+        created_at: chrono::DateTime::from_timestamp(1_700_000_000 + n, 0)
+            .expect("valid")
+            .naive_utc(),
+        rule_id: rule_id.into(),
+        trigger: "\"fileSytemChange\"".into(),
+        action: "{}".into(),
+        changes: "[]".into(),
+        description: format!("evaluation {n}"),
+        executed: true,
+        snapshot_id: None,
+        error: None,
+    }
+}
+
+#[test]
+fn only_the_newest_evaluations_of_each_rule_are_kept() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let mut db = DbHandle::new_in_directory(tmp.path())?;
+    let excess = 5;
+    for n in 0..EVALUATIONS_PER_RULE + excess {
+        db.rule_evaluations().insert(evaluation("a", n))?;
+    }
+    db.rule_evaluations().insert(evaluation("b", 0))?;
+
+    let kept = db.rule_evaluations().list(Some("a"), i64::MAX)?;
+    assert_eq!(kept.len() as i64, EVALUATIONS_PER_RULE);
+    assert_eq!(
+        kept.first().map(|e| e.id.as_str()),
+        Some(&*format!("a-{}", EVALUATIONS_PER_RULE + excess - 1)),
+        "the newest comes first"
+    );
+    assert_eq!(
+        kept.last().map(|e| e.id.as_str()),
+        Some(&*format!("a-{excess}")),
+        "the oldest ones were deleted"
+    );
+    assert_eq!(
+        db.rule_evaluations().list(Some("b"), i64::MAX)?.len(),
+        1,
+        "evaluations of other rules are unaffected"
+    );
+    Ok(())
+}
@@ -5,7 +5,7 @@
 
 use crate::commit::DateMode;
 use anyhow::{Context, Ok, Result, anyhow, bail};
-use bstr::BString;
+use bstr::{BStr, BString, ByteSlice};
 use gix::objs::Exists;
 use gix::prelude::ObjectIdExt;
 use tracing::instrument;
@@ -29,9 +29,13 @@ pub enum RebaseStep {
         commit_id: gix::ObjectId,
         /// Optional message to use for newly produced commit
         new_message: Option<BString>,
-        // TODO: add `base: Option<ObjectId>` to allow restarting the sequence at a new base
-        //       for multi-branch rebasing. It would keep the previous cursor, to allow the last
-        //       branch to contain a pick of the merge commit on top, which it can then correctly re-merge.
+        /// If set, restart the sequence at this commit, so the picked commit is placed on top of `base`
+        /// instead of on top of the commit produced by the previous step.
+        ///
+        /// This allows to rebase multiple branches in one go, with each branch starting a sequence at its own base.
+        /// Commits rewritten in previous sequences are remembered, so a merge commit that is picked
+        /// later, like the workspace commit on top of all branches, is re-merged with all of the rewritten branches.
+        base: Option<gix::ObjectId>,
     },
     /// Squashes an existing commit into the one in the first `Pick` or `Merge` RebaseStep that precedes it.
     ///
@@ -46,6 +50,30 @@ pub enum RebaseStep {
         /// Optional message to use for newly produced commit
         new_message: Option<BString>,
     },
+    /// Like [`RebaseStep::SquashIntoPreceding`], but keeps the message of the commit that is squashed into,
+    /// discarding the one of `commit_id`.
+    Fixup {
+        /// Id of an already existing commit
+        commit_id: gix::ObjectId,
+    },
+    /// Pick an existing commit just like [`RebaseStep::Pick`], to mark it as the commit the user wants to edit.
+    ///
+    /// The rebase itself can't stop here as it never touches the worktree. Instead, the caller is expected to
+    /// end the steps at the edit, hand control to the user at the rewritten commit, found via
+    /// [`RebaseOutput::commit_mapping`], and only perform the remaining steps on top of the amended commit
+    /// once the user is done.
+    Edit {
+        /// Id of an already existing commit
+        commit_id: gix::ObjectId,
+    },
+    /// Run `command` with a shell in a temporary worktree holding the tree of the commit produced by the preceding step.
+    ///
+    /// The rebase is aborted if the command fails, which makes it possible to assure each commit passes a check,
+    /// like building or testing successfully.
+    Exec {
+        /// The command to run, interpreted by the shell.
+        command: BString,
+    },
     /// Create a new reference pointing to the commit that precedes this step.
     /// If this is the first step in the list, the reference will be to the `base` commit.
     /// If the step before this one is another `Reference` step, this reference will point to the same commit.
@@ -57,8 +85,10 @@ impl RebaseStep {
     pub fn commit_id(&self) -> Option<&gix::oid> {
         match self {
             RebaseStep::Pick { commit_id, .. }
-            | RebaseStep::SquashIntoPreceding { commit_id, .. } => Some(commit_id),
-            RebaseStep::Reference { .. } => None,
+            | RebaseStep::SquashIntoPreceding { commit_id, .. }
+            | RebaseStep::Fixup { commit_id }
+            | RebaseStep::Edit { commit_id } => Some(commit_id),
+            RebaseStep::Exec { .. } | RebaseStep::Reference { .. } => None,
         }
     }
 }
@@ -160,22 +190,38 @@ impl Rebase<'_> {
     ///
     /// Pick and Merge operations:
     /// - The commit must not be a commit that is already in a pick, merge or fixup step
+    /// - The `base` to restart the sequence at must exist, if set
     ///
     /// Fixup operations:
     /// - Must not be a reference step immediately before it
     /// - Must not be the first operation
     ///
+    /// Exec operations:
+    /// - The command must not be empty
+    /// - Must not be the first operation
+    ///
     /// Reference operations:
     /// - The refname must be a valid reference name
     fn validate_step(&self, step: &RebaseStep) -> Result<()> {
         match step {
-            RebaseStep::Pick { commit_id, .. } => {
+            RebaseStep::Pick {
+                commit_id, base, ..
+            } => {
+                self.assure_unique_step_and_existing_non_base(commit_id, "Picked")?;
+                if let Some(base) = base {
+                    self.repo
+                        .find_commit(*base)
+                        .with_context(|| format!("Base of picked commit {commit_id} must exist"))?;
+                }
+            }
+            RebaseStep::Edit { commit_id } => {
                 self.assure_unique_step_and_existing_non_base(commit_id, "Picked")?;
             }
             RebaseStep::SquashIntoPreceding {
                 commit_id,
                 new_message: _,
-            } => {
+            }
+            | RebaseStep::Fixup { commit_id } => {
                 self.assure_unique_step_and_existing_non_base(commit_id, "Fixup")?;
                 if matches!(self.steps.last(), Some(RebaseStep::Reference { .. })) {
                     bail!("Fixup commit must not come after a reference step");
@@ -184,6 +230,14 @@ impl Rebase<'_> {
                     bail!("Fixup must have a commit to work on");
                 }
             }
+            RebaseStep::Exec { command } => {
+                if command.trim().is_empty() {
+                    bail!("Exec step must have a non-empty command");
+                }
+                if self.steps.is_empty() && self.base.is_none() {
+                    bail!("Exec step must have a commit to run on");
+                }
+            }
             RebaseStep::Reference(name) => {
                 if matches!(name, but_core::Reference::Virtual(name) if name.is_empty()) {
                     return Err(anyhow!(
@@ -224,14 +278,40 @@ fn rebase(
         Vec::<(Option<gix::ObjectId>, gix::ObjectId, gix::ObjectId)>::new(),
     );
     let (mut cursor, mut last_seen_commit) = (base, base);
+    // The base of the current sequence, and the index into `commit_mapping` at which the sequence starts.
+    let (mut sequence_base, mut sequence_start) = (base, 0);
     let cache = repo.commit_graph_if_enabled()?;
     let mut graph = repo.revision_graph(cache.as_ref());
     for step in steps {
+        let step = match step {
+            RebaseStep::Edit { commit_id } => RebaseStep::Pick {
+                commit_id,
+                new_message: None,
+                base: None,
+            },
+            RebaseStep::Fixup { commit_id } => RebaseStep::SquashIntoPreceding {
+                commit_id,
+                new_message: Some(
+                    to_commit(
+                        repo,
+                        cursor.context("Can't fixup if previous commit is missing")?,
+                    )?
+                    .message,
+                ),
+            },
+            step => step,
+        };
         match step {
             RebaseStep::Pick {
                 commit_id,
                 new_message,
+                base: new_base,
             } => {
+                if let Some(new_base) = new_base {
+                    cursor = Some(new_base);
+                    sequence_base = Some(new_base);
+                    sequence_start = commit_mapping.len();
+                }
                 // This should be the source commit id
                 last_seen_commit = Some(commit_id);
 
@@ -241,21 +321,35 @@ fn rebase(
                     if let Some(new_message) = new_message {
                         merge_commit.message = new_message;
                     }
-                    // Find any parent that we have seen during picking.
-                    let parent_to_replace = match merge_commit.parents.iter_mut().find(|id| {
-                        (Some(**id) == base_substitute)
-                            || commit_mapping.iter().any(|(mapping_base, old, _new)| {
-                                *mapping_base == base && (*id == old)
-                            })
-                    }) {
-                        None => merge_commit
-                            .parents
-                            .iter_mut()
-                            .next()
-                            .expect("more than one parents"),
-                        Some(parent) => parent,
-                    };
-                    *parent_to_replace = cursor.context("Expecting a base for any merge")?;
+                    let (previous_sequences, current_sequence) =
+                        commit_mapping.split_at(sequence_start);
+                    // Parents that were rewritten in previous sequences are replaced with their rewritten version.
+                    let mut parents_in_current_sequence = Vec::new();
+                    for parent in merge_commit.parents.iter_mut() {
+                        match previous_sequences
+                            .iter()
+                            .rev()
+                            .find_map(|(_base, old, new)| (*old == *parent).then_some(*new))
+                        {
+                            Some(rewritten) => *parent = rewritten,
+                            None => parents_in_current_sequence.push(parent),
+                        }
+                    }
+                    // Find any parent that we have seen during picking in the current sequence.
+                    let idx = parents_in_current_sequence
+                        .iter()
+                        .position(|id| {
+                            (Some(**id) == base_substitute)
+                                || current_sequence.iter().any(|(mapping_base, old, _new)| {
+                                    *mapping_base == sequence_base && (*id == old)
+                                })
+                        })
+                        .unwrap_or_default();
+                    let parent_to_replace = parents_in_current_sequence.into_iter().nth(idx);
+                    // If all parents were rewritten in previous sequences, the merge is repeated as is.
+                    if let Some(parent_to_replace) = parent_to_replace {
+                        *parent_to_replace = cursor.context("Expecting a base for any merge")?;
+                    }
                     cursor = merge::octopus(repo, merge_commit, &mut graph)
                         .context(
                             "The rebase failed as a merge could not be repeated without conflicts",
@@ -321,6 +415,13 @@ fn rebase(
                 }
                 *cursor = commit::create(repo, new_commit, DateMode::CommitterUpdateAuthorKeep)?;
             }
+            RebaseStep::Exec { command } => {
+                let commit_id = cursor.context("Can't execute a command without a commit")?;
+                exec_in_temporary_worktree(repo, commit_id, command.as_ref())?;
+            }
+            RebaseStep::Edit { .. } | RebaseStep::Fixup { .. } => {
+                unreachable!("turned into picks and squashes above")
+            }
             RebaseStep::Reference(reference) => {
                 references.push(ReferenceSpec {
                     reference,
@@ -332,7 +433,7 @@ fn rebase(
             }
         }
         if let Some((old, new)) = last_seen_commit.zip(cursor) {
-            commit_mapping.push((base, old, new));
+            commit_mapping.push((sequence_base, old, new));
         }
     }
 
@@ -366,6 +467,50 @@ fn reword_commit(
     )?)
 }
 
+/// Check out the tree of `commit_id` into a temporary directory and run `command` in it using a shell,
+/// failing if it doesn't succeed.
+fn exec_in_temporary_worktree(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+    command: &BStr,
+) -> Result<()> {
+    let tree_id = repo.find_commit(commit_id)?.tree_id()?;
+    let mut index = repo.index_from_tree(&tree_id)?;
+    let mut opts =
+        repo.checkout_options(gix::worktree::stack::state::attributes::Source::IdMapping)?;
+    opts.destination_is_initially_empty = true;
+    let worktree = tempfile::tempdir()?;
+    gix::worktree::state::checkout(
+        &mut index,
+        worktree.path(),
+        repo.clone().objects.into_arc()?,
+        &gix::progress::Discard,
+        &gix::progress::Discard,
+        &gix::interrupt::IS_INTERRUPTED,
+        opts,
+    )?;
+
+    let mut cmd: std::process::Command =
+        gix::command::prepare(gix::path::from_bstr(command).into_owned())
+            .with_shell()
+            .into();
+    let output = cmd
+        .current_dir(worktree.path())
+        .stdin(std::process::Stdio::null())
+        .output()
+        .with_context(|| format!("Could not run '{command}'"))?;
+    if !output.status.success() {
+        bail!(
+            "Command '{command}' failed on commit {} with {}:\n{}{}",
+            commit_id.to_hex_with_len(7),
+            output.status,
+            output.stdout.as_bstr(),
+            output.stderr.as_bstr()
+        );
+    }
+    Ok(())
+}
+
 /// Replaces the tree of a commit for use in the rebase engine.
 pub fn replace_commit_tree(
     repo: &gix::Repository,
@@ -25,6 +25,7 @@ fn non_existing_commit_in_pick_step() -> anyhow::Result<()> {
     let result = builder.steps([RebaseStep::Pick {
         commit_id: non_existing_commit(),
         new_message: None,
+        base: None,
     }]);
     assert_eq!(
         result.unwrap_err().to_string(),
@@ -86,10 +87,12 @@ fn using_picked_commit_in_a_pick_step() -> anyhow::Result<()> {
         RebaseStep::Pick {
             commit_id: commits.a,
             new_message: None,
+            base: None,
         },
         RebaseStep::Pick {
             commit_id: commits.a,
             new_message: None,
+            base: None,
         },
     ]);
     assert_eq!(
@@ -107,6 +110,7 @@ fn using_fixup_commit_in_a_pick_step() -> anyhow::Result<()> {
         RebaseStep::Pick {
             commit_id: commits.a,
             new_message: None,
+            base: None,
         },
         RebaseStep::SquashIntoPreceding {
             commit_id: commits.b,
@@ -115,6 +119,7 @@ fn using_fixup_commit_in_a_pick_step() -> anyhow::Result<()> {
         RebaseStep::Pick {
             commit_id: commits.b,
             new_message: None,
+            base: None,
         },
     ]);
     assert_eq!(
@@ -132,6 +137,7 @@ fn using_picked_commit_in_a_fixup_step() -> anyhow::Result<()> {
         RebaseStep::Pick {
             commit_id: commits.a,
             new_message: None,
+            base: None,
         },
         RebaseStep::SquashIntoPreceding {
             commit_id: commits.a,
@@ -153,6 +159,7 @@ fn using_fixup_commit_in_a_fixup_step() -> anyhow::Result<()> {
         RebaseStep::Pick {
             commit_id: commits.a,
             new_message: None,
+            base: None,
         },
         RebaseStep::SquashIntoPreceding {
             commit_id: commits.b,
@@ -203,6 +210,64 @@ fn fixup_is_only_preceeded_by_a_reference_step() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn fixup_variant_is_first_step() -> anyhow::Result<()> {
+    let (repo, commits) = four_commits()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let result = builder.steps([RebaseStep::Fixup {
+        commit_id: commits.a,
+    }]);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Fixup must have a commit to work on"
+    );
+    Ok(())
+}
+
+#[test]
+fn exec_is_first_step_without_base() -> anyhow::Result<()> {
+    let (repo, _commits) = four_commits()?;
+    let mut builder = Rebase::new(&repo, None, None)?;
+    let result = builder.steps([RebaseStep::Exec {
+        command: "true".into(),
+    }]);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Exec step must have a commit to run on"
+    );
+    Ok(())
+}
+
+#[test]
+fn empty_exec_step() -> anyhow::Result<()> {
+    let (repo, commits) = four_commits()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let result = builder.steps([RebaseStep::Exec {
+        command: " ".into(),
+    }]);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Exec step must have a non-empty command"
+    );
+    Ok(())
+}
+
+#[test]
+fn non_existing_base_in_pick_step() -> anyhow::Result<()> {
+    let (repo, commits) = four_commits()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let result = builder.steps([RebaseStep::Pick {
+        commit_id: commits.a,
+        new_message: None,
+        base: Some(non_existing_commit()),
+    }]);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        format!("Base of picked commit {} must exist", commits.a)
+    );
+    Ok(())
+}
+
 #[test]
 fn empty_reference_step() -> anyhow::Result<()> {
     let (repo, commits) = four_commits()?;
@@ -76,6 +76,7 @@ fn single_stack_journey() -> Result<()> {
             RebaseStep::Pick {
                 commit_id: commits.a,
                 new_message: Some("first step: pick a".into()),
+                base: None,
             },
             RebaseStep::SquashIntoPreceding {
                 commit_id: commits.b,
@@ -169,12 +170,14 @@ fn amended_commit() -> Result<()> {
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C")?.into(),
                 new_message: Some("C: add another 10 lines to new file - amended".into()),
+                base: None,
             },
             // Picking a merge commit means to repeat the merge with the latest rewritten commit
             // from the previous step.
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("main")?.into(),
                 new_message: Some("Merge branches 'A', 'B' and 'C' - rewritten".into()),
+                base: None,
             },
         ])?
         .rebase()?;
@@ -241,16 +244,19 @@ fn reorder_merge_in_reverse() -> Result<()> {
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("with-inner-merge~1")?.into(),
                 new_message: Some("was merge 2fc288c one below top".into()),
+                base: None,
             },
             // Pick top
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("with-inner-merge")?.into(),
                 new_message: Some("was e8ee978 on top".into()),
+                base: None,
             },
             // Pick one above the base (to be the new top)
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("with-inner-merge~2")?.into(),
                 new_message: Some("was dd59d2 below merge".into()),
+                base: None,
             },
         ])?
         .rebase()
@@ -323,19 +329,23 @@ fn reorder_with_conflict_and_remerge_and_pick_from_conflicts() -> Result<()> {
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C~2")?.into(),
                 new_message: Some("C~2".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C")?.into(),
                 new_message: Some("C".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 // This will conflict,
                 commit_id: repo.rev_parse_single("C~1")?.into(),
                 new_message: Some("C~1".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("main")?.into(),
                 new_message: Some("Re-merge branches 'A', 'B' and 'C'".into()),
+                base: None,
             },
         ])?
         .rebase()?;
@@ -475,6 +485,7 @@ fn reorder_with_conflict_and_remerge_and_pick_from_conflicts() -> Result<()> {
         .steps([RebaseStep::Pick {
             commit_id: repo.rev_parse_single("C~2")?.into(),
             new_message: Some("picked on top of conflicted base".into()),
+            base: None,
         }])?
         .rebase()?;
 
@@ -513,18 +524,22 @@ fn reversible_conflicts() -> anyhow::Result<()> {
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C~2")?.into(),
                 new_message: Some("C~2".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C")?.into(),
                 new_message: Some("C".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C~1")?.into(),
                 new_message: Some("C~1".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("main")?.into(),
                 new_message: Some("Re-merge branches 'A', 'B' and 'C'".into()),
+                base: None,
             },
         ])?
         .rebase()?;
@@ -541,18 +556,22 @@ fn reversible_conflicts() -> anyhow::Result<()> {
                 RebaseStep::Pick {
                     commit_id: repo.rev_parse_single("C~2")?.into(),
                     new_message: Some("C~2".into()),
+                    base: None,
                 },
                 RebaseStep::Pick {
                     commit_id: repo.rev_parse_single("C~1")?.into(),
                     new_message: Some("C~1".into()),
+                    base: None,
                 },
                 RebaseStep::Pick {
                     commit_id: repo.rev_parse_single("C")?.into(),
                     new_message: Some("C".into()),
+                    base: None,
                 },
                 RebaseStep::Pick {
                     commit_id: repo.rev_parse_single("main")?.into(),
                     new_message: Some("Re-merge branches 'A', 'B' and 'C'".into()),
+                    base: None,
                 },
             ])?
             .rebase()?;
@@ -573,6 +592,7 @@ fn reversible_conflicts() -> anyhow::Result<()> {
             .steps([RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C")?.into(),
                 new_message: Some("C~1".into()),
+                base: None,
             }])?
             .rebase()?;
         assert_eq!(conflicted(&repo, &out), [false]);
@@ -611,18 +631,22 @@ fn reversible_conflicts() -> anyhow::Result<()> {
                     .rev_parse_single(format!("{conflict_tip}~2").as_str())?
                     .into(),
                 new_message: Some("C~2 is first".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: conflict_tip.detach(),
                 new_message: Some("This commit is now unconflicted".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C")?.into(),
                 new_message: Some("The original C will fit right on top".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: out.top_commit,
                 new_message: Some("Re-merge branches 'A', 'B' and 'C'".into()),
+                base: None,
             },
         ])?
         .rebase()?;
@@ -650,6 +674,7 @@ fn pick_the_first_commit_with_no_parents_for_squashing() -> Result<()> {
             RebaseStep::Pick {
                 commit_id: commits.base,
                 new_message: Some("reword base".into()),
+                base: None,
             },
             RebaseStep::SquashIntoPreceding {
                 commit_id: commits.a,
@@ -680,6 +705,212 @@ fn pick_the_first_commit_with_no_parents_for_squashing() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn fixup_keeps_the_message_of_the_preceding_commit() -> Result<()> {
+    assure_stable_env();
+    let (repo, commits, _tmp) = four_commits_writable()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let out = builder
+        .steps([
+            RebaseStep::Pick {
+                commit_id: commits.a,
+                new_message: Some("reworded a".into()),
+                base: None,
+            },
+            RebaseStep::Fixup {
+                commit_id: commits.b,
+            },
+        ])?
+        .rebase()?;
+    let top = repo.find_commit(out.top_commit)?;
+    assert_eq!(
+        top.message_raw()?,
+        "reworded a",
+        "the message of the fixup commit is discarded"
+    );
+    assert_eq!(
+        top.parent_ids().map(|id| id.detach()).collect::<Vec<_>>(),
+        [commits.base],
+        "the fixup commit is melded into the preceding one"
+    );
+    assert!(
+        top.tree()?.find_entry("b").is_some(),
+        "the changes of the fixup commit are kept"
+    );
+    assure_nonconflicting(&repo, &out)?;
+    Ok(())
+}
+
+#[test]
+fn edit_picks_the_commit() -> Result<()> {
+    assure_stable_env();
+    let (repo, commits, _tmp) = four_commits_writable()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let out = builder
+        .steps([
+            RebaseStep::Edit {
+                commit_id: commits.b,
+            },
+            RebaseStep::Pick {
+                commit_id: commits.a,
+                new_message: None,
+                base: None,
+            },
+        ])?
+        .rebase()?;
+    let edited = out
+        .commit_mapping
+        .iter()
+        .find_map(|(_base, old, new)| (*old == commits.b).then_some(*new))
+        .expect("the edited commit is rewritten");
+    let edited = repo.find_commit(edited)?;
+    assert_eq!(edited.message_raw()?, "b\n");
+    assert_eq!(
+        edited
+            .parent_ids()
+            .map(|id| id.detach())
+            .collect::<Vec<_>>(),
+        [commits.base]
+    );
+    assure_nonconflicting(&repo, &out)?;
+    Ok(())
+}
+
+#[test]
+fn exec_runs_in_a_worktree_of_the_preceding_commit() -> Result<()> {
+    assure_stable_env();
+    let (repo, commits, _tmp) = four_commits_writable()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let out = builder
+        .steps([
+            RebaseStep::Pick {
+                commit_id: commits.a,
+                new_message: None,
+                base: None,
+            },
+            RebaseStep::Exec {
+                command: "test -f base && test -f a && test ! -f b".into(),
+            },
+            RebaseStep::Pick {
+                commit_id: commits.b,
+                new_message: None,
+                base: None,
+            },
+            RebaseStep::Exec {
+                command: "test -f b".into(),
+            },
+        ])?
+        .rebase()?;
+    assure_nonconflicting(&repo, &out)?;
+    Ok(())
+}
+
+#[test]
+fn failing_exec_aborts_the_rebase() -> Result<()> {
+    assure_stable_env();
+    let (repo, commits, _tmp) = four_commits_writable()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let err = builder
+        .steps([
+            RebaseStep::Pick {
+                commit_id: commits.a,
+                new_message: None,
+                base: None,
+            },
+            RebaseStep::Exec {
+                command: "echo out && echo err >&2 && exit 1".into(),
+            },
+            RebaseStep::Pick {
+                commit_id: commits.b,
+                new_message: None,
+                base: None,
+            },
+        ])?
+        .rebase()
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.starts_with("Command 'echo out && echo err >&2 && exit 1' failed on commit "),
+        "{err}"
+    );
+    assert!(err.ends_with("out\nerr\n"), "the output is included: {err}");
+    Ok(())
+}
+
+#[test]
+fn multiple_branches_and_their_merge_in_one_pass() -> Result<()> {
+    assure_stable_env();
+    let (repo, _tmp) = fixture_writable("three-branches-merged")?;
+    let base = repo.rev_parse_single("base")?.detach();
+    let id = |spec: &str| -> Result<gix::ObjectId> { Ok(repo.rev_parse_single(spec)?.detach()) };
+    let pick = |spec: &str, new_base: Option<gix::ObjectId>| -> Result<RebaseStep> {
+        Ok(RebaseStep::Pick {
+            commit_id: id(spec)?,
+            new_message: Some(format!("{spec} reworded").into()),
+            base: new_base,
+        })
+    };
+    let reference = |name: &str| RebaseStep::Reference(but_core::Reference::Virtual(name.into()));
+
+    let mut builder = Rebase::new(&repo, base, None)?;
+    let out = builder
+        .steps([
+            pick("A", Some(base))?,
+            reference("A"),
+            pick("B~1", Some(base))?,
+            pick("B", None)?,
+            reference("B"),
+            pick("C~2", Some(base))?,
+            pick("C~1", None)?,
+            pick("C", None)?,
+            reference("C"),
+            // The merge is re-created with the rewritten tips of all branches, not just the last one.
+            RebaseStep::Pick {
+                commit_id: id("main")?,
+                new_message: None,
+                base: None,
+            },
+        ])?
+        .rebase()?;
+
+    let new_tips: Vec<_> = out.references.iter().map(|r| r.commit_id).collect();
+    let merge = repo.find_commit(out.top_commit)?;
+    assert_eq!(
+        merge.parent_ids().map(|id| id.detach()).collect::<Vec<_>>(),
+        new_tips,
+        "each parent of the merge is replaced by the rewritten tip of its branch"
+    );
+    assert_eq!(merge.message()?.title, "Merge branches 'A', 'B' and 'C'");
+    for (tip, name) in new_tips.iter().zip(["A", "B", "C"]) {
+        let tip = repo.find_commit(*tip)?;
+        assert_eq!(tip.message_raw()?, format!("{name} reworded").as_str());
+        let first_commit = tip
+            .id()
+            .ancestors()
+            .all()?
+            .map(Result::unwrap)
+            .take_while(|info| info.id != base)
+            .last()
+            .expect("each branch has commits");
+        assert_eq!(
+            repo.find_commit(first_commit.id)?
+                .parent_ids()
+                .next()
+                .map(|id| id.detach()),
+            Some(base),
+            "each branch was restarted at the base"
+        );
+    }
+    assert!(
+        out.commit_mapping
+            .iter()
+            .all(|(mapping_base, _old, _new)| *mapping_base == Some(base)),
+        "all sequences started at the same base"
+    );
+    assure_nonconflicting(&repo, &out)?;
+    Ok(())
+}
+
 pub mod utils {
     use anyhow::Result;
     use but_rebase::RebaseOutput;
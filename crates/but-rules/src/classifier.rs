@@ -0,0 +1,202 @@
+//! Classifiers that infer the [`SemanticType`] of a change, used to evaluate [`Filter::SemanticType`](crate::Filter::SemanticType).
+
+use std::{path::Path, sync::Arc};
+
+use bstr::ByteSlice;
+use but_action::llm::LlmProvider;
+use but_hunk_assignment::HunkAssignment;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::SemanticType;
+
+/// Infers the semantic type of a change, i.e. whether it is a refactor, a new feature, a bug fix or documentation.
+pub trait SemanticClassifier {
+    /// Infer the semantic type of the change in `assignment`, or return `None` if it can't be determined.
+    ///
+    /// `user_defined_types` are the names of [`SemanticType::UserDefined`] types that rules filter on,
+    /// which the classifier may pick from in addition to the predefined types.
+    fn classify(
+        &self,
+        assignment: &HunkAssignment,
+        user_defined_types: &[String],
+    ) -> anyhow::Result<Option<SemanticType>>;
+}
+
+/// The maximum amount of changed lines for a change that replaces lines to be considered a bug fix, rather than a refactor.
+const MAX_BUG_FIX_LINES: usize = 6;
+
+/// A classifier that works offline by looking at the path and the shape of the diff.
+///
+/// It never infers [`SemanticType::UserDefined`] types.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeuristicClassifier;
+
+impl SemanticClassifier for HeuristicClassifier {
+    fn classify(
+        &self,
+        assignment: &HunkAssignment,
+        _user_defined_types: &[String],
+    ) -> anyhow::Result<Option<SemanticType>> {
+        if is_documentation_path(Path::new(&assignment.path)) {
+            return Ok(Some(SemanticType::Documentation));
+        }
+        let Some(diff) = &assignment.diff else {
+            return Ok(None);
+        };
+
+        let (mut added, mut removed, mut comments) = (0, 0, 0);
+        for line in diff.lines().filter(|line| !line.starts_with(b"@@")) {
+            let (is_added, content) = match line.split_first() {
+                Some((b'+', content)) => (true, content),
+                Some((b'-', content)) => (false, content),
+                _ => continue,
+            };
+            let content = content.trim();
+            if content.is_empty() {
+                continue;
+            }
+            if is_comment(content) {
+                comments += 1;
+            }
+            if is_added {
+                added += 1;
+            } else {
+                removed += 1;
+            }
+        }
+
+        let semantic_type = if added + removed == 0 {
+            return Ok(None);
+        } else if comments == added + removed {
+            SemanticType::Documentation
+        } else if removed == 0 {
+            SemanticType::NewFeature
+        } else if added + removed <= MAX_BUG_FIX_LINES {
+            SemanticType::BugFix
+        } else {
+            SemanticType::Refactor
+        };
+        Ok(Some(semantic_type))
+    }
+}
+
+fn is_documentation_path(path: &Path) -> bool {
+    let is_doc_file = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ["md", "mdx", "markdown", "rst", "adoc"]
+                .iter()
+                .any(|doc_ext| ext.eq_ignore_ascii_case(doc_ext))
+        });
+    let is_in_doc_dir = path.parent().is_some_and(|dir| {
+        dir.components().any(|c| {
+            c.as_os_str()
+                .to_str()
+                .is_some_and(|c| c.eq_ignore_ascii_case("docs") || c.eq_ignore_ascii_case("doc"))
+        })
+    });
+    is_doc_file || is_in_doc_dir
+}
+
+fn is_comment(line: &[u8]) -> bool {
+    ["//", "/*", "*/", "* ", "<!--", "-- ", ";;", "# ", "\"\"\""]
+        .iter()
+        .any(|prefix| line.starts_with(prefix.as_bytes()))
+        || line == b"*"
+        || line == b"#"
+}
+
+/// A classifier that asks an LLM to infer the semantic type, which is able to infer [`SemanticType::UserDefined`] types.
+#[derive(Debug, Clone)]
+pub struct LlmClassifier {
+    llm: Arc<dyn LlmProvider>,
+}
+
+impl LlmClassifier {
+    /// Create a new instance which uses `llm` to classify changes.
+    pub fn new(llm: Arc<dyn LlmProvider>) -> Self {
+        LlmClassifier { llm }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+enum Category {
+    Refactor,
+    NewFeature,
+    BugFix,
+    Documentation,
+    UserDefined,
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[schemars(deny_unknown_fields)]
+struct Classification {
+    #[schemars(description = "The category that describes the change best.")]
+    category: Category,
+    #[schemars(
+        description = "The name of the user defined type, exactly as listed, if the category is `userDefined`."
+    )]
+    user_defined_type: Option<String>,
+}
+
+impl SemanticClassifier for LlmClassifier {
+    fn classify(
+        &self,
+        assignment: &HunkAssignment,
+        user_defined_types: &[String],
+    ) -> anyhow::Result<Option<SemanticType>> {
+        let system_message = "
+        You are an expert in classifying code changes.
+        When given a change to a file, determine whether it is a refactor, a new feature, a bug fix or documentation,
+        or whether it matches one of the user defined types, if there are any.
+        If none of them applies, use the `unknown` category.
+        ";
+        let user_defined_types = if user_defined_types.is_empty() {
+            "There are no user defined types.".to_owned()
+        } else {
+            format!(
+                "These are the user defined types:\n{}",
+                user_defined_types
+                    .iter()
+                    .map(|t| format!("- {t}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        };
+        let diff = assignment
+            .diff
+            .as_ref()
+            .map(|diff| diff.to_str_lossy().into_owned())
+            .unwrap_or_else(|| "<binary or too large to display>".to_owned());
+        let user_message = format!(
+            "
+        Please classify the following change to the file `{path}`.
+        {user_defined_types}
+
+        <diff>
+        {diff}
+        </diff>
+            ",
+            path = assignment.path,
+        );
+
+        let classification = but_action::structured_output_blocking::<Classification>(
+            &*self.llm,
+            system_message,
+            vec![but_action::ChatMessage::User(user_message)],
+        )?;
+        Ok(classification.and_then(|c| match c.category {
+            Category::Refactor => Some(SemanticType::Refactor),
+            Category::NewFeature => Some(SemanticType::NewFeature),
+            Category::BugFix => Some(SemanticType::BugFix),
+            Category::Documentation => Some(SemanticType::Documentation),
+            Category::UserDefined => c.user_defined_type.map(SemanticType::UserDefined),
+            Category::Unknown => None,
+        }))
+    }
+}
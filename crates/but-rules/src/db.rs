@@ -11,6 +11,7 @@ impl TryFrom<but_db::WorkspaceRule> for crate::WorkspaceRule {
             trigger: serde_json::from_str(&value.trigger)?,
             filters: serde_json::from_str(&value.filters)?,
             action: serde_json::from_str(&value.action)?,
+            source: crate::RuleSource::Personal,
         })
     }
 }
@@ -29,6 +30,56 @@ impl TryFrom<crate::WorkspaceRule> for but_db::WorkspaceRule {
     }
 }
 
+impl TryFrom<but_db::RuleEvaluation> for crate::RuleEvaluation {
+    type Error = anyhow::Error;
+    fn try_from(value: but_db::RuleEvaluation) -> Result<Self, Self::Error> {
+        Ok(crate::RuleEvaluation {
+            id: value.id,
+            created_at: value.created_at,
+            rule_id: value.rule_id,
+            trigger: serde_json::from_str(&value.trigger)?,
+            action: serde_json::from_str(&value.action)?,
+            changes: serde_json::from_str(&value.changes)?,
+            description: value.description,
+            executed: value.executed,
+            snapshot_id: value.snapshot_id,
+            error: value.error,
+        })
+    }
+}
+
+/// Record what the rule with `outcome` did after it was evaluated because of `trigger`.
+pub(crate) fn record_evaluation(
+    ctx: &mut CommandContext,
+    trigger: &crate::Trigger,
+    outcome: &crate::handler::RuleOutcome,
+) -> Result<()> {
+    let changes = outcome
+        .changes
+        .iter()
+        .map(|c| crate::EvaluatedChange {
+            path: c.path.clone(),
+            hunk_header: c.hunk_header,
+        })
+        .collect::<Vec<_>>();
+    ctx.db()?
+        .rule_evaluations()
+        .insert(but_db::RuleEvaluation {
+            id: uuid::Uuid::new_v4().to_string(),
+            created_at: chrono::Local::now().naive_local(),
+            rule_id: outcome.rule_id.clone(),
+            trigger: serde_json::to_string(trigger)?,
+            action: serde_json::to_string(&outcome.action)?,
+            changes: serde_json::to_string(&changes)?,
+            description: outcome.description.clone(),
+            executed: outcome.executed,
+            snapshot_id: outcome.snapshot_id.clone(),
+            error: outcome.error.clone(),
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to insert rule evaluation: {}", e))?;
+    Ok(())
+}
+
 pub fn workspace_rules(ctx: &mut CommandContext) -> Result<Vec<crate::WorkspaceRule>> {
     let rules = ctx
         .db()?
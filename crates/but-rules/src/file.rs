@@ -0,0 +1,132 @@
+//! Workspace rules that are checked into the repository, so they can be shared with everyone working on it.
+//!
+//! They are read from [`RULES_FILE_PATH`] in the worktree, which looks like this:
+//!
+//! ```toml
+//! [[rule]]
+//! id = "schema-migrations"
+//! # Optional, defaults to `fs`.
+//! trigger = "fs"
+//! filters = ["path:^migrations/"]
+//! action = "assign:leftmost"
+//! # Optional, defaults to `true`.
+//! enabled = true
+//! ```
+//!
+//! Triggers, filters and actions use the [compact syntax](crate::syntax).
+
+use std::{collections::HashSet, path::Path, str::FromStr};
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::{RuleSource, Trigger, WorkspaceRule};
+
+/// The path of the rules file, relative to the root of the worktree.
+pub const RULES_FILE_PATH: &str = ".gitbutler/rules.toml";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default, rename = "rule")]
+    rules: Vec<RuleEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    id: Spanned<String>,
+    trigger: Option<Spanned<String>>,
+    #[serde(default)]
+    filters: Vec<Spanned<String>>,
+    action: Spanned<String>,
+    #[serde(default = "enabled_by_default")]
+    enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// Read all rules from the rules file in `worktree_dir`, in the order in which they are defined,
+/// or return an empty list if there is no such file.
+///
+/// Errors, like invalid regular expressions, mention the line they occurred on.
+pub fn load(worktree_dir: &Path) -> anyhow::Result<Vec<WorkspaceRule>> {
+    let path = worktree_dir.join(RULES_FILE_PATH);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Could not read {}", path.display()));
+        }
+    };
+    let created_at = std::fs::metadata(&path)
+        .and_then(|m| m.modified())
+        .map(|time| chrono::DateTime::<chrono::Local>::from(time).naive_local())
+        .unwrap_or_else(|_| chrono::Local::now().naive_local());
+    parse(&content, created_at).with_context(|| format!("Invalid rules in {RULES_FILE_PATH}"))
+}
+
+fn parse(content: &str, created_at: chrono::NaiveDateTime) -> anyhow::Result<Vec<WorkspaceRule>> {
+    let file: RulesFile = toml::from_str(content)?;
+    let mut ids = HashSet::new();
+    file.rules
+        .into_iter()
+        .map(|entry| {
+            let id = entry.id.get_ref();
+            if id.is_empty() {
+                bail!(
+                    "line {}: The rule id must not be empty",
+                    line(content, &entry.id)
+                );
+            }
+            if !ids.insert(id.clone()) {
+                bail!(
+                    "line {}: The rule id '{id}' is used more than once",
+                    line(content, &entry.id)
+                );
+            }
+            let trigger = match &entry.trigger {
+                Some(trigger) => parse_at(content, trigger)?,
+                None => Trigger::FileSytemChange,
+            };
+            let action = parse_at(content, &entry.action)?;
+            crate::ensure_compatible(&trigger, &action)
+                .map_err(|err| anyhow::anyhow!("line {}: {err:#}", line(content, &entry.action)))?;
+            Ok(WorkspaceRule {
+                id: id.clone(),
+                created_at,
+                enabled: entry.enabled,
+                trigger,
+                filters: entry
+                    .filters
+                    .iter()
+                    .map(|filter| parse_at(content, filter))
+                    .collect::<anyhow::Result<_>>()?,
+                action,
+                source: RuleSource::Repository,
+            })
+        })
+        .collect()
+}
+
+/// Parse `value`, mentioning the line it is on in `content` if it is invalid.
+fn parse_at<T>(content: &str, value: &Spanned<String>) -> anyhow::Result<T>
+where
+    T: FromStr<Err = anyhow::Error>,
+{
+    value
+        .get_ref()
+        .parse()
+        .map_err(|err: anyhow::Error| anyhow::anyhow!("line {}: {err:#}", line(content, value)))
+}
+
+/// Return the one-based line number of `value` within `content`.
+fn line(content: &str, value: &Spanned<String>) -> usize {
+    content[..value.span().start.min(content.len())]
+        .matches('\n')
+        .count()
+        + 1
+}
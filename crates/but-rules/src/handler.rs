@@ -1,107 +1,337 @@
+use anyhow::{Context, bail};
+use bstr::BString;
+use but_action::ChatMessage;
+use but_core::TreeStatusKind;
 use but_graph::VirtualBranchesTomlMetadata;
 use but_hunk_assignment::{HunkAssignment, assign, assignments_to_requests};
-use but_hunk_dependency::ui::HunkDependencies;
-use but_workspace::{StackId, StacksFilter, ui::StackEntry};
+use but_hunk_dependency::ui::{
+    HunkDependencies, HunkLock, hunk_dependencies_for_workspace_changes_by_worktree_dir,
+};
+use but_workspace::{DiffSpec, HunkHeader, StackId, StacksFilter, commit_engine, ui::StackEntry};
 use gitbutler_command_context::CommandContext;
+use gitbutler_oplog::{
+    OplogExt,
+    entry::{OperationKind, SnapshotDetails, Trailer},
+};
 use itertools::Itertools;
-use std::str::FromStr;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::Arc,
+};
 
-use crate::{Filter, StackTarget};
+use crate::{
+    Action, Filter, ImplicitOperation, Operation, SemanticType, StackTarget, TreeStatus, Trigger,
+    WorkspaceRule,
+    classifier::{HeuristicClassifier, LlmClassifier, SemanticClassifier},
+};
+
+/// Additional information about the changes that rules are evaluated for, which isn't part of their hunk assignments.
+#[derive(Default)]
+pub struct MatchContext<'a> {
+    /// The Claude Code session the changes originated from, if any.
+    /// Rules with a [`Filter::ClaudeCodeSessionId`] only match changes of the very same session.
+    pub session_id: Option<&'a str>,
+    /// The classifier to evaluate [`Filter::SemanticType`] with.
+    ///
+    /// If `None`, [`evaluate_workspace_rules()`] uses the LLM configured for the project, see [`llm_classifier()`],
+    /// while [`evaluate_rules()`] uses the [`HeuristicClassifier`].
+    /// The heuristic is also used whenever a classifier fails.
+    pub classifier: Option<&'a dyn SemanticClassifier>,
+}
+
+/// What a rule did with the changes it matched, or would do in a dry run.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleOutcome {
+    /// The ID of the rule.
+    pub rule_id: String,
+    /// The action of the rule.
+    pub action: Action,
+    /// The changes the action applies to.
+    pub changes: Vec<HunkAssignment>,
+    /// A human readable description of what the action did, or would do in a dry run.
+    pub description: String,
+    /// Whether the action was performed, which is never the case in a dry run.
+    pub executed: bool,
+    /// The oplog snapshot taken right before the action was performed, if it changes commits.
+    pub snapshot_id: Option<String>,
+    /// The error the action failed with, if any.
+    pub error: Option<String>,
+}
 
 pub fn process_workspace_rules(
     ctx: &mut CommandContext,
     assignments: &[HunkAssignment],
     dependencies: &Option<HunkDependencies>,
 ) -> anyhow::Result<usize> {
-    let mut updates = 0;
+    process_workspace_rules_in_context(
+        ctx,
+        &Trigger::FileSytemChange,
+        assignments,
+        dependencies,
+        &MatchContext::default(),
+    )
+}
+
+/// Like [`process_workspace_rules()`], but for rules with `trigger`,
+/// and with additional `context` about where the changes in `assignments` came from.
+pub fn process_workspace_rules_in_context(
+    ctx: &mut CommandContext,
+    trigger: &Trigger,
+    assignments: &[HunkAssignment],
+    dependencies: &Option<HunkDependencies>,
+    context: &MatchContext<'_>,
+) -> anyhow::Result<usize> {
+    let outcomes =
+        evaluate_workspace_rules(ctx, trigger, assignments, dependencies, context, false)?;
+    Ok(affected_changes(&outcomes))
+}
+
+/// Return the amount of changes that were affected by successfully performed actions in `outcomes`.
+pub fn affected_changes(outcomes: &[RuleOutcome]) -> usize {
+    outcomes
+        .iter()
+        .filter(|o| o.executed && o.error.is_none())
+        .map(|o| o.changes.len())
+        .sum()
+}
+
+/// Evaluate all enabled rules with `trigger` against `assignments`, in order, and perform their actions.
+/// If `dry_run` is `true`, nothing is changed and the outcomes describe what the rules would do.
+///
+/// Actions that change commits create an oplog snapshot before they are performed, so each of them can be undone.
+/// Failing actions don't prevent the following rules from being evaluated, their error is part of the outcome instead.
+pub fn evaluate_workspace_rules(
+    ctx: &mut CommandContext,
+    trigger: &Trigger,
+    assignments: &[HunkAssignment],
+    dependencies: &Option<HunkDependencies>,
+    context: &MatchContext<'_>,
+    dry_run: bool,
+) -> anyhow::Result<Vec<RuleOutcome>> {
     if assignments.is_empty() {
         // Dont create stacks if there are no changes to assign anywhere
-        return Ok(updates);
+        return Ok(Vec::new());
     }
     let rules = super::list_rules(ctx)?
         .into_iter()
         .filter(|r| r.enabled)
-        .filter(|r| matches!(r.trigger, super::Trigger::FileSytemChange))
-        .filter(|r| {
-            matches!(
-                &r.action,
-                super::Action::Explicit(super::Operation::Assign { .. })
-            )
-        })
+        .filter(|r| r.trigger == *trigger)
         .collect_vec();
+    let classifier = context
+        .classifier
+        .is_none()
+        .then(|| llm_classifier(ctx, &rules))
+        .flatten();
+    let context = MatchContext {
+        session_id: context.session_id,
+        classifier: context
+            .classifier
+            .or(classifier.as_ref().map(|c| c as &dyn SemanticClassifier)),
+    };
+    evaluate_rules(ctx, rules, assignments, dependencies, &context, dry_run)
+}
+
+/// Return a classifier that asks the LLM configured for the project of `ctx`,
+/// or `None` if none of the `rules` filter on semantic types or there is no LLM to ask.
+pub fn llm_classifier(ctx: &CommandContext, rules: &[WorkspaceRule]) -> Option<LlmClassifier> {
+    let needs_classifier = rules
+        .iter()
+        .flat_map(|r| &r.filters)
+        .any(|f| matches!(f, Filter::SemanticType(_)));
+    if !needs_classifier {
+        return None;
+    }
+    but_action::llm::for_project(ctx.project(), None)
+        .map(|llm| LlmClassifier::new(llm.into()))
+        .inspect_err(|err| tracing::debug!("Classifying changes without an LLM: {err:#}"))
+        .ok()
+}
 
-    if rules.is_empty() {
-        return Ok(updates);
+/// Like [`evaluate_workspace_rules()`], but evaluates exactly the given `rules`, regardless of their trigger
+/// and whether they are enabled.
+///
+/// Rules whose matched changes needed no action have no outcome.
+/// Unless this is a `dry_run`, each outcome is recorded in the database, see [`list_evaluations()`](crate::list_evaluations).
+pub fn evaluate_rules(
+    ctx: &mut CommandContext,
+    rules: Vec<WorkspaceRule>,
+    assignments: &[HunkAssignment],
+    dependencies: &Option<HunkDependencies>,
+    context: &MatchContext<'_>,
+    dry_run: bool,
+) -> anyhow::Result<Vec<RuleOutcome>> {
+    let mut outcomes = Vec::new();
+    if rules.is_empty() || assignments.is_empty() {
+        return Ok(outcomes);
+    }
+
+    let mut matcher = Matcher::new(context, &rules);
+    let needs_change_kinds = rules
+        .iter()
+        .flat_map(|r| &r.filters)
+        .any(|f| matches!(f, Filter::FileChangeType(_)));
+    let mut assignments = assignments.to_vec();
+    let mut dependencies = dependencies.clone();
+    let mut stacks_in_ws = stacks_in_workspace(ctx)?;
+    if needs_change_kinds {
+        matcher.change_kinds = change_kinds(ctx)?;
     }
 
+    for rule in rules {
+        let changes = matching(&assignments, &rule.filters, &mut matcher);
+        if changes.is_empty() {
+            continue;
+        }
+        let mut outcome = RuleOutcome {
+            rule_id: rule.id.clone(),
+            action: rule.action.clone(),
+            changes,
+            description: String::new(),
+            executed: false,
+            snapshot_id: None,
+            error: None,
+        };
+        // Rules that were stored before their trigger and action were checked may not be compatible.
+        let res = crate::ensure_compatible(&rule.trigger, &rule.action).and_then(|()| {
+            perform(
+                ctx,
+                &rule.action,
+                &assignments,
+                dependencies.as_ref(),
+                &stacks_in_ws,
+                &mut outcome,
+                dry_run,
+            )
+        });
+        if let Err(err) = res {
+            outcome.error = Some(format!("{err:#}"));
+        }
+        if outcome.changes.is_empty() && outcome.error.is_none() {
+            // The matched changes needed no action, like when they already are where they would be assigned to.
+            continue;
+        }
+
+        if outcome.executed {
+            // The action changed the workspace, which the following rules need to see.
+            let wt_changes = but_core::diff::worktree_changes(&ctx.gix_repo()?)?.changes;
+            if outcome.snapshot_id.is_some() {
+                dependencies = hunk_dependencies_for_workspace_changes_by_worktree_dir(
+                    ctx,
+                    &ctx.project().path,
+                    &ctx.project().gb_dir(),
+                    Some(wt_changes.clone()),
+                )
+                .ok();
+            }
+            (assignments, _) = but_hunk_assignment::assignments_with_fallback(
+                ctx,
+                false,
+                Some(wt_changes),
+                dependencies.as_ref(),
+            )?;
+            stacks_in_ws = stacks_in_workspace(ctx)?;
+            if needs_change_kinds {
+                matcher.change_kinds = change_kinds(ctx)?;
+            }
+        }
+        if !dry_run && let Err(err) = crate::db::record_evaluation(ctx, &rule.trigger, &outcome) {
+            tracing::warn!(
+                "Failed to record the evaluation of rule {}: {err:#}",
+                rule.id
+            );
+        }
+        outcomes.push(outcome);
+    }
+    Ok(outcomes)
+}
+
+/// Perform `action` on the changes of `outcome`, or only describe it in a `dry_run`.
+fn perform(
+    ctx: &mut CommandContext,
+    action: &Action,
+    assignments: &[HunkAssignment],
+    dependencies: Option<&HunkDependencies>,
+    stacks_in_ws: &[StackEntry],
+    outcome: &mut RuleOutcome,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    match action {
+        Action::Explicit(Operation::Assign { target }) => {
+            assign_to_target(ctx, target, stacks_in_ws, dependencies, outcome, dry_run)
+        }
+        Action::Explicit(Operation::Amend { commit_id }) => amend(ctx, commit_id, outcome, dry_run),
+        Action::Explicit(Operation::NewCommit { branch_name }) => {
+            new_commit(ctx, branch_name, stacks_in_ws, outcome, dry_run)
+        }
+        Action::Implicit(ImplicitOperation::AssignToAppropriateBranch) => {
+            assign_to_appropriate_branch(ctx, assignments, dependencies, outcome, dry_run)
+        }
+        Action::Implicit(ImplicitOperation::AbsorbIntoDependentCommit) => {
+            absorb(ctx, outcome, dry_run)
+        }
+        Action::Implicit(ImplicitOperation::LLMPrompt(prompt)) => {
+            llm_prompt(ctx, prompt, outcome, dry_run)
+        }
+    }
+}
+
+fn stacks_in_workspace(ctx: &CommandContext) -> anyhow::Result<Vec<StackEntry>> {
     let repo = ctx.gix_repo_for_merging_non_persisting()?;
-    let stacks_in_ws = if ctx.app_settings().feature_flags.ws3 {
+    if ctx.app_settings().feature_flags.ws3 {
         let meta = VirtualBranchesTomlMetadata::from_path(
             ctx.project().gb_dir().join("virtual_branches.toml"),
         )?;
         but_workspace::stacks_v3(&repo, &meta, StacksFilter::InWorkspace, None)
     } else {
         but_workspace::stacks(ctx, &ctx.project().gb_dir(), &repo, StacksFilter::default())
-    }?;
-
-    for rule in rules {
-        match rule.action {
-            super::Action::Explicit(super::Operation::Assign { target }) => {
-                if let Some(stack_id) = get_or_create_stack_id(ctx, target, &stacks_in_ws) {
-                    let assignments = matching(assignments, rule.filters.clone())
-                        .into_iter()
-                        .filter(|e| e.stack_id != Some(stack_id))
-                        .map(|mut e| {
-                            e.stack_id = Some(stack_id);
-                            e
-                        })
-                        .collect_vec();
-                    updates +=
-                        handle_assign(ctx, assignments, dependencies.as_ref()).unwrap_or_default();
-                }
-            }
-            _ => continue,
-        };
     }
-    Ok(updates)
 }
 
-fn get_or_create_stack_id(
-    ctx: &CommandContext,
-    target: StackTarget,
-    stacks_in_ws: &[StackEntry],
-) -> Option<StackId> {
+fn change_kinds(ctx: &CommandContext) -> anyhow::Result<HashMap<BString, TreeStatusKind>> {
+    Ok(but_core::diff::worktree_changes(&ctx.gix_repo()?)?
+        .changes
+        .into_iter()
+        .map(|change| {
+            let kind = change.status.kind();
+            (change.path, kind)
+        })
+        .collect())
+}
+
+/// The stack a [`StackTarget`] refers to.
+enum TargetStack {
+    /// The stack exists in the workspace.
+    Existing(StackId),
+    /// The stack has to be created as there are no stacks in the workspace.
+    New,
+    /// The stack doesn't exist and can't be created.
+    Missing,
+}
+
+fn target_stack(target: &StackTarget, stacks_in_ws: &[StackEntry]) -> TargetStack {
     let sorted_stack_ids = stacks_in_ws
         .iter()
         .sorted_by(|a, b| Ord::cmp(&a.order.unwrap_or_default(), &b.order.unwrap_or_default()))
         .filter_map(|s| s.id)
         .collect_vec();
-    match target {
+    let stack_id = match target {
         StackTarget::StackId(stack_id) => {
-            if let Ok(stack_id) = StackId::from_str(&stack_id) {
-                if sorted_stack_ids.iter().any(|e| e == &stack_id) {
-                    Option::Some(stack_id)
-                } else {
-                    Option::None
+            return match StackId::from_str(stack_id) {
+                Ok(stack_id) if sorted_stack_ids.contains(&stack_id) => {
+                    TargetStack::Existing(stack_id)
                 }
-            } else {
-                Option::None
-            }
-        }
-        StackTarget::Leftmost => {
-            if sorted_stack_ids.is_empty() {
-                create_stack(ctx).ok()
-            } else {
-                sorted_stack_ids.first().cloned()
-            }
-        }
-        StackTarget::Rightmost => {
-            if sorted_stack_ids.is_empty() {
-                create_stack(ctx).ok()
-            } else {
-                sorted_stack_ids.last().cloned()
-            }
+                _ => TargetStack::Missing,
+            };
         }
+        StackTarget::Leftmost => sorted_stack_ids.first(),
+        StackTarget::Rightmost => sorted_stack_ids.last(),
+    };
+    match stack_id {
+        Some(stack_id) => TargetStack::Existing(*stack_id),
+        None => TargetStack::New,
     }
 }
 
@@ -124,49 +354,450 @@ fn create_stack(ctx: &CommandContext) -> anyhow::Result<StackId> {
     Ok(stack.id)
 }
 
-fn handle_assign(
-    ctx: &mut CommandContext,
-    assignments: Vec<HunkAssignment>,
-    deps: Option<&HunkDependencies>,
-) -> anyhow::Result<usize> {
-    let len = assignments.len();
-    if assign(ctx, assignments_to_requests(assignments), deps).is_ok() {
-        Ok(len)
-    } else {
-        Ok(0)
+/// Create an oplog snapshot to allow undoing the action of the rule with `rule_id`, returning its ID.
+fn snapshot(ctx: &CommandContext, rule_id: &str) -> anyhow::Result<String> {
+    let mut guard = ctx.project().exclusive_worktree_access();
+    let details =
+        SnapshotDetails::new(OperationKind::ApplyWorkspaceRule).with_trailers(vec![Trailer {
+            key: "rule_id".to_string(),
+            value: rule_id.to_string(),
+        }]);
+    Ok(ctx
+        .create_snapshot(details, guard.write_permission())?
+        .to_string())
+}
+
+/// Describe `changes` in a way that fits into a sentence, like "2 changes in `a.rs`".
+fn describe(changes: &[HunkAssignment]) -> String {
+    let paths = changes
+        .iter()
+        .map(|c| c.path.as_str())
+        .unique()
+        .collect_vec();
+    let amount = match changes.len() {
+        1 => "1 change".to_owned(),
+        n => format!("{n} changes"),
+    };
+    match paths.as_slice() {
+        [path] => format!("{amount} in `{path}`"),
+        paths => format!("{amount} in {} files", paths.len()),
     }
 }
 
-fn matching(wt_assignments: &[HunkAssignment], filters: Vec<Filter>) -> Vec<HunkAssignment> {
-    if filters.is_empty() {
-        return wt_assignments.to_vec();
+fn assign_to_target(
+    ctx: &mut CommandContext,
+    target: &StackTarget,
+    stacks_in_ws: &[StackEntry],
+    dependencies: Option<&HunkDependencies>,
+    outcome: &mut RuleOutcome,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let stack_id = match target_stack(target, stacks_in_ws) {
+        TargetStack::Existing(stack_id) => {
+            outcome.changes.retain(|c| c.stack_id != Some(stack_id));
+            outcome.description =
+                format!("Assign {} to stack {stack_id}", describe(&outcome.changes));
+            Some(stack_id)
+        }
+        TargetStack::New => {
+            outcome.description = format!("Assign {} to a new stack", describe(&outcome.changes));
+            None
+        }
+        TargetStack::Missing => {
+            outcome.changes.clear();
+            bail!("The target stack isn't part of the workspace");
+        }
+    };
+    if dry_run || outcome.changes.is_empty() {
+        return Ok(());
     }
+
+    let stack_id = match stack_id {
+        Some(stack_id) => stack_id,
+        None => create_stack(ctx)?,
+    };
+    let assignments = outcome
+        .changes
+        .iter()
+        .cloned()
+        .map(|mut e| {
+            e.stack_id = Some(stack_id);
+            e
+        })
+        .collect_vec();
+    outcome.executed = true;
+    assign(ctx, assignments_to_requests(assignments), dependencies)?;
+    Ok(())
+}
+
+/// Assign changes to the stack they depend on, or to the stack that other changes to the same file are assigned to.
+fn assign_to_appropriate_branch(
+    ctx: &mut CommandContext,
+    all_assignments: &[HunkAssignment],
+    dependencies: Option<&HunkDependencies>,
+    outcome: &mut RuleOutcome,
+    dry_run: bool,
+) -> anyhow::Result<()> {
     let mut assignments = Vec::new();
-    for filter in filters {
+    for change in &outcome.changes {
+        let locked_stack_ids = change
+            .hunk_locks
+            .iter()
+            .flatten()
+            .map(|lock| lock.stack_id)
+            .unique()
+            .collect_vec();
+        let stack_id = match locked_stack_ids.as_slice() {
+            [stack_id] => Some(*stack_id),
+            [] => all_assignments
+                .iter()
+                .filter(|a| a.path_bytes == change.path_bytes && a.id != change.id)
+                .filter_map(|a| a.stack_id)
+                .unique()
+                .exactly_one()
+                .ok(),
+            _ => None,
+        };
+        if let Some(stack_id) = stack_id.filter(|stack_id| change.stack_id != Some(*stack_id)) {
+            let mut assignment = change.clone();
+            assignment.stack_id = Some(stack_id);
+            assignments.push(assignment);
+        }
+    }
+    outcome.changes = assignments;
+    outcome.description = format!(
+        "Assign {} to the stacks they belong to",
+        describe(&outcome.changes)
+    );
+    if dry_run || outcome.changes.is_empty() {
+        return Ok(());
+    }
+
+    outcome.executed = true;
+    assign(
+        ctx,
+        assignments_to_requests(outcome.changes.clone()),
+        dependencies,
+    )?;
+    Ok(())
+}
+
+fn amend(
+    ctx: &mut CommandContext,
+    commit_id: &str,
+    outcome: &mut RuleOutcome,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let repo = ctx.gix_repo_for_merging()?;
+    let commit_id = repo
+        .rev_parse_single(commit_id)
+        .with_context(|| format!("Commit {commit_id} doesn't exist"))?
+        .detach();
+    outcome.description = format!(
+        "Amend {} into commit {}",
+        describe(&outcome.changes),
+        commit_id.to_hex_with_len(7)
+    );
+    if dry_run {
+        return Ok(());
+    }
+
+    outcome.snapshot_id = Some(snapshot(ctx, &outcome.rule_id)?);
+    outcome.executed = true;
+    let mut guard = ctx.project().exclusive_worktree_access();
+    let commit_outcome = commit_engine::create_commit_and_update_refs_with_project(
+        &repo,
+        ctx.project(),
+        None,
+        commit_engine::Destination::AmendCommit {
+            commit_id,
+            new_message: None,
+        },
+        None,
+        diff_specs(&outcome.changes),
+        ctx.app_settings().context_lines,
+        guard.write_permission(),
+    )?;
+    check_rejections(&commit_outcome)
+}
+
+fn new_commit(
+    ctx: &mut CommandContext,
+    branch_name: &str,
+    stacks_in_ws: &[StackEntry],
+    outcome: &mut RuleOutcome,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let (stack_id, tip) = stacks_in_ws
+        .iter()
+        .find_map(|s| {
+            s.heads
+                .iter()
+                .find(|h| h.name == branch_name)
+                .map(|h| (s.id, h.tip))
+        })
+        .with_context(|| format!("Branch '{branch_name}' isn't part of the workspace"))?;
+    let stack_id = stack_id.with_context(|| format!("Branch '{branch_name}' isn't in a stack"))?;
+    outcome.description = format!(
+        "Commit {} to branch '{branch_name}'",
+        describe(&outcome.changes)
+    );
+    if dry_run {
+        return Ok(());
+    }
+
+    let paths = outcome
+        .changes
+        .iter()
+        .map(|c| c.path.as_str())
+        .unique()
+        .collect_vec();
+    let message = match paths.as_slice() {
+        [path] => format!("Update {path}"),
+        paths => format!("Update {} files", paths.len()),
+    };
+    outcome.snapshot_id = Some(snapshot(ctx, &outcome.rule_id)?);
+    outcome.executed = true;
+    let mut guard = ctx.project().exclusive_worktree_access();
+    let commit_outcome = commit_engine::create_commit_and_update_refs_with_project(
+        &ctx.gix_repo_for_merging()?,
+        ctx.project(),
+        Some(stack_id),
+        commit_engine::Destination::NewCommit {
+            parent_commit_id: Some(tip),
+            message,
+            stack_segment: Some(commit_engine::StackSegmentId {
+                stack_id,
+                segment_ref: format!("refs/heads/{branch_name}").try_into()?,
+            }),
+        },
+        None,
+        diff_specs(&outcome.changes),
+        ctx.app_settings().context_lines,
+        guard.write_permission(),
+    )?;
+    check_rejections(&commit_outcome)
+}
+
+fn diff_specs(changes: &[HunkAssignment]) -> Vec<DiffSpec> {
+    but_workspace::flatten_diff_specs(changes.iter().cloned().map(Into::into).collect())
+}
+
+fn check_rejections(outcome: &commit_engine::CreateCommitOutcome) -> anyhow::Result<()> {
+    if outcome.rejected_specs.is_empty() {
+        return Ok(());
+    }
+    bail!(
+        "Some changes were rejected: {}",
+        outcome
+            .rejected_specs
+            .iter()
+            .map(|(reason, spec)| format!("{} ({reason:?})", spec.path))
+            .join(", ")
+    )
+}
+
+/// Amend each matched hunk into the one commit it depends on, skipping hunks that depend on no or several commits.
+fn absorb(
+    ctx: &mut CommandContext,
+    outcome: &mut RuleOutcome,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    outcome.changes.retain(|c| single_lock(c).is_some());
+    outcome.description = format!(
+        "Absorb {} into the commits they depend on",
+        describe(&outcome.changes)
+    );
+    if dry_run || outcome.changes.is_empty() {
+        return Ok(());
+    }
+
+    let changes_by_commit = outcome
+        .changes
+        .iter()
+        .filter_map(|c| single_lock(c).map(|lock| (lock.commit_id, c.clone())))
+        .into_group_map();
+    outcome.snapshot_id = Some(snapshot(ctx, &outcome.rule_id)?);
+    outcome.executed = true;
+    let repo = ctx.gix_repo_for_merging()?;
+    let mut guard = ctx.project().exclusive_worktree_access();
+    // Amending a commit rewrites the commits above it, which other changes may depend on.
+    let mut rewritten = HashMap::new();
+    for (commit_id, changes) in changes_by_commit {
+        let mut commit_id = commit_id;
+        while let Some(new_id) = rewritten.get(&commit_id) {
+            commit_id = *new_id;
+        }
+        let commit_outcome = commit_engine::create_commit_and_update_refs_with_project(
+            &repo,
+            ctx.project(),
+            None,
+            commit_engine::Destination::AmendCommit {
+                commit_id,
+                new_message: None,
+            },
+            None,
+            diff_specs(&changes),
+            ctx.app_settings().context_lines,
+            guard.write_permission(),
+        )?;
+        check_rejections(&commit_outcome)?;
+        if let Some(rebase_output) = commit_outcome.rebase_output {
+            rewritten.extend(
+                rebase_output
+                    .commit_mapping
+                    .into_iter()
+                    .map(|(_base, old, new)| (old, new))
+                    .filter(|(old, new)| old != new),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Return the lock of the hunk in `change` if it depends on exactly one commit.
+fn single_lock(change: &HunkAssignment) -> Option<HunkLock> {
+    change
+        .hunk_locks
+        .iter()
+        .flatten()
+        .unique_by(|lock| lock.commit_id)
+        .exactly_one()
+        .ok()
+        .copied()
+}
+
+fn llm_prompt(
+    ctx: &mut CommandContext,
+    prompt: &str,
+    outcome: &mut RuleOutcome,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    outcome.description = format!(
+        "Let the LLM handle {} as prompted: {prompt}",
+        describe(&outcome.changes)
+    );
+    if dry_run {
+        return Ok(());
+    }
+
+    let llm = but_action::llm::for_project(ctx.project(), None)?;
+    let files = outcome
+        .changes
+        .iter()
+        .map(|c| c.path.as_str())
+        .unique()
+        .join("\n");
+    let message =
+        format!("{prompt}\n\nOnly handle the changes to these files:\n<files>\n{files}\n</files>");
+    outcome.snapshot_id = Some(snapshot(ctx, &outcome.rule_id)?);
+    outcome.executed = true;
+    but_action::freestyle(
+        ctx.project().id,
+        uuid::Uuid::new_v4().to_string(),
+        silent_emitter(),
+        ctx,
+        llm.as_ref(),
+        vec![ChatMessage::User(message)],
+        None,
+    )?;
+    Ok(())
+}
+
+/// An emitter for tools that is used when no frontend listens to their events.
+fn silent_emitter() -> Arc<dyn Fn(&str, serde_json::Value) + Send + Sync> {
+    Arc::new(|_: &str, _: serde_json::Value| {})
+}
+
+/// Return all assignments that match all `filters`, or all assignments if there are no filters.
+fn matching(
+    wt_assignments: &[HunkAssignment],
+    filters: &[Filter],
+    matcher: &mut Matcher<'_>,
+) -> Vec<HunkAssignment> {
+    // Classifying semantic types is the most expensive, so only do it for changes that match all other filters.
+    let filters = filters
+        .iter()
+        .sorted_by_key(|f| matches!(f, Filter::SemanticType(_)))
+        .collect_vec();
+    wt_assignments
+        .iter()
+        .filter(|assignment| filters.iter().all(|f| matcher.matches(f, assignment)))
+        .cloned()
+        .collect()
+}
+
+/// Evaluates filters against hunk assignments, keeping information that is expensive to obtain across rules.
+struct Matcher<'a> {
+    context: &'a MatchContext<'a>,
+    /// The kind of change of each changed path in the worktree, only set if a rule filters on it.
+    change_kinds: HashMap<BString, TreeStatusKind>,
+    /// The names of all user defined semantic types that rules filter on.
+    user_defined_types: Vec<String>,
+    /// The semantic type of each hunk that was classified so far.
+    semantic_types: BTreeMap<(BString, Option<HunkHeader>), Option<SemanticType>>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(context: &'a MatchContext<'a>, rules: &[WorkspaceRule]) -> Self {
+        let user_defined_types = rules
+            .iter()
+            .flat_map(|r| &r.filters)
+            .filter_map(|f| match f {
+                Filter::SemanticType(SemanticType::UserDefined(name)) => Some(name.clone()),
+                _ => None,
+            })
+            .unique()
+            .collect();
+        Matcher {
+            context,
+            change_kinds: HashMap::new(),
+            user_defined_types,
+            semantic_types: BTreeMap::new(),
+        }
+    }
+
+    fn matches(&mut self, filter: &Filter, assignment: &HunkAssignment) -> bool {
         match filter {
-            Filter::PathMatchesRegex(regex) => {
-                for change in wt_assignments.iter() {
-                    if regex.is_match(&change.path) {
-                        assignments.push(change.clone());
-                    }
-                }
+            Filter::PathMatchesRegex(regex) => regex.is_match(&assignment.path),
+            Filter::ContentMatchesRegex(regex) => assignment.diff.as_ref().is_some_and(|diff| {
+                diff.to_string()
+                    .lines()
+                    .filter(|line| line.starts_with('+'))
+                    .any(|line| regex.is_match(line))
+            }),
+            Filter::FileChangeType(status) => self
+                .change_kinds
+                .get(&assignment.path_bytes)
+                .is_some_and(|kind| TreeStatus::from(*kind) == *status),
+            Filter::SemanticType(semantic_type) => {
+                self.semantic_type(assignment).as_ref() == Some(semantic_type)
             }
-            Filter::ContentMatchesRegex(regex) => {
-                for change in wt_assignments.iter() {
-                    if let Some(diff) = change.diff.clone() {
-                        let diff = diff.to_string();
-                        let matching_lines: Vec<&str> =
-                            diff.lines().filter(|line| line.starts_with('+')).collect();
-                        if matching_lines.iter().any(|line| regex.is_match(line)) {
-                            assignments.push(change.clone());
-                        }
-                    }
-                }
+            Filter::ClaudeCodeSessionId(session_id) => {
+                self.context.session_id == Some(session_id.as_str())
             }
-            Filter::FileChangeType(_) => continue,
-            Filter::SemanticType(_) => continue,
-            Filter::ClaudeCodeSessionId(_) => continue,
         }
     }
-    assignments
+
+    fn semantic_type(&mut self, assignment: &HunkAssignment) -> Option<SemanticType> {
+        let key = (assignment.path_bytes.clone(), assignment.hunk_header);
+        if let Some(semantic_type) = self.semantic_types.get(&key) {
+            return semantic_type.clone();
+        }
+        let semantic_type = match self.context.classifier {
+            Some(classifier) => classifier
+                .classify(assignment, &self.user_defined_types)
+                .or_else(|err| {
+                    tracing::warn!(
+                        "Failed to classify the change to {}, falling back to heuristics: {err:#}",
+                        assignment.path
+                    );
+                    HeuristicClassifier.classify(assignment, &self.user_defined_types)
+                }),
+            None => HeuristicClassifier.classify(assignment, &self.user_defined_types),
+        }
+        .unwrap_or_default();
+        self.semantic_types.insert(key, semantic_type.clone());
+        semantic_type
+    }
 }
@@ -2,8 +2,11 @@ use but_hunk_dependency::ui::hunk_dependencies_for_workspace_changes_by_worktree
 use gitbutler_command_context::CommandContext;
 use serde::{Deserialize, Serialize};
 
+pub mod classifier;
 pub mod db;
+pub mod file;
 pub mod handler;
+pub mod syntax;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -23,6 +26,22 @@ pub struct WorkspaceRule {
     filters: Vec<Filter>,
     /// The action determines what happens to the files or changes that matched the filters.
     action: Action,
+    /// Where the rule is defined.
+    #[serde(default)]
+    source: RuleSource,
+}
+
+/// Where a [`WorkspaceRule`] is defined.
+///
+/// Repository rules are evaluated before personal rules, so personal rules can refine what shared rules did.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum RuleSource {
+    /// The rule was created by the user and is stored in the per-user database.
+    #[default]
+    Personal,
+    /// The rule is checked into the repository, see [`file`].
+    Repository,
 }
 
 impl WorkspaceRule {
@@ -57,6 +76,22 @@ impl WorkspaceRule {
     pub fn created_at(&self) -> chrono::NaiveDateTime {
         self.created_at
     }
+
+    pub fn trigger(&self) -> &Trigger {
+        &self.trigger
+    }
+
+    pub fn filters(&self) -> &[Filter] {
+        &self.filters
+    }
+
+    pub fn action(&self) -> &Action {
+        &self.action
+    }
+
+    pub fn source(&self) -> RuleSource {
+        self.source
+    }
 }
 
 /// Represents the kinds of events in the app that can cause a rule to be evaluated.
@@ -67,6 +102,12 @@ pub enum Trigger {
     FileSytemChange,
     /// Whenever a Claude Code hook is invoked.
     ClaudeCodeHook,
+    /// When a commit was created, be it by GitButler or by Git directly.
+    CommitCreated,
+    /// When remote tracking branches may have moved, which happens after every fetch.
+    UpstreamChange,
+    /// Right before a branch is pushed.
+    PrePush,
 }
 
 /// A filter is a condition that determines what files or changes the rule applies to.
@@ -90,7 +131,7 @@ pub enum Filter {
 
 /// Represents the type of change that occurred in the Git worktree.
 /// Matches the TreeStatus of the TreeChange
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub enum TreeStatus {
     /// Something was added or scheduled to be added.
@@ -103,9 +144,21 @@ pub enum TreeStatus {
     Rename,
 }
 
+impl From<but_core::TreeStatusKind> for TreeStatus {
+    fn from(kind: but_core::TreeStatusKind) -> Self {
+        match kind {
+            but_core::TreeStatusKind::Addition => TreeStatus::Addition,
+            but_core::TreeStatusKind::Deletion => TreeStatus::Deletion,
+            but_core::TreeStatusKind::Modification => TreeStatus::Modification,
+            but_core::TreeStatusKind::Rename => TreeStatus::Rename,
+        }
+    }
+}
+
 /// Represents a semantic type of change that was inferred for the change.
 /// Typically this means a heuristic or an LLM determinded that a change represents a refactor, a new feature, a bug fix, or documentation update.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// See [`classifier`] for how it is inferred.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase", tag = "type", content = "subject")]
 pub enum SemanticType {
     /// A change that is a refactor, meaning it does not change the external behavior of the code but improves its structure.
@@ -132,6 +185,28 @@ pub enum Action {
     Implicit(ImplicitOperation),
 }
 
+impl Action {
+    /// Return `true` if the action creates commits or rewrites them in ways that aren't implied by the changes,
+    /// which is why it can't be used with [`Trigger::FileSytemChange`], as it would be performed on every save.
+    pub fn needs_explicit_trigger(&self) -> bool {
+        matches!(
+            self,
+            Action::Explicit(Operation::Amend { .. } | Operation::NewCommit { .. })
+                | Action::Implicit(ImplicitOperation::LLMPrompt(_))
+        )
+    }
+}
+
+/// Fail if `action` can't be performed by rules with `trigger`, see [`Action::needs_explicit_trigger()`].
+pub fn ensure_compatible(trigger: &Trigger, action: &Action) -> anyhow::Result<()> {
+    if *trigger == Trigger::FileSytemChange && action.needs_explicit_trigger() {
+        anyhow::bail!(
+            "Action '{action}' would be performed on every save, use it with a trigger other than '{trigger}'"
+        );
+    }
+    Ok(())
+}
+
 /// Represents the operation that a user can configure to be performed in an explicit action.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase", tag = "type", content = "subject")]
@@ -182,6 +257,7 @@ pub fn create_rule(
     ctx: &mut CommandContext,
     req: CreateRuleRequest,
 ) -> anyhow::Result<WorkspaceRule> {
+    ensure_compatible(&req.trigger, &req.action)?;
     let rule = WorkspaceRule {
         id: uuid::Uuid::new_v4().to_string(),
         created_at: chrono::Local::now().naive_local(),
@@ -189,6 +265,7 @@ pub fn create_rule(
         trigger: req.trigger,
         filters: req.filters,
         action: req.action,
+        source: RuleSource::Personal,
     };
 
     ctx.db()?
@@ -201,6 +278,7 @@ pub fn create_rule(
 
 /// Deletes an existing workspace rule by its ID.
 pub fn delete_rule(ctx: &mut CommandContext, id: &str) -> anyhow::Result<()> {
+    ensure_personal(ctx, id)?;
     ctx.db()?
         .workspace_rules()
         .delete(id)
@@ -241,6 +319,7 @@ pub fn update_rule(
     ctx: &mut CommandContext,
     req: UpdateRuleRequest,
 ) -> anyhow::Result<WorkspaceRule> {
+    ensure_personal(ctx, &req.id)?;
     let mut rule: WorkspaceRule = ctx
         .db()?
         .workspace_rules()
@@ -260,6 +339,7 @@ pub fn update_rule(
     if let Some(action) = req.action {
         rule.action = action;
     }
+    ensure_compatible(&rule.trigger, &rule.action)?;
 
     ctx.db()?
         .workspace_rules()
@@ -269,8 +349,22 @@ pub fn update_rule(
     Ok(rule)
 }
 
+/// Fail if the rule with `id` is defined in the repository, as it can only be changed by editing the rules file.
+fn ensure_personal(ctx: &CommandContext, id: &str) -> anyhow::Result<()> {
+    if repository_rules(ctx).iter().any(|r| r.id == id) {
+        anyhow::bail!(
+            "Rule {id} is defined in {}, edit the file to change it",
+            file::RULES_FILE_PATH
+        );
+    }
+    Ok(())
+}
+
 /// Retrieves a workspace rule by its ID.
 pub fn get_rule(ctx: &mut CommandContext, id: &str) -> anyhow::Result<WorkspaceRule> {
+    if let Some(rule) = repository_rules(ctx).into_iter().find(|r| r.id == id) {
+        return Ok(rule);
+    }
     let rule = ctx
         .db()?
         .workspace_rules()
@@ -280,19 +374,148 @@ pub fn get_rule(ctx: &mut CommandContext, id: &str) -> anyhow::Result<WorkspaceR
     Ok(rule)
 }
 
-/// Lists all workspace rules in the database.
+/// Lists all workspace rules in the order in which they are evaluated,
+/// which are the rules checked into the repository followed by the personal rules in the database.
 pub fn list_rules(ctx: &mut CommandContext) -> anyhow::Result<Vec<WorkspaceRule>> {
-    let rules = ctx
+    let mut rules = repository_rules(ctx);
+    let personal_rules = ctx
         .db()?
         .workspace_rules()
         .list()?
         .into_iter()
         .map(|r| r.try_into())
         .collect::<Result<Vec<WorkspaceRule>, _>>()?;
+    rules.extend(personal_rules);
     Ok(rules)
 }
 
+/// Return the rules checked into the repository, or no rules if they are invalid,
+/// so personal rules keep working while the rules file is edited.
+fn repository_rules(ctx: &CommandContext) -> Vec<WorkspaceRule> {
+    file::load(&ctx.project().path).unwrap_or_else(|err| {
+        tracing::warn!("Ignoring repository rules: {err:#}");
+        Vec::new()
+    })
+}
+
+/// A record of a rule that matched changes, and what its action did with them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleEvaluation {
+    /// A UUID unique identifier for the evaluation.
+    pub id: String,
+    /// The time of the evaluation.
+    pub created_at: chrono::NaiveDateTime,
+    /// The ID of the rule that was evaluated.
+    pub rule_id: String,
+    /// The trigger that caused the rule to be evaluated.
+    pub trigger: Trigger,
+    /// The action of the rule at the time of the evaluation.
+    pub action: Action,
+    /// The changes that matched the filters of the rule.
+    pub changes: Vec<EvaluatedChange>,
+    /// A human readable description of what the action did.
+    pub description: String,
+    /// Whether the action was performed.
+    pub executed: bool,
+    /// The oplog snapshot taken before the action was performed, which can be restored to undo it.
+    pub snapshot_id: Option<String>,
+    /// The error the action failed with, if any.
+    pub error: Option<String>,
+}
+
+/// A change that matched a rule, as recorded in a [`RuleEvaluation`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluatedChange {
+    /// The path of the changed file.
+    pub path: String,
+    /// The hunk within the file, or `None` if the whole file matched.
+    pub hunk_header: Option<but_workspace::HunkHeader>,
+}
+
+/// Lists the `limit` most recent rule evaluations, newest first, optionally only those of the rule with `rule_id`.
+pub fn list_evaluations(
+    ctx: &mut CommandContext,
+    rule_id: Option<&str>,
+    limit: usize,
+) -> anyhow::Result<Vec<RuleEvaluation>> {
+    ctx.db()?
+        .rule_evaluations()
+        .list(rule_id, limit.try_into()?)?
+        .into_iter()
+        .map(|e| e.try_into())
+        .collect()
+}
+
+/// Evaluates the rule with `id` against the current worktree changes without performing its action,
+/// regardless of its trigger and whether it is enabled.
+/// Returns `None` if the rule doesn't match any changes.
+pub fn test_rule(
+    ctx: &mut CommandContext,
+    id: &str,
+) -> anyhow::Result<Option<handler::RuleOutcome>> {
+    let rule = get_rule(ctx, id)?;
+    let (assignments, dependencies) = current_assignments(ctx)?;
+    let rules = vec![rule];
+    let classifier = handler::llm_classifier(ctx, &rules);
+    let outcomes = handler::evaluate_rules(
+        ctx,
+        rules,
+        &assignments,
+        &Some(dependencies),
+        &handler::MatchContext {
+            session_id: None,
+            classifier: classifier
+                .as_ref()
+                .map(|c| c as &dyn classifier::SemanticClassifier),
+        },
+        true,
+    )?;
+    Ok(outcomes.into_iter().next())
+}
+
 fn process_rules(ctx: &mut CommandContext) -> anyhow::Result<()> {
+    process_rules_for_trigger(ctx, Trigger::FileSytemChange)?;
+    Ok(())
+}
+
+/// Evaluates all enabled rules with `trigger` against the current worktree changes,
+/// returning the amount of changes that were affected.
+pub fn process_rules_for_trigger(
+    ctx: &mut CommandContext,
+    trigger: Trigger,
+) -> anyhow::Result<usize> {
+    let outcomes = evaluate_rules_for_trigger(ctx, trigger, false)?;
+    Ok(handler::affected_changes(&outcomes))
+}
+
+/// Evaluates all enabled rules with `trigger` against the current worktree changes and performs their actions,
+/// returning what each rule that matched any changes did.
+/// If `dry_run` is `true`, nothing is changed and the outcomes describe what the rules would do instead.
+pub fn evaluate_rules_for_trigger(
+    ctx: &mut CommandContext,
+    trigger: Trigger,
+    dry_run: bool,
+) -> anyhow::Result<Vec<handler::RuleOutcome>> {
+    let (assignments, dependencies) = current_assignments(ctx)?;
+    handler::evaluate_workspace_rules(
+        ctx,
+        &trigger,
+        &assignments,
+        &Some(dependencies),
+        &handler::MatchContext::default(),
+        dry_run,
+    )
+}
+
+/// Return the assignments of all current worktree changes, along with their dependencies.
+fn current_assignments(
+    ctx: &mut CommandContext,
+) -> anyhow::Result<(
+    Vec<but_hunk_assignment::HunkAssignment>,
+    but_hunk_dependency::ui::HunkDependencies,
+)> {
     let wt_changes = but_core::diff::worktree_changes(&ctx.gix_repo()?)?;
 
     let dependencies = hunk_dependencies_for_workspace_changes_by_worktree_dir(
@@ -309,7 +532,5 @@ fn process_rules(ctx: &mut CommandContext) -> anyhow::Result<()> {
         Some(&dependencies),
     )
     .map_err(|e| anyhow::anyhow!("Failed to get assignments: {}", e))?;
-
-    handler::process_workspace_rules(ctx, &assignments, &Some(dependencies))?;
-    Ok(())
+    Ok((assignments, dependencies))
 }
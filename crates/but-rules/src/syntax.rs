@@ -0,0 +1,179 @@
+//! A compact textual syntax for triggers, filters and actions, used where rules are written by hand.
+//!
+//! * **Triggers** are one of `fs`, `claude`, `commit`, `upstream` or `pre-push`.
+//! * **Filters** are `<kind>:<value>` pairs:
+//!     - `path:<regex>` and `content:<regex>`
+//!     - `change:<added|deleted|modified|renamed>`
+//!     - `type:<refactor|feature|fix|docs>`, with any other value being a user defined type
+//!     - `session:<id>`
+//! * **Actions** are one of
+//!     - `assign:<leftmost|rightmost|stack-id>`, `amend:<commit>` and `commit:<branch>`
+//!     - `assign-appropriate`, `absorb` and `prompt:<text>`
+//!
+//! Each of them can be parsed with [`FromStr`] and is displayed in the same syntax.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{Context, bail};
+
+use crate::{
+    Action, Filter, ImplicitOperation, Operation, SemanticType, StackTarget, TreeStatus, Trigger,
+};
+
+impl FromStr for Trigger {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "fs" => Trigger::FileSytemChange,
+            "claude" => Trigger::ClaudeCodeHook,
+            "commit" => Trigger::CommitCreated,
+            "upstream" => Trigger::UpstreamChange,
+            "pre-push" => Trigger::PrePush,
+            _ => bail!(
+                "Unknown trigger '{s}', expected one of fs, claude, commit, upstream or pre-push"
+            ),
+        })
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Trigger::FileSytemChange => "fs",
+            Trigger::ClaudeCodeHook => "claude",
+            Trigger::CommitCreated => "commit",
+            Trigger::UpstreamChange => "upstream",
+            Trigger::PrePush => "pre-push",
+        })
+    }
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s
+            .split_once(':')
+            .with_context(|| format!("Filter '{s}' must have the form <kind>:<value>"))?;
+        Ok(match kind {
+            "path" => Filter::PathMatchesRegex(parse_regex(value)?),
+            "content" => Filter::ContentMatchesRegex(parse_regex(value)?),
+            "change" => Filter::FileChangeType(match value {
+                "added" => TreeStatus::Addition,
+                "deleted" => TreeStatus::Deletion,
+                "modified" => TreeStatus::Modification,
+                "renamed" => TreeStatus::Rename,
+                _ => bail!(
+                    "Unknown change type '{value}', expected one of added, deleted, modified or renamed"
+                ),
+            }),
+            "type" => Filter::SemanticType(match value {
+                "refactor" => SemanticType::Refactor,
+                "feature" => SemanticType::NewFeature,
+                "fix" => SemanticType::BugFix,
+                "docs" => SemanticType::Documentation,
+                "" => bail!("The semantic type must not be empty"),
+                _ => SemanticType::UserDefined(value.to_owned()),
+            }),
+            "session" => Filter::ClaudeCodeSessionId(value.to_owned()),
+            _ => bail!(
+                "Unknown filter '{kind}', expected one of path, content, change, type or session"
+            ),
+        })
+    }
+}
+
+fn parse_regex(value: &str) -> anyhow::Result<regex::Regex> {
+    regex::Regex::new(value).with_context(|| format!("Invalid regular expression '{value}'"))
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::PathMatchesRegex(regex) => write!(f, "path:{}", regex.as_str()),
+            Filter::ContentMatchesRegex(regex) => write!(f, "content:{}", regex.as_str()),
+            Filter::FileChangeType(status) => write!(
+                f,
+                "change:{}",
+                match status {
+                    TreeStatus::Addition => "added",
+                    TreeStatus::Deletion => "deleted",
+                    TreeStatus::Modification => "modified",
+                    TreeStatus::Rename => "renamed",
+                }
+            ),
+            Filter::SemanticType(semantic_type) => write!(
+                f,
+                "type:{}",
+                match semantic_type {
+                    SemanticType::Refactor => "refactor",
+                    SemanticType::NewFeature => "feature",
+                    SemanticType::BugFix => "fix",
+                    SemanticType::Documentation => "docs",
+                    SemanticType::UserDefined(name) => name,
+                }
+            ),
+            Filter::ClaudeCodeSessionId(id) => write!(f, "session:{id}"),
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = match s.split_once(':') {
+            Some((kind, value)) => (kind, Some(value)),
+            None => (s, None),
+        };
+        let value = || {
+            value
+                .filter(|v| !v.is_empty())
+                .map(ToOwned::to_owned)
+                .with_context(|| format!("Action '{kind}' must have the form {kind}:<value>"))
+        };
+        Ok(match kind {
+            "assign" => Action::Explicit(Operation::Assign {
+                target: match value()?.as_str() {
+                    "leftmost" => StackTarget::Leftmost,
+                    "rightmost" => StackTarget::Rightmost,
+                    stack_id => StackTarget::StackId(stack_id.to_owned()),
+                },
+            }),
+            "amend" => Action::Explicit(Operation::Amend {
+                commit_id: value()?,
+            }),
+            "commit" => Action::Explicit(Operation::NewCommit {
+                branch_name: value()?,
+            }),
+            "assign-appropriate" => Action::Implicit(ImplicitOperation::AssignToAppropriateBranch),
+            "absorb" => Action::Implicit(ImplicitOperation::AbsorbIntoDependentCommit),
+            "prompt" => Action::Implicit(ImplicitOperation::LLMPrompt(value()?)),
+            _ => bail!(
+                "Unknown action '{kind}', expected one of assign, amend, commit, assign-appropriate, absorb or prompt"
+            ),
+        })
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Explicit(Operation::Assign { target }) => match target {
+                StackTarget::StackId(stack_id) => write!(f, "assign:{stack_id}"),
+                StackTarget::Leftmost => f.write_str("assign:leftmost"),
+                StackTarget::Rightmost => f.write_str("assign:rightmost"),
+            },
+            Action::Explicit(Operation::Amend { commit_id }) => write!(f, "amend:{commit_id}"),
+            Action::Explicit(Operation::NewCommit { branch_name }) => {
+                write!(f, "commit:{branch_name}")
+            }
+            Action::Implicit(ImplicitOperation::AssignToAppropriateBranch) => {
+                f.write_str("assign-appropriate")
+            }
+            Action::Implicit(ImplicitOperation::AbsorbIntoDependentCommit) => f.write_str("absorb"),
+            Action::Implicit(ImplicitOperation::LLMPrompt(prompt)) => write!(f, "prompt:{prompt}"),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+#!/usr/bin/env bash
+set -eu -o pipefail
+CLI=${1:?The first argument is the GitButler CLI}
+
+git init remote
+(cd remote
+  git config user.name "Author"
+  git config user.email "author@example.com"
+  mkdir src docs
+  printf 'fn main() {\n    run();\n}\n' > src/main.rs
+  echo "old" > obsolete.txt
+  echo "# Guide" > docs/guide.md
+  seq 1 20 > lines.txt
+  git add . && git commit -m "init"
+)
+
+export GITBUTLER_CLI_DATA_DIR=../user/gitbutler/app-data
+
+# Setup:
+# * (HEAD -> gitbutler/workspace) GitButler Workspace Commit
+# * (my_stack) add feature
+# * (origin/main, origin/HEAD, main) init
+# With uncommitted changes that cover each kind of change:
+#   src/main.rs    modified, adding a TODO comment
+#   src/helper.rs  added
+#   obsolete.txt   deleted
+#   docs/guide.md  modified
+git clone remote workspace
+(cd workspace
+  git config user.name "Author"
+  git config user.email "author@example.com"
+  $CLI project add --switch-to-workspace "$(git rev-parse --symbolic-full-name @{u})"
+  $CLI branch create --set-default my_stack
+  echo "feature" > feature.rs
+  $CLI branch commit my_stack -m "add feature"
+
+  printf 'fn main() {\n    run();\n    // TODO: clean up\n}\n' > src/main.rs
+  printf 'pub fn helper() {}\n' > src/helper.rs
+  rm obsolete.txt
+  echo "More" >> docs/guide.md
+)
+
+# Setup:
+# * (HEAD -> gitbutler/workspace) GitButler Workspace Commit
+# * (my_stack) change two
+# * (origin/main, origin/HEAD, main) init
+# With uncommitted changes to lines.txt, of which the first hunk depends on "change two", and the second one
+# depends on no commit in the workspace.
+git clone remote absorb
+(cd absorb
+  git config user.name "Author"
+  git config user.email "author@example.com"
+  $CLI project add --switch-to-workspace "$(git rev-parse --symbolic-full-name @{u})"
+  $CLI branch create --set-default my_stack
+  sed 's/^2$/two/' lines.txt > lines.tmp && mv lines.tmp lines.txt
+  $CLI branch commit my_stack -m "change two"
+
+  sed 's/^two$/TWO/; s/^19$/nineteen/' lines.txt > lines.tmp && mv lines.tmp lines.txt
+)
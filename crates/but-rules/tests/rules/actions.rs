@@ -0,0 +1,260 @@
+use std::path::Path;
+
+use but_rules::{CreateRuleRequest, Trigger, handler::MatchContext};
+use gitbutler_command_context::CommandContext;
+use gitbutler_oplog::OplogExt;
+use tempfile::TempDir;
+
+use crate::{dry_run, evaluate, paths, rules, workspace};
+
+fn absorb_workspace() -> anyhow::Result<(CommandContext, TempDir)> {
+    gitbutler_testsupport::writable::fixture("rules.sh", "absorb")
+}
+
+/// Return the content of `path` in the tree of the tip of `my_stack`.
+fn committed_content(ctx: &CommandContext, path: &str) -> anyhow::Result<String> {
+    let repo = ctx.repo();
+    let tree = repo
+        .find_reference("refs/heads/my_stack")?
+        .peel_to_commit()?
+        .tree()?;
+    let blob = repo.find_blob(tree.get_path(Path::new(path))?.id())?;
+    Ok(String::from_utf8(blob.content().to_owned())?)
+}
+
+fn tip_message(ctx: &CommandContext) -> anyhow::Result<String> {
+    Ok(ctx
+        .repo()
+        .find_reference("refs/heads/my_stack")?
+        .peel_to_commit()?
+        .summary()
+        .unwrap_or_default()
+        .to_owned())
+}
+
+#[test]
+fn dry_runs_describe_actions_without_performing_them() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let head = ctx.repo().head()?.peel_to_commit()?.id();
+    let tip = ctx
+        .repo()
+        .find_reference("refs/heads/my_stack")?
+        .peel_to_commit()?
+        .id()
+        .to_string();
+    let short_tip = &tip[..7];
+    let outcomes = dry_run(
+        &mut ctx,
+        rules(
+            r#"
+            [[rule]]
+            id = "commit"
+            trigger = "commit"
+            filters = ["path:^src/"]
+            action = "commit:my_stack"
+
+            [[rule]]
+            id = "amend"
+            trigger = "commit"
+            filters = ["path:^docs/"]
+            action = "amend:my_stack"
+
+            [[rule]]
+            id = "prompt"
+            trigger = "pre-push"
+            filters = ["path:^docs/"]
+            action = "prompt:Describe the change"
+            "#,
+        )?,
+        &MatchContext::default(),
+    )?;
+    assert_eq!(
+        outcomes
+            .iter()
+            .map(|o| (o.rule_id.as_str(), paths(o), o.description.as_str()))
+            .collect::<Vec<_>>(),
+        [
+            (
+                "commit",
+                vec!["src/helper.rs", "src/main.rs"],
+                "Commit 2 changes in 2 files to branch 'my_stack'"
+            ),
+            (
+                "amend",
+                vec!["docs/guide.md"],
+                &*format!("Amend 1 change in `docs/guide.md` into commit {short_tip}")
+            ),
+            (
+                "prompt",
+                vec!["docs/guide.md"],
+                "Let the LLM handle 1 change in `docs/guide.md` as prompted: Describe the change"
+            ),
+        ]
+    );
+    assert!(
+        outcomes
+            .iter()
+            .all(|o| !o.executed && o.snapshot_id.is_none() && o.error.is_none()),
+        "nothing was done"
+    );
+    assert_eq!(ctx.repo().head()?.peel_to_commit()?.id(), head);
+    assert_eq!(tip_message(&ctx)?, "add feature");
+    assert!(ctx.list_snapshots(10, None, Vec::new())?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn new_commit_commits_the_matched_changes_to_the_branch() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let outcomes = evaluate(
+        &mut ctx,
+        rules(
+            r#"
+            [[rule]]
+            id = "commit"
+            trigger = "commit"
+            filters = ["path:^src/"]
+            action = "commit:my_stack"
+            "#,
+        )?,
+        &MatchContext::default(),
+        false,
+    )?;
+    assert_eq!(outcomes.len(), 1);
+    let outcome = &outcomes[0];
+    assert_eq!(outcome.error, None);
+    assert!(outcome.executed);
+    assert!(
+        outcome.snapshot_id.is_some(),
+        "a snapshot allows to undo the action"
+    );
+
+    assert_eq!(tip_message(&ctx)?, "Update 2 files");
+    assert_eq!(
+        committed_content(&ctx, "src/helper.rs")?,
+        "pub fn helper() {}\n"
+    );
+    let changes = but_core::diff::worktree_changes(&ctx.gix_repo()?)?.changes;
+    assert!(
+        changes.iter().all(|c| !c.path.starts_with(b"src/")),
+        "the committed changes aren't in the worktree anymore"
+    );
+    assert_eq!(changes.len(), 2, "the other changes remain");
+    Ok(())
+}
+
+#[test]
+fn amend_amends_the_matched_changes_into_the_commit() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let outcomes = evaluate(
+        &mut ctx,
+        rules(
+            r#"
+            [[rule]]
+            id = "amend"
+            trigger = "commit"
+            filters = ["path:^docs/"]
+            action = "amend:my_stack"
+            "#,
+        )?,
+        &MatchContext::default(),
+        false,
+    )?;
+    assert_eq!(outcomes.len(), 1);
+    assert_eq!(outcomes[0].error, None);
+    assert!(outcomes[0].executed && outcomes[0].snapshot_id.is_some());
+
+    assert_eq!(tip_message(&ctx)?, "add feature", "the message is kept");
+    assert_eq!(committed_content(&ctx, "docs/guide.md")?, "# Guide\nMore\n");
+    Ok(())
+}
+
+#[test]
+fn absorb_only_amends_the_hunks_that_depend_on_a_commit() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = absorb_workspace()?;
+    let absorb_rules = || {
+        rules(
+            r#"
+            [[rule]]
+            id = "absorb"
+            action = "absorb"
+            "#,
+        )
+    };
+    let outcomes = dry_run(&mut ctx, absorb_rules()?, &MatchContext::default())?;
+    assert_eq!(outcomes.len(), 1);
+    assert_eq!(
+        outcomes[0].changes.len(),
+        1,
+        "only the hunk that depends on a commit is absorbed"
+    );
+
+    let outcomes = evaluate(&mut ctx, absorb_rules()?, &MatchContext::default(), false)?;
+    assert_eq!(outcomes[0].error, None);
+    assert!(outcomes[0].executed);
+
+    let committed = committed_content(&ctx, "lines.txt")?;
+    assert_eq!(
+        committed.lines().nth(1),
+        Some("TWO"),
+        "the dependent hunk was amended"
+    );
+    assert_eq!(
+        committed.lines().nth(18),
+        Some("19"),
+        "the other hunk of the same file wasn't"
+    );
+    assert_eq!(tip_message(&ctx)?, "change two");
+    let worktree = std::fs::read_to_string(ctx.project().path.join("lines.txt"))?;
+    assert_eq!(
+        worktree.lines().nth(18),
+        Some("nineteen"),
+        "it's still in the worktree"
+    );
+    Ok(())
+}
+
+#[test]
+fn actions_that_change_commits_need_an_explicit_trigger() -> anyhow::Result<()> {
+    for action in ["amend:my_stack", "commit:my_stack", "prompt:Commit this"] {
+        let err = rules(&format!(
+            r#"
+            [[rule]]
+            id = "on-save"
+            action = "{action}"
+            "#
+        ))
+        .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("line 4: Action"),
+            "{action} is rejected in the rules file: {err:#}"
+        );
+    }
+
+    let (mut ctx, _tmp) = workspace()?;
+    let err = but_rules::create_rule(
+        &mut ctx,
+        CreateRuleRequest {
+            trigger: Trigger::FileSytemChange,
+            filters: Vec::new(),
+            action: "amend:my_stack".parse()?,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Action 'amend:my_stack' would be performed on every save, use it with a trigger other than 'fs'"
+    );
+    assert!(but_rules::list_rules(&mut ctx)?.is_empty());
+
+    but_rules::create_rule(
+        &mut ctx,
+        CreateRuleRequest {
+            trigger: Trigger::CommitCreated,
+            filters: Vec::new(),
+            action: "amend:my_stack".parse()?,
+        },
+    )?;
+    assert_eq!(but_rules::list_rules(&mut ctx)?.len(), 1);
+    Ok(())
+}
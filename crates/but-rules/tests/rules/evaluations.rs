@@ -0,0 +1,60 @@
+use but_rules::handler::MatchContext;
+
+use crate::{dry_run, evaluate, rules, workspace};
+
+#[test]
+fn evaluations_are_recorded_unless_in_a_dry_run() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let commit_rules = || {
+        rules(
+            r#"
+            [[rule]]
+            id = "commit"
+            trigger = "commit"
+            filters = ["path:^src/"]
+            action = "commit:my_stack"
+            "#,
+        )
+    };
+    dry_run(&mut ctx, commit_rules()?, &MatchContext::default())?;
+    assert!(but_rules::list_evaluations(&mut ctx, None, 10)?.is_empty());
+
+    evaluate(&mut ctx, commit_rules()?, &MatchContext::default(), false)?;
+    let evaluations = but_rules::list_evaluations(&mut ctx, None, 10)?;
+    assert_eq!(evaluations.len(), 1);
+    let evaluation = &evaluations[0];
+    assert_eq!(evaluation.rule_id, "commit");
+    assert_eq!(evaluation.trigger, but_rules::Trigger::CommitCreated);
+    assert!(evaluation.executed && evaluation.snapshot_id.is_some());
+    let mut paths = evaluation
+        .changes
+        .iter()
+        .map(|c| c.path.as_str())
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(paths, ["src/helper.rs", "src/main.rs"]);
+    Ok(())
+}
+
+#[test]
+fn rules_with_nothing_to_do_have_no_outcome_and_are_not_recorded() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let outcomes = evaluate(
+        &mut ctx,
+        rules(
+            r#"
+            [[rule]]
+            id = "appropriate"
+            action = "assign-appropriate"
+            "#,
+        )?,
+        &MatchContext::default(),
+        false,
+    )?;
+    assert!(
+        outcomes.is_empty(),
+        "none of the changes depends on a commit or shares a file with assigned changes"
+    );
+    assert!(but_rules::list_evaluations(&mut ctx, None, 10)?.is_empty());
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use but_rules::{RuleSource, Trigger};
+
+use crate::rules;
+
+#[test]
+fn rules_are_loaded_in_order_with_defaults() -> anyhow::Result<()> {
+    let rules = rules(
+        r#"
+        [[rule]]
+        id = "migrations"
+        filters = ["path:^migrations/", "change:added"]
+        action = "assign:leftmost"
+
+        [[rule]]
+        id = "docs"
+        trigger = "commit"
+        action = "commit:docs"
+        enabled = false
+        "#,
+    )?;
+    assert_eq!(
+        rules
+            .iter()
+            .map(|r| (
+                r.id(),
+                r.trigger().clone(),
+                r.filters()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                r.action().to_string(),
+                r.enabled(),
+                r.source()
+            ))
+            .collect::<Vec<_>>(),
+        [
+            (
+                "migrations".to_owned(),
+                Trigger::FileSytemChange,
+                vec!["path:^migrations/".to_owned(), "change:added".to_owned()],
+                "assign:leftmost".to_owned(),
+                true,
+                RuleSource::Repository
+            ),
+            (
+                "docs".to_owned(),
+                Trigger::CommitCreated,
+                vec![],
+                "commit:docs".to_owned(),
+                false,
+                RuleSource::Repository
+            ),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn a_missing_file_has_no_rules() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    assert!(but_rules::file::load(dir.path())?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn errors_mention_the_line() {
+    let err = |toml: &str| format!("{:#}", rules(toml).unwrap_err());
+    assert!(
+        err(r#"
+[[rule]]
+id = "a"
+filters = ["path:^src/", "path:("]
+action = "absorb"
+"#)
+        .starts_with(
+            "Invalid rules in .gitbutler/rules.toml: line 4: Invalid regular expression '(': regex parse error"
+        ),
+        "invalid regular expressions are rejected"
+    );
+    assert_eq!(
+        err(r#"
+[[rule]]
+id = "a"
+trigger = "save"
+action = "absorb"
+"#),
+        "Invalid rules in .gitbutler/rules.toml: line 4: Unknown trigger 'save', expected one of fs, claude, commit, upstream or pre-push"
+    );
+    assert_eq!(
+        err(r#"
+[[rule]]
+id = "a"
+
+action = "move:leftmost"
+"#),
+        "Invalid rules in .gitbutler/rules.toml: line 5: Unknown action 'move', expected one of assign, amend, commit, assign-appropriate, absorb or prompt"
+    );
+    assert_eq!(
+        err(r#"
+[[rule]]
+id = ""
+action = "absorb"
+"#),
+        "Invalid rules in .gitbutler/rules.toml: line 3: The rule id must not be empty"
+    );
+}
+
+#[test]
+fn ids_must_be_unique() {
+    let err = rules(
+        r#"
+[[rule]]
+id = "same"
+action = "absorb"
+
+[[rule]]
+id = "same"
+action = "assign:leftmost"
+"#,
+    )
+    .unwrap_err();
+    assert_eq!(
+        format!("{err:#}"),
+        "Invalid rules in .gitbutler/rules.toml: line 7: The rule id 'same' is used more than once"
+    );
+}
+
+#[test]
+fn unknown_and_missing_fields_are_rejected() {
+    let err = |toml: &str| format!("{:#}", rules(toml).unwrap_err());
+    assert!(
+        err(r#"
+[[rule]]
+id = "a"
+action = "absorb"
+filter = ["path:^src/"]
+"#)
+        .contains("unknown field `filter`"),
+        "typos don't go unnoticed"
+    );
+    assert!(
+        err(r#"
+[[rule]]
+id = "a"
+"#)
+        .contains("missing field `action`")
+    );
+}
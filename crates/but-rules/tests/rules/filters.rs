@@ -0,0 +1,237 @@
+use std::sync::Arc;
+
+use but_action::llm::{ChatResponse, MockProvider};
+use but_rules::{
+    classifier::{LlmClassifier, SemanticClassifier},
+    handler::MatchContext,
+};
+
+use crate::{dry_run, paths, rules, workspace};
+
+#[test]
+fn all_filters_of_a_rule_must_match() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let outcomes = dry_run(
+        &mut ctx,
+        rules(
+            r#"
+            [[rule]]
+            id = "new-sources"
+            filters = ["path:^src/", "change:added"]
+            action = "absorb"
+            "#,
+        )?,
+        &MatchContext::default(),
+    )?;
+    assert_eq!(outcomes.len(), 1);
+    assert_eq!(paths(&outcomes[0]), ["src/helper.rs"]);
+    assert!(!outcomes[0].executed, "nothing is done in a dry run");
+    Ok(())
+}
+
+#[test]
+fn change_types() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let outcomes = dry_run(
+        &mut ctx,
+        rules(
+            r#"
+            [[rule]]
+            id = "added"
+            filters = ["change:added"]
+            action = "absorb"
+
+            [[rule]]
+            id = "deleted"
+            filters = ["change:deleted"]
+            action = "absorb"
+
+            [[rule]]
+            id = "modified"
+            filters = ["change:modified"]
+            action = "absorb"
+
+            [[rule]]
+            id = "renamed"
+            filters = ["change:renamed"]
+            action = "absorb"
+            "#,
+        )?,
+        &MatchContext::default(),
+    )?;
+    assert_eq!(
+        outcomes
+            .iter()
+            .map(|o| (o.rule_id.as_str(), paths(o)))
+            .collect::<Vec<_>>(),
+        [
+            ("added", vec!["src/helper.rs"]),
+            ("deleted", vec!["obsolete.txt"]),
+            ("modified", vec!["docs/guide.md", "src/main.rs"]),
+        ],
+        "rules that don't match any change have no outcome"
+    );
+    Ok(())
+}
+
+#[test]
+fn content_only_matches_added_lines() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let outcomes = dry_run(
+        &mut ctx,
+        rules(
+            r#"
+            [[rule]]
+            id = "todos"
+            filters = ["content:TODO"]
+            action = "absorb"
+
+            [[rule]]
+            id = "removed"
+            filters = ["content:old"]
+            action = "absorb"
+            "#,
+        )?,
+        &MatchContext::default(),
+    )?;
+    assert_eq!(outcomes.len(), 1, "removed lines never match");
+    assert_eq!(outcomes[0].rule_id, "todos");
+    assert_eq!(paths(&outcomes[0]), ["src/main.rs"]);
+    Ok(())
+}
+
+#[test]
+fn semantic_types_are_inferred_by_heuristics_without_classifier() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let outcomes = dry_run(
+        &mut ctx,
+        rules(
+            r#"
+            [[rule]]
+            id = "docs"
+            filters = ["type:docs"]
+            action = "absorb"
+
+            [[rule]]
+            id = "features"
+            filters = ["type:feature"]
+            action = "absorb"
+            "#,
+        )?,
+        &MatchContext::default(),
+    )?;
+    assert_eq!(
+        outcomes
+            .iter()
+            .map(|o| (o.rule_id.as_str(), paths(o)))
+            .collect::<Vec<_>>(),
+        [
+            ("docs", vec!["docs/guide.md", "src/main.rs"]),
+            ("features", vec!["src/helper.rs"]),
+        ],
+        "documentation paths and comment-only changes are documentation, additions are features"
+    );
+    Ok(())
+}
+
+#[test]
+fn semantic_types_are_inferred_by_the_given_classifier() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let llm = Arc::new(MockProvider::new([ChatResponse {
+        text: Some(r#"{"category": "userDefined", "userDefinedType": "helpers"}"#.into()),
+        tool_calls: Vec::new(),
+    }]));
+    let classifier = LlmClassifier::new(llm.clone());
+    let outcomes = dry_run(
+        &mut ctx,
+        rules(
+            r#"
+            [[rule]]
+            id = "helpers"
+            filters = ["type:helpers", "path:helper"]
+            action = "absorb"
+            "#,
+        )?,
+        &MatchContext {
+            session_id: None,
+            classifier: Some(&classifier as &dyn SemanticClassifier),
+        },
+    )?;
+    assert_eq!(outcomes.len(), 1);
+    assert_eq!(paths(&outcomes[0]), ["src/helper.rs"]);
+
+    let requests = llm.requests();
+    assert_eq!(
+        requests.len(),
+        1,
+        "only changes that match all other filters are classified"
+    );
+    assert!(
+        format!("{:?}", requests[0].messages).contains("- helpers"),
+        "user defined types are offered to the LLM"
+    );
+    Ok(())
+}
+
+#[test]
+fn failing_classifiers_fall_back_to_heuristics() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let classifier = LlmClassifier::new(Arc::new(MockProvider::new(Vec::new())));
+    let outcomes = dry_run(
+        &mut ctx,
+        rules(
+            r#"
+            [[rule]]
+            id = "features"
+            filters = ["type:feature"]
+            action = "absorb"
+            "#,
+        )?,
+        &MatchContext {
+            session_id: None,
+            classifier: Some(&classifier as &dyn SemanticClassifier),
+        },
+    )?;
+    assert_eq!(outcomes.len(), 1);
+    assert_eq!(paths(&outcomes[0]), ["src/helper.rs"]);
+    Ok(())
+}
+
+#[test]
+fn session_filters_only_match_changes_of_that_session() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let session_rules = || {
+        rules(
+            r#"
+            [[rule]]
+            id = "session"
+            filters = ["session:abc", "path:^docs/"]
+            action = "absorb"
+            "#,
+        )
+    };
+    let outcomes = dry_run(&mut ctx, session_rules()?, &MatchContext::default())?;
+    assert!(outcomes.is_empty(), "changes without session never match");
+
+    let outcomes = dry_run(
+        &mut ctx,
+        session_rules()?,
+        &MatchContext {
+            session_id: Some("other"),
+            classifier: None,
+        },
+    )?;
+    assert!(outcomes.is_empty(), "changes of other sessions don't match");
+
+    let outcomes = dry_run(
+        &mut ctx,
+        session_rules()?,
+        &MatchContext {
+            session_id: Some("abc"),
+            classifier: None,
+        },
+    )?;
+    assert_eq!(outcomes.len(), 1);
+    assert_eq!(paths(&outcomes[0]), ["docs/guide.md"]);
+    Ok(())
+}
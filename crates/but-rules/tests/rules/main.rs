@@ -0,0 +1,84 @@
+use but_hunk_dependency::ui::hunk_dependencies_for_workspace_changes_by_worktree_dir;
+use but_rules::{
+    WorkspaceRule,
+    handler::{MatchContext, RuleOutcome},
+};
+use gitbutler_command_context::CommandContext;
+use itertools::Itertools;
+use tempfile::TempDir;
+
+/// What rules do with the changes they match.
+mod actions;
+/// Recording what rules did.
+mod evaluations;
+/// Shared rules in the repository.
+mod file;
+/// Filters narrowing down the changes a rule applies to.
+mod filters;
+/// The compact syntax of triggers, filters and actions.
+mod syntax;
+/// The events that cause rules to be evaluated.
+mod triggers;
+
+/// Return a context for the workspace of `rules.sh`, which has a stack with one commit and uncommitted changes.
+fn workspace() -> anyhow::Result<(CommandContext, TempDir)> {
+    gitbutler_testsupport::writable::fixture("rules.sh", "workspace")
+}
+
+/// Parse `toml` as the content of a rules file.
+fn rules(toml: &str) -> anyhow::Result<Vec<WorkspaceRule>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::create_dir_all(dir.path().join(".gitbutler"))?;
+    std::fs::write(dir.path().join(but_rules::file::RULES_FILE_PATH), toml)?;
+    but_rules::file::load(dir.path())
+}
+
+/// Evaluate `rules` against all uncommitted changes with `context`, and perform their actions unless this is a `dry_run`.
+fn evaluate(
+    ctx: &mut CommandContext,
+    rules: Vec<WorkspaceRule>,
+    context: &MatchContext<'_>,
+    dry_run: bool,
+) -> anyhow::Result<Vec<RuleOutcome>> {
+    let changes = but_core::diff::worktree_changes(&ctx.gix_repo()?)?.changes;
+    let dependencies = hunk_dependencies_for_workspace_changes_by_worktree_dir(
+        ctx,
+        &ctx.project().path,
+        &ctx.project().gb_dir(),
+        Some(changes.clone()),
+    )?;
+    let (assignments, _error) = but_hunk_assignment::assignments_with_fallback(
+        ctx,
+        false,
+        Some(changes),
+        Some(&dependencies),
+    )?;
+    but_rules::handler::evaluate_rules(
+        ctx,
+        rules,
+        &assignments,
+        &Some(dependencies),
+        context,
+        dry_run,
+    )
+}
+
+/// Evaluate `rules` against all uncommitted changes with `context`, without performing their actions.
+fn dry_run(
+    ctx: &mut CommandContext,
+    rules: Vec<WorkspaceRule>,
+    context: &MatchContext<'_>,
+) -> anyhow::Result<Vec<RuleOutcome>> {
+    evaluate(ctx, rules, context, true)
+}
+
+/// The paths of the changes of `outcome`, sorted.
+fn paths(outcome: &RuleOutcome) -> Vec<&str> {
+    outcome
+        .changes
+        .iter()
+        .map(|c| c.path.as_str())
+        .unique()
+        .sorted()
+        .collect()
+}
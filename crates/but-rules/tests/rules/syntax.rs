@@ -0,0 +1,90 @@
+use but_rules::{Action, Filter, Trigger};
+
+#[test]
+fn triggers_round_trip() -> anyhow::Result<()> {
+    for input in ["fs", "claude", "commit", "upstream", "pre-push"] {
+        assert_eq!(input.parse::<Trigger>()?.to_string(), input);
+    }
+    Ok(())
+}
+
+#[test]
+fn filters_round_trip() -> anyhow::Result<()> {
+    for input in [
+        "path:^src/.*\\.rs$",
+        "content:TODO|FIXME",
+        "content:a:b",
+        "change:added",
+        "change:deleted",
+        "change:modified",
+        "change:renamed",
+        "type:refactor",
+        "type:feature",
+        "type:fix",
+        "type:docs",
+        "type:migration",
+        "session:0b5c6d1e",
+    ] {
+        assert_eq!(input.parse::<Filter>()?.to_string(), input);
+    }
+    Ok(())
+}
+
+#[test]
+fn actions_round_trip() -> anyhow::Result<()> {
+    for input in [
+        "assign:leftmost",
+        "assign:rightmost",
+        "assign:0b5c6d1e-6a7b-4c8d-9e0f-1a2b3c4d5e6f",
+        "amend:1a2b3c4",
+        "commit:feature-branch",
+        "assign-appropriate",
+        "absorb",
+        "prompt:Commit with a message: describe why",
+    ] {
+        assert_eq!(input.parse::<Action>()?.to_string(), input);
+    }
+    Ok(())
+}
+
+#[test]
+fn parse_errors() {
+    let err = |res: anyhow::Result<()>| format!("{:#}", res.unwrap_err());
+    assert_eq!(
+        err("push".parse::<Trigger>().map(drop)),
+        "Unknown trigger 'push', expected one of fs, claude, commit, upstream or pre-push"
+    );
+    assert_eq!(
+        err("src/".parse::<Filter>().map(drop)),
+        "Filter 'src/' must have the form <kind>:<value>"
+    );
+    assert_eq!(
+        err("name:src/".parse::<Filter>().map(drop)),
+        "Unknown filter 'name', expected one of path, content, change, type or session"
+    );
+    assert_eq!(
+        err("change:copied".parse::<Filter>().map(drop)),
+        "Unknown change type 'copied', expected one of added, deleted, modified or renamed"
+    );
+    assert_eq!(
+        err("type:".parse::<Filter>().map(drop)),
+        "The semantic type must not be empty"
+    );
+    assert!(
+        err("path:(".parse::<Filter>().map(drop))
+            .starts_with("Invalid regular expression '(': regex parse error"),
+        "the reason is included"
+    );
+    assert_eq!(
+        err("amend".parse::<Action>().map(drop)),
+        "Action 'amend' must have the form amend:<value>"
+    );
+    assert_eq!(
+        err("commit:".parse::<Action>().map(drop)),
+        "Action 'commit' must have the form commit:<value>"
+    );
+    assert_eq!(
+        err("squash:abc".parse::<Action>().map(drop)),
+        "Unknown action 'squash', expected one of assign, amend, commit, assign-appropriate, absorb or prompt"
+    );
+}
@@ -0,0 +1,66 @@
+use but_rules::{Trigger, evaluate_rules_for_trigger};
+
+use crate::workspace;
+
+#[test]
+fn rules_are_only_evaluated_for_their_trigger() -> anyhow::Result<()> {
+    let (mut ctx, _tmp) = workspace()?;
+    let worktree = ctx.project().path.clone();
+    std::fs::create_dir_all(worktree.join(".gitbutler"))?;
+    std::fs::write(
+        worktree.join(but_rules::file::RULES_FILE_PATH),
+        r#"
+        [[rule]]
+        id = "on-save"
+        filters = ["path:^src/"]
+        action = "absorb"
+
+        [[rule]]
+        id = "on-commit"
+        trigger = "commit"
+        filters = ["path:^src/"]
+        action = "absorb"
+
+        [[rule]]
+        id = "on-upstream"
+        trigger = "upstream"
+        filters = ["path:^src/"]
+        action = "absorb"
+
+        [[rule]]
+        id = "on-push"
+        trigger = "pre-push"
+        filters = ["path:^src/"]
+        action = "absorb"
+
+        [[rule]]
+        id = "disabled-on-push"
+        trigger = "pre-push"
+        filters = ["path:^src/"]
+        action = "absorb"
+        enabled = false
+        "#,
+    )?;
+
+    for (trigger, expected) in [
+        (Trigger::FileSytemChange, "on-save"),
+        (Trigger::CommitCreated, "on-commit"),
+        (Trigger::UpstreamChange, "on-upstream"),
+        (Trigger::PrePush, "on-push"),
+    ] {
+        let outcomes = evaluate_rules_for_trigger(&mut ctx, trigger.clone(), true)?;
+        assert_eq!(
+            outcomes
+                .iter()
+                .map(|o| o.rule_id.as_str())
+                .collect::<Vec<_>>(),
+            [expected],
+            "{trigger:?} only evaluates enabled rules with that trigger"
+        );
+    }
+    assert!(
+        evaluate_rules_for_trigger(&mut ctx, Trigger::ClaudeCodeHook, true)?.is_empty(),
+        "there are no rules for this trigger"
+    );
+    Ok(())
+}
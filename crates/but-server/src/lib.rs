@@ -60,6 +60,7 @@ pub async fn run() {
     let app_data_dir = but_path::app_data_dir().unwrap();
 
     let broadcaster = Arc::new(Mutex::new(Broadcaster::new()));
+    git::broadcast_git_progress(broadcaster.clone());
     let extra = Extra {
         active_projects: Arc::new(Mutex::new(ActiveProjects::new())),
     };
@@ -156,6 +157,8 @@ async fn handle_command(
         "git_set_global_config" => git::git_set_global_config_cmd(request.params),
         "git_remove_global_config" => git::git_remove_global_config_cmd(request.params),
         "git_get_global_config" => git::git_get_global_config_cmd(request.params),
+        "git_cancel_operation" => git::git_cancel_operation_cmd(request.params),
+        "git_store_token" => git::git_store_token_cmd(request.params),
         // Diff commands
         "tree_change_diffs" => diff::tree_change_diffs_cmd(request.params),
         "commit_details" => diff::commit_details_cmd(request.params),
@@ -278,6 +281,7 @@ async fn handle_command(
             virtual_branches::upstream_integration_statuses_cmd(request.params)
         }
         "integrate_upstream" => virtual_branches::integrate_upstream_cmd(request.params),
+        "sync_workspace" => virtual_branches::sync_workspace_cmd(request.params),
         "resolve_upstream_integration" => {
             virtual_branches::resolve_upstream_integration_cmd(request.params)
         }
@@ -315,7 +319,11 @@ async fn handle_command(
         // Undo/Snapshot commands
         "list_snapshots" => undo::list_snapshots_cmd(request.params),
         "restore_snapshot" => undo::restore_snapshot_cmd(request.params),
+        "restore_stack_snapshot" => undo::restore_stack_snapshot_cmd(request.params),
         "snapshot_diff" => undo::snapshot_diff_cmd(request.params),
+        "undo_tree" => undo::undo_tree_cmd(request.params),
+        "undo_operation" => undo::undo_operation_cmd(request.params),
+        "redo_operation" => undo::redo_operation_cmd(request.params),
         // "oplog_diff_worktrees" => undo::oplog_diff_worktrees(&ctx, request.params),
         // Config management commands
         "get_gb_config" => config::get_gb_config_cmd(request.params),
@@ -332,6 +340,7 @@ async fn handle_command(
         "delete_workspace_rule" => rules::delete_workspace_rule_cmd(request.params),
         "update_workspace_rule" => rules::update_workspace_rule_cmd(request.params),
         "list_workspace_rules" => rules::list_workspace_rules_cmd(request.params),
+        "dry_run_workspace_rules" => rules::dry_run_workspace_rules_cmd(request.params),
         "init_device_oauth" => {
             let result = github::init_device_oauth(NoParams {}).await;
             result.map(|r| json!(r))
@@ -64,6 +64,10 @@ impl ActiveProjects {
                         name: format!("project://{project_id}/worktree_changes"),
                         payload: serde_json::json!(&changes),
                     },
+                    Change::RulesFile { project_id, error } => FrontendEvent {
+                        name: format!("project://{project_id}/rule-updates"),
+                        payload: serde_json::json!({ "kind": "rules", "error": error }),
+                    },
                 };
 
                 println!("Sending event");
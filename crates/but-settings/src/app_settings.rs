@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -82,3 +84,28 @@ pub struct Claude {
     /// Whether to use the configured model in .claude/settings.json instead of passing --model.
     pub use_configured_model: bool,
 }
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Mcp {
+    /// The tools that rewrite history or discard work which each MCP client may call, by client name.
+    /// Use `*` as the client name to allow tools for all clients, and as the tool name to allow all tools.
+    ///
+    /// This is advisory only: the client name is the one a client reports about itself when connecting,
+    /// so it keeps well-behaved agents from rewriting history by accident, but doesn't authenticate them.
+    pub allowed_destructive_tools: BTreeMap<String, Vec<String>>,
+}
+
+impl Mcp {
+    /// Return `true` if the MCP client named `client` may call the destructive `tool`.
+    ///
+    /// `client` is the self-reported name of the client, so this isn't a security boundary.
+    pub fn is_destructive_tool_allowed(&self, client: Option<&str>, tool: &str) -> bool {
+        ["*"]
+            .into_iter()
+            .chain(client)
+            .filter_map(|client| self.allowed_destructive_tools.get(client))
+            .flatten()
+            .any(|allowed| allowed == "*" || allowed == tool)
+    }
+}
@@ -20,6 +20,8 @@ pub struct AppSettings {
     pub fetch: app_settings::Fetch,
     /// Settings related to Claude Code.
     pub claude: app_settings::Claude,
+    /// Settings related to the MCP server.
+    pub mcp: app_settings::Mcp,
 }
 
 impl Default for AppSettings {
@@ -15,3 +15,28 @@ fn test_load_settings() {
         "cd51880daa675d9e6452"
     ); // default
 }
+
+#[test]
+fn destructive_mcp_tools_are_allowed_per_client() {
+    let mut settings = AppSettings::default();
+    assert!(
+        !settings
+            .mcp
+            .is_destructive_tool_allowed(Some("claude-code"), "undo"),
+        "nothing is allowed by default"
+    );
+
+    settings.mcp.allowed_destructive_tools = [
+        ("claude-code".to_owned(), vec!["undo".to_owned()]),
+        ("cursor".to_owned(), vec!["*".to_owned()]),
+        ("*".to_owned(), vec!["squash_commits".to_owned()]),
+    ]
+    .into();
+    let mcp = &settings.mcp;
+    assert!(mcp.is_destructive_tool_allowed(Some("claude-code"), "undo"));
+    assert!(!mcp.is_destructive_tool_allowed(Some("claude-code"), "amend"));
+    assert!(mcp.is_destructive_tool_allowed(Some("cursor"), "amend"));
+    assert!(mcp.is_destructive_tool_allowed(Some("claude-code"), "squash_commits"));
+    assert!(mcp.is_destructive_tool_allowed(None, "squash_commits"));
+    assert!(!mcp.is_destructive_tool_allowed(None, "undo"));
+}
@@ -101,6 +101,10 @@ pub trait Tool: 'static + Send + Sync {
     fn name(&self) -> String;
     fn description(&self) -> String;
     fn parameters(&self) -> serde_json::Value;
+    /// Whether the tool rewrites existing commits or discards work, instead of only adding to the workspace.
+    fn is_destructive(&self) -> bool {
+        false
+    }
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
@@ -110,6 +114,35 @@ pub trait Tool: 'static + Send + Sync {
     ) -> anyhow::Result<serde_json::Value>;
 }
 
+/// Allow tools that are already shared to be registered in a [`Toolset`].
+impl Tool for Arc<dyn Tool> {
+    fn name(&self) -> String {
+        (**self).name()
+    }
+
+    fn description(&self) -> String {
+        (**self).description()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        (**self).parameters()
+    }
+
+    fn is_destructive(&self) -> bool {
+        (**self).is_destructive()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        emitter: Arc<Emitter>,
+        commit_mapping: &mut HashMap<ObjectId, ObjectId>,
+    ) -> anyhow::Result<serde_json::Value> {
+        <dyn Tool>::call(Arc::clone(&*self), parameters, ctx, emitter, commit_mapping)
+    }
+}
+
 pub fn error_to_json(error: &anyhow::Error, action_identifier: &str) -> serde_json::Value {
     serde_json::json!({
         "error": format!("Failed to {}: {}", action_identifier, error.to_string())
@@ -11,7 +11,7 @@ use but_workspace::{CommmitSplitOutcome, StackId};
 use gitbutler_branch_actions::{BranchManagerExt, update_workspace_commit};
 use gitbutler_command_context::CommandContext;
 use gitbutler_oplog::entry::{OperationKind, SnapshotDetails};
-use gitbutler_oplog::{OplogExt, SnapshotExt};
+use gitbutler_oplog::{OplogExt, SnapshotExt, UndoNode};
 use gitbutler_oxidize::{ObjectIdExt, OidExt, git2_to_gix_object_id};
 use gitbutler_project::Project;
 use gitbutler_reference::{LocalRefname, Refname};
@@ -69,6 +69,26 @@ pub fn amend_toolset(
     toolset
 }
 
+/// Returns all workspace tools, including the ones that reorder commits or undo operations,
+/// for clients that pick the tools to call themselves.
+pub fn all_workspace_tools() -> Vec<Arc<dyn Tool>> {
+    vec![
+        Arc::new(Commit),
+        Arc::new(CreateBranch),
+        Arc::new(Amend),
+        Arc::new(CreateBlankCommit),
+        Arc::new(SquashCommits),
+        Arc::new(GetProjectStatus),
+        Arc::new(MoveFileChanges),
+        Arc::new(GetCommitDetails),
+        Arc::new(GetBranchChanges),
+        Arc::new(SplitBranch),
+        Arc::new(SplitCommit),
+        Arc::new(ReorderCommits),
+        Arc::new(Undo),
+    ]
+}
+
 pub struct Commit;
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
@@ -245,6 +265,8 @@ pub fn create_commit(
         params.message_title.trim(),
         params.message_body.trim()
     );
+    let message =
+        gitbutler_branch_actions::hooks::run_pre_commit_hooks(ctx, None, &file_changes, message)?;
 
     let outcome = but_workspace::commit_engine::create_commit_simple(
         ctx,
@@ -276,8 +298,11 @@ pub fn create_commit(
     let (name, payload) = stack_update.emittable();
     (emitter)(&name, payload);
 
-    let outcome: but_workspace::commit_engine::ui::CreateCommitOutcome = outcome?.into();
-    Ok(outcome)
+    let outcome = outcome?;
+    if outcome.new_commit.is_some() {
+        gitbutler_branch_actions::hooks::run_post_commit_hook(ctx)?;
+    }
+    Ok(outcome.into())
 }
 
 pub struct CreateBranch;
@@ -492,6 +517,10 @@ impl Tool for Amend {
         serde_json::to_value(&schema).unwrap_or_default()
     }
 
+    fn is_destructive(&self) -> bool {
+        true
+    }
+
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
@@ -558,6 +587,13 @@ pub fn amend_commit_inner(
         commit_id
     };
 
+    let message = gitbutler_branch_actions::hooks::run_pre_commit_hooks(
+        ctx,
+        Some(commit_id),
+        &file_changes,
+        message,
+    )?;
+
     let outcome = but_workspace::commit_engine::create_commit_and_update_refs_with_project(
         &repo,
         project,
@@ -581,6 +617,12 @@ pub fn amend_commit_inner(
     let (name, payload) = stack_update.emittable();
     (emitter)(&name, payload);
 
+    if outcome
+        .as_ref()
+        .is_ok_and(|outcome| outcome.new_commit.is_some())
+    {
+        gitbutler_branch_actions::hooks::run_post_commit_hook(ctx)?;
+    }
     outcome
 }
 
@@ -875,6 +917,10 @@ impl Tool for MoveFileChanges {
         serde_json::to_value(&schema).unwrap_or_default()
     }
 
+    fn is_destructive(&self) -> bool {
+        true
+    }
+
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
@@ -1211,6 +1257,10 @@ impl Tool for SquashCommits {
         serde_json::to_value(&schema).unwrap_or_default()
     }
 
+    fn is_destructive(&self) -> bool {
+        true
+    }
+
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
@@ -1349,6 +1399,10 @@ impl Tool for SplitBranch {
         serde_json::to_value(&schema).unwrap_or_default()
     }
 
+    fn is_destructive(&self) -> bool {
+        true
+    }
+
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
@@ -1499,6 +1553,10 @@ impl Tool for SplitCommit {
         serde_json::to_value(&schema).unwrap_or_default()
     }
 
+    fn is_destructive(&self) -> bool {
+        true
+    }
+
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
@@ -1624,6 +1682,226 @@ impl From<CommitShard> for but_workspace::CommitFiles {
     }
 }
 
+pub struct ReorderCommits;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReorderCommitsParameters {
+    /// The stack id containing the commits to reorder.
+    #[schemars(description = "
+    <description>
+        The stack id containing the commits to reorder.
+    </description>
+
+    <important_notes>
+        The stack id should refer to an existing stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+    /// The desired order of the commits in each branch of the stack.
+    #[schemars(description = "
+    <description>
+        The desired order of the commits in each branch of the stack.
+    </description>
+
+    <important_notes>
+        All branches of the stack must be listed, newest first, just like in the project status.
+        All commits of the stack must be listed exactly once, newest first.
+        Commits can be moved between the branches of the stack by listing them under a different branch.
+    </important_notes>
+    ")]
+    pub branches: Vec<BranchOrder>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchOrder {
+    /// The name of the branch.
+    #[schemars(description = "
+    <description>
+        The name of the branch.
+    </description>
+
+    <important_notes>
+        The branch must already exist in the stack.
+    </important_notes>
+    ")]
+    pub name: String,
+    /// The commit ids of the branch, newest first.
+    #[schemars(description = "
+    <description>
+        The commit ids that should be in the branch, newest first.
+    </description>
+    ")]
+    pub commit_ids: Vec<String>,
+}
+
+impl Tool for ReorderCommits {
+    fn name(&self) -> String {
+        "reorder_commits".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Reorder the commits of a stack, and move commits between the branches of the stack.
+        </description>
+
+        <important_notes>
+            This tool rebases the commits of the stack into the given order, which changes their commit ids.
+            The order of the commits is newest first, just like in the project status.
+            Reordering can fail if commits depend on changes of the commits they are moved past.
+        </important_notes>
+        ".to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(ReorderCommitsParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn is_destructive(&self) -> bool {
+        true
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        emitter: Arc<Emitter>,
+        commit_mapping: &mut HashMap<gix::ObjectId, gix::ObjectId>,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: ReorderCommitsParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value =
+            reorder_commits(ctx, emitter, params, commit_mapping).to_json("reorder_commits");
+        Ok(value)
+    }
+}
+
+pub fn reorder_commits(
+    ctx: &mut CommandContext,
+    emitter: Arc<Emitter>,
+    params: ReorderCommitsParameters,
+    commit_mapping: &mut HashMap<gix::ObjectId, gix::ObjectId>,
+) -> Result<StackId, anyhow::Error> {
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let series = params
+        .branches
+        .into_iter()
+        .map(|branch| {
+            let commit_ids = branch
+                .commit_ids
+                .iter()
+                .map(|id| {
+                    gix::ObjectId::from_str(id)
+                        .map(|oid| find_the_right_commit_id(oid, commit_mapping).to_git2())
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(gitbutler_branch_actions::SeriesOrder {
+                name: branch.name,
+                commit_ids,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    gitbutler_branch_actions::reorder_stack(
+        ctx,
+        stack_id,
+        gitbutler_branch_actions::StackOrder { series },
+    )?;
+
+    let project_id = ctx.project().id;
+    let stack_update = StackUpdate {
+        project_id,
+        stack_id,
+    };
+    let (name, payload) = stack_update.emittable();
+    (emitter)(&name, payload);
+
+    Ok(stack_id)
+}
+
+pub struct Undo;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoParameters {}
+
+impl Tool for Undo {
+    fn name(&self) -> String {
+        "undo".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Undo the most recent operation on the workspace, like a commit, an amend or a squash.
+        </description>
+
+        <important_notes>
+            This restores the workspace to the state right before the operation, including the uncommitted changes.
+            Calling it again undoes the operation before that.
+            Returns the undone operation, or nothing if there was nothing to undo.
+        </important_notes>
+        ".to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(UndoParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn is_destructive(&self) -> bool {
+        true
+    }
+
+    fn call(
+        self: Arc<Self>,
+        _parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        emitter: Arc<Emitter>,
+        commit_mapping: &mut HashMap<gix::ObjectId, gix::ObjectId>,
+    ) -> anyhow::Result<serde_json::Value> {
+        let value = undo(ctx, emitter, commit_mapping).to_json("undo");
+        Ok(value)
+    }
+}
+
+pub fn undo(
+    ctx: &mut CommandContext,
+    emitter: Arc<Emitter>,
+    commit_mapping: &mut HashMap<gix::ObjectId, gix::ObjectId>,
+) -> Result<Option<UndoNode>, anyhow::Error> {
+    let mut guard = ctx.project().exclusive_worktree_access();
+    let undone = ctx.undo(guard.write_permission())?;
+
+    // The commits that were rewritten before are restored, so their new ids are gone.
+    commit_mapping.clear();
+
+    let project_id = ctx.project().id;
+    let repo = ctx.gix_repo()?;
+    for stack in stacks(ctx, &repo)? {
+        if let Some(stack_id) = stack.id {
+            let stack_update = StackUpdate {
+                project_id,
+                stack_id,
+            };
+            let (name, payload) = stack_update.emittable();
+            (emitter)(&name, payload);
+        }
+    }
+
+    Ok(undone)
+}
+
+impl ToolResult for Result<Option<UndoNode>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "UndoNode")
+    }
+}
+
 fn ref_metadata_toml(project: &Project) -> anyhow::Result<VirtualBranchesTomlMetadata> {
     VirtualBranchesTomlMetadata::from_path(project.gb_dir().join("virtual_branches.toml"))
 }
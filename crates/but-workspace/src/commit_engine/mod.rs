@@ -494,6 +494,7 @@ pub fn create_commit_and_update_refs(
                     but_rebase::RebaseStep::Pick {
                         commit_id,
                         new_message: None,
+                        base: None,
                     }
                 }))?;
                 if let Some(workspace_tip) = workspace_tip {
@@ -527,6 +528,7 @@ pub fn create_commit_and_update_refs(
                     builder.steps([but_rebase::RebaseStep::Pick {
                         commit_id,
                         new_message: None,
+                        base: None,
                     }])?;
                     match builder.rebase() {
                         Ok(mut outcome) => {
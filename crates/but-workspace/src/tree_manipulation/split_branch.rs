@@ -103,6 +103,7 @@ pub fn split_branch(
             let pick_step = RebaseStep::Pick {
                 commit_id: new_commit_id,
                 new_message: None,
+                base: None,
             };
             steps.push(pick_step);
         }
@@ -196,6 +197,7 @@ pub fn split_into_dependent_branch(
             let pick_step = RebaseStep::Pick {
                 commit_id: new_commit_id,
                 new_message: None,
+                base: None,
             };
             dependent_branch_steps.push(pick_step);
         }
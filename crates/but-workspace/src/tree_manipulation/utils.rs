@@ -367,6 +367,7 @@ pub fn replace_pick_with_multiple_commits(
                 new_steps.push(RebaseStep::Pick {
                     commit_id: *replacement_commit_id,
                     new_message: new_message.clone().map(|msg| msg.into()),
+                    base: None,
                 });
             }
         } else {
@@ -0,0 +1,307 @@
+use std::{borrow::Cow, path::Path};
+
+use anyhow::{Context, bail};
+use but_settings::AppSettings;
+use but_workspace::{
+    StackId,
+    branch::create_reference::{Anchor, Position},
+};
+use colored::Colorize;
+use gitbutler_branch::{BranchCreateRequest, BranchUpdateRequest};
+use gitbutler_branch_actions::stack::CreateSeriesRequest;
+use gitbutler_command_context::CommandContext;
+use gitbutler_oplog::SnapshotExt;
+use gitbutler_project::Project;
+use gitbutler_stack::CommitOrChangeId;
+use gix::refs::Category;
+use serde::Serialize;
+
+use crate::{
+    args::branch::Subcommands,
+    id::{CliId, IdMap},
+};
+
+/// The result of a `but branch` operation, printed as JSON if requested.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BranchOutcome {
+    /// The short name of the branch that was affected.
+    pub name: String,
+    /// The short CLI id of the branch, usable with other commands.
+    pub id: String,
+    /// The stack containing the branch, if it still exists.
+    pub stack_id: Option<StackId>,
+}
+
+impl BranchOutcome {
+    fn new(
+        ctx: &mut CommandContext,
+        name: &str,
+        stack_id: Option<StackId>,
+    ) -> anyhow::Result<Self> {
+        Ok(BranchOutcome {
+            name: name.to_owned(),
+            id: IdMap::new(ctx)?.short_id(&CliId::branch(name)),
+            stack_id,
+        })
+    }
+}
+
+pub(crate) fn handle(cmd: &Subcommands, repo_path: &Path, json: bool) -> anyhow::Result<()> {
+    let project = Project::from_path(repo_path).expect("Failed to create project from path");
+    let ctx = &mut CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    match cmd {
+        Subcommands::New { name } => {
+            let outcome = new(ctx, name, None)?;
+            print(&outcome, json, "Created")
+        }
+        Subcommands::StackOn { branch, name } => {
+            let anchor = branch_name_from_id(ctx, branch)?;
+            let outcome = new(ctx, name, Some(&anchor))?;
+            print(&outcome, json, &format!("Stacked on [{anchor}]:"))
+        }
+        Subcommands::Rename { branch, new_name } => {
+            let branch = branch_name_from_id(ctx, branch)?;
+            let outcome = rename(ctx, &branch, new_name)?;
+            print(&outcome, json, &format!("Renamed [{branch}] →"))
+        }
+        Subcommands::Delete { branch } => {
+            let branch = branch_name_from_id(ctx, branch)?;
+            let outcome = delete(ctx, &branch)?;
+            print(&outcome, json, "Deleted")
+        }
+        Subcommands::Move { branch, position } => {
+            let branch = branch_name_from_id(ctx, branch)?;
+            let outcome = move_stack(ctx, &branch, *position)?;
+            print(&outcome, json, &format!("Moved to position {position}:"))
+        }
+    }
+}
+
+/// Create a new branch `name`, either in its own stack or stacked directly on top of the branch named `anchor`.
+fn new(
+    ctx: &mut CommandContext,
+    name: &str,
+    anchor: Option<&str>,
+) -> anyhow::Result<BranchOutcome> {
+    if !ctx.app_settings().feature_flags.ws3 {
+        return new_legacy(ctx, name, anchor);
+    }
+    let new_ref = Category::LocalBranch.to_full_name(name)?;
+    let anchor_ref = anchor
+        .map(|anchor| Category::LocalBranch.to_full_name(anchor))
+        .transpose()?;
+
+    let mut guard = ctx.project().exclusive_worktree_access();
+    if anchor.is_some() {
+        ctx.snapshot_create_dependent_branch(name, guard.write_permission())
+            .ok();
+    } else {
+        ctx.snapshot_branch_creation(name.to_owned(), guard.write_permission())
+            .ok();
+    }
+    let (repo, mut meta, graph) = ctx.graph_and_meta_mut_and_repo(guard.write_permission())?;
+    let graph = but_workspace::branch::create_reference(
+        new_ref.as_ref(),
+        anchor_ref.as_ref().map(|rn| Anchor::AtSegment {
+            ref_name: Cow::Borrowed(rn.as_ref()),
+            position: Position::Above,
+        }),
+        &repo,
+        &graph.to_workspace()?,
+        &mut *meta,
+    )?;
+
+    let ws = graph.to_workspace()?;
+    let stack_id = ws
+        .find_segment_and_stack_by_refname(new_ref.as_ref())
+        .and_then(|(stack, _segment)| stack.id);
+    // Persist the metadata and release the lock before the workspace is read again.
+    drop((meta, guard));
+    BranchOutcome::new(ctx, name, stack_id)
+}
+
+fn new_legacy(
+    ctx: &mut CommandContext,
+    name: &str,
+    anchor: Option<&str>,
+) -> anyhow::Result<BranchOutcome> {
+    let stack_id = match anchor {
+        Some(anchor) => {
+            let stack = crate::log::stacks(ctx)?
+                .into_iter()
+                .find(|s| s.heads.iter().any(|h| h.name == anchor))
+                .with_context(|| format!("Could not find stack for branch {anchor}"))?;
+            let stack_id = stack.id.context("BUG: legacy stacks always have an id")?;
+            let tip = stack
+                .heads
+                .iter()
+                .find(|h| h.name == anchor)
+                .map(|h| h.tip)
+                .expect("found above");
+            // Point the new branch at the tip of the anchor, and order it right after it.
+            gitbutler_branch_actions::stack::create_branch(
+                ctx,
+                stack_id,
+                CreateSeriesRequest {
+                    name: name.to_owned(),
+                    description: None,
+                    target_patch: Some(CommitOrChangeId::CommitId(tip.to_string())),
+                    preceding_head: Some(anchor.to_owned()),
+                },
+            )?;
+            stack_id
+        }
+        None => {
+            gitbutler_branch_actions::create_virtual_branch(
+                ctx,
+                &BranchCreateRequest {
+                    name: Some(name.to_owned()),
+                    ..Default::default()
+                },
+                ctx.project().exclusive_worktree_access().write_permission(),
+            )?
+            .id
+        }
+    };
+    BranchOutcome::new(ctx, name, Some(stack_id))
+}
+
+fn rename(ctx: &mut CommandContext, branch: &str, new_name: &str) -> anyhow::Result<BranchOutcome> {
+    let stack_id = stack_id_by_branch_name(ctx, branch)?;
+    gitbutler_branch_actions::stack::update_branch_name(
+        ctx,
+        stack_id,
+        branch.to_owned(),
+        new_name.to_owned(),
+    )?;
+    BranchOutcome::new(ctx, new_name, Some(stack_id))
+}
+
+fn delete(ctx: &mut CommandContext, branch: &str) -> anyhow::Result<BranchOutcome> {
+    if !ctx.app_settings().feature_flags.ws3 {
+        let stack_id = stack_id_by_branch_name(ctx, branch)?;
+        gitbutler_branch_actions::stack::remove_branch(ctx, stack_id, branch.to_owned())?;
+        let stack_id = crate::log::stacks(ctx)?
+            .iter()
+            .any(|s| s.id == Some(stack_id))
+            .then_some(stack_id);
+        return BranchOutcome::new(ctx, branch, stack_id);
+    }
+    let ref_name = Category::LocalBranch.to_full_name(branch)?;
+    let mut guard = ctx.project().exclusive_worktree_access();
+    ctx.snapshot_remove_dependent_branch(branch, guard.write_permission())
+        .ok();
+    let (repo, mut meta, graph) = ctx.graph_and_meta_mut_and_repo(guard.write_permission())?;
+    let ws = graph.to_workspace()?;
+    let stack_id = ws
+        .find_segment_and_stack_by_refname(ref_name.as_ref())
+        .and_then(|(stack, _segment)| stack.id);
+    let graph = but_workspace::branch::remove_reference(
+        ref_name.as_ref(),
+        &repo,
+        &ws,
+        &mut *meta,
+        but_workspace::branch::remove_reference::Options {
+            avoid_anonymous_stacks: true,
+            keep_metadata: false,
+        },
+    )?
+    .with_context(|| format!("Branch '{branch}' could not be found in the workspace"))?;
+
+    // The stack itself is gone if this was its only branch.
+    let stack_id = stack_id.filter(|id| {
+        graph
+            .to_workspace()
+            .is_ok_and(|ws| ws.stacks.iter().any(|s| s.id == Some(*id)))
+    });
+    drop((meta, guard));
+    BranchOutcome::new(ctx, branch, stack_id)
+}
+
+/// Move the stack containing `branch` to `position`, shifting all other stacks accordingly.
+fn move_stack(
+    ctx: &mut CommandContext,
+    branch: &str,
+    position: usize,
+) -> anyhow::Result<BranchOutcome> {
+    let stack_id = stack_id_by_branch_name(ctx, branch)?;
+    let mut order: Vec<StackId> = crate::log::stacks(ctx)?
+        .iter()
+        .filter_map(|s| s.id)
+        .filter(|id| *id != stack_id)
+        .collect();
+    if position > order.len() {
+        bail!(
+            "Position {position} is out of range, there are only {} stacks",
+            order.len() + 1
+        );
+    }
+    order.insert(position, stack_id);
+
+    let updates = order
+        .into_iter()
+        .enumerate()
+        .map(|(idx, id)| BranchUpdateRequest {
+            id: Some(id),
+            order: Some(idx),
+            ..Default::default()
+        })
+        .collect();
+    gitbutler_branch_actions::update_stack_order(ctx, updates)?;
+    BranchOutcome::new(ctx, branch, Some(stack_id))
+}
+
+/// Resolve `input`, which is either the name of a branch in the workspace or its short [`CliId`], to the branch name.
+pub(crate) fn branch_name_from_id(ctx: &mut CommandContext, input: &str) -> anyhow::Result<String> {
+    let input = input.strip_prefix("refs/heads/").unwrap_or(input);
+    if crate::log::stacks(ctx)?
+        .iter()
+        .flat_map(|s| s.heads.iter())
+        .any(|h| h.name == input)
+    {
+        return Ok(input.to_owned());
+    }
+
+    let id_map = IdMap::new(ctx)?;
+    let mut branches = id_map
+        .parse(input)?
+        .into_iter()
+        .filter(|id| matches!(id, CliId::Branch { .. }))
+        .collect::<Vec<_>>();
+    match branches.len() {
+        1 => match branches.remove(0) {
+            CliId::Branch { name } => Ok(name),
+            _ => unreachable!("filtered above"),
+        },
+        0 => bail!("No branch named or identified by '{input}' found in the workspace"),
+        _ => bail!(
+            "Branch id '{input}' is ambiguous, use a longer id to pick one of:\n{}",
+            id_map.disambiguation_list(&branches)
+        ),
+    }
+}
+
+pub(crate) fn stack_id_by_branch_name(
+    ctx: &CommandContext,
+    branch_name: &str,
+) -> anyhow::Result<StackId> {
+    crate::log::stacks(ctx)?
+        .iter()
+        .find(|s| s.heads.iter().any(|h| h.name == branch_name))
+        .and_then(|s| s.id)
+        .with_context(|| format!("Could not find stack for branch {branch_name}"))
+}
+
+fn print(outcome: &BranchOutcome, json: bool, action: &str) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(outcome)?);
+    } else {
+        println!(
+            "{action} {} {}",
+            format!("[{}]", outcome.name).green(),
+            outcome.id.blue().underline()
+        );
+    }
+    Ok(())
+}
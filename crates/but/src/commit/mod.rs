@@ -0,0 +1,208 @@
+use std::path::Path;
+
+use anyhow::{Context, bail};
+use but_hunk_assignment::HunkAssignment;
+use but_settings::AppSettings;
+use but_workspace::{
+    DiffSpec, StackId,
+    commit_engine::{self, StackSegmentId},
+};
+use colored::Colorize;
+use gitbutler_command_context::CommandContext;
+use gitbutler_oplog::{OplogExt, SnapshotExt};
+use gitbutler_oxidize::ObjectIdExt;
+use gitbutler_project::Project;
+use gitbutler_repo::hooks::{self, HookResult};
+
+use crate::id::{CliId, IdMap};
+
+pub(crate) fn commit(
+    repo_path: &Path,
+    json: bool,
+    branch: Option<&str>,
+    message: Option<&str>,
+    only: &[String],
+) -> anyhow::Result<()> {
+    let project = Project::from_path(repo_path).expect("Failed to create project from path");
+    let ctx = &mut CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+
+    let (branch_name, stack_id, parent_commit_id) = target_branch(ctx, branch)?;
+    let only_paths = only
+        .iter()
+        .map(|id| file_path_from_id(ctx, id, stack_id))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let diff_specs: Vec<DiffSpec> = wt_assignments(ctx)?
+        .into_iter()
+        .filter(|assignment| assignment.stack_id == Some(stack_id))
+        .filter(|assignment| only_paths.is_empty() || only_paths.contains(&assignment.path))
+        .map(Into::into)
+        .collect();
+    if diff_specs.is_empty() {
+        bail!("There are no changes assigned to [{branch_name}] to commit");
+    }
+    let diff_specs = but_workspace::flatten_diff_specs(diff_specs);
+
+    let message = match message {
+        Some(message) => message.to_owned(),
+        None => message_from_editor(ctx, &branch_name, &diff_specs)?,
+    };
+    if message.trim().is_empty() {
+        bail!("Aborting commit due to empty commit message");
+    }
+
+    let message =
+        gitbutler_branch_actions::hooks::run_pre_commit_hooks(ctx, None, &diff_specs, message)?;
+
+    let mut guard = ctx.project().exclusive_worktree_access();
+    let snapshot_tree = ctx.prepare_snapshot(guard.read_permission());
+    let outcome = commit_engine::create_commit_and_update_refs_with_project(
+        &ctx.gix_repo_for_merging()?,
+        ctx.project(),
+        Some(stack_id),
+        commit_engine::Destination::NewCommit {
+            parent_commit_id: Some(parent_commit_id),
+            message: message.clone(),
+            stack_segment: Some(StackSegmentId {
+                stack_id,
+                segment_ref: format!("refs/heads/{branch_name}")
+                    .try_into()
+                    .map_err(anyhow::Error::from)?,
+            }),
+        },
+        None,
+        diff_specs,
+        ctx.app_settings().context_lines,
+        guard.write_permission(),
+    );
+    let _ = snapshot_tree.and_then(|snapshot_tree| {
+        ctx.snapshot_commit_creation(
+            snapshot_tree,
+            outcome.as_ref().err(),
+            message.clone(),
+            outcome
+                .as_ref()
+                .ok()
+                .and_then(|outcome| outcome.new_commit)
+                .map(|id| id.to_git2()),
+            guard.write_permission(),
+        )
+    });
+    drop(guard);
+    let outcome = outcome?;
+
+    if outcome.new_commit.is_some()
+        && let HookResult::Failure(error) = hooks::post_commit(ctx)?
+    {
+        eprintln!("The post-commit hook failed:\n{}", error.error);
+    }
+    if outcome.new_commit.is_some() && ctx.app_settings().feature_flags.rules {
+        but_rules::process_rules_for_trigger(ctx, but_rules::Trigger::CommitCreated).ok();
+    }
+
+    if json {
+        let outcome: commit_engine::ui::CreateCommitOutcome = outcome.into();
+        println!("{}", serde_json::to_string_pretty(&outcome)?);
+        return Ok(());
+    }
+    let Some(new_commit) = outcome.new_commit else {
+        bail!("No commit was created as all changes were rejected");
+    };
+    println!(
+        "Committed {} → {}",
+        IdMap::new(ctx)?.commit_colored(new_commit),
+        format!("[{branch_name}]").green()
+    );
+    if !outcome.rejected_specs.is_empty() {
+        println!("{}", "Some changes could not be committed:".yellow());
+        for (reason, spec) in &outcome.rejected_specs {
+            println!("  {} ({reason:?})", spec.path);
+        }
+    }
+    Ok(())
+}
+
+/// Figure out the branch to commit to, along with its stack and the commit that will be the parent of the new commit.
+/// If no `branch` is given, there must be exactly one stack, and its top-most branch is used.
+fn target_branch(
+    ctx: &mut CommandContext,
+    branch: Option<&str>,
+) -> anyhow::Result<(String, StackId, gix::ObjectId)> {
+    let branch = branch
+        .map(|branch| crate::branch::branch_name_from_id(ctx, branch))
+        .transpose()?;
+    let stacks = crate::log::stacks(ctx)?;
+    let (stack, head) = match branch {
+        Some(branch) => stacks
+            .iter()
+            .find_map(|s| s.heads.iter().find(|h| h.name == branch).map(|h| (s, h)))
+            .with_context(|| format!("Could not find stack for branch {branch}"))?,
+        None => match stacks.as_slice() {
+            [stack] => (
+                stack,
+                stack.heads.first().context("BUG: stacks are never empty")?,
+            ),
+            [] => bail!("There are no branches to commit to, create one with `but branch new`"),
+            _ => bail!("There are multiple stacks, please specify one with --branch"),
+        },
+    };
+    let stack_id = stack
+        .id
+        .with_context(|| format!("Stack with branch {} has no id", head.name))?;
+    Ok((head.name.to_string(), stack_id, head.tip))
+}
+
+/// Resolve `input`, a short file [`CliId`] or a worktree-relative path, to the path of a file assigned to `stack_id`.
+fn file_path_from_id(
+    ctx: &mut CommandContext,
+    input: &str,
+    stack_id: StackId,
+) -> anyhow::Result<String> {
+    if wt_assignments(ctx)?.iter().any(|a| a.path == input) {
+        return Ok(input.to_owned());
+    }
+    let id_map = IdMap::new(ctx)?;
+    let mut files = id_map
+        .parse(input)?
+        .into_iter()
+        .filter(|id| {
+            matches!(id, CliId::UncommittedFile { assignment, .. } if *assignment == Some(stack_id))
+        })
+        .collect::<Vec<_>>();
+    match files.len() {
+        1 => match files.remove(0) {
+            CliId::UncommittedFile { path, .. } => Ok(path),
+            _ => unreachable!("filtered above"),
+        },
+        0 => bail!("No file assigned to this branch is identified by '{input}'"),
+        _ => bail!(
+            "File id '{input}' is ambiguous, use a longer id to pick one of:\n{}",
+            id_map.disambiguation_list(&files)
+        ),
+    }
+}
+
+fn message_from_editor(
+    ctx: &CommandContext,
+    branch_name: &str,
+    diff_specs: &[DiffSpec],
+) -> anyhow::Result<String> {
+    let mut template = format!(
+        "\n# Please enter the commit message for your changes. Lines starting\n\
+         # with '#' will be ignored, and an empty message aborts the commit.\n\
+         #\n\
+         # On branch {branch_name}\n\
+         # Changes to be committed:\n"
+    );
+    for spec in diff_specs {
+        template.push_str(&format!("#\t{}\n", spec.path));
+    }
+    crate::editor::edit_text(&ctx.gix_repo()?, "COMMIT_EDITMSG", &template)
+}
+
+fn wt_assignments(ctx: &mut CommandContext) -> anyhow::Result<Vec<HunkAssignment>> {
+    let changes =
+        but_core::diff::ui::worktree_changes_by_worktree_dir(ctx.project().path.clone())?.changes;
+    let (assignments, _assignments_error) =
+        but_hunk_assignment::assignments_with_fallback(ctx, false, Some(changes.clone()), None)?;
+    Ok(assignments)
+}
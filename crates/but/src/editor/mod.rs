@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use anyhow::{Context, bail};
+
+/// Write `initial` into `file_name` inside the `.git` directory of `repo`, let the user edit it with their
+/// configured editor, and return the edited text with all `#`-comment lines removed.
+///
+/// The editor is chosen like Git does: `GIT_EDITOR`, `core.editor`, `VISUAL`, `EDITOR` and finally `vi`.
+pub(crate) fn edit_text(
+    repo: &gix::Repository,
+    file_name: &str,
+    initial: &str,
+) -> anyhow::Result<String> {
+    let path = repo.git_dir().join(file_name);
+    std::fs::write(&path, initial)
+        .with_context(|| format!("Could not write '{}'", path.display()))?;
+    launch(&editor(repo), &path)?;
+    let edited = std::fs::read_to_string(&path)
+        .with_context(|| format!("Could not read back '{}'", path.display()))?;
+    Ok(strip_comments(&edited))
+}
+
+fn editor(repo: &gix::Repository) -> String {
+    std::env::var("GIT_EDITOR")
+        .ok()
+        .or_else(|| {
+            repo.config_snapshot()
+                .string("core.editor")
+                .map(|editor| editor.to_string())
+        })
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".into())
+}
+
+fn launch(editor: &str, path: &Path) -> anyhow::Result<()> {
+    let status = std::process::Command::from(
+        gix::command::prepare(editor)
+            .command_may_be_shell_script()
+            .arg(path),
+    )
+    .status()
+    .with_context(|| format!("Could not launch editor '{editor}'"))?;
+    if !status.success() {
+        bail!("Editor '{editor}' exited with {status}, aborting");
+    }
+    Ok(())
+}
+
+fn strip_comments(text: &str) -> String {
+    let mut out = text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned();
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
@@ -1,7 +1,9 @@
-use std::fmt::Display;
+use std::collections::{HashMap, HashSet};
 
+use anyhow::{Context, bail};
 use but_hunk_assignment::HunkAssignment;
 use but_workspace::StackId;
+use colored::Colorize;
 use gitbutler_command_context::CommandContext;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -49,65 +51,221 @@ impl CliId {
         }
     }
 
-    pub fn matches(&self, s: &str) -> bool {
-        s == self.to_string()
+    /// A human-readable description of the entity, to tell it apart from others.
+    pub fn describe(&self) -> String {
+        match self {
+            CliId::UncommittedFile { path, assignment } => match assignment {
+                Some(stack_id) => format!("file {path} (assigned to stack {stack_id})"),
+                None => format!("file {path} (unassigned)"),
+            },
+            CliId::Branch { name } => format!("branch {name}"),
+            CliId::Commit { oid } => format!("commit {}", oid.to_hex_with_len(7)),
+            CliId::Unassigned => "the unassigned area".into(),
+        }
     }
 
-    pub fn from_str(ctx: &mut CommandContext, s: &str) -> anyhow::Result<Vec<Self>> {
-        if s.len() < 2 {
-            return Err(anyhow::anyhow!("Id needs to be 3 characters long: {}", s));
+    /// Return the kind of the entity along with the string that identifies it, as used for persistence.
+    fn kind_and_target(&self) -> (&'static str, String) {
+        match self {
+            CliId::UncommittedFile { path, assignment } => (
+                "file",
+                format!(
+                    "{}:{path}",
+                    assignment.map(|id| id.to_string()).unwrap_or_default()
+                ),
+            ),
+            CliId::Branch { name } => ("branch", name.clone()),
+            CliId::Commit { oid } => ("commit", oid.to_string()),
+            CliId::Unassigned => ("unassigned", String::new()),
         }
-        let s = &s[..2];
-        let mut everything = Vec::new();
-        crate::status::all_files(ctx)?
-            .into_iter()
-            .filter(|id| id.matches(s))
-            .for_each(|id| everything.push(id));
-        crate::status::all_branches(ctx)?
+    }
+
+    /// The full hash of which short ids are prefixes. For commits, this is their hex-encoded object id.
+    fn full_hash(&self) -> String {
+        match self {
+            CliId::UncommittedFile { path, assignment } => match assignment {
+                Some(assignment) => hash(&format!("{assignment}{path}")),
+                None => hash(path),
+            },
+            CliId::Branch { name } => hash(name),
+            CliId::Commit { oid } => oid.to_string(),
+            CliId::Unassigned => UNASSIGNED.into(),
+        }
+    }
+}
+
+/// The short id that is always used for the unassigned area.
+const UNASSIGNED: &str = "00";
+/// The length of the shortest id that is handed out.
+const MIN_LEN: usize = 2;
+
+/// Hands out short ids for all files, branches and commits in the workspace.
+///
+/// Ids are prefixes of a hash of each entity, and the shortest prefix that isn't taken yet is used,
+/// similar to how Git abbreviates object ids. Once handed out, ids are persisted in the database
+/// for the current workspace generation so they remain the same across invocations, until the
+/// workspace base changes.
+pub struct IdMap {
+    short_ids: HashMap<CliId, String>,
+}
+
+impl IdMap {
+    /// Collect all entities in the workspace and assign short ids to them, reusing the ones that were handed out previously.
+    pub fn new(ctx: &mut CommandContext) -> anyhow::Result<Self> {
+        let mut entities = crate::status::all_files(ctx)?;
+        entities.extend(crate::status::all_branches(ctx)?);
+        entities.extend(crate::log::all_commits(ctx)?);
+        entities.sort_by_cached_key(CliId::kind_and_target);
+        entities.dedup();
+
+        let generation = generation(ctx);
+        let db = ctx.db()?;
+        db.cli_ids().delete_all_but(&generation)?;
+        let persisted = db.cli_ids().list(&generation)?;
+        let persisted: HashMap<(String, String), String> = persisted
             .into_iter()
-            .filter(|id| id.matches(s))
-            .for_each(|id| everything.push(id));
-        crate::log::all_commits(ctx)?
+            .map(|id| ((id.kind, id.target), id.short_id))
+            .collect();
+
+        let (mut short_ids, new_ids) = assign_short_ids(entities, &persisted)?;
+        let new_ids: Vec<_> = new_ids
             .into_iter()
-            .filter(|id| id.matches(s))
-            .for_each(|id| everything.push(id));
-        everything.push(CliId::unassigned());
-
-        let mut matches = Vec::new();
-        for id in everything {
-            if id.matches(s) {
-                matches.push(id);
+            .map(|entity| {
+                let (kind, target) = entity.kind_and_target();
+                but_db::CliId {
+                    generation: generation.clone(),
+                    short_id: short_ids[&entity].clone(),
+                    kind: kind.into(),
+                    target,
+                    created_at: chrono::Local::now().naive_local(),
+                }
+            })
+            .collect();
+        if !new_ids.is_empty() {
+            // A concurrent invocation may have handed out the same ids, in which case ours are only valid for now.
+            if let Err(err) = db.cli_ids().insert(new_ids) {
+                tracing::warn!("Could not persist new short ids: {err}");
             }
         }
+        short_ids.insert(CliId::Unassigned, UNASSIGNED.into());
+        Ok(IdMap { short_ids })
+    }
+
+    /// Return the short id of `id`, or the shortest possible one if it isn't known.
+    pub fn short_id(&self, id: &CliId) -> String {
+        self.short_ids
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| id.full_hash()[..MIN_LEN].to_owned())
+    }
+
+    /// Format `oid` with its short id underlined, followed by enough hex digits to make it recognizable.
+    pub fn commit_colored(&self, oid: gix::ObjectId) -> String {
+        let short_id = self.short_id(&CliId::commit(oid));
+        let hex = oid.to_string();
+        format!(
+            "{}{}",
+            short_id.blue().underline(),
+            hex.get(short_id.len()..7).unwrap_or_default().blue()
+        )
+    }
+
+    /// Return all entities that `input` could refer to.
+    ///
+    /// An exact match of a short id always wins, otherwise all entities whose hash starts with `input` are returned.
+    pub fn parse(&self, input: &str) -> anyhow::Result<Vec<CliId>> {
+        if input.len() < MIN_LEN {
+            bail!("Id needs to be at least {MIN_LEN} characters long: {input}");
+        }
+        if let Some((id, _)) = self.short_ids.iter().find(|(_, short)| *short == input) {
+            return Ok(vec![id.clone()]);
+        }
+        let mut matches = self
+            .short_ids
+            .keys()
+            .filter(|id| **id != CliId::Unassigned && id.full_hash().starts_with(input))
+            .cloned()
+            .collect::<Vec<_>>();
+        matches.sort_by_cached_key(CliId::kind_and_target);
         Ok(matches)
     }
+
+    /// Like [`Self::parse()`], but fail unless `input` refers to exactly one entity.
+    /// `role` describes what the entity is used for, like `Source`.
+    pub fn resolve(&self, input: &str, role: &str) -> anyhow::Result<CliId> {
+        let mut matches = self.parse(input)?;
+        match matches.len() {
+            1 => Ok(matches.remove(0)),
+            0 => bail!("{role} {input} doesn't match any file, branch or commit"),
+            _ => bail!(
+                "{role} {input} is ambiguous, use a longer id to pick one of:\n{}",
+                self.disambiguation_list(&matches)
+            ),
+        }
+    }
+
+    /// List each of `ids` with the shortest id that identifies it unambiguously.
+    pub fn disambiguation_list(&self, ids: &[CliId]) -> String {
+        ids.iter()
+            .map(|id| {
+                format!(
+                    "  {} {}",
+                    self.short_id(id).blue().underline(),
+                    id.describe()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
-impl Display for CliId {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            CliId::UncommittedFile { path, assignment } => {
-                if let Some(assignment) = assignment {
-                    let value = hash(&format!("{assignment}{path}"));
-                    write!(f, "{value}")
-                } else {
-                    write!(f, "{}", hash(path))
-                }
-            }
-            CliId::Branch { name } => {
-                write!(f, "{}", hash(name))
-            }
-            CliId::Unassigned => {
-                write!(f, "00")
+/// Assign a short id to each of `entities`, reusing the `persisted` ones which are keyed by kind and target,
+/// and return them along with the entities that got a new id.
+///
+/// New ids are the shortest prefix of the full hash of the entity that isn't taken yet, so they grow on collision.
+fn assign_short_ids(
+    entities: Vec<CliId>,
+    persisted: &HashMap<(String, String), String>,
+) -> anyhow::Result<(HashMap<CliId, String>, Vec<CliId>)> {
+    let mut taken: HashSet<String> = persisted.values().cloned().collect();
+    taken.insert(UNASSIGNED.into());
+    let mut short_ids = HashMap::new();
+    let mut new_ids = Vec::new();
+    for entity in entities {
+        let (kind, target) = entity.kind_and_target();
+        let short_id = match persisted.get(&(kind.to_owned(), target)) {
+            Some(short_id) => short_id.clone(),
+            None => {
+                let full_hash = entity.full_hash();
+                let short_id = (MIN_LEN..=full_hash.len())
+                    .map(|len| &full_hash[..len])
+                    .find(|candidate| !taken.contains(*candidate))
+                    .with_context(|| {
+                        format!("Could not find a unique id for {}", entity.describe())
+                    })?
+                    .to_owned();
+                taken.insert(short_id.clone());
+                new_ids.push(entity.clone());
+                short_id
             }
-            CliId::Commit { oid } => {
-                let oid = oid.to_string();
-                write!(f, "{}", &oid[..2])
-            }
-        }
+        };
+        short_ids.insert(entity, short_id);
     }
+    Ok((short_ids, new_ids))
+}
+
+/// Return a string that changes whenever previously handed out ids should be forgotten,
+/// which is when the base of the workspace changes.
+fn generation(ctx: &CommandContext) -> String {
+    gitbutler_stack::VirtualBranchesHandle::new(ctx.project().gb_dir())
+        .maybe_get_default_target()
+        .ok()
+        .flatten()
+        .map(|target| target.sha.to_string())
+        .unwrap_or_default()
 }
 
+/// Hash `input` into a base-36 string that is long enough to represent all 64 bits of the hash.
 pub(crate) fn hash(input: &str) -> String {
     let mut hash = 0u64;
     for byte in input.bytes() {
@@ -116,9 +274,59 @@ pub(crate) fn hash(input: &str) -> String {
     // Convert to base 36 (0-9, a-z)
     let chars = "0123456789abcdefghijklmnopqrstuvwxyz";
     let mut result = String::new();
-    for _ in 0..2 {
+    for _ in 0..13 {
         result.push(chars.chars().nth((hash % 36) as usize).unwrap());
         hash /= 36;
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(hex: &str) -> CliId {
+        CliId::commit(gix::ObjectId::from_hex(hex.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn short_ids_grow_on_collision() -> anyhow::Result<()> {
+        let a = commit("abc1000000000000000000000000000000000000");
+        let b = commit("abc2000000000000000000000000000000000000");
+        let c = commit("ab00000000000000000000000000000000000000");
+        let (ids, new_ids) =
+            assign_short_ids(vec![a.clone(), b.clone(), c.clone()], &HashMap::new())?;
+        assert_eq!(ids[&a], "ab");
+        assert_eq!(ids[&b], "abc", "'ab' is taken");
+        assert_eq!(
+            ids[&c], "ab0",
+            "'ab' is taken, and the prefix grows until it is free"
+        );
+        assert_eq!(new_ids, [a, b, c]);
+        Ok(())
+    }
+
+    #[test]
+    fn unassigned_id_is_never_handed_out() -> anyhow::Result<()> {
+        let a = commit("0000000000000000000000000000000000000001");
+        let (ids, _) = assign_short_ids(vec![a.clone()], &HashMap::new())?;
+        assert_eq!(ids[&a], "000");
+        Ok(())
+    }
+
+    #[test]
+    fn persisted_ids_are_kept_and_avoided() -> anyhow::Result<()> {
+        let a = commit("abc1000000000000000000000000000000000000");
+        let b = commit("abc2000000000000000000000000000000000000");
+        // `b` got 'ab' in an earlier invocation, before `a` existed.
+        let persisted = HashMap::from([(b.kind_and_target(), "ab".to_owned())])
+            .into_iter()
+            .map(|((kind, target), id)| ((kind.to_owned(), target), id))
+            .collect();
+        let (ids, new_ids) = assign_short_ids(vec![a.clone(), b.clone()], &persisted)?;
+        assert_eq!(ids[&b], "ab", "persisted ids remain stable");
+        assert_eq!(ids[&a], "abc");
+        assert_eq!(new_ids, [a], "only new ids need to be persisted");
+        Ok(())
+    }
+}
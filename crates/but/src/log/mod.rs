@@ -9,7 +9,7 @@ use gitbutler_command_context::CommandContext;
 use gitbutler_project::Project;
 use std::path::Path;
 
-use crate::id::CliId;
+use crate::id::{CliId, IdMap};
 
 pub(crate) fn commit_graph(repo_path: &Path, _json: bool) -> anyhow::Result<()> {
     let project = Project::from_path(repo_path).expect("Failed to create project from path");
@@ -19,6 +19,7 @@ pub(crate) fn commit_graph(repo_path: &Path, _json: bool) -> anyhow::Result<()>
         .filter_map(|s| s.id.map(|id| stack_details(ctx, id)))
         .filter_map(Result::ok)
         .collect::<Vec<_>>();
+    let id_map = IdMap::new(ctx)?;
 
     let mut nesting = 0;
     for (i, stack) in stacks.iter().enumerate() {
@@ -40,8 +41,8 @@ pub(crate) fn commit_graph(repo_path: &Path, _json: bool) -> anyhow::Result<()>
             } else {
                 ""
             };
-            let id = CliId::branch(&branch.name.to_string())
-                .to_string()
+            let id = id_map
+                .short_id(&CliId::branch(&branch.name.to_string()))
                 .underline()
                 .blue();
             println!(
@@ -60,11 +61,10 @@ pub(crate) fn commit_graph(repo_path: &Path, _json: bool) -> anyhow::Result<()>
                 let state_str = "{upstream}";
                 let extra_space = if stacked { "│ " } else { "  " };
                 println!(
-                    "{}{}● {}{} {} {} {}",
+                    "{}{}● {} {} {} {}",
                     "│ ".repeat(nesting),
                     extra_space,
-                    &commit.id.to_string()[..2].blue().underline(),
-                    &commit.id.to_string()[2..7].blue(),
+                    id_map.commit_colored(commit.id),
                     state_str.yellow(),
                     commit.author.name,
                     time_string.dimmed(),
@@ -98,10 +98,9 @@ pub(crate) fn commit_graph(repo_path: &Path, _json: bool) -> anyhow::Result<()>
                     .format("%Y-%m-%d %H:%M:%S")
                     .to_string();
                 println!(
-                    "{}● {}{} {} {} {} {}",
+                    "{}● {} {} {} {} {}",
                     "│ ".repeat(nesting),
-                    &commit.id.to_string()[..2].blue().underline(),
-                    &commit.id.to_string()[2..7].blue(),
+                    id_map.commit_colored(commit.id),
                     state_str,
                     conflicted_str,
                     commit.author.name,
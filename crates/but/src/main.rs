@@ -6,15 +6,23 @@ use but_settings::AppSettings;
 use metrics::{Event, Metrics, Props, metrics_if_configured};
 
 use but_claude::hooks::OutputAsJson;
+mod branch;
 mod command;
+mod commit;
+mod editor;
 mod id;
 mod init;
 mod log;
 mod mcp;
 mod mcp_internal;
 mod metrics;
+mod rebase;
 mod rub;
+mod rules;
+mod secret_scan;
 mod status;
+mod sync;
+mod undo;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -26,11 +34,15 @@ async fn main() -> Result<()> {
     let start = std::time::Instant::now();
 
     match &args.cmd {
-        Subcommands::Mcp { internal } => {
+        Subcommands::Mcp {
+            internal,
+            listen,
+            token,
+        } => {
             if *internal {
-                mcp_internal::start(app_settings).await
+                mcp_internal::start(app_settings, &args.current_dir, *listen, token.clone()).await
             } else {
-                mcp::start(app_settings).await
+                mcp::start(app_settings, &args.current_dir, *listen, token.clone()).await
             }
         }
         Subcommands::Actions(actions::Platform { cmd }) => match cmd {
@@ -115,6 +127,108 @@ async fn main() -> Result<()> {
             metrics_if_configured(app_settings, CommandName::Rub, props(start, &result)).ok();
             Ok(())
         }
+        Subcommands::Commit {
+            branch,
+            message,
+            editor: _,
+            only,
+        } => {
+            let result = commit::commit(
+                &args.current_dir,
+                args.json,
+                branch.as_deref(),
+                message.as_deref(),
+                only,
+            );
+            if let Err(e) = &result {
+                eprintln!("{} {}", e, e.root_cause());
+            }
+            metrics_if_configured(app_settings, CommandName::Commit, props(start, &result)).ok();
+            Ok(())
+        }
+        Subcommands::Branch(args::branch::Platform { cmd }) => {
+            let result = branch::handle(cmd, &args.current_dir, args.json);
+            if let Err(e) = &result {
+                eprintln!("{} {}", e, e.root_cause());
+            }
+            metrics_if_configured(app_settings, CommandName::Branch, props(start, &result)).ok();
+            Ok(())
+        }
+        Subcommands::Rebase {
+            branch,
+            continue_edit,
+            abort,
+        } => {
+            let result = match branch {
+                Some(branch) if !continue_edit && !abort => {
+                    rebase::rebase(&args.current_dir, args.json, branch)
+                }
+                _ => rebase::finish_edit(&args.current_dir, args.json, *abort),
+            };
+            if let Err(e) = &result {
+                eprintln!("{} {}", e, e.root_cause());
+            }
+            metrics_if_configured(app_settings, CommandName::Rebase, props(start, &result)).ok();
+            Ok(())
+        }
+        Subcommands::Rules(args::rules::Platform { cmd }) => {
+            let result = rules::handle(cmd, &args.current_dir, args.json);
+            if let Err(e) = &result {
+                eprintln!("{} {}", e, e.root_cause());
+            }
+            metrics_if_configured(app_settings, CommandName::Rules, props(start, &result)).ok();
+            Ok(())
+        }
+        Subcommands::ScanSecrets { branch } => {
+            let result = secret_scan::scan(&args.current_dir, args.json, branch.as_deref());
+            if let Err(e) = &result {
+                eprintln!("{} {}", e, e.root_cause());
+            }
+            metrics_if_configured(
+                app_settings,
+                CommandName::ScanSecrets,
+                props(start, &result),
+            )
+            .ok();
+            // Unlike other commands, exit with an error if secrets were found, so CI jobs and hooks can rely on it.
+            if result.is_err() {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Subcommands::Sync { approach, no_push } => {
+            let result = sync::sync(
+                &args.current_dir,
+                args.json,
+                approach.map(Into::into),
+                !no_push,
+            );
+            if let Err(e) = &result {
+                eprintln!("{} {}", e, e.root_cause());
+            }
+            metrics_if_configured(app_settings, CommandName::Sync, props(start, &result)).ok();
+            // Unlike other commands, exit with an error if the sync failed or was aborted, so scripts can rely on it.
+            if result.is_err() {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Subcommands::Undo => {
+            let result = undo::undo(&args.current_dir, args.json);
+            if let Err(e) = &result {
+                eprintln!("{} {}", e, e.root_cause());
+            }
+            metrics_if_configured(app_settings, CommandName::Undo, props(start, &result)).ok();
+            Ok(())
+        }
+        Subcommands::Redo => {
+            let result = undo::redo(&args.current_dir, args.json);
+            if let Err(e) = &result {
+                eprintln!("{} {}", e, e.root_cause());
+            }
+            metrics_if_configured(app_settings, CommandName::Redo, props(start, &result)).ok();
+            Ok(())
+        }
         Subcommands::Init { repo } => init::repo(&args.current_dir, args.json, *repo)
             .context("Failed to initialize GitButler project."),
     }
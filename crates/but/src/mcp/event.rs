@@ -13,30 +13,23 @@ pub struct Handler {
 
 impl Handler {
     pub fn new_with_background_handling() -> Self {
-        let (credentials_kind, sender) = OpenAiProvider::with(None)
-            .and_then(|openai| {
-                openai
-                    .client()
-                    .ok()
-                    .map(|client| (openai.credentials_kind(), client))
-            })
-            .map(|(credentials_kind, client)| {
-                let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
-                tokio::task::spawn(async move {
-                    while let Some(event) = receiver.recv().await {
-                        match event {
-                            Event::Commit(c) => {
-                                let _ = but_action::reword::commit(&client, c).await;
-                            }
+        let credentials_kind = OpenAiProvider::with(None).map(|openai| openai.credentials_kind());
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        tokio::task::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                match event {
+                    Event::Commit(c) => {
+                        // Each project may use a different provider.
+                        if let Ok(llm) = but_action::llm::for_project(&c.project, None) {
+                            let _ = but_action::reword::commit(llm.as_ref(), c).await;
                         }
                     }
-                });
-                (Some(credentials_kind), Some(sender))
-            })
-            .unwrap_or((None, None));
+                }
+            }
+        });
 
         Self {
-            sender,
+            sender: Some(sender),
             credentials_kind,
         }
     }
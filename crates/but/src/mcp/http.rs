@@ -0,0 +1,524 @@
+//! Serve the MCP servers over the Streamable HTTP transport, so clients that aren't spawned by
+//! `but` can connect, each in a session of its own.
+//!
+//! Clients post their messages to [`MCP_PATH`] and receive the responses to their requests in return.
+//! The first message is the initialization request, whose response carries the id of the new session in
+//! the [`SESSION_ID_HEADER`], which has to be sent along with all other messages of the session.
+//! Clients may open an event stream with a `GET` request to receive notifications from the server, and end
+//! their session with a `DELETE` request.
+//! All requests have to be authenticated with the bearer token of the server.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use axum::{
+    Json, Router,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::post,
+};
+use futures::{
+    FutureExt, SinkExt, StreamExt,
+    channel::{mpsc, oneshot},
+    future::BoxFuture,
+    sink::SinkMapErr,
+};
+use rmcp::{
+    ServerHandler, ServiceExt,
+    model::{
+        ClientJsonRpcMessage, ClientRequest, JsonRpcError, JsonRpcMessage, JsonRpcRequest,
+        JsonRpcResponse, RequestId, ServerJsonRpcMessage,
+    },
+};
+
+/// The path clients post their messages to, and open their event stream at.
+pub const MCP_PATH: &str = "/mcp";
+/// The header with the id of the session a message belongs to.
+pub const SESSION_ID_HEADER: &str = "mcp-session-id";
+/// The environment variable with the token clients have to authenticate with.
+pub const TOKEN_ENV: &str = "BUT_MCP_TOKEN";
+
+/// Listen on `addr` until the process ends, and serve each session with a server created by `new_session`.
+///
+/// Clients have to send `token` as bearer token. If it's `None`, a random one is generated and printed.
+pub(crate) async fn serve<S, F>(
+    addr: SocketAddr,
+    token: Option<String>,
+    new_session: F,
+) -> anyhow::Result<()>
+where
+    S: ServerHandler,
+    F: Fn() -> S + Send + Sync + 'static,
+{
+    let token = match token {
+        Some(token) if !token.is_empty() => token,
+        _ => {
+            let token = uuid::Uuid::new_v4().simple().to_string();
+            eprintln!("Generated the token {token}, set {TOKEN_ENV} to choose one instead.");
+            token
+        }
+    };
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Could not listen on {addr}"))?;
+    let addr = listener.local_addr()?;
+    tracing::info!(%addr, "Serving MCP over HTTP");
+    eprintln!(
+        "Listening on http://{addr}{MCP_PATH}, clients have to send the token as 'Authorization: Bearer <token>'."
+    );
+    Ok(axum::serve(listener, router(token, new_session)).await?)
+}
+
+/// The routes of the transport, which authenticate all requests with `token` and serve each session
+/// with a server created by `new_session`.
+fn router<S, F>(token: String, new_session: F) -> Router
+where
+    S: ServerHandler,
+    F: Fn() -> S + Send + Sync + 'static,
+{
+    let sessions = Sessions {
+        serve_session: Arc::new(move |transport| {
+            let server = new_session();
+            async move {
+                match server.serve(transport).await {
+                    Ok(service) => {
+                        if let Err(err) = service.waiting().await {
+                            tracing::warn!(?err, "MCP session failed");
+                        }
+                    }
+                    Err(err) => tracing::warn!(?err, "Could not initialize MCP session"),
+                }
+            }
+            .boxed()
+        }),
+        by_id: Arc::default(),
+    };
+    let token = Arc::new(token);
+    Router::new()
+        .route(
+            MCP_PATH,
+            post(post_message)
+                .get(open_event_stream)
+                .delete(end_session),
+        )
+        .with_state(sessions)
+        .layer(axum::middleware::from_fn(
+            move |request: Request, next: Next| {
+                let token = token.clone();
+                async move { authenticate(&token, request, next).await }
+            },
+        ))
+}
+
+/// The transport of the server of a session, which receives the messages posted by the client
+/// and sends the messages for the client.
+type Transport = (
+    SinkMapErr<mpsc::UnboundedSender<ServerJsonRpcMessage>, fn(mpsc::SendError) -> std::io::Error>,
+    mpsc::UnboundedReceiver<ClientJsonRpcMessage>,
+);
+
+/// The error of the server of a session when the session ended.
+fn session_ended(err: mpsc::SendError) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::BrokenPipe, err)
+}
+
+/// The sessions of clients, by their id.
+#[derive(Clone)]
+struct Sessions {
+    /// Serve a new session on the given transport until it ends.
+    serve_session: Arc<dyn Fn(Transport) -> BoxFuture<'static, ()> + Send + Sync>,
+    by_id: Arc<Mutex<HashMap<String, Session>>>,
+}
+
+#[derive(Clone)]
+struct Session {
+    /// The messages of the client for the server.
+    to_server: mpsc::UnboundedSender<ClientJsonRpcMessage>,
+    /// The responses the client waits for in the response to its post, by the id of their request.
+    responses: Arc<Mutex<HashMap<RequestId, oneshot::Sender<ServerJsonRpcMessage>>>>,
+    /// The event stream of the client, if it opened one, for all messages that aren't responses.
+    events: Arc<Mutex<Option<mpsc::UnboundedSender<ServerJsonRpcMessage>>>>,
+}
+
+impl Sessions {
+    /// Start a new session and return its id along with it.
+    fn start(&self) -> (String, Session) {
+        let (to_server, from_client) = mpsc::unbounded();
+        let (to_client, mut from_server) = mpsc::unbounded();
+        let session = Session {
+            to_server,
+            responses: Arc::default(),
+            events: Arc::default(),
+        };
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        self.by_id
+            .lock()
+            .expect("poisoned")
+            .insert(id.clone(), session.clone());
+
+        let map_err: fn(mpsc::SendError) -> std::io::Error = session_ended;
+        tokio::spawn((self.serve_session)((
+            to_client.sink_map_err(map_err),
+            from_client,
+        )));
+        // Route the messages of the server until it's done, which ends the session.
+        let (sessions, routed, session_id) = (self.by_id.clone(), session.clone(), id.clone());
+        tokio::spawn(async move {
+            while let Some(message) = from_server.next().await {
+                routed.send_to_client(message);
+            }
+            sessions.lock().expect("poisoned").remove(&session_id);
+            // Let requests that are still waiting know that there won't be a response.
+            routed.responses.lock().expect("poisoned").clear();
+        });
+        (id, session)
+    }
+
+    fn get(&self, headers: &HeaderMap) -> Result<Session, StatusCode> {
+        let id = headers
+            .get(SESSION_ID_HEADER)
+            .and_then(|id| id.to_str().ok())
+            .ok_or(StatusCode::BAD_REQUEST)?;
+        self.by_id
+            .lock()
+            .expect("poisoned")
+            .get(id)
+            .cloned()
+            .ok_or(StatusCode::NOT_FOUND)
+    }
+}
+
+impl Session {
+    /// Pass `message` to whoever waits for it, or drop it if the client isn't listening.
+    fn send_to_client(&self, message: ServerJsonRpcMessage) {
+        let response_id = match &message {
+            JsonRpcMessage::Response(JsonRpcResponse { id, .. })
+            | JsonRpcMessage::Error(JsonRpcError { id, .. }) => Some(id),
+            _ => None,
+        };
+        let waiting =
+            response_id.and_then(|id| self.responses.lock().expect("poisoned").remove(id));
+        if let Some(waiting) = waiting {
+            waiting.send(message).ok();
+        } else if let Some(events) = self.events.lock().expect("poisoned").as_ref() {
+            events.unbounded_send(message).ok();
+        }
+    }
+}
+
+/// Pass the posted `message` to the server of its session, and respond with the response of the server
+/// if it's a request. Initialization requests start a new session.
+async fn post_message(
+    State(sessions): State<Sessions>,
+    headers: HeaderMap,
+    Json(message): Json<ClientJsonRpcMessage>,
+) -> Response {
+    let is_initialization = matches!(
+        &message,
+        JsonRpcMessage::Request(JsonRpcRequest {
+            request: ClientRequest::InitializeRequest(_),
+            ..
+        })
+    );
+    let (new_session_id, session) = if is_initialization {
+        let (id, session) = sessions.start();
+        (Some(id), session)
+    } else {
+        match sessions.get(&headers) {
+            Ok(session) => (None, session),
+            Err(status) => return status.into_response(),
+        }
+    };
+
+    let response = match &message {
+        JsonRpcMessage::Request(JsonRpcRequest { id, .. }) => {
+            let (respond, response) = oneshot::channel();
+            session
+                .responses
+                .lock()
+                .expect("poisoned")
+                .insert(id.clone(), respond);
+            Some(response)
+        }
+        _ => None,
+    };
+    if session.to_server.unbounded_send(message).is_err() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Some(response) = response else {
+        return StatusCode::ACCEPTED.into_response();
+    };
+    // The sender is dropped without a response if the session ends first.
+    let Ok(response) = response.await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match new_session_id {
+        Some(id) => ([(SESSION_ID_HEADER, id)], Json(response)).into_response(),
+        None => Json(response).into_response(),
+    }
+}
+
+/// Stream the notifications and requests of the server to the client, replacing any previous stream.
+async fn open_event_stream(State(sessions): State<Sessions>, headers: HeaderMap) -> Response {
+    let session = match sessions.get(&headers) {
+        Ok(session) => session,
+        Err(status) => return status.into_response(),
+    };
+    let (events, stream) = mpsc::unbounded();
+    session.events.lock().expect("poisoned").replace(events);
+    let stream = stream.map(|message: ServerJsonRpcMessage| {
+        let data = serde_json::to_string(&message).unwrap_or_default();
+        Ok::<_, Infallible>(Event::default().event("message").data(data))
+    });
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// End the session, which stops its server.
+async fn end_session(State(sessions): State<Sessions>, headers: HeaderMap) -> StatusCode {
+    let session = match sessions.get(&headers) {
+        Ok(session) => session,
+        Err(status) => return status,
+    };
+    // The server stops once the client can't send any more messages.
+    session.to_server.close_channel();
+    StatusCode::OK
+}
+
+/// Reject requests that don't carry `token` as bearer token.
+async fn authenticate(token: &str, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|candidate| tokens_match(candidate, token));
+    if authorized {
+        next.run(request).await
+    } else {
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+/// Compare tokens in constant time, so their content can't be guessed from how long a comparison takes.
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{
+        body::Body,
+        http::{self, Method},
+    };
+    use serde_json::json;
+    use tower::ServiceExt as _;
+
+    use super::*;
+
+    const TOKEN: &str = "secret";
+
+    #[tokio::test]
+    async fn requests_without_the_token_are_rejected() -> anyhow::Result<()> {
+        let router = router(TOKEN.into(), || Server);
+        for authorization in [
+            None,
+            Some("Bearer"),
+            Some("Bearer "),
+            Some("Bearer secre"),
+            Some("Bearer secret!"),
+            Some("Bearer SECRET"),
+            Some("Basic secret"),
+            Some("secret"),
+        ] {
+            for method in [Method::POST, Method::GET, Method::DELETE] {
+                let response = router
+                    .clone()
+                    .oneshot(request(
+                        method.clone(),
+                        authorization,
+                        None,
+                        Some(initialize()),
+                    )?)
+                    .await?;
+                assert_eq!(
+                    response.status(),
+                    StatusCode::UNAUTHORIZED,
+                    "{method} with {authorization:?}"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn requests_with_the_token_are_served_in_their_session() -> anyhow::Result<()> {
+        let router = router(TOKEN.into(), || Server);
+        let authorization = Some("Bearer secret");
+
+        let response = router
+            .clone()
+            .oneshot(request(
+                Method::POST,
+                authorization,
+                None,
+                Some(initialize()),
+            )?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let session = response
+            .headers()
+            .get(SESSION_ID_HEADER)
+            .context("the initialization starts a session")?
+            .to_str()?
+            .to_owned();
+        let body = json_body(response).await?;
+        assert_eq!(body["id"], 1);
+        assert!(body["result"]["serverInfo"].is_object());
+
+        let initialized = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        let response = router
+            .clone()
+            .oneshot(request(
+                Method::POST,
+                authorization,
+                Some(&session),
+                Some(initialized),
+            )?)
+            .await?;
+        assert_eq!(
+            response.status(),
+            StatusCode::ACCEPTED,
+            "notifications have no response"
+        );
+
+        let ping = || json!({ "jsonrpc": "2.0", "id": 2, "method": "ping" });
+        let response = router
+            .clone()
+            .oneshot(request(
+                Method::POST,
+                authorization,
+                Some(&session),
+                Some(ping()),
+            )?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            json_body(response).await?,
+            json!({ "jsonrpc": "2.0", "id": 2, "result": {} })
+        );
+
+        for (session, status) in [
+            (None, StatusCode::BAD_REQUEST),
+            (Some("unknown"), StatusCode::NOT_FOUND),
+        ] {
+            let response = router
+                .clone()
+                .oneshot(request(Method::POST, authorization, session, Some(ping()))?)
+                .await?;
+            assert_eq!(response.status(), status, "{session:?}");
+        }
+
+        let response = router
+            .clone()
+            .oneshot(request(
+                Method::DELETE,
+                authorization,
+                Some(&session),
+                None,
+            )?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let response = router
+                    .clone()
+                    .oneshot(request(
+                        Method::POST,
+                        authorization,
+                        Some(&session),
+                        Some(ping()),
+                    )?)
+                    .await?;
+                if response.status() == StatusCode::NOT_FOUND {
+                    return anyhow::Ok(());
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .context("the session ends along with its server")??;
+        Ok(())
+    }
+
+    #[test]
+    fn tokens_only_match_if_they_are_equal() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secret", "secreT"));
+        assert!(!tokens_match("secret", "secret "));
+        assert!(!tokens_match("", "secret"));
+    }
+
+    /// A server with the default capabilities.
+    #[derive(Clone)]
+    struct Server;
+
+    impl ServerHandler for Server {}
+
+    fn initialize() -> serde_json::Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "0.0.0" },
+            },
+        })
+    }
+
+    fn request(
+        method: Method,
+        authorization: Option<&str>,
+        session: Option<&str>,
+        body: Option<serde_json::Value>,
+    ) -> anyhow::Result<Request> {
+        let mut request = http::Request::builder()
+            .method(method)
+            .uri(MCP_PATH)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "application/json, text/event-stream");
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        if let Some(session) = session {
+            request = request.header(SESSION_ID_HEADER, session);
+        }
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        Ok(request.body(body)?)
+    }
+
+    async fn json_body(response: Response) -> anyhow::Result<serde_json::Value> {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
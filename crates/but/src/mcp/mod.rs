@@ -1,27 +1,45 @@
 use std::{
-    path::PathBuf,
+    net::SocketAddr,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::{Arc, Mutex},
 };
 
 mod event;
+pub(crate) mod http;
+pub(crate) mod resources;
+mod toolset;
 use anyhow::Result;
 use but_action::{ActionHandler, Outcome, Source, reword::CommitEvent};
 use but_settings::AppSettings;
 use gitbutler_command_context::CommandContext;
-use gitbutler_project::Project;
 use rmcp::{
-    Error as McpError, ServerHandler, ServiceExt,
+    Error as McpError, RoleServer, ServerHandler, ServiceExt,
+    handler::server::tool::ToolCallContext,
     model::{
-        CallToolResult, Content, Implementation, ProtocolVersion, ServerCapabilities, ServerInfo,
+        CallToolRequestParam, CallToolResult, Content, Implementation, InitializeRequestParam,
+        InitializeResult, ListResourceTemplatesResult, ListResourcesResult, ListToolsResult,
+        PaginatedRequestParamInner, ProtocolVersion, ReadResourceRequestParam, ReadResourceResult,
+        ServerCapabilities, ServerInfo, SubscribeRequestParam, UnsubscribeRequestParam,
     },
-    schemars, tool,
+    schemars,
+    service::RequestContext,
+    tool,
 };
 use tracing_subscriber::{self, EnvFilter};
 
-use crate::metrics::{Event, EventKind, Metrics};
+use crate::{
+    mcp_internal::project::Projects,
+    metrics::{Event, EventKind, Metrics},
+};
 
-pub(crate) async fn start(app_settings: AppSettings) -> Result<()> {
+/// Serve over stdio, or over HTTP on `listen` with `token` as bearer token, in the project in `project_dir`.
+pub(crate) async fn start(
+    app_settings: AppSettings,
+    project_dir: &Path,
+    listen: Option<SocketAddr>,
+    token: Option<String>,
+) -> Result<()> {
     // Initialize the tracing subscriber with file and stdout logging
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env().add_directive(tracing::Level::DEBUG.into()))
@@ -31,15 +49,13 @@ pub(crate) async fn start(app_settings: AppSettings) -> Result<()> {
 
     tracing::info!("Starting MCP server");
 
-    let client_info = Arc::new(Mutex::new(None));
-    let transport = (tokio::io::stdin(), tokio::io::stdout());
-    let service = Mcp::new(app_settings, client_info.clone())
-        .serve(transport)
-        .await?;
-    let info = service.peer_info();
-    if let Ok(mut guard) = client_info.lock() {
-        guard.replace(info.client_info.clone());
+    let mcp = Mcp::new(app_settings, project_dir.to_owned());
+    if let Some(addr) = listen {
+        return http::serve(addr, token, move || mcp.new_session()).await;
     }
+
+    let transport = (tokio::io::stdin(), tokio::io::stdout());
+    let service = mcp.serve(transport).await?;
     service.waiting().await?;
     Ok(())
 }
@@ -48,23 +64,97 @@ pub(crate) async fn start(app_settings: AppSettings) -> Result<()> {
 pub struct Mcp {
     app_settings: AppSettings,
     metrics: Metrics,
-    client_info: Arc<Mutex<Option<Implementation>>>,
     event_handler: event::Handler,
+    workspace_tools: toolset::WorkspaceTools,
+    /// The project the server was started in, which is where sessions start out.
+    project_dir: PathBuf,
+    client_info: Arc<Mutex<Option<Implementation>>>,
+    projects: Projects,
+    resources: resources::Resources,
 }
 
 #[tool(tool_box)]
 impl Mcp {
-    pub fn new(app_settings: AppSettings, client_info: Arc<Mutex<Option<Implementation>>>) -> Self {
+    pub fn new(app_settings: AppSettings, project_dir: PathBuf) -> Self {
         let metrics = Metrics::new_with_background_handling(&app_settings);
         let event_handler = event::Handler::new_with_background_handling();
+        let projects = Projects::default();
         Self {
             app_settings,
             metrics,
-            client_info,
             event_handler,
+            workspace_tools: toolset::WorkspaceTools::default(),
+            client_info: Arc::default(),
+            resources: resources::Resources::new(project_dir.clone(), projects.clone()),
+            projects,
+            project_dir,
+        }
+    }
+
+    /// Create the server for another session, which shares the metrics and the handling of events with this one.
+    pub fn new_session(&self) -> Self {
+        let projects = Projects::default();
+        Self {
+            client_info: Arc::default(),
+            resources: resources::Resources::new(self.project_dir.clone(), projects.clone()),
+            projects,
+            ..self.clone()
         }
     }
 
+    /// Call one of the workspace tools of `but_tools`, which are listed alongside the tools of this server.
+    fn call_workspace_tool(
+        &self,
+        request: CallToolRequestParam,
+    ) -> Result<CallToolResult, McpError> {
+        let client_info = self
+            .client_info
+            .lock()
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?
+            .clone();
+        let start_time = std::time::Instant::now();
+        let result = self.workspace_tools.call(
+            &request.name,
+            request.arguments,
+            client_info.as_ref(),
+            &self.projects,
+        );
+        let error = match &result {
+            Ok(result) if result.is_error == Some(true) => Some(format!("{:?}", result.content)),
+            Ok(_) => None,
+            Err(e) => Some(e.to_string()),
+        };
+        let event = &mut Event::new(EventKind::Mcp);
+        event.insert_prop("endpoint", request.name.to_string());
+        event.insert_prop("durationMs", start_time.elapsed().as_millis());
+        event.insert_prop("error", error);
+        event.insert_prop("clientName", client_info.clone().map(|i| i.name));
+        event.insert_prop("clientVersion", client_info.clone().map(|i| i.version));
+        self.metrics.capture(event);
+
+        result
+    }
+
+    #[tool(
+        description = "Bind this session to the project the agent works in, so the resources show the state of its workspace."
+    )]
+    pub fn bind_project(
+        &self,
+        #[tool(aggr)] params: resources::BindProjectParams,
+    ) -> Result<CallToolResult, McpError> {
+        let project = self
+            .projects
+            .from_path(Path::new(&params.current_working_directory))
+            .map_err(|e| McpError::invalid_params(e.to_string(), None))?;
+        self.resources
+            .bind(project.path.clone())
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Bound to the project at {}",
+            project.path.display()
+        ))]))
+    }
+
     #[tool(
         description = "Update commits on the current branch based on the prompt used to modify the codebase and a summary of the changes made."
     )]
@@ -131,7 +221,10 @@ impl Mcp {
         }
 
         let repo_path = PathBuf::from(request.current_working_directory.clone());
-        let project = Project::from_path(&repo_path).expect("Failed to create project from path");
+        let project = self
+            .projects
+            .from_path(&repo_path)
+            .map_err(|e| McpError::invalid_params(e.to_string(), None))?;
         let settings = AppSettings::load_from_default_path_creating()
             .map_err(|e| McpError::internal_error(e.to_string(), None))?;
         let ctx = &mut CommandContext::open(&project, settings)
@@ -182,12 +275,24 @@ pub struct GitButlerUpdateBranchesRequest {
     pub current_working_directory: String,
 }
 
-#[tool(tool_box)]
 impl ServerHandler for Mcp {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
-            instructions: Some("GitButler MCP server".into()),
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            instructions: Some(
+                "GitButler MCP server.\n\
+                Besides updating branches after changes were made, the tools allow to inspect the workspace \
+                and to commit, amend, squash, split, reorder and undo granularly.\n\
+                Tools that rewrite history or discard work have to be allowed for each client in the GitButler settings.\n\
+                Subscribe to the resources of the workspace to follow its state instead of polling the project status.\n\
+                Bind the session to the project you work in first, as the resources otherwise show the project \
+                the server was started in."
+                    .into(),
+            ),
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_resources()
+                .enable_resources_subscribe()
+                .build(),
             server_info: Implementation {
                 name: "GitButler MCP Server".into(),
                 version: "1.0.0".into(),
@@ -195,4 +300,87 @@ impl ServerHandler for Mcp {
             protocol_version: ProtocolVersion::LATEST,
         }
     }
+
+    async fn initialize(
+        &self,
+        request: InitializeRequestParam,
+        _: RequestContext<RoleServer>,
+    ) -> Result<InitializeResult, McpError> {
+        if let Ok(mut guard) = self.client_info.lock() {
+            guard.replace(request.client_info);
+        }
+        Ok(self.get_info())
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParamInner>,
+        _: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        let mut tools = Self::tool_box().list();
+        tools.extend(self.workspace_tools.list());
+        Ok(ListToolsResult {
+            next_cursor: None,
+            tools,
+        })
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        if self.workspace_tools.contains(&request.name) {
+            return self.call_workspace_tool(request);
+        }
+        let context = ToolCallContext::new(self, request, context);
+        Self::tool_box().call(context).await
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParamInner>,
+        _: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        Ok(ListResourcesResult {
+            next_cursor: None,
+            resources: self.resources.list(),
+        })
+    }
+
+    async fn list_resource_templates(
+        &self,
+        _request: Option<PaginatedRequestParamInner>,
+        _: RequestContext<RoleServer>,
+    ) -> Result<ListResourceTemplatesResult, McpError> {
+        Ok(ListResourceTemplatesResult {
+            next_cursor: None,
+            resource_templates: resources::Resources::templates(),
+        })
+    }
+
+    async fn read_resource(
+        &self,
+        ReadResourceRequestParam { uri }: ReadResourceRequestParam,
+        _: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        self.resources.read(&uri)
+    }
+
+    async fn subscribe(
+        &self,
+        SubscribeRequestParam { uri }: SubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        self.resources.subscribe(uri, context.peer)
+    }
+
+    async fn unsubscribe(
+        &self,
+        UnsubscribeRequestParam { uri }: UnsubscribeRequestParam,
+        _: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        self.resources.unsubscribe(&uri);
+        Ok(())
+    }
 }
@@ -0,0 +1,636 @@
+//! MCP resources exposing the state of the workspace of the project a session is bound to,
+//! along with notifications about their updates which are driven by the file watcher.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use but_graph::VirtualBranchesTomlMetadata;
+use but_settings::{AppSettings, AppSettingsWithDiskSync};
+use but_tools::workspace::GetCommitDetailsParameters;
+use but_workspace::StackId;
+use gitbutler_command_context::CommandContext;
+use gitbutler_oplog::OplogExt;
+use gitbutler_project::Project;
+use gitbutler_watcher::{Change, WatcherHandle};
+use rmcp::{
+    Error as McpError, Peer, RoleServer,
+    model::{
+        AnnotateAble, RawResource, RawResourceTemplate, ReadResourceResult, Resource,
+        ResourceContents, ResourceTemplate, ResourceUpdatedNotificationParam,
+    },
+    schemars,
+};
+
+use crate::mcp_internal::project::Projects;
+
+const STATUS_URI: &str = "gitbutler://status";
+const OPLOG_URI: &str = "gitbutler://oplog";
+const STACK_PREFIX: &str = "gitbutler://stack/";
+const COMMIT_PREFIX: &str = "gitbutler://commit/";
+const DIFF_SUFFIX: &str = "/diff";
+/// The amount of operations listed in the oplog resource, most recent first.
+const OPLOG_LIMIT: usize = 50;
+
+/// A resource, as identified by its URI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResourceUri {
+    Status,
+    Stack(StackId),
+    CommitDiff(gix::ObjectId),
+    Oplog,
+}
+
+impl FromStr for ResourceUri {
+    type Err = McpError;
+
+    fn from_str(uri: &str) -> Result<Self, Self::Err> {
+        let parsed = if uri == STATUS_URI {
+            Some(ResourceUri::Status)
+        } else if uri == OPLOG_URI {
+            Some(ResourceUri::Oplog)
+        } else if let Some(id) = uri.strip_prefix(STACK_PREFIX) {
+            StackId::from_str(id).ok().map(ResourceUri::Stack)
+        } else if let Some(oid) = uri
+            .strip_prefix(COMMIT_PREFIX)
+            .and_then(|rest| rest.strip_suffix(DIFF_SUFFIX))
+        {
+            gix::ObjectId::from_hex(oid.as_bytes())
+                .ok()
+                .map(ResourceUri::CommitDiff)
+        } else {
+            None
+        };
+        parsed.ok_or_else(|| McpError::resource_not_found(format!("Unknown resource {uri}"), None))
+    }
+}
+
+impl ResourceUri {
+    /// Return `true` if the content of the resource may have changed with `change`.
+    fn is_affected_by(&self, change: &Change) -> bool {
+        match (self, change) {
+            // Commits never change, and the rules aren't part of any resource.
+            (ResourceUri::CommitDiff(_), _) | (_, Change::RulesFile { .. }) => false,
+            (ResourceUri::Status, _) => true,
+            (ResourceUri::Stack(_) | ResourceUri::Oplog, Change::WorktreeChanges { .. }) => false,
+            (ResourceUri::Stack(_) | ResourceUri::Oplog, _) => true,
+        }
+    }
+}
+
+/// The resources of the project in `project_dir`, and the subscriptions to them.
+#[derive(Clone)]
+pub struct Resources {
+    project_dir: Arc<Mutex<PathBuf>>,
+    /// The projects of the session, to open the one in `project_dir` with.
+    projects: Projects,
+    subscriptions: Arc<Mutex<BTreeSet<String>>>,
+    /// The watcher that runs while there are subscriptions, along with the client it notifies.
+    ///
+    /// `subscriptions` may be locked while holding this lock, but never the other way around.
+    watcher: Arc<Mutex<Option<(WatcherHandle, Peer<RoleServer>)>>>,
+}
+
+impl std::fmt::Debug for Resources {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Resources")
+            .field("project_dir", &self.project_dir)
+            .field("subscriptions", &self.subscriptions)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Resources {
+    pub fn new(project_dir: PathBuf, projects: Projects) -> Self {
+        Resources {
+            project_dir: Arc::new(Mutex::new(project_dir)),
+            projects,
+            subscriptions: Arc::default(),
+            watcher: Arc::default(),
+        }
+    }
+
+    /// Bind the resources to the project in `project_dir`, notifying about updates of all subscribed resources
+    /// as they now show the state of that project.
+    pub fn bind(&self, project_dir: PathBuf) -> anyhow::Result<()> {
+        *self.project_dir.lock().expect("poisoned") = project_dir;
+
+        let subscriptions = self.subscriptions.lock().expect("poisoned").clone();
+        let mut watcher = self.watcher.lock().expect("poisoned");
+        let Some((previous, peer)) = watcher.take() else {
+            return Ok(());
+        };
+        // Stop watching the previous project before watching the new one.
+        drop(previous);
+        *watcher = Some((self.watch(peer.clone())?, peer.clone()));
+        notify_updated(&tokio::runtime::Handle::current(), &peer, subscriptions);
+        Ok(())
+    }
+
+    /// List the status and the oplog, along with a resource for each stack in the workspace.
+    pub fn list(&self) -> Vec<Resource> {
+        let mut resources = vec![
+            resource(
+                STATUS_URI.into(),
+                "status",
+                "The stacks applied to the workspace and the uncommitted changes",
+            ),
+            resource(
+                OPLOG_URI.into(),
+                "oplog",
+                "The most recent operations on the workspace, which can be undone",
+            ),
+        ];
+        match self.open().and_then(|ctx| stacks(&ctx)) {
+            Ok(stacks) => resources.extend(stacks.into_iter().filter_map(|stack| {
+                let name = stack.heads.first().map(|head| head.name.to_string())?;
+                Some(resource(
+                    format!("{STACK_PREFIX}{}", stack.id?),
+                    &name,
+                    "The branches and commits of the stack",
+                ))
+            })),
+            Err(err) => tracing::warn!(?err, "could not list the stacks of the workspace"),
+        }
+        resources
+    }
+
+    pub fn templates() -> Vec<ResourceTemplate> {
+        vec![
+            template(
+                format!("{STACK_PREFIX}{{id}}"),
+                "stack",
+                "The branches and commits of the stack with the given id",
+            ),
+            template(
+                format!("{COMMIT_PREFIX}{{oid}}{DIFF_SUFFIX}"),
+                "commit diff",
+                "The changes of the commit with the given id, as unified diffs",
+            ),
+        ]
+    }
+
+    /// Read the resource at `uri` as JSON.
+    pub fn read(&self, uri: &str) -> Result<ReadResourceResult, McpError> {
+        let parsed = uri.parse()?;
+        let content = self
+            .read_json(parsed)
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::TextResourceContents {
+                uri: uri.to_owned(),
+                mime_type: Some("application/json".into()),
+                text: serde_json::to_string_pretty(&content)
+                    .map_err(|e| McpError::internal_error(e.to_string(), None))?,
+            }],
+        })
+    }
+
+    fn read_json(&self, uri: ResourceUri) -> anyhow::Result<serde_json::Value> {
+        let mut ctx = self.open()?;
+        Ok(match uri {
+            ResourceUri::Status => {
+                let repo = ctx.gix_repo()?;
+                serde_json::to_value(but_tools::workspace::get_project_status(
+                    &mut ctx, &repo, None,
+                )?)?
+            }
+            ResourceUri::Stack(stack_id) => {
+                let details = if ctx.app_settings().feature_flags.ws3 {
+                    let repo = ctx.gix_repo_for_merging_non_persisting()?;
+                    let meta = ref_metadata_toml(ctx.project())?;
+                    but_workspace::stack_details_v3(Some(stack_id), &repo, &meta)?
+                } else {
+                    but_workspace::stack_details(&ctx.project().gb_dir(), stack_id, &ctx)?
+                };
+                serde_json::to_value(details)?
+            }
+            ResourceUri::CommitDiff(commit_id) => {
+                let params = GetCommitDetailsParameters {
+                    commit_id: commit_id.to_string(),
+                };
+                serde_json::to_value(but_tools::workspace::commit_details(
+                    &mut ctx,
+                    params,
+                    &HashMap::new(),
+                )?)?
+            }
+            ResourceUri::Oplog => {
+                serde_json::to_value(ctx.list_snapshots(OPLOG_LIMIT, None, vec![])?)?
+            }
+        })
+    }
+
+    /// Notify `peer` whenever the resource at `uri` changes, watching the project while there are subscriptions.
+    pub fn subscribe(&self, uri: String, peer: Peer<RoleServer>) -> Result<(), McpError> {
+        uri.parse::<ResourceUri>()?;
+        self.subscriptions.lock().expect("poisoned").insert(uri);
+
+        let mut watcher = self.watcher.lock().expect("poisoned");
+        if watcher.is_none() {
+            let handle = self
+                .watch(peer.clone())
+                .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+            *watcher = Some((handle, peer));
+        }
+        Ok(())
+    }
+
+    pub fn unsubscribe(&self, uri: &str) {
+        self.subscriptions.lock().expect("poisoned").remove(uri);
+
+        let mut watcher = self.watcher.lock().expect("poisoned");
+        // Check again, as another subscription may have been made in the meantime.
+        if self.subscriptions.lock().expect("poisoned").is_empty() {
+            // Dropping the handle stops the watcher.
+            watcher.take();
+        }
+    }
+
+    fn watch(&self, peer: Peer<RoleServer>) -> anyhow::Result<WatcherHandle> {
+        let project = self.projects.from_path(&self.project_dir())?;
+        let runtime = tokio::runtime::Handle::current();
+        let subscriptions = self.subscriptions.clone();
+        let handler = gitbutler_watcher::Handler::new(move |change| {
+            let updated = affected_by(&subscriptions.lock().expect("poisoned"), &change);
+            notify_updated(&runtime, &peer, updated);
+            Ok(())
+        });
+        let config_dir = but_path::app_config_dir()?;
+        let app_settings = AppSettingsWithDiskSync::new(config_dir)?;
+        gitbutler_watcher::watch_in_background(handler, &project.path, project.id, app_settings)
+    }
+
+    fn open(&self) -> anyhow::Result<CommandContext> {
+        let project_dir = self.project_dir();
+        let project = self
+            .projects
+            .from_path(&project_dir)
+            .with_context(|| format!("{} isn't a project", project_dir.display()))?;
+        CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)
+    }
+
+    fn project_dir(&self) -> PathBuf {
+        self.project_dir.lock().expect("poisoned").clone()
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BindProjectParams {
+    #[schemars(
+        description = "The full root path of the Git project the agent is actively working in"
+    )]
+    pub current_working_directory: String,
+}
+
+/// Return the `subscriptions` whose resources may have changed with `change`.
+fn affected_by(subscriptions: &BTreeSet<String>, change: &Change) -> Vec<String> {
+    subscriptions
+        .iter()
+        .filter(|uri| {
+            uri.parse::<ResourceUri>()
+                .is_ok_and(|resource| resource.is_affected_by(change))
+        })
+        .cloned()
+        .collect()
+}
+
+fn notify_updated(
+    runtime: &tokio::runtime::Handle,
+    peer: &Peer<RoleServer>,
+    uris: impl IntoIterator<Item = String>,
+) {
+    for uri in uris {
+        let peer = peer.clone();
+        runtime.spawn(async move {
+            let param = ResourceUpdatedNotificationParam { uri };
+            if let Err(err) = peer.notify_resource_updated(param).await {
+                tracing::warn!(?err, "could not notify about updated resource");
+            }
+        });
+    }
+}
+
+fn resource(uri: String, name: &str, description: &str) -> Resource {
+    let mut raw = RawResource::new(uri, name);
+    raw.description = Some(description.into());
+    raw.mime_type = Some("application/json".into());
+    raw.no_annotation()
+}
+
+fn template(uri_template: String, name: &str, description: &str) -> ResourceTemplate {
+    RawResourceTemplate {
+        uri_template,
+        name: name.into(),
+        description: Some(description.into()),
+        mime_type: Some("application/json".into()),
+    }
+    .no_annotation()
+}
+
+fn stacks(ctx: &CommandContext) -> anyhow::Result<Vec<but_workspace::ui::StackEntry>> {
+    let repo = ctx.gix_repo()?;
+    if ctx.app_settings().feature_flags.ws3 {
+        let meta = ref_metadata_toml(ctx.project())?;
+        but_workspace::stacks_v3(&repo, &meta, but_workspace::StacksFilter::InWorkspace, None)
+    } else {
+        but_workspace::stacks(
+            ctx,
+            &ctx.project().gb_dir(),
+            &repo,
+            but_workspace::StacksFilter::InWorkspace,
+        )
+    }
+}
+
+fn ref_metadata_toml(project: &Project) -> anyhow::Result<VirtualBranchesTomlMetadata> {
+    VirtualBranchesTomlMetadata::from_path(project.gb_dir().join("virtual_branches.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use gitbutler_branch::BranchCreateRequest;
+    use gitbutler_project::ProjectId;
+    use gitbutler_testsupport::{TestProject, paths};
+    use rmcp::{
+        ClientHandler, ServerHandler, ServiceExt,
+        model::{ResourceUpdatedNotificationParam, SubscribeRequestParam},
+        service::RequestContext,
+    };
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn uris_of_resources_and_templates_are_parsed() {
+        let stack_id = StackId::generate();
+        let commit_id = gix::ObjectId::empty_tree(gix::hash::Kind::Sha1);
+        assert_eq!(
+            STATUS_URI.parse::<ResourceUri>().ok(),
+            Some(ResourceUri::Status)
+        );
+        assert_eq!(
+            OPLOG_URI.parse::<ResourceUri>().ok(),
+            Some(ResourceUri::Oplog)
+        );
+        assert_eq!(
+            format!("{STACK_PREFIX}{stack_id}")
+                .parse::<ResourceUri>()
+                .ok(),
+            Some(ResourceUri::Stack(stack_id))
+        );
+        assert_eq!(
+            format!("{COMMIT_PREFIX}{commit_id}{DIFF_SUFFIX}")
+                .parse::<ResourceUri>()
+                .ok(),
+            Some(ResourceUri::CommitDiff(commit_id))
+        );
+
+        for uri in [
+            "gitbutler://unknown",
+            "gitbutler://stack/not-an-id",
+            "gitbutler://commit/not-an-id/diff",
+            &format!("{COMMIT_PREFIX}{commit_id}"),
+        ] {
+            assert!(uri.parse::<ResourceUri>().is_err(), "{uri} is unknown");
+        }
+
+        let templates: Vec<_> = Resources::templates()
+            .into_iter()
+            .map(|template| template.raw.uri_template)
+            .collect();
+        assert_eq!(
+            templates,
+            ["gitbutler://stack/{id}", "gitbutler://commit/{oid}/diff"]
+        );
+    }
+
+    #[test]
+    fn only_subscriptions_affected_by_a_change_are_updated() {
+        let stack_id = StackId::generate();
+        let commit_id = gix::ObjectId::empty_tree(gix::hash::Kind::Sha1);
+        let project_id = ProjectId::generate();
+        let subscriptions: BTreeSet<String> = [
+            STATUS_URI.to_owned(),
+            OPLOG_URI.to_owned(),
+            format!("{STACK_PREFIX}{stack_id}"),
+            format!("{COMMIT_PREFIX}{commit_id}{DIFF_SUFFIX}"),
+        ]
+        .into();
+
+        assert_eq!(
+            affected_by(
+                &subscriptions,
+                &Change::WorktreeChanges {
+                    project_id,
+                    changes: but_core::ui::WorktreeChanges {
+                        changes: vec![],
+                        ignored_changes: vec![],
+                    }
+                    .into(),
+                }
+            ),
+            [STATUS_URI],
+            "changes to the worktree don't change history"
+        );
+        assert_eq!(
+            affected_by(&subscriptions, &Change::GitActivity(project_id)),
+            [
+                OPLOG_URI.to_owned(),
+                format!("{STACK_PREFIX}{stack_id}"),
+                STATUS_URI.to_owned(),
+            ],
+            "commits never change, so their diffs don't either"
+        );
+        assert_eq!(
+            affected_by(
+                &subscriptions,
+                &Change::RulesFile {
+                    project_id,
+                    error: None,
+                }
+            ),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn stacks_are_listed_and_resources_are_read() -> anyhow::Result<()> {
+        let data_dir = paths::data_dir();
+        let repo = TestProject::default();
+        let project =
+            gitbutler_project::add_with_path(data_dir.path(), repo.path())?.unwrap_project();
+        let ctx = &mut CommandContext::open(&project, AppSettings::default())?;
+        gitbutler_branch_actions::set_base_branch(
+            ctx,
+            &"refs/remotes/origin/master".parse()?,
+            false,
+            ctx.project().exclusive_worktree_access().write_permission(),
+        )?;
+        let stack = gitbutler_branch_actions::create_virtual_branch(
+            ctx,
+            &BranchCreateRequest::default(),
+            ctx.project().exclusive_worktree_access().write_permission(),
+        )?;
+        fs::write(repo.path().join("file.txt"), "content")?;
+        let commit_id = gitbutler_branch_actions::create_commit(ctx, stack.id, "commit", None)?;
+
+        let resources = Resources::new(repo.path().to_owned(), Projects::default());
+        let stack_uri = format!("{STACK_PREFIX}{}", stack.id);
+        let listed: Vec<_> = resources
+            .list()
+            .into_iter()
+            .map(|resource| resource.raw.uri)
+            .collect();
+        assert_eq!(listed, [STATUS_URI, OPLOG_URI, stack_uri.as_str()]);
+
+        assert!(read(&resources, STATUS_URI)?.is_object());
+        assert!(read(&resources, OPLOG_URI)?.is_array());
+        assert!(
+            read(&resources, &stack_uri)?
+                .to_string()
+                .contains(&stack.heads[0].name.to_string()),
+            "the stack shows its branch"
+        );
+        assert!(
+            read(
+                &resources,
+                &format!("{COMMIT_PREFIX}{commit_id}{DIFF_SUFFIX}")
+            )?
+            .to_string()
+            .contains("file.txt"),
+            "the diff shows the changed file"
+        );
+        assert!(resources.read("gitbutler://unknown").is_err());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn subscribers_are_notified_when_binding_another_project() -> anyhow::Result<()> {
+        let (first, second) = (TestProject::default(), TestProject::default());
+        let resources = Resources::new(first.path().to_owned(), Projects::default());
+        let (server_io, client_io) = tokio::io::duplex(4096);
+        let server = Server(resources.clone());
+        tokio::spawn(async move {
+            server.serve(server_io).await?.waiting().await?;
+            anyhow::Ok(())
+        });
+        let (updates, mut updated) = mpsc::unbounded_channel();
+        let client = Client(updates).serve(client_io).await?;
+
+        client
+            .subscribe(SubscribeRequestParam {
+                uri: STATUS_URI.into(),
+            })
+            .await?;
+        resources.bind(second.path().to_owned())?;
+
+        let uri = tokio::time::timeout(Duration::from_secs(10), updated.recv()).await?;
+        assert_eq!(
+            uri.as_deref(),
+            Some(STATUS_URI),
+            "the subscribed resource now shows another workspace"
+        );
+
+        resources.unsubscribe(STATUS_URI);
+        assert!(
+            resources.watcher.lock().expect("poisoned").is_none(),
+            "there is nothing to watch without subscriptions"
+        );
+        client.cancel().await?;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn binding_while_unsubscribing_does_not_deadlock() -> anyhow::Result<()> {
+        let (first, second) = (TestProject::default(), TestProject::default());
+        let resources = Resources::new(first.path().to_owned(), Projects::default());
+        let (server_io, client_io) = tokio::io::duplex(4096);
+        let server = Server(resources.clone());
+        tokio::spawn(async move {
+            server.serve(server_io).await?.waiting().await?;
+            anyhow::Ok(())
+        });
+        let (updates, _updated) = mpsc::unbounded_channel();
+        let client = Client(updates).serve(client_io).await?;
+
+        for round in 0..20 {
+            client
+                .subscribe(SubscribeRequestParam {
+                    uri: STATUS_URI.into(),
+                })
+                .await?;
+            let project = if round % 2 == 0 { &second } else { &first };
+            let project_dir = project.path().to_owned();
+            let binding = tokio::task::spawn_blocking({
+                let resources = resources.clone();
+                move || resources.bind(project_dir)
+            });
+            let unsubscribing = tokio::task::spawn_blocking({
+                let resources = resources.clone();
+                move || resources.unsubscribe(STATUS_URI)
+            });
+            let (bound, _) = tokio::time::timeout(
+                Duration::from_secs(10),
+                futures::future::try_join(binding, unsubscribing),
+            )
+            .await
+            .context("binding and unsubscribing at once must not deadlock")??;
+            bound?;
+            assert!(
+                resources.watcher.lock().expect("poisoned").is_none(),
+                "nothing is watched once all subscriptions are gone"
+            );
+        }
+        client.cancel().await?;
+        Ok(())
+    }
+
+    fn read(resources: &Resources, uri: &str) -> anyhow::Result<serde_json::Value> {
+        let result = resources.read(uri)?;
+        let [
+            ResourceContents::TextResourceContents {
+                uri: read_uri,
+                mime_type,
+                text,
+            },
+        ] = result.contents.as_slice()
+        else {
+            anyhow::bail!("expected a single text resource, got {:?}", result.contents);
+        };
+        assert_eq!(read_uri, uri);
+        assert_eq!(mime_type.as_deref(), Some("application/json"));
+        Ok(serde_json::from_str(text)?)
+    }
+
+    /// A server that only serves the subscriptions of `Resources`.
+    #[derive(Clone)]
+    struct Server(Resources);
+
+    impl ServerHandler for Server {
+        async fn subscribe(
+            &self,
+            SubscribeRequestParam { uri }: SubscribeRequestParam,
+            context: RequestContext<RoleServer>,
+        ) -> Result<(), McpError> {
+            self.0.subscribe(uri, context.peer)
+        }
+    }
+
+    /// A client that sends the URIs of updated resources.
+    #[derive(Clone)]
+    struct Client(mpsc::UnboundedSender<String>);
+
+    impl ClientHandler for Client {
+        async fn on_resource_updated(&self, params: ResourceUpdatedNotificationParam) {
+            self.0.send(params.uri).ok();
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
+
+use but_settings::AppSettings;
+use but_tools::tool::{Tool, Toolset, WorkspaceToolset};
+use gitbutler_command_context::CommandContext;
+use rmcp::{
+    Error as McpError,
+    model::{CallToolResult, Content, Implementation, JsonObject},
+};
+
+use crate::mcp_internal::project::Projects;
+
+/// The parameter that is added to every workspace tool, as MCP clients may work in any project.
+const WORKING_DIRECTORY: &str = "currentWorkingDirectory";
+/// Appended to the description of destructive tools, so clients can tell why calls may be rejected.
+const DESTRUCTIVE_NOTE: &str = "This tool rewrites history or discards work, so it must be allowed for this client in the GitButler settings.";
+
+/// The workspace tools of `but_tools`, bridged to MCP.
+#[derive(Clone)]
+pub struct WorkspaceTools {
+    tools: BTreeMap<String, Arc<dyn Tool>>,
+}
+
+impl std::fmt::Debug for WorkspaceTools {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.tools.keys()).finish()
+    }
+}
+
+impl Default for WorkspaceTools {
+    fn default() -> Self {
+        let tools = but_tools::workspace::all_workspace_tools()
+            .into_iter()
+            .map(|tool| (tool.name(), tool))
+            .collect();
+        WorkspaceTools { tools }
+    }
+}
+
+impl WorkspaceTools {
+    pub fn contains(&self, name: &str) -> bool {
+        self.tools.contains_key(name)
+    }
+
+    /// Describe all tools for MCP, marking the destructive ones as such.
+    pub fn list(&self) -> Vec<rmcp::model::Tool> {
+        self.tools
+            .values()
+            .map(|tool| {
+                let mut description = tool.description().trim().to_owned();
+                if tool.is_destructive() {
+                    description.push_str("\n\n");
+                    description.push_str(DESTRUCTIVE_NOTE);
+                }
+                let schema = Arc::new(input_schema(tool.as_ref()));
+                rmcp::model::Tool::new(tool.name(), description, schema)
+            })
+            .collect()
+    }
+
+    /// Call the tool `name` with `arguments` in the project of their working directory among `projects`,
+    /// on behalf of `client`.
+    ///
+    /// Destructive tools are only called if they are allowed for `client` in the settings. As `client` is what
+    /// the client reported about itself on initialization, this guards against accidents, not against
+    /// clients that pretend to be another.
+    pub fn call(
+        &self,
+        name: &str,
+        arguments: Option<JsonObject>,
+        client: Option<&Implementation>,
+        projects: &Projects,
+    ) -> Result<CallToolResult, McpError> {
+        let tool = self
+            .tools
+            .get(name)
+            .ok_or_else(|| McpError::invalid_params(format!("Tool '{name}' not found"), None))?;
+        let mut arguments = arguments.unwrap_or_default();
+        let repo_path = match arguments.remove(WORKING_DIRECTORY) {
+            Some(serde_json::Value::String(path)) if !path.is_empty() => PathBuf::from(path),
+            _ => {
+                return Err(McpError::invalid_params(
+                    format!("{WORKING_DIRECTORY} must be the path of the project"),
+                    None,
+                ));
+            }
+        };
+
+        let settings = AppSettings::load_from_default_path_creating()
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        let client_name = client.map(|client| client.name.as_str());
+        if tool.is_destructive() && !settings.mcp.is_destructive_tool_allowed(client_name, name) {
+            return Ok(CallToolResult::error(vec![Content::text(format!(
+                "'{name}' rewrites history or discards work and isn't allowed for the client '{}'. \
+                Ask the user to add it to `mcp.allowedDestructiveTools` in the GitButler settings.",
+                client_name.unwrap_or("unknown")
+            ))]));
+        }
+
+        let project = projects
+            .from_path(&repo_path)
+            .map_err(|e| McpError::invalid_params(e.to_string(), None))?;
+        let ctx = &mut CommandContext::open(&project, settings)
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        let mut toolset = WorkspaceToolset::new(ctx, Arc::new(|_, _| {}), None);
+        toolset.register_tool(tool.clone());
+        let result = toolset.call_tool(name, &serde_json::Value::Object(arguments).to_string());
+
+        let is_error = result.get("error").is_some();
+        let content = vec![Content::json(result)?];
+        Ok(if is_error {
+            CallToolResult::error(content)
+        } else {
+            CallToolResult::success(content)
+        })
+    }
+}
+
+/// The JSON schema of the parameters of `tool`, along with the working directory.
+fn input_schema(tool: &dyn Tool) -> JsonObject {
+    let mut schema = match tool.parameters() {
+        serde_json::Value::Object(schema) => schema,
+        _ => JsonObject::new(),
+    };
+    schema.insert("type".into(), "object".into());
+    let properties = schema
+        .entry("properties")
+        .or_insert_with(|| serde_json::json!({}));
+    if let Some(properties) = properties.as_object_mut() {
+        properties.insert(
+            WORKING_DIRECTORY.into(),
+            serde_json::json!({
+                "type": "string",
+                "description": "The full root path of the Git project the agent is actively working in",
+            }),
+        );
+    }
+    let required = schema
+        .entry("required")
+        .or_insert_with(|| serde_json::json!([]));
+    if let Some(required) = required.as_array_mut() {
+        required.push(WORKING_DIRECTORY.into());
+    }
+    schema
+}
@@ -1,7 +1,9 @@
 use std::path::Path;
 
 use bstr::BString;
+use but_settings::AppSettings;
 use but_workspace::commit_engine::StackSegmentId;
+use gitbutler_command_context::CommandContext;
 use rmcp::schemars;
 use serde::{Deserialize, Serialize};
 
@@ -55,6 +57,14 @@ pub fn commit(
         }
     };
 
+    let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let commit_message = gitbutler_branch_actions::hooks::run_pre_commit_hooks(
+        &ctx,
+        None,
+        &changes,
+        commit_message,
+    )?;
+
     let destination = but_workspace::commit_engine::Destination::NewCommit {
         parent_commit_id,
         message: commit_message,
@@ -72,7 +82,11 @@ pub fn commit(
         0, /* context-lines */
         guard.write_permission(),
     )?;
+    drop(guard);
 
+    if outcome.new_commit.is_some() {
+        gitbutler_branch_actions::hooks::run_post_commit_hook(&ctx)?;
+    }
     Ok(outcome.into())
 }
 
@@ -105,6 +119,14 @@ pub fn amend(
         .find(|s| s.heads(false).contains(&branch_name))
         .map(|s| s.id);
 
+    let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let commit_message = gitbutler_branch_actions::hooks::run_pre_commit_hooks(
+        &ctx,
+        Some(commit_id),
+        &changes,
+        commit_message,
+    )?;
+
     let destination = but_workspace::commit_engine::Destination::AmendCommit {
         commit_id,
         new_message: Some(commit_message),
@@ -121,7 +143,11 @@ pub fn amend(
         0, /* context-lines */
         guard.write_permission(),
     )?;
+    drop(guard);
 
+    if outcome.new_commit.is_some() {
+        gitbutler_branch_actions::hooks::run_post_commit_hook(&ctx)?;
+    }
     Ok(outcome.into())
 }
 
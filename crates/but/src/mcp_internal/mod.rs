@@ -1,13 +1,20 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
 use anyhow::Result;
 use but_settings::AppSettings;
 use rmcp::{
     RoleServer, ServerHandler, ServiceExt,
     model::{
-        CallToolResult, GetPromptRequestParam, GetPromptResult, Implementation, ListPromptsResult,
-        Prompt, PromptMessage, PromptMessageContent, PromptMessageRole, ProtocolVersion,
-        ServerCapabilities, ServerInfo,
+        CallToolResult, GetPromptRequestParam, GetPromptResult, Implementation,
+        InitializeRequestParam, InitializeResult, ListPromptsResult, ListResourceTemplatesResult,
+        ListResourcesResult, PaginatedRequestParamInner, Prompt, PromptMessage,
+        PromptMessageContent, PromptMessageRole, ProtocolVersion, ReadResourceRequestParam,
+        ReadResourceResult, ServerCapabilities, ServerInfo, SubscribeRequestParam,
+        UnsubscribeRequestParam,
     },
     schemars,
     service::RequestContext,
@@ -15,14 +22,27 @@ use rmcp::{
 };
 use tracing_subscriber::{self, EnvFilter};
 
-use crate::metrics::{Event, EventKind, Metrics};
+use crate::{
+    mcp::{
+        http,
+        resources::{BindProjectParams, Resources},
+    },
+    mcp_internal::project::Projects,
+    metrics::{Event, EventKind, Metrics},
+};
 
 pub mod commit;
 pub mod project;
 pub mod stack;
 pub mod status;
 
-pub(crate) async fn start(app_settings: AppSettings) -> Result<()> {
+/// Serve over stdio, or over HTTP on `listen` with `token` as bearer token, in the project in `project_dir`.
+pub(crate) async fn start(
+    app_settings: AppSettings,
+    project_dir: &Path,
+    listen: Option<SocketAddr>,
+    token: Option<String>,
+) -> Result<()> {
     // Initialize the tracing subscriber with file and stdout logging
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env().add_directive(tracing::Level::DEBUG.into()))
@@ -32,15 +52,13 @@ pub(crate) async fn start(app_settings: AppSettings) -> Result<()> {
 
     tracing::info!("Starting MCP server");
 
-    let client_info = Arc::new(Mutex::new(None));
-    let transport = (tokio::io::stdin(), tokio::io::stdout());
-    let service = Mcp::new(app_settings, client_info.clone())
-        .serve(transport)
-        .await?;
-    let info = service.peer_info();
-    if let Ok(mut guard) = client_info.lock() {
-        guard.replace(info.client_info.clone());
+    let mcp = Mcp::new(app_settings, project_dir.to_owned());
+    if let Some(addr) = listen {
+        return http::serve(addr, token, move || mcp.new_session()).await;
     }
+
+    let transport = (tokio::io::stdin(), tokio::io::stdout());
+    let service = mcp.serve(transport).await?;
     service.waiting().await?;
     Ok(())
 }
@@ -48,19 +66,58 @@ pub(crate) async fn start(app_settings: AppSettings) -> Result<()> {
 #[derive(Debug, Clone)]
 pub struct Mcp {
     metrics: Metrics,
+    /// The project the server was started in, which is where sessions start out.
+    project_dir: PathBuf,
     client_info: Arc<Mutex<Option<Implementation>>>,
+    projects: Projects,
+    resources: Resources,
 }
 
 #[tool(tool_box)]
 impl Mcp {
-    pub fn new(app_settings: AppSettings, client_info: Arc<Mutex<Option<Implementation>>>) -> Self {
+    pub fn new(app_settings: AppSettings, project_dir: PathBuf) -> Self {
         let metrics = Metrics::new_with_background_handling(&app_settings);
+        let projects = Projects::default();
         Self {
             metrics,
-            client_info,
+            client_info: Arc::default(),
+            resources: Resources::new(project_dir.clone(), projects.clone()),
+            projects,
+            project_dir,
         }
     }
 
+    /// Create the server for another session, which shares the metrics with this one.
+    pub fn new_session(&self) -> Self {
+        let projects = Projects::default();
+        Self {
+            metrics: self.metrics.clone(),
+            client_info: Arc::default(),
+            resources: Resources::new(self.project_dir.clone(), projects.clone()),
+            projects,
+            project_dir: self.project_dir.clone(),
+        }
+    }
+
+    #[tool(
+        description = "Bind this session to the project you work in, so the resources show the state of its workspace."
+    )]
+    pub fn bind_project(
+        &self,
+        #[tool(aggr)] params: BindProjectParams,
+    ) -> Result<CallToolResult, rmcp::Error> {
+        let project = self
+            .projects
+            .from_path(Path::new(&params.current_working_directory))
+            .map_err(|e| rmcp::Error::invalid_params(e.to_string(), None))?;
+        self.resources
+            .bind(project.path.clone())
+            .map_err(|e| rmcp::Error::internal_error(e.to_string(), None))?;
+        Ok(CallToolResult::success(vec![rmcp::model::Content::text(
+            format!("Bound to the project at {}", project.path.display()),
+        )]))
+    }
+
     #[tool(description = "Get the status of a project.
         This contains information about the branches applied, uncommitted file changes and any uncommitted changes assigned to the branches .")]
     pub fn project_status(
@@ -75,7 +132,7 @@ impl Mcp {
 
         let start_time = std::time::Instant::now();
         let project_path = std::path::PathBuf::from(&params.current_working_directory);
-        let status = crate::mcp_internal::status::project_status(&project_path)
+        let status = crate::mcp_internal::status::project_status(&project_path, &self.projects)
             .map_err(|e| rmcp::Error::internal_error(e.to_string(), None))?;
 
         let event = &mut Event::new(EventKind::McpInternal);
@@ -170,9 +227,12 @@ impl Mcp {
 
         let start_time = std::time::Instant::now();
         let project_path = std::path::PathBuf::from(&params.current_working_directory);
-        let details =
-            crate::mcp_internal::stack::branch_details(&params.branch_name, &project_path)
-                .map_err(|e| rmcp::Error::internal_error(e.to_string(), None))?;
+        let details = crate::mcp_internal::stack::branch_details(
+            &params.branch_name,
+            &project_path,
+            &self.projects,
+        )
+        .map_err(|e| rmcp::Error::internal_error(e.to_string(), None))?;
 
         let event = &mut Event::new(EventKind::McpInternal);
         event.insert_prop("endpoint", "branch_details");
@@ -204,6 +264,7 @@ impl Mcp {
             &params.branch_name,
             &params.description,
             &project_path,
+            &self.projects,
         )
         .map_err(|e| rmcp::Error::internal_error(e.to_string(), None))?;
 
@@ -304,10 +365,19 @@ pub struct CreateBranchParams {
 impl ServerHandler for Mcp {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
-            instructions: Some("This is the GitButler MCP server.
-            This provides tools and other context resources that allow you to interact with your project's version control.
-            If enabled, these are the tools that should be used for any Git operations".into()),
-            capabilities: ServerCapabilities::builder().enable_tools().enable_prompts().build(),
+            instructions: Some(
+                "This is the GitButler MCP server.\n\
+                This provides tools and other context resources that allow you to interact with your project's version control.\n\
+                If enabled, these are the tools that should be used for any Git operations.\n\
+                Bind the session to the project you work in before using its resources."
+                    .into(),
+            ),
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_prompts()
+                .enable_resources()
+                .enable_resources_subscribe()
+                .build(),
             server_info: Implementation {
                 name: "GitButler MCP Server".into(),
                 version: "1.0.0".into(),
@@ -316,6 +386,17 @@ impl ServerHandler for Mcp {
         }
     }
 
+    async fn initialize(
+        &self,
+        request: InitializeRequestParam,
+        _: RequestContext<RoleServer>,
+    ) -> Result<InitializeResult, rmcp::Error> {
+        if let Ok(mut guard) = self.client_info.lock() {
+            guard.replace(request.client_info);
+        }
+        Ok(self.get_info())
+    }
+
     async fn list_prompts(
         &self,
         _request: Option<rmcp::model::PaginatedRequestParamInner>,
@@ -387,4 +468,51 @@ impl ServerHandler for Mcp {
             _ => Err(rmcp::Error::invalid_params("prompt not found", None)),
         }
     }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParamInner>,
+        _: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, rmcp::Error> {
+        Ok(ListResourcesResult {
+            next_cursor: None,
+            resources: self.resources.list(),
+        })
+    }
+
+    async fn list_resource_templates(
+        &self,
+        _request: Option<PaginatedRequestParamInner>,
+        _: RequestContext<RoleServer>,
+    ) -> Result<ListResourceTemplatesResult, rmcp::Error> {
+        Ok(ListResourceTemplatesResult {
+            next_cursor: None,
+            resource_templates: Resources::templates(),
+        })
+    }
+
+    async fn read_resource(
+        &self,
+        ReadResourceRequestParam { uri }: ReadResourceRequestParam,
+        _: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, rmcp::Error> {
+        self.resources.read(&uri)
+    }
+
+    async fn subscribe(
+        &self,
+        SubscribeRequestParam { uri }: SubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), rmcp::Error> {
+        self.resources.subscribe(uri, context.peer)
+    }
+
+    async fn unsubscribe(
+        &self,
+        UnsubscribeRequestParam { uri }: UnsubscribeRequestParam,
+        _: RequestContext<RoleServer>,
+    ) -> Result<(), rmcp::Error> {
+        self.resources.unsubscribe(&uri);
+        Ok(())
+    }
 }
@@ -1,18 +1,58 @@
 use but_graph::VirtualBranchesTomlMetadata;
-use gitbutler_project::Project;
-use std::path::Path;
+use gitbutler_project::{Project, ProjectId};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
-pub fn project_from_path(path: &Path) -> anyhow::Result<Project> {
-    Project::from_path(path)
+/// The projects opened by a session of the MCP servers so far, by their worktree.
+#[derive(Debug, Clone, Default)]
+pub struct Projects(Arc<Mutex<HashMap<PathBuf, Project>>>);
+
+impl Projects {
+    /// Return the project that contains `path`, which is the stored one if the project was added to GitButler.
+    ///
+    /// Other projects get an id derived from their worktree. `CommandContext` locks the worktree by project id,
+    /// so this makes concurrent sessions use the same lock when modifying the same workspace.
+    pub fn from_path(&self, path: &Path) -> anyhow::Result<Project> {
+        let project = Project::from_path(path)?;
+        let worktree_dir = gix::path::realpath(&project.path)?;
+        let mut projects = self.0.lock().expect("poisoned");
+        if let Some(project) = projects.get(&worktree_dir) {
+            return Ok(project.clone());
+        }
+        let project = match gitbutler_project::list()?
+            .into_iter()
+            .find(|stored| stored.path == worktree_dir)
+        {
+            Some(stored) => stored,
+            None => Project {
+                id: worktree_project_id(&worktree_dir),
+                ..project
+            },
+        };
+        projects.insert(worktree_dir, project.clone());
+        Ok(project)
+    }
+
+    pub fn repo(&self, path: &Path) -> anyhow::Result<gix::Repository> {
+        let project = self.from_path(path)?;
+        configured_repo(
+            gix::open(project.worktree_path())?,
+            RepositoryOpenMode::General,
+        )
+    }
 }
 
-pub fn project_repo(path: &Path) -> anyhow::Result<gix::Repository> {
-    let project = project_from_path(path)?;
-    configured_repo(
-        gix::open(project.worktree_path())?,
-        RepositoryOpenMode::General,
+fn worktree_project_id(worktree_dir: &Path) -> ProjectId {
+    uuid::Uuid::new_v5(
+        &uuid::Uuid::NAMESPACE_URL,
+        &gix::path::into_bstr(worktree_dir),
     )
+    .into()
 }
+
 pub enum RepositoryOpenMode {
     // We'll need this later for the commit command
     Merge,
@@ -57,3 +97,35 @@ pub fn repo_and_maybe_project(
     };
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use gitbutler_testsupport::TestProject;
+
+    use super::*;
+
+    #[test]
+    fn sessions_share_the_project_id_of_a_worktree() -> anyhow::Result<()> {
+        let repo = TestProject::default();
+        let (session, other_session) = (Projects::default(), Projects::default());
+
+        let project = session.from_path(repo.path())?;
+        let subdir = repo.path().join("subdir");
+        std::fs::create_dir(&subdir)?;
+        assert_eq!(
+            session.from_path(&subdir)?.id,
+            project.id,
+            "paths within the worktree are the same project"
+        );
+        assert_eq!(
+            other_session.from_path(repo.path())?.id,
+            project.id,
+            "the worktree is locked for all sessions alike"
+        );
+        assert_ne!(
+            session.from_path(TestProject::default().path())?.id,
+            project.id
+        );
+        Ok(())
+    }
+}
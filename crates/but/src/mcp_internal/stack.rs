@@ -6,11 +6,17 @@ use gitbutler_command_context::CommandContext;
 use gitbutler_stack::{PatchReferenceUpdate, VirtualBranchesHandle};
 use serde::Serialize;
 
+use super::project::Projects;
+
 /// Get the details of a branch by its name.
 ///
 /// This includes information about the branch itself and its commits
-pub fn branch_details(ref_name: &str, current_dir: &Path) -> anyhow::Result<BranchDetails> {
-    let project = super::project::project_from_path(current_dir)?;
+pub fn branch_details(
+    ref_name: &str,
+    current_dir: &Path,
+    projects: &Projects,
+) -> anyhow::Result<BranchDetails> {
+    let project = projects.from_path(current_dir)?;
     let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
     let meta = super::project::ref_metadata_toml(ctx.project())?;
     let repo = ctx.gix_repo_for_merging_non_persisting()?;
@@ -25,8 +31,9 @@ pub fn create_stack_with_branch(
     name: &str,
     description: &str,
     current_dir: &Path,
+    projects: &Projects,
 ) -> anyhow::Result<but_workspace::ui::StackEntryNoOpt> {
-    let project = super::project::project_from_path(current_dir)?;
+    let project = projects.from_path(current_dir)?;
     let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
 
     let creation_request = gitbutler_branch::BranchCreateRequest {
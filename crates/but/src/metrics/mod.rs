@@ -28,6 +28,14 @@ pub enum Command {
     Log,
     Status,
     Rub,
+    Branch,
+    Commit,
+    Rebase,
+    Rules,
+    ScanSecrets,
+    Sync,
+    Undo,
+    Redo,
     ClaudePreTool,
     ClaudePostTool,
     ClaudeStop,
@@ -40,6 +48,14 @@ impl From<CommandName> for EventKind {
             CommandName::Log => EventKind::Cli(Command::Log),
             CommandName::Status => EventKind::Cli(Command::Status),
             CommandName::Rub => EventKind::Cli(Command::Rub),
+            CommandName::Branch => EventKind::Cli(Command::Branch),
+            CommandName::Commit => EventKind::Cli(Command::Commit),
+            CommandName::Rebase => EventKind::Cli(Command::Rebase),
+            CommandName::Rules => EventKind::Cli(Command::Rules),
+            CommandName::ScanSecrets => EventKind::Cli(Command::ScanSecrets),
+            CommandName::Sync => EventKind::Cli(Command::Sync),
+            CommandName::Undo => EventKind::Cli(Command::Undo),
+            CommandName::Redo => EventKind::Cli(Command::Redo),
             CommandName::ClaudePreTool => EventKind::Cli(Command::ClaudePreTool),
             CommandName::ClaudePostTool => EventKind::Cli(Command::ClaudePostTool),
             CommandName::ClaudeStop => EventKind::Cli(Command::ClaudeStop),
@@ -0,0 +1,720 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use bstr::{BString, ByteSlice};
+use but_rebase::RebaseStep;
+use but_settings::AppSettings;
+use but_workspace::{StackId, stack_ext::StackExt};
+use colored::Colorize;
+use gitbutler_command_context::CommandContext;
+use gitbutler_oxidize::{ObjectIdExt, OidExt};
+use gitbutler_project::Project;
+use gitbutler_stack::VirtualBranchesHandle;
+use serde::{Deserialize, Serialize};
+
+use crate::id::IdMap;
+
+/// An instruction of the todo list, as edited by the user.
+#[derive(Debug, Clone, PartialEq)]
+enum Todo {
+    /// Keep the commit, optionally letting the user edit its message.
+    Pick {
+        commit_id: gix::ObjectId,
+        reword: bool,
+    },
+    /// Meld the commit into the one before it, letting the user edit the combined message.
+    Squash { commit_id: gix::ObjectId },
+    /// Meld the commit into the one before it, keeping only the message of the latter.
+    Fixup { commit_id: gix::ObjectId },
+    /// Keep the commit, and stop the rebase at it to amend it in edit mode before carrying out the todos after it.
+    Edit { commit_id: gix::ObjectId },
+    /// Run a shell command on the commit before it, aborting the rebase if it fails.
+    Exec { command: String },
+    /// Point a branch of the stack to the commit before it.
+    Reference(but_core::Reference),
+}
+
+impl Todo {
+    fn commit_id(&self) -> Option<gix::ObjectId> {
+        match self {
+            Todo::Pick { commit_id, .. }
+            | Todo::Squash { commit_id }
+            | Todo::Fixup { commit_id }
+            | Todo::Edit { commit_id } => Some(*commit_id),
+            Todo::Exec { .. } | Todo::Reference(_) => None,
+        }
+    }
+
+    fn commit_id_mut(&mut self) -> Option<&mut gix::ObjectId> {
+        match self {
+            Todo::Pick { commit_id, .. }
+            | Todo::Squash { commit_id }
+            | Todo::Fixup { commit_id }
+            | Todo::Edit { commit_id } => Some(commit_id),
+            Todo::Exec { .. } | Todo::Reference(_) => None,
+        }
+    }
+
+    /// Parse a `line` as written by the [`Display`](std::fmt::Display) implementation, looking up branches
+    /// of the stack by name in `references`.
+    fn from_line(
+        line: &str,
+        references: &HashMap<String, but_core::Reference>,
+    ) -> anyhow::Result<Self> {
+        let (command, argument) = line
+            .split_once(' ')
+            .with_context(|| format!("Invalid todo '{line}'"))?;
+        let commit_id = || {
+            gix::ObjectId::from_hex(argument.as_bytes())
+                .with_context(|| format!("Invalid commit in todo '{line}'"))
+        };
+        Ok(match command {
+            "pick" => Todo::Pick {
+                commit_id: commit_id()?,
+                reword: false,
+            },
+            "reword" => Todo::Pick {
+                commit_id: commit_id()?,
+                reword: true,
+            },
+            "squash" => Todo::Squash {
+                commit_id: commit_id()?,
+            },
+            "fixup" => Todo::Fixup {
+                commit_id: commit_id()?,
+            },
+            "edit" => Todo::Edit {
+                commit_id: commit_id()?,
+            },
+            "exec" => Todo::Exec {
+                command: argument.to_owned(),
+            },
+            "ref" => {
+                Todo::Reference(references.get(argument).cloned().with_context(|| {
+                    format!("Branch '{argument}' isn't part of the stack anymore")
+                })?)
+            }
+            _ => bail!("Invalid todo '{line}'"),
+        })
+    }
+}
+
+/// Writes the todo as a line of the todo list, with the full id of its commit.
+impl std::fmt::Display for Todo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Todo::Pick {
+                commit_id,
+                reword: false,
+            } => write!(f, "pick {commit_id}"),
+            Todo::Pick {
+                commit_id,
+                reword: true,
+            } => write!(f, "reword {commit_id}"),
+            Todo::Squash { commit_id } => write!(f, "squash {commit_id}"),
+            Todo::Fixup { commit_id } => write!(f, "fixup {commit_id}"),
+            Todo::Edit { commit_id } => write!(f, "edit {commit_id}"),
+            Todo::Exec { command } => write!(f, "exec {command}"),
+            Todo::Reference(reference) => write!(f, "ref {reference}"),
+        }
+    }
+}
+
+/// The result of `but rebase`, printed as JSON if requested.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RebaseOutcome {
+    stack_id: StackId,
+    /// The hex-encoded id of the commit at the top of the rebased stack.
+    top_commit: String,
+    /// The branches of the stack along with the commits they now point to, from bottom to top.
+    branches: Vec<BranchTip>,
+    /// The hex-encoded id of the commit that is now checked out in edit mode, if any.
+    edited_commit: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BranchTip {
+    name: String,
+    commit_id: String,
+}
+
+/// The rest of the todo list of a `but rebase` that stopped at a commit for editing, persisted until
+/// the rebase is continued or aborted.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoppedRebase {
+    stack_id: StackId,
+    branch: String,
+    /// The commit the rebase stopped at, as checked out in edit mode.
+    #[serde(with = "gitbutler_serde::object_id")]
+    edited_commit: gix::ObjectId,
+    /// The todos after the edited commit, one per line like in the todo list, but with full commit ids.
+    todos: Vec<String>,
+}
+
+fn stopped_rebase_path(ctx: &CommandContext) -> PathBuf {
+    ctx.project().gb_dir().join("rebase_todo.json")
+}
+
+pub(crate) fn rebase(repo_path: &Path, json: bool, branch: &str) -> anyhow::Result<()> {
+    let project = Project::from_path(repo_path).expect("Failed to create project from path");
+    let ctx = &mut CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+
+    let branch = crate::branch::branch_name_from_id(ctx, branch)?;
+    let stack_id = crate::branch::stack_id_by_branch_name(ctx, &branch)?;
+    let repo = ctx.gix_repo()?;
+    let original = stack_todos(ctx, &repo, stack_id)?;
+    if !original
+        .iter()
+        .any(|todo| matches!(todo, Todo::Pick { .. }))
+    {
+        bail!("There are no commits in the stack of [{branch}] to rebase");
+    }
+
+    let edited = crate::editor::edit_text(
+        &repo,
+        "BUT_REBASE_TODO",
+        &todo_list(&repo, &original, &branch)?,
+    )?;
+    if edited.trim().is_empty() {
+        bail!("Aborting rebase due to an empty todo list");
+    }
+    let todos = parse_todo_list(&edited, &original)?;
+    if todos == original {
+        println!("Nothing to do, the stack of [{branch}] remains unchanged");
+        return Ok(());
+    }
+    run_todos(ctx, json, &branch, stack_id, todos)
+}
+
+/// Leave edit mode after a `but rebase` stopped at a commit for editing, either keeping the changes made
+/// to the worktree by amending them into the commit and continuing with the rest of the todo list,
+/// or discarding them along with the rest of the todo list.
+pub(crate) fn finish_edit(repo_path: &Path, json: bool, abort: bool) -> anyhow::Result<()> {
+    let project = Project::from_path(repo_path).expect("Failed to create project from path");
+    let ctx = &mut CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let stopped = read_stopped_rebase(ctx)?;
+    if abort {
+        gitbutler_edit_mode::commands::abort_and_return_to_workspace(ctx)?;
+        remove_stopped_rebase(ctx)?;
+        println!("Discarded the edits and returned to the workspace");
+        if stopped.is_some_and(|stopped| !stopped.todos.is_empty()) {
+            println!(
+                "The rest of the todo list was dropped, leaving the commits after the edited one as they are"
+            );
+        }
+        return Ok(());
+    }
+
+    gitbutler_edit_mode::commands::save_and_return_to_workspace(ctx)?;
+    remove_stopped_rebase(ctx)?;
+    let Some(stopped) = stopped else {
+        println!("Amended the edited commit and returned to the workspace");
+        return Ok(());
+    };
+    if !json {
+        println!("Amended the edited commit and continuing the rebase");
+    }
+
+    let repo = ctx.gix_repo()?;
+    let mut todos = stack_todos(ctx, &repo, stopped.stack_id)?;
+    let references: HashMap<String, but_core::Reference> = todos
+        .iter()
+        .filter_map(|todo| match todo {
+            Todo::Reference(reference) => Some((reference.to_string(), reference.clone())),
+            _ => None,
+        })
+        .collect();
+    let mut remaining = stopped
+        .todos
+        .iter()
+        .map(|line| Todo::from_line(line, &references))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    // The commits of the remaining todos were kept on top of the edited commit in the order of the todo list,
+    // and were rewritten along with it when the edits were amended.
+    let kept = remaining
+        .iter()
+        .filter(|todo| todo.commit_id().is_some())
+        .count();
+    let mut rewritten = Vec::with_capacity(kept);
+    let edited_idx = todos.iter().rposition(|todo| match todo {
+        Todo::Pick { commit_id, .. } if rewritten.len() < kept => {
+            rewritten.push(*commit_id);
+            false
+        }
+        Todo::Pick { .. } => true,
+        _ => false,
+    });
+    let Some(edited_idx) = edited_idx.filter(|_| rewritten.len() == kept) else {
+        bail!(
+            "The stack of [{}] changed while editing, cannot continue the rebase",
+            stopped.branch
+        );
+    };
+    for (commit_id, rewritten) in remaining
+        .iter_mut()
+        .filter_map(Todo::commit_id_mut)
+        .zip(rewritten.into_iter().rev())
+    {
+        *commit_id = rewritten;
+    }
+    todos.truncate(edited_idx + 1);
+    todos.extend(remaining);
+    run_todos(ctx, json, &stopped.branch, stopped.stack_id, todos)
+}
+
+/// The commits and branches of the stack with `stack_id` as todos, from bottom to top.
+fn stack_todos(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    stack_id: StackId,
+) -> anyhow::Result<Vec<Todo>> {
+    let stack = VirtualBranchesHandle::new(ctx.project().gb_dir()).get_stack(stack_id)?;
+    stack
+        .as_rebase_steps(ctx, repo)?
+        .into_iter()
+        .map(|step| match step {
+            RebaseStep::Pick { commit_id, .. } => Ok(Todo::Pick {
+                commit_id,
+                reword: false,
+            }),
+            RebaseStep::Reference(reference) => Ok(Todo::Reference(reference)),
+            RebaseStep::SquashIntoPreceding { .. }
+            | RebaseStep::Fixup { .. }
+            | RebaseStep::Edit { .. }
+            | RebaseStep::Exec { .. } => {
+                bail!("BUG: a stack only consists of picks and references")
+            }
+        })
+        .collect()
+}
+
+/// Rebase the stack with `stack_id` according to `todos`, stopping at the first commit to edit.
+///
+/// When stopping, the commits of the todos after the edited commit are kept on top of it in the order of
+/// the todo list, and the todos themselves are persisted for `but rebase --continue` to carry them out
+/// once the user is done editing.
+fn run_todos(
+    ctx: &mut CommandContext,
+    json: bool,
+    branch: &str,
+    stack_id: StackId,
+    mut todos: Vec<Todo>,
+) -> anyhow::Result<()> {
+    let repo = ctx.gix_repo()?;
+    let remaining = match todos
+        .iter()
+        .position(|todo| matches!(todo, Todo::Edit { .. }))
+    {
+        Some(idx) => todos.split_off(idx + 1),
+        None => Vec::new(),
+    };
+    let edit = match todos.last() {
+        Some(Todo::Edit { commit_id }) => Some(*commit_id),
+        _ => None,
+    };
+    let mut steps = into_steps(&repo, todos)?;
+    steps.extend(remaining.iter().filter_map(|todo| match todo {
+        Todo::Exec { .. } => None,
+        Todo::Reference(reference) => Some(RebaseStep::Reference(reference.clone())),
+        todo => todo.commit_id().map(|commit_id| RebaseStep::Pick {
+            commit_id,
+            new_message: None,
+            base: None,
+        }),
+    }));
+
+    let output = gitbutler_branch_actions::rebase_stack(ctx, stack_id, steps)?;
+    let edited_commit = edit
+        .map(|old| {
+            output
+                .commit_mapping
+                .iter()
+                .find_map(|(_base, from, to)| (*from == old).then_some(*to))
+                .context("BUG: the commit to edit is always rewritten")
+        })
+        .transpose()?;
+    if let Some(commit_id) = edited_commit {
+        gitbutler_edit_mode::commands::enter_edit_mode(ctx, commit_id.to_git2(), stack_id)?;
+        write_stopped_rebase(
+            ctx,
+            &StoppedRebase {
+                stack_id,
+                branch: branch.to_owned(),
+                edited_commit: commit_id,
+                todos: remaining.iter().map(Todo::to_string).collect(),
+            },
+        )?;
+    }
+    let branches: Vec<_> = output
+        .references
+        .iter()
+        .map(|spec| BranchTip {
+            name: spec.reference.to_string(),
+            commit_id: spec.commit_id.to_string(),
+        })
+        .collect();
+    if json {
+        let outcome = RebaseOutcome {
+            stack_id,
+            top_commit: output.top_commit.to_string(),
+            branches,
+            edited_commit: edited_commit.map(|id| id.to_string()),
+        };
+        println!("{}", serde_json::to_string_pretty(&outcome)?);
+        return Ok(());
+    }
+
+    let id_map = IdMap::new(ctx)?;
+    println!("Rebased the stack of {}", format!("[{branch}]").green());
+    for (spec, tip) in output.references.iter().zip(&branches) {
+        println!(
+            "  {} → {}",
+            format!("[{}]", tip.name).green(),
+            id_map.commit_colored(spec.commit_id)
+        );
+    }
+    if let Some(commit_id) = edited_commit {
+        println!(
+            "Stopped at {} for editing. Amend the worktree as needed, then run `but rebase --continue`, or `but rebase --abort` to discard the edits along with the rest of the todo list.",
+            id_map.commit_colored(commit_id)
+        );
+    }
+    Ok(())
+}
+
+/// Read the rest of the todo list of the `but rebase` that stopped at the commit currently being edited, if any.
+fn read_stopped_rebase(ctx: &CommandContext) -> anyhow::Result<Option<StoppedRebase>> {
+    let path = stopped_rebase_path(ctx);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let stopped: StoppedRebase = serde_json::from_str(&std::fs::read_to_string(&path)?)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    // Edit mode may also have been entered by other means after a rebase that was never continued.
+    let Ok(edit_mode) = gitbutler_operating_modes::read_edit_mode_metadata(ctx) else {
+        return Ok(None);
+    };
+    Ok((edit_mode.commit_oid.to_gix() == stopped.edited_commit).then_some(stopped))
+}
+
+fn write_stopped_rebase(ctx: &CommandContext, stopped: &StoppedRebase) -> anyhow::Result<()> {
+    gitbutler_fs::write(
+        stopped_rebase_path(ctx),
+        serde_json::to_string_pretty(stopped)?,
+    )
+    .context("Failed to persist the rest of the todo list")
+}
+
+fn remove_stopped_rebase(ctx: &CommandContext) -> anyhow::Result<()> {
+    match std::fs::remove_file(stopped_rebase_path(ctx)) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Render `todos` into a todo list for the user to edit, with the oldest commit at the top.
+fn todo_list(repo: &gix::Repository, todos: &[Todo], branch: &str) -> anyhow::Result<String> {
+    let mut out = String::new();
+    for todo in todos {
+        match todo {
+            Todo::Pick { commit_id, .. } => {
+                let commit = repo.find_commit(*commit_id)?;
+                out.push_str(&format!(
+                    "pick {} {}\n",
+                    commit_id.to_hex_with_len(7),
+                    commit.message()?.summary()
+                ));
+            }
+            Todo::Squash { .. } | Todo::Fixup { .. } | Todo::Edit { .. } | Todo::Exec { .. } => {
+                unreachable!("the original stack only contains picks and references")
+            }
+            Todo::Reference(reference) => out.push_str(&format!("ref {reference}\n")),
+        }
+    }
+    out.push_str(&format!(
+        "\n# Rebase the stack of [{branch}], with the oldest commit at the top.\n\
+         #\n\
+         # Commands:\n\
+         # p, pick <commit> = use commit\n\
+         # r, reword <commit> = use commit, but edit the commit message\n\
+         # s, squash <commit> = use commit, but meld it into the previous commit\n\
+         # f, fixup <commit> = like \"squash\", but keep only the previous commit's message\n\
+         # e, edit <commit> = use commit, but stop for amending it in edit mode\n\
+         # x, exec <command> = run command (the rest of the line) on the commit above, abort if it fails\n\
+         # d, drop <commit> = remove commit\n\
+         # ref <branch> = point the branch to the commit above this line\n\
+         #\n\
+         # Lines can be re-ordered, and removing a line drops its commit.\n\
+         # Each branch of the stack must be referenced exactly once, and the last line must be a `ref`.\n\
+         # An empty list aborts the rebase.\n"
+    ));
+    Ok(out)
+}
+
+/// Parse the todo list in `text` as edited by the user, validating it against the `original` todos of the stack.
+fn parse_todo_list(text: &str, original: &[Todo]) -> anyhow::Result<Vec<Todo>> {
+    let commits: Vec<gix::ObjectId> = original
+        .iter()
+        .filter_map(|todo| match todo {
+            Todo::Pick { commit_id, .. } => Some(*commit_id),
+            _ => None,
+        })
+        .collect();
+    let references: HashMap<String, &but_core::Reference> = original
+        .iter()
+        .filter_map(|todo| match todo {
+            Todo::Reference(reference) => Some((reference.to_string(), reference)),
+            _ => None,
+        })
+        .collect();
+
+    let mut todos = Vec::new();
+    let mut seen_commits = Vec::new();
+    let mut seen_references = Vec::new();
+    for (line_number, line) in text.lines().enumerate().map(|(idx, line)| (idx + 1, line)) {
+        let mut tokens = line.split_whitespace();
+        let Some(command) = tokens.next() else {
+            continue;
+        };
+        let argument = tokens
+            .next()
+            .with_context(|| format!("Line {line_number}: '{command}' needs an argument"))?;
+        let todo = match command {
+            "x" | "exec" => {
+                if todos.is_empty() {
+                    bail!("Line {line_number}: cannot execute a command without a previous commit");
+                }
+                Todo::Exec {
+                    command: line.trim_start()[command.len()..].trim().to_owned(),
+                }
+            }
+            "ref" => {
+                let reference = references.get(argument).with_context(|| {
+                    format!("Line {line_number}: '{argument}' isn't a branch of this stack")
+                })?;
+                if seen_references.contains(&argument) {
+                    bail!("Line {line_number}: branch '{argument}' is referenced more than once");
+                }
+                seen_references.push(argument);
+                Todo::Reference((*reference).clone())
+            }
+            "p" | "pick" | "r" | "reword" | "s" | "squash" | "f" | "fixup" | "e" | "edit" | "d"
+            | "drop" => {
+                let commit_id = resolve_commit(&commits, argument)
+                    .with_context(|| format!("Line {line_number}: invalid commit"))?;
+                if seen_commits.contains(&commit_id) {
+                    bail!("Line {line_number}: commit {argument} is used more than once");
+                }
+                seen_commits.push(commit_id);
+                match command {
+                    "p" | "pick" => Todo::Pick {
+                        commit_id,
+                        reword: false,
+                    },
+                    "r" | "reword" => Todo::Pick {
+                        commit_id,
+                        reword: true,
+                    },
+                    "e" | "edit" => Todo::Edit { commit_id },
+                    "s" | "squash" | "f" | "fixup" => {
+                        match todos.last() {
+                            None => {
+                                bail!("Line {line_number}: cannot squash without a previous commit")
+                            }
+                            Some(Todo::Reference(_)) => bail!(
+                                "Line {line_number}: cannot squash into a commit of another branch, move the `ref` line below it instead"
+                            ),
+                            Some(_) => {}
+                        }
+                        if command.starts_with('s') {
+                            Todo::Squash { commit_id }
+                        } else {
+                            Todo::Fixup { commit_id }
+                        }
+                    }
+                    _ => continue,
+                }
+            }
+            unknown => bail!("Line {line_number}: unknown command '{unknown}'"),
+        };
+        todos.push(todo);
+    }
+
+    if let Some(missing) = references
+        .keys()
+        .find(|name| !seen_references.contains(&name.as_str()))
+    {
+        bail!(
+            "Branch '{missing}' is missing from the todo list, use `but branch delete` to remove branches"
+        );
+    }
+    if !matches!(todos.last(), Some(Todo::Reference(_))) {
+        bail!(
+            "The last line must be a `ref`, or the commits after it wouldn't belong to any branch"
+        );
+    }
+    Ok(todos)
+}
+
+/// Find the single commit in `commits` whose hex-encoded id starts with `prefix`.
+fn resolve_commit(commits: &[gix::ObjectId], prefix: &str) -> anyhow::Result<gix::ObjectId> {
+    let matches: Vec<_> = commits
+        .iter()
+        .filter(|id| id.to_string().starts_with(prefix))
+        .collect();
+    match matches.as_slice() {
+        [id] => Ok(**id),
+        [] => bail!("'{prefix}' isn't a commit of this stack"),
+        _ => bail!("'{prefix}' is ambiguous, use more characters of the commit hash"),
+    }
+}
+
+/// Turn `todos` into rebase steps, opening the editor for each commit that is reworded, and once for
+/// each run of squashes to edit the message of the combined commit.
+fn into_steps(repo: &gix::Repository, todos: Vec<Todo>) -> anyhow::Result<Vec<RebaseStep>> {
+    let message_of = |commit_id: gix::ObjectId| -> anyhow::Result<BString> {
+        Ok(repo.find_commit(commit_id)?.message_raw()?.to_owned())
+    };
+    let mut steps = Vec::with_capacity(todos.len());
+    // The messages of the commit that is squashed into, followed by those of all commits squashed into it.
+    let mut squashed_messages: Vec<BString> = Vec::new();
+    for todo in todos {
+        if !matches!(
+            todo,
+            Todo::Squash { .. } | Todo::Fixup { .. } | Todo::Exec { .. }
+        ) {
+            finish_squash(repo, &mut steps, std::mem::take(&mut squashed_messages))?;
+        }
+        match todo {
+            Todo::Pick { commit_id, reword } => {
+                let new_message = reword
+                    .then(|| edit_message(repo, &message_of(commit_id)?))
+                    .transpose()?;
+                squashed_messages.push(match &new_message {
+                    Some(message) => message.clone(),
+                    None => message_of(commit_id)?,
+                });
+                steps.push(RebaseStep::Pick {
+                    commit_id,
+                    new_message,
+                    base: None,
+                });
+            }
+            Todo::Edit { commit_id } => {
+                squashed_messages.push(message_of(commit_id)?);
+                steps.push(RebaseStep::Edit { commit_id });
+            }
+            Todo::Squash { commit_id } => {
+                squashed_messages.push(message_of(commit_id)?);
+                steps.push(RebaseStep::SquashIntoPreceding {
+                    commit_id,
+                    new_message: None,
+                });
+            }
+            // Within a run of squashes, the message of the combined commit is set by the last squash,
+            // so fixups only need to keep their message out of it.
+            Todo::Fixup { commit_id } if squashed_messages.len() > 1 => {
+                steps.push(RebaseStep::SquashIntoPreceding {
+                    commit_id,
+                    new_message: None,
+                });
+            }
+            Todo::Fixup { commit_id } => steps.push(RebaseStep::Fixup { commit_id }),
+            Todo::Exec { command } => steps.push(RebaseStep::Exec {
+                command: command.into(),
+            }),
+            Todo::Reference(reference) => steps.push(RebaseStep::Reference(reference)),
+        }
+    }
+    finish_squash(repo, &mut steps, squashed_messages)?;
+    Ok(steps)
+}
+
+/// If `messages` belong to more than one commit, let the user combine them and set the result
+/// as the message of the final squash.
+fn finish_squash(
+    repo: &gix::Repository,
+    steps: &mut [RebaseStep],
+    messages: Vec<BString>,
+) -> anyhow::Result<()> {
+    if messages.len() < 2 {
+        return Ok(());
+    }
+    let mut template = format!("# This is a combination of {} commits.\n", messages.len());
+    for (idx, message) in messages.iter().enumerate() {
+        template.push_str(&format!(
+            "# This is commit message #{}:\n\n{}\n",
+            idx + 1,
+            message.to_str_lossy().trim_end()
+        ));
+    }
+    let combined = edit_message(repo, template.as_bytes().as_bstr())?;
+    if let Some(new_message) = steps.iter_mut().rev().find_map(|step| match step {
+        RebaseStep::SquashIntoPreceding { new_message, .. } => Some(new_message),
+        _ => None,
+    }) {
+        *new_message = Some(combined);
+    }
+    Ok(())
+}
+
+fn edit_message(repo: &gix::Repository, initial: &bstr::BStr) -> anyhow::Result<BString> {
+    let template = format!(
+        "{}\n\n# Please enter the commit message for your changes. Lines starting\n\
+         # with '#' will be ignored, and an empty message aborts the rebase.\n",
+        initial.to_str_lossy().trim_end()
+    );
+    let message = crate::editor::edit_text(repo, "COMMIT_EDITMSG", &template)?;
+    if message.trim().is_empty() {
+        bail!("Aborting rebase due to empty commit message");
+    }
+    Ok(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn todos_round_trip_through_their_lines() -> anyhow::Result<()> {
+        let commit_id = gix::ObjectId::from_hex(b"6c8d2e01a3b9f00f5e0d5b7e4cfd3a1c0a9b8c7d")?;
+        let reference = but_core::Reference::Virtual("feature".into());
+        let references = HashMap::from([(reference.to_string(), reference.clone())]);
+        let todos = [
+            Todo::Pick {
+                commit_id,
+                reword: false,
+            },
+            Todo::Pick {
+                commit_id,
+                reword: true,
+            },
+            Todo::Squash { commit_id },
+            Todo::Fixup { commit_id },
+            Todo::Edit { commit_id },
+            Todo::Exec {
+                command: "cargo test --workspace".into(),
+            },
+            Todo::Reference(reference),
+        ];
+        for todo in todos {
+            assert_eq!(Todo::from_line(&todo.to_string(), &references)?, todo);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn branches_that_left_the_stack_are_rejected() {
+        let err = Todo::from_line("ref feature", &HashMap::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Branch 'feature' isn't part of the stack anymore"
+        );
+    }
+}
@@ -9,6 +9,7 @@ use gitbutler_project::access::WorktreeWritePermission;
 use gix::ObjectId;
 
 use super::assign::branch_name_to_stack_id;
+use crate::id::IdMap;
 
 pub(crate) fn file_to_commit(
     ctx: &mut CommandContext,
@@ -23,13 +24,10 @@ pub(crate) fn file_to_commit(
         .collect();
 
     let mut guard = ctx.project().exclusive_worktree_access();
-    let new_commit = amend_diff_specs(ctx, diff_specs, stack_id, *oid, guard.write_permission())?
-        .new_commit
-        .map(|c| {
-            let s = c.to_string();
-            format!("{}{}", s[..2].blue().underline(), s[2..7].blue())
-        })
-        .unwrap_or_default();
+    let new_commit =
+        amend_diff_specs(ctx, diff_specs, stack_id, *oid, guard.write_permission())?.new_commit;
+    drop(guard);
+    let new_commit = new_commit_colored(ctx, new_commit)?;
     println!("Amended {} → {}", path.bold(), new_commit);
     Ok(())
 }
@@ -46,13 +44,10 @@ pub(crate) fn assignments_to_commit(
         .map(|assignment| assignment.into())
         .collect();
     let mut guard = ctx.project().exclusive_worktree_access();
-    let new_commit = amend_diff_specs(ctx, diff_specs, stack_id, *oid, guard.write_permission())?
-        .new_commit
-        .map(|c| {
-            let s = c.to_string();
-            format!("{}{}", s[..2].blue().underline(), s[2..7].blue())
-        })
-        .unwrap_or_default();
+    let new_commit =
+        amend_diff_specs(ctx, diff_specs, stack_id, *oid, guard.write_permission())?.new_commit;
+    drop(guard);
+    let new_commit = new_commit_colored(ctx, new_commit)?;
 
     if let Some(branch_name) = branch_name {
         println!(
@@ -66,6 +61,16 @@ pub(crate) fn assignments_to_commit(
     Ok(())
 }
 
+fn new_commit_colored(
+    ctx: &mut CommandContext,
+    new_commit: Option<ObjectId>,
+) -> anyhow::Result<String> {
+    Ok(match new_commit {
+        Some(id) => IdMap::new(ctx)?.commit_colored(id),
+        None => String::new(),
+    })
+}
+
 fn wt_assignments(ctx: &mut CommandContext) -> anyhow::Result<Vec<HunkAssignment>> {
     let changes =
         but_core::diff::ui::worktree_changes_by_worktree_dir(ctx.project().path.clone())?.changes;
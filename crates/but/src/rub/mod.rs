@@ -11,7 +11,7 @@ mod move_commit;
 mod squash;
 mod undo;
 
-use crate::id::CliId;
+use crate::id::{CliId, IdMap};
 
 pub(crate) fn handle(
     repo_path: &Path,
@@ -21,11 +21,13 @@ pub(crate) fn handle(
 ) -> anyhow::Result<()> {
     let project = Project::from_path(repo_path).expect("Failed to create project from path");
     let ctx = &mut CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
-    let (source, target) = ids(ctx, source_str, target_str)?;
+    let id_map = IdMap::new(ctx)?;
+    let source = id_map.resolve(source_str, "Source")?;
+    let target = id_map.resolve(target_str, "Target")?;
 
     match (&source, &target) {
         (CliId::UncommittedFile { .. }, CliId::UncommittedFile { .. }) => {
-            bail!(makes_no_sense_error(&source, &target))
+            bail!(makes_no_sense_error(&id_map, &source, &target))
         }
         (CliId::UncommittedFile { path, .. }, CliId::Unassigned) => {
             assign::unassign_file(ctx, path)
@@ -37,15 +39,15 @@ pub(crate) fn handle(
             assign::assign_file_to_branch(ctx, path, name)
         }
         (CliId::Unassigned, CliId::UncommittedFile { .. }) => {
-            bail!(makes_no_sense_error(&source, &target))
+            bail!(makes_no_sense_error(&id_map, &source, &target))
         }
         (CliId::Unassigned, CliId::Unassigned) => {
-            bail!(makes_no_sense_error(&source, &target))
+            bail!(makes_no_sense_error(&id_map, &source, &target))
         }
         (CliId::Unassigned, CliId::Commit { oid }) => amend::assignments_to_commit(ctx, None, oid),
         (CliId::Unassigned, CliId::Branch { name: to }) => assign::assign_all(ctx, None, Some(to)),
         (CliId::Commit { .. }, CliId::UncommittedFile { .. }) => {
-            bail!(makes_no_sense_error(&source, &target))
+            bail!(makes_no_sense_error(&id_map, &source, &target))
         }
         (CliId::Commit { oid }, CliId::Unassigned) => undo::commit(ctx, oid),
         (CliId::Commit { oid: source }, CliId::Commit { oid: destination }) => {
@@ -53,7 +55,7 @@ pub(crate) fn handle(
         }
         (CliId::Commit { oid }, CliId::Branch { name }) => move_commit::to_branch(ctx, oid, name),
         (CliId::Branch { .. }, CliId::UncommittedFile { .. }) => {
-            bail!(makes_no_sense_error(&source, &target))
+            bail!(makes_no_sense_error(&id_map, &source, &target))
         }
         (CliId::Branch { name: from }, CliId::Unassigned) => {
             assign::assign_all(ctx, Some(from), None)
@@ -67,32 +69,12 @@ pub(crate) fn handle(
     }
 }
 
-fn makes_no_sense_error(source: &CliId, target: &CliId) -> String {
+fn makes_no_sense_error(id_map: &IdMap, source: &CliId, target: &CliId) -> String {
     format!(
         "Operation doesn't make sense. Source {} is {} and target {} is {}.",
-        source.to_string().blue().underline(),
+        id_map.short_id(source).blue().underline(),
         source.kind().yellow(),
-        target.to_string().blue().underline(),
+        id_map.short_id(target).blue().underline(),
         target.kind().yellow()
     )
 }
-
-fn ids(ctx: &mut CommandContext, source: &str, target: &str) -> anyhow::Result<(CliId, CliId)> {
-    let source_result = crate::id::CliId::from_str(ctx, source)?;
-    if source_result.len() != 1 {
-        return Err(anyhow::anyhow!(
-            "Source {} is ambiguous: {:?}",
-            source,
-            source_result
-        ));
-    }
-    let target_result = crate::id::CliId::from_str(ctx, target)?;
-    if target_result.len() != 1 {
-        return Err(anyhow::anyhow!(
-            "Target {} is ambiguous: {:?}",
-            target,
-            target_result
-        ));
-    }
-    Ok((source_result[0].clone(), target_result[0].clone()))
-}
@@ -0,0 +1,199 @@
+use std::path::Path;
+
+use anyhow::bail;
+use but_rules::{
+    Action, CreateRuleRequest, Filter, RuleEvaluation, RuleSource, Trigger, UpdateRuleRequest,
+    WorkspaceRule, handler::RuleOutcome,
+};
+use but_settings::AppSettings;
+use colored::Colorize;
+use gitbutler_command_context::CommandContext;
+use gitbutler_project::Project;
+
+use crate::args::rules::Subcommands;
+
+/// The amount of characters of a rule id that are shown, which is usually enough to identify it.
+const SHORT_ID_LEN: usize = 8;
+
+pub(crate) fn handle(cmd: &Subcommands, repo_path: &Path, json: bool) -> anyhow::Result<()> {
+    let project = Project::from_path(repo_path).expect("Failed to create project from path");
+    let ctx = &mut CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    match cmd {
+        Subcommands::List => {
+            if let Err(err) = but_rules::file::load(&project.path) {
+                eprintln!("{} {err:#}", "warning:".yellow());
+            }
+            let rules = but_rules::list_rules(ctx)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&rules)?);
+            } else if rules.is_empty() {
+                println!("No rules");
+            } else {
+                for rule in &rules {
+                    print_rule(rule);
+                }
+            }
+            Ok(())
+        }
+        Subcommands::Add {
+            action,
+            filters,
+            trigger,
+        } => {
+            let request = CreateRuleRequest {
+                trigger: trigger.parse()?,
+                filters: filters
+                    .iter()
+                    .map(|f| f.parse())
+                    .collect::<anyhow::Result<Vec<Filter>>>()?,
+                action: action.parse::<Action>()?,
+            };
+            let rule = but_rules::create_rule(ctx, request)?;
+            print_changed_rule(&rule, json, "Added")
+        }
+        Subcommands::Enable { id } => {
+            let rule = set_enabled(ctx, id, true)?;
+            print_changed_rule(&rule, json, "Enabled")
+        }
+        Subcommands::Disable { id } => {
+            let rule = set_enabled(ctx, id, false)?;
+            print_changed_rule(&rule, json, "Disabled")
+        }
+        Subcommands::Test { id, trigger } => {
+            let outcomes = match id {
+                Some(id) => {
+                    let id = rule_id(ctx, id)?;
+                    but_rules::test_rule(ctx, &id)?.into_iter().collect()
+                }
+                None => but_rules::evaluate_rules_for_trigger(ctx, trigger.parse()?, true)?,
+            };
+            if json {
+                println!("{}", serde_json::to_string_pretty(&outcomes)?);
+            } else if outcomes.is_empty() {
+                println!("No rule matches the current changes");
+            } else {
+                for outcome in &outcomes {
+                    print_outcome(outcome);
+                }
+            }
+            Ok(())
+        }
+        Subcommands::Log { id, limit } => {
+            let id = id.as_deref().map(|id| rule_id(ctx, id)).transpose()?;
+            let evaluations = but_rules::list_evaluations(ctx, id.as_deref(), *limit)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&evaluations)?);
+            } else if evaluations.is_empty() {
+                println!("No rule was evaluated yet");
+            } else {
+                for evaluation in &evaluations {
+                    print_evaluation(evaluation);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn set_enabled(ctx: &mut CommandContext, id: &str, enabled: bool) -> anyhow::Result<WorkspaceRule> {
+    let id = rule_id(ctx, id)?;
+    let mut request = UpdateRuleRequest::from(but_rules::get_rule(ctx, &id)?);
+    request.enabled = Some(enabled);
+    but_rules::update_rule(ctx, request)
+}
+
+/// Resolve `input`, which is a rule id or an unambiguous prefix of it, to the full rule id.
+fn rule_id(ctx: &mut CommandContext, input: &str) -> anyhow::Result<String> {
+    let mut ids = but_rules::list_rules(ctx)?
+        .iter()
+        .map(|r| r.id())
+        .filter(|id| id.starts_with(input))
+        .collect::<Vec<_>>();
+    match ids.len() {
+        1 => Ok(ids.remove(0)),
+        0 => bail!("No rule with id '{input}' found"),
+        _ => bail!(
+            "Rule id '{input}' is ambiguous, use a longer id to pick one of:\n{}",
+            ids.join("\n")
+        ),
+    }
+}
+
+fn short_id(id: &str) -> &str {
+    &id[..id.len().min(SHORT_ID_LEN)]
+}
+
+/// Format a rule as `on <trigger>, if <filter> and …, <action>`, using the syntax of `but rules add`.
+fn describe_rule(trigger: &Trigger, filters: &[Filter], action: &Action) -> String {
+    let filters = if filters.is_empty() {
+        "any change".to_owned()
+    } else {
+        filters
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" and ")
+    };
+    format!("on {trigger}, if {filters}, {action}")
+}
+
+fn print_rule(rule: &WorkspaceRule) {
+    let status = if rule.enabled() {
+        "enabled".green()
+    } else {
+        "disabled".dimmed()
+    };
+    let source = match rule.source() {
+        RuleSource::Personal => String::new(),
+        RuleSource::Repository => format!(" {}", "(repository)".dimmed()),
+    };
+    println!(
+        "{} {status} {}{source}",
+        short_id(&rule.id()).blue().underline(),
+        describe_rule(rule.trigger(), rule.filters(), rule.action())
+    );
+}
+
+fn print_changed_rule(rule: &WorkspaceRule, json: bool, action: &str) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(rule)?);
+    } else {
+        print!("{action} ");
+        print_rule(rule);
+    }
+    Ok(())
+}
+
+fn print_outcome(outcome: &RuleOutcome) {
+    println!(
+        "{} {}: {}",
+        short_id(&outcome.rule_id).blue().underline(),
+        outcome.action,
+        outcome.description
+    );
+    if let Some(error) = &outcome.error {
+        println!("  {}", format!("would fail: {error}").red());
+    }
+    for change in &outcome.changes {
+        println!("  {}", change.path);
+    }
+}
+
+fn print_evaluation(evaluation: &RuleEvaluation) {
+    let result = match (&evaluation.error, evaluation.executed) {
+        (Some(error), _) => format!("failed: {error}").red(),
+        (None, true) => "done".green(),
+        (None, false) => "nothing to do".dimmed(),
+    };
+    println!(
+        "{} {} {} {}: {} ({result})",
+        evaluation.created_at.format("%Y-%m-%d %H:%M:%S"),
+        short_id(&evaluation.rule_id).blue().underline(),
+        format!("on {}", evaluation.trigger).dimmed(),
+        evaluation.action,
+        evaluation.description,
+    );
+    if let Some(snapshot_id) = &evaluation.snapshot_id {
+        println!("  snapshot {}", short_id(snapshot_id).blue());
+    }
+}
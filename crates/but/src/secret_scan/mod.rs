@@ -0,0 +1,60 @@
+use std::path::Path;
+
+use anyhow::bail;
+use but_settings::AppSettings;
+use colored::Colorize;
+use gitbutler_branch_actions::secret_scan::{self, SecretFinding};
+use gitbutler_command_context::CommandContext;
+use gitbutler_project::Project;
+
+use crate::branch::{branch_name_from_id, stack_id_by_branch_name};
+
+/// Scan the commits that pushing `branch` would push for secrets, or those of all branches in the workspace,
+/// failing if any were found.
+pub(crate) fn scan(repo_path: &Path, json: bool, branch: Option<&str>) -> anyhow::Result<()> {
+    let project = Project::from_path(repo_path).expect("Failed to create project from path");
+    let ctx = &mut CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let findings = match branch {
+        Some(branch) => {
+            let branch = branch_name_from_id(ctx, branch)?;
+            let stack_id = stack_id_by_branch_name(ctx, &branch)?;
+            secret_scan::scan_stack(ctx, stack_id, Some(&branch))?
+        }
+        None => {
+            let mut findings = Vec::new();
+            for stack_id in crate::log::stacks(ctx)?.iter().filter_map(|s| s.id) {
+                findings.extend(secret_scan::scan_stack(ctx, stack_id, None)?);
+            }
+            findings
+        }
+    };
+    print(&findings, json)?;
+    if !findings.is_empty() {
+        bail!(
+            "{} possible secret(s) would be pushed, see `but scan-secrets --help` for how to allow false positives",
+            findings.len()
+        );
+    }
+    Ok(())
+}
+
+fn print(findings: &[SecretFinding], json: bool) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(findings)?);
+        return Ok(());
+    }
+    if findings.is_empty() {
+        println!("{}", "No secrets found".green());
+    }
+    for finding in findings {
+        println!(
+            "{} {}:{} {} {}",
+            finding.commit_id.to_string()[..7].blue(),
+            finding.path,
+            finding.line,
+            finding.kind.red(),
+            finding.redacted.dimmed()
+        );
+    }
+    Ok(())
+}
@@ -10,7 +10,7 @@ use std::collections::BTreeMap;
 use std::path::Path;
 pub(crate) mod assignment;
 
-use crate::id::CliId;
+use crate::id::{CliId, IdMap};
 
 pub(crate) fn worktree(repo_path: &Path, _json: bool) -> anyhow::Result<()> {
     let project = Project::from_path(repo_path).expect("Failed to create project from path");
@@ -50,30 +50,32 @@ pub(crate) fn worktree(repo_path: &Path, _json: bool) -> anyhow::Result<()> {
         return Ok(());
     }
 
+    let id_map = IdMap::new(ctx)?;
     let unassigned = assignment::filter_by_stack_id(assignments_by_file.values(), &None);
-    print_group(None, unassigned, &changes)?;
+    print_group(&id_map, None, unassigned, &changes)?;
 
     for (stack_id, branch) in &stack_id_to_branch {
         let filtered =
             assignment::filter_by_stack_id(assignments_by_file.values(), &Some(*stack_id));
-        print_group(Some(branch.as_str()), filtered, &changes)?;
+        print_group(&id_map, Some(branch.as_str()), filtered, &changes)?;
     }
     Ok(())
 }
 
 pub fn print_group(
+    id_map: &IdMap,
     group: Option<&str>,
     assignments: Vec<FileAssignment>,
     changes: &[TreeChange],
 ) -> anyhow::Result<()> {
-    let id = if let Some(group) = group {
-        CliId::branch(group)
-    } else {
-        CliId::unassigned()
-    }
-    .to_string()
-    .underline()
-    .blue();
+    let id = id_map
+        .short_id(&if let Some(group) = group {
+            CliId::branch(group)
+        } else {
+            CliId::unassigned()
+        })
+        .underline()
+        .blue();
     let group = &group
         .map(|s| format!("[{s}]"))
         .unwrap_or("<UNASSIGNED>".to_string());
@@ -90,8 +92,8 @@ pub fn print_group(
             None => fa.path.to_string().normal(),
         };
 
-        let id = CliId::file_from_assignment(&fa.assignments[0])
-            .to_string()
+        let id = id_map
+            .short_id(&CliId::file_from_assignment(&fa.assignments[0]))
             .underline()
             .blue();
 
@@ -100,16 +102,10 @@ pub fn print_group(
             .iter()
             .flat_map(|a| a.hunk_locks.iter())
             .flatten()
-            .map(|l| l.commit_id.to_string())
+            .map(|l| l.commit_id)
             .collect::<std::collections::BTreeSet<_>>()
             .into_iter()
-            .map(|commit_id| {
-                format!(
-                    "{}{}",
-                    commit_id[..2].blue().underline(),
-                    commit_id[2..7].blue()
-                )
-            })
+            .map(|commit_id| id_map.commit_colored(commit_id))
             .collect::<Vec<_>>()
             .join(", ");
 
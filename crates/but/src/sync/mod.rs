@@ -0,0 +1,130 @@
+use std::{
+    io::{IsTerminal, Write},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::bail;
+use but_settings::AppSettings;
+use colored::Colorize;
+use gitbutler_branch_actions::{
+    sync::{self, SyncApproach, SyncOptions, SyncReport},
+    upstream_integration::ResolutionApproach,
+};
+use gitbutler_command_context::CommandContext;
+use gitbutler_git::Stage;
+use gitbutler_project::Project;
+use gitbutler_repo_actions::progress::{self, OperationKind, ProgressEvent};
+
+/// Fetch all remotes, update all stacks with the changes of the target branch and push the branches whose remote
+/// is behind, failing if the stacks couldn't be updated without conflicts.
+pub(crate) fn sync(
+    repo_path: &Path,
+    json: bool,
+    approach: Option<SyncApproach>,
+    push: bool,
+) -> anyhow::Result<()> {
+    let project = Project::from_path(repo_path).expect("Failed to create project from path");
+    let mut ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    show_progress_and_cancel_on_interrupt(json);
+    let report = sync::sync(&mut ctx, SyncOptions { approach, push }, |ctx| {
+        if ctx.app_settings().feature_flags.rules {
+            but_rules::process_rules_for_trigger(ctx, but_rules::Trigger::PrePush).ok();
+        }
+        Ok(())
+    });
+    if PROGRESS_SHOWN.load(Ordering::Relaxed) {
+        eprintln!();
+    }
+    let report = report?;
+    print(&report, json)?;
+    if let Some(reason) = report.aborted {
+        bail!("{reason}");
+    }
+    Ok(())
+}
+
+/// Set once a progress line was written to stderr, which then needs to be ended.
+static PROGRESS_SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// Show the progress of fetches and pushes on stderr if it's a terminal, and cancel them on Ctrl-C.
+///
+/// Ctrl-C still exits right away if no fetch or push is running.
+fn show_progress_and_cancel_on_interrupt(json: bool) {
+    if !json && std::io::stderr().is_terminal() {
+        progress::init(|event| {
+            PROGRESS_SHOWN.store(true, Ordering::Relaxed);
+            let mut stderr = std::io::stderr().lock();
+            write!(stderr, "\r\x1b[2K{}", progress_line(&event)).ok();
+            stderr.flush().ok();
+        });
+    }
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if progress::cancel_all() == 0 {
+                std::process::exit(130);
+            }
+            eprintln!("\nCancelling…");
+        }
+    });
+}
+
+fn progress_line(event: &ProgressEvent) -> String {
+    let operation = match &event.kind {
+        OperationKind::Fetch { remote } => format!("Fetching {remote}"),
+        OperationKind::Push { remote, branch } => format!("Pushing {branch} to {remote}"),
+    };
+    let Some(progress) = event.progress else {
+        return format!("{operation}…");
+    };
+    let stage = match progress.stage {
+        Stage::CountingObjects => "counting objects",
+        Stage::CompressingObjects => "compressing objects",
+        Stage::ReceivingObjects => "receiving objects",
+        Stage::ResolvingDeltas => "resolving deltas",
+        Stage::WritingObjects => "writing objects",
+    };
+    match progress.total {
+        Some(total) if total > 0 => format!(
+            "{operation}: {stage} {}% ({}/{total})",
+            progress.done * 100 / total,
+            progress.done
+        ),
+        _ => format!("{operation}: {stage} {}", progress.done),
+    }
+}
+
+fn print(report: &SyncReport, json: bool) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(report)?);
+        return Ok(());
+    }
+    if report.aborted.is_some() {
+        println!("{}", "Nothing was changed".red());
+    } else if report.integrated_upstream {
+        println!("{}", "Integrated the changes of the target branch".green());
+    } else {
+        println!(
+            "{}",
+            "The workspace is up to date with the target branch".green()
+        );
+    }
+    for stack in &report.stacks {
+        let approach = match stack.approach {
+            _ if stack.conflicted => "conflicts".red(),
+            Some(ResolutionApproach::Rebase) => "rebased".green(),
+            Some(ResolutionApproach::Merge) => "merged".green(),
+            Some(ResolutionApproach::Unapply) => "unapplied".yellow(),
+            Some(ResolutionApproach::Delete) => "integrated".green(),
+            None => "up to date".dimmed(),
+        };
+        println!("{} {}", stack.branches.join(" ← ").blue(), approach);
+        for branch in &stack.pushed {
+            println!("  pushed {}", branch.green());
+        }
+        if let Some(err) = &stack.push_error {
+            println!("  {} {}", "push failed:".red(), err);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use but_settings::AppSettings;
+use colored::Colorize;
+use gitbutler_command_context::CommandContext;
+use gitbutler_oplog::{OplogExt, UndoNode};
+use gitbutler_project::Project;
+
+/// Undo the operation that led to the current state of the workspace.
+pub(crate) fn undo(repo_path: &Path, json: bool) -> anyhow::Result<()> {
+    let project = Project::from_path(repo_path).expect("Failed to create project from path");
+    let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let mut guard = project.exclusive_worktree_access();
+    let node = ctx.undo(guard.write_permission())?;
+    print(node.as_ref(), json, "Undid", "Nothing to undo")
+}
+
+/// Redo the most recently undone operation.
+pub(crate) fn redo(repo_path: &Path, json: bool) -> anyhow::Result<()> {
+    let project = Project::from_path(repo_path).expect("Failed to create project from path");
+    let ctx = CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
+    let mut guard = project.exclusive_worktree_access();
+    let node = ctx.redo(guard.write_permission())?;
+    print(node.as_ref(), json, "Redid", "Nothing to redo")
+}
+
+fn print(node: Option<&UndoNode>, json: bool, action: &str, nothing: &str) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&node)?);
+        return Ok(());
+    }
+    match node {
+        Some(node) => println!(
+            "{action} {} {}",
+            node.details
+                .as_ref()
+                .map_or("unknown operation", |details| details.title.as_str())
+                .green(),
+            node.snapshot_id.to_string()[..7].blue()
+        ),
+        None => println!("{nothing}"),
+    }
+    Ok(())
+}
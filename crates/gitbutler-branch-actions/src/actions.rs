@@ -17,6 +17,7 @@ use crate::{
     VirtualBranchesExt,
 };
 use anyhow::{Context, Result};
+use but_rebase::{RebaseOutput, RebaseStep};
 use but_workspace::{commit_engine, stack_heads_info, ui, DiffSpec};
 use gitbutler_branch::{BranchCreateRequest, BranchUpdateRequest};
 use gitbutler_command_context::CommandContext;
@@ -376,6 +377,22 @@ pub fn reorder_stack(
     Ok(())
 }
 
+/// Rebase the stack with `stack_id` according to `steps`, as authored by the user, and return the outcome.
+pub fn rebase_stack(
+    ctx: &CommandContext,
+    stack_id: StackId,
+    steps: Vec<RebaseStep>,
+) -> Result<RebaseOutput> {
+    let mut guard = ctx.project().exclusive_worktree_access();
+    ctx.verify(guard.write_permission())?;
+    ensure_open_workspace_mode(ctx).context("Rebasing a stack requires open workspace mode")?;
+    let _ = ctx.create_snapshot(
+        SnapshotDetails::new(OperationKind::RebaseStack),
+        guard.write_permission(),
+    );
+    reorder::rebase_stack(ctx, stack_id, steps, guard.write_permission())
+}
+
 pub fn find_git_branches(ctx: &CommandContext, branch_name: &str) -> Result<Vec<RemoteBranchData>> {
     remote::find_git_branches(ctx, branch_name)
 }
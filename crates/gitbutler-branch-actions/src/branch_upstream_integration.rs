@@ -151,7 +151,10 @@ pub fn integrate_branch_with_steps(
             RebaseStep::Pick { .. } => {
                 continue;
             }
-            RebaseStep::SquashIntoPreceding { .. } => {
+            RebaseStep::SquashIntoPreceding { .. }
+            | RebaseStep::Fixup { .. }
+            | RebaseStep::Edit { .. }
+            | RebaseStep::Exec { .. } => {
                 continue;
             }
             RebaseStep::Reference(_) => {
@@ -192,6 +195,7 @@ fn integration_steps_to_rebase_steps(
                 rebase_steps.push(RebaseStep::Pick {
                     commit_id: commit_id.to_owned(),
                     new_message: None,
+                    base: None,
                 });
             }
             InteractiveIntegrationStep::PickUpstream {
@@ -201,6 +205,7 @@ fn integration_steps_to_rebase_steps(
                 rebase_steps.push(RebaseStep::Pick {
                     commit_id: upstream_commit.to_owned(),
                     new_message: None,
+                    base: None,
                 });
             }
             InteractiveIntegrationStep::Skip { .. } => {
@@ -225,6 +230,7 @@ fn integration_steps_to_rebase_steps(
                     rebase_steps.push(RebaseStep::Pick {
                         commit_id: last_commit.to_owned(),
                         new_message: None,
+                        base: None,
                     });
                 }
             }
@@ -1,6 +1,9 @@
+use anyhow::{bail, Context};
+use but_workspace::{commit_engine, DiffSpec};
 use gitbutler_command_context::CommandContext;
+use gitbutler_oxidize::ObjectIdExt;
 use gitbutler_repo::{
-    hooks::{self, HookResult},
+    hooks::{self, HookResult, MessageHookResult},
     staging,
 };
 use gitbutler_stack::BranchOwnershipClaims;
@@ -28,3 +31,65 @@ pub fn pre_commit_with_tree(
 ) -> Result<HookResult, anyhow::Error> {
     hooks::pre_commit_with_tree(ctx, tree_id)
 }
+
+/// Run the `pre-commit` hook against the tree that would result from committing `diff_specs`.
+///
+/// The changes are applied onto the tree of `amend`, the commit that is about to be amended, or onto the tree of `HEAD`
+/// if a new commit is created.
+pub fn pre_commit_with_diff_specs(
+    ctx: &CommandContext,
+    amend: Option<gix::ObjectId>,
+    diff_specs: &[DiffSpec],
+) -> Result<HookResult, anyhow::Error> {
+    let repo = ctx.gix_repo()?;
+    let base = match amend {
+        Some(commit_id) => repo
+            .find_commit(commit_id)?
+            .tree_id()
+            .context("Failed to get tree of the commit to amend")?,
+        None => repo
+            .head_tree_id_or_empty()
+            .context("Failed to get head tree")?,
+    };
+    let mut changes = diff_specs.iter().cloned().map(Ok).collect::<Vec<_>>();
+    let (new_tree, ..) = commit_engine::apply_worktree_changes(
+        base.detach(),
+        &repo,
+        &mut changes,
+        ctx.app_settings().context_lines,
+    )?;
+    hooks::pre_commit_with_tree(ctx, new_tree.to_git2())
+}
+
+/// Run the `pre-commit` and `commit-msg` hooks like `git commit` would before committing `diff_specs` with `message`,
+/// either as new commit, or by amending the commit `amend`.
+///
+/// Returns the message to commit with, which the `commit-msg` hook may have changed, or fails if either hook failed.
+/// This is for callers that commit without user interaction, while the UI runs each hook on its own.
+pub fn run_pre_commit_hooks(
+    ctx: &CommandContext,
+    amend: Option<gix::ObjectId>,
+    diff_specs: &[DiffSpec],
+    message: String,
+) -> Result<String, anyhow::Error> {
+    if let HookResult::Failure(error) = pre_commit_with_diff_specs(ctx, amend, diff_specs)? {
+        bail!("The pre-commit hook failed:\n{}", error.error);
+    }
+    Ok(match hooks::commit_msg(ctx, message.clone())? {
+        MessageHookResult::Success | MessageHookResult::NotConfigured => message,
+        MessageHookResult::Message(data) => data.message,
+        MessageHookResult::Failure(error) => {
+            bail!("The commit-msg hook failed:\n{}", error.error)
+        }
+    })
+}
+
+/// Run the `post-commit` hook after a commit was created without user interaction.
+///
+/// As the commit exists already, a failing hook is only logged.
+pub fn run_post_commit_hook(ctx: &CommandContext) -> Result<(), anyhow::Error> {
+    if let HookResult::Failure(error) = hooks::post_commit(ctx)? {
+        tracing::warn!("The post-commit hook failed: {}", error.error);
+    }
+    Ok(())
+}
@@ -53,6 +53,17 @@ fn write_workspace_file(head: &git2::Reference, path: PathBuf) -> Result<()> {
 pub fn update_workspace_commit(
     vb_state: &VirtualBranchesHandle,
     ctx: &CommandContext,
+) -> Result<git2::Oid> {
+    update_workspace_commit_to(vb_state, ctx, None)
+}
+
+/// Like [`update_workspace_commit()`], but take the tree and parents of the new workspace commit from
+/// `workspace_merge` if set, a merge of all stacks in the workspace, instead of re-merging them.
+#[instrument(level = tracing::Level::DEBUG, skip(vb_state, ctx), err(Debug))]
+pub(crate) fn update_workspace_commit_to(
+    vb_state: &VirtualBranchesHandle,
+    ctx: &CommandContext,
+    workspace_merge: Option<git2::Oid>,
 ) -> Result<git2::Oid> {
     let target = vb_state
         .get_default_target()
@@ -84,7 +95,10 @@ pub fn update_workspace_commit(
         .list_stacks_in_workspace()
         .context("failed to list virtual branches")?;
 
-    let workspace_head = repo.find_commit(but_workspace::remerged_workspace_commit_v2(ctx)?)?;
+    let workspace_head = match workspace_merge {
+        Some(workspace_merge) => repo.find_commit(workspace_merge)?,
+        None => repo.find_commit(but_workspace::remerged_workspace_commit_v2(ctx)?)?,
+    };
 
     // message that says how to get back to where they were
     let mut message = GITBUTLER_WORKSPACE_COMMIT_TITLE.to_string();
@@ -161,6 +161,7 @@ fn take_commit_from_source_stack(
             RebaseStep::Pick {
                 commit_id,
                 new_message: _,
+                base: _,
             } => commit_id != &subject_commit.id().to_gix(),
             _ => true,
         })
@@ -193,6 +194,7 @@ fn move_commit_to_destination_stack(
         RebaseStep::Pick {
             commit_id: commit_id.to_gix(),
             new_message: None,
+            base: None,
         },
     );
     let mut rebase = but_rebase::Rebase::new(&gix_repo, Some(merge_base), None)?;
@@ -29,35 +29,51 @@ pub fn reorder_stack(
     new_order: StackOrder,
     perm: &mut WorktreeWritePermission,
 ) -> Result<RebaseOutput> {
-    let old_workspace = WorkspaceState::create(ctx, perm.read_permission())?;
-    let state = ctx.project().virtual_branches();
-    let repo = ctx.repo();
-    let mut stack = state.get_stack(stack_id)?;
+    let stack = ctx.project().virtual_branches().get_stack(stack_id)?;
     let current_order = commits_order(ctx, &stack)?;
     new_order.validate(current_order.clone())?;
 
-    let gix_repo = ctx.gix_repo()?;
-    let default_target = state.get_default_target()?;
-    let default_target_commit = repo
-        .find_reference(&default_target.branch.to_string())?
-        .peel_to_commit()?;
-    let merge_base = repo.merge_base(
-        default_target_commit.id(),
-        stack.head_oid(&gix_repo)?.to_git2(),
-    )?;
-
     let mut steps: Vec<RebaseStep> = Vec::new();
     for series in new_order.series.iter().rev() {
         for oid in series.commit_ids.iter().rev() {
             steps.push(RebaseStep::Pick {
                 commit_id: oid.to_gix(),
                 new_message: None,
+                base: None,
             });
         }
         steps.push(RebaseStep::Reference(but_core::Reference::Virtual(
             series.name.clone(),
         )));
     }
+    rebase_stack(ctx, stack_id, steps, perm)
+}
+
+/// Rebase the stack with `stack_id` onto the merge-base of its head and the default target, according to `steps`.
+/// `steps` are ordered from oldest to newest and must contain a [`RebaseStep::Reference`] for each of the branches of the stack.
+///
+/// The branches of the stack are updated to point to the rewritten commits, along with the workspace commit.
+pub fn rebase_stack(
+    ctx: &CommandContext,
+    stack_id: StackId,
+    steps: Vec<RebaseStep>,
+    perm: &mut WorktreeWritePermission,
+) -> Result<RebaseOutput> {
+    let old_workspace = WorkspaceState::create(ctx, perm.read_permission())?;
+    let state = ctx.project().virtual_branches();
+    let repo = ctx.repo();
+    let mut stack = state.get_stack(stack_id)?;
+
+    let gix_repo = ctx.gix_repo()?;
+    let default_target = state.get_default_target()?;
+    let default_target_commit = repo
+        .find_reference(&default_target.branch.to_string())?
+        .peel_to_commit()?;
+    let merge_base = repo.merge_base(
+        default_target_commit.id(),
+        stack.head_oid(&gix_repo)?.to_git2(),
+    )?;
+
     let mut builder = but_rebase::Rebase::new(&gix_repo, merge_base.to_gix(), None)?;
     let builder = builder.steps(steps)?;
     builder.rebase_noops(false);
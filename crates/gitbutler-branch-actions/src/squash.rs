@@ -230,6 +230,7 @@ fn do_squash_commits(
             steps.push(RebaseStep::Pick {
                 commit_id: new_commit_oid.to_gix(),
                 new_message: None,
+                base: None,
             });
         } else if source_ids.contains(oid) {
             // noop - skipping this
@@ -237,6 +238,7 @@ fn do_squash_commits(
             steps.push(RebaseStep::Pick {
                 commit_id: oid.to_gix(),
                 new_message: None,
+                base: None,
             });
         }
         for head in stack.heads_by_commit(commit, &gix_repo) {
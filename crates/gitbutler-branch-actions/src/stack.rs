@@ -12,6 +12,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::actions::Verify;
 use crate::r#virtual::PushResult;
+use crate::secret_scan;
 use crate::{r#virtual::IsCommitIntegrated, VirtualBranchesExt};
 use gitbutler_operating_modes::ensure_open_workspace_mode;
 
@@ -192,6 +193,7 @@ pub fn push_stack(
 
     let force_push_protection = !skip_force_push_protection && ctx.project().force_push_protection;
 
+    let mut to_push = Vec::new();
     for branch in stack_branches {
         if branch.archived {
             // Nothing to push for this one
@@ -206,30 +208,44 @@ pub fn push_stack(
             continue;
         }
         let push_details = stack.push_details(ctx, branch.name().to_owned())?;
+        secret_scan::ensure_no_secrets(ctx, &push_details, default_target.sha)?;
+        let is_limit = branch.name().eq(&branch_limit);
+        to_push.push((branch, push_details));
+        if is_limit {
+            break;
+        }
+    }
 
-        if run_hooks {
-            let remote_name = default_target.push_remote_name();
-            let remote = ctx.repo().find_remote(&remote_name)?;
-            let url = &remote
-                .url()
-                .with_context(|| format!("Remote named {remote_name} didn't have a URL"))?;
-            match hooks::pre_push(
-                ctx.repo(),
-                &remote_name,
-                url,
-                push_details.head,
-                &push_details.remote_refname,
-            )? {
-                hooks::HookResult::Success | hooks::HookResult::NotConfigured => {}
-                hooks::HookResult::Failure(error_data) => {
-                    return Err(anyhow::anyhow!(
-                        "pre-push hook failed: {}",
-                        error_data.error
-                    ));
-                }
+    // Like Git, run the hook once with all ref updates, before anything is pushed.
+    if run_hooks && !to_push.is_empty() {
+        let remote_name = default_target.push_remote_name();
+        let remote = ctx.repo().find_remote(&remote_name)?;
+        let url = &remote
+            .url()
+            .with_context(|| format!("Remote named {remote_name} didn't have a URL"))?;
+        let updates: Vec<_> = to_push
+            .iter()
+            .map(|(branch, push_details)| {
+                hooks::PushRefUpdate::new(
+                    ctx.repo(),
+                    branch.name(),
+                    push_details.head,
+                    &push_details.remote_refname,
+                )
+            })
+            .collect();
+        match hooks::pre_push(ctx.repo(), &remote_name, url, &updates)? {
+            hooks::HookResult::Success(_) | hooks::HookResult::NotConfigured => {}
+            hooks::HookResult::Failure(error_data) => {
+                return Err(anyhow::anyhow!(
+                    "pre-push hook failed: {}",
+                    error_data.error
+                ));
             }
         }
+    }
 
+    for (branch, push_details) in to_push {
         ctx.push(
             push_details.head,
             &push_details.remote_refname,
@@ -243,10 +259,6 @@ pub fn push_stack(
             branch.name().to_owned(),
             push_details.remote_refname.to_owned().into(),
         ));
-
-        if branch.name().eq(&branch_limit) {
-            break;
-        }
     }
 
     Ok(result)
@@ -0,0 +1,380 @@
+//! Bring the whole workspace up to date in one step: fetch all remotes, integrate the upstream changes
+//! into all applied stacks, and push the branches whose remote is behind.
+//!
+//! How each stack is updated is decided by a policy in [`POLICY_PATH`], which looks like this:
+//!
+//! ```toml
+//! # How stacks are updated: "rebase", "merge" or "unapply".
+//! default = "rebase"
+//! # Stacks with a branch whose name matches the regular expression are updated differently.
+//! [[overrides]]
+//! branch = "^release/"
+//! approach = "merge"
+//! ```
+//!
+//! If updating any stack leads to conflicts, the workspace is restored from the oplog snapshot taken
+//! right before, so either all stacks are updated, or none of them.
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use gitbutler_command_context::CommandContext;
+use gitbutler_commit::commit_ext::CommitExt as _;
+use gitbutler_operating_modes::ensure_open_workspace_mode;
+use gitbutler_oplog::{
+    entry::{OperationKind, SnapshotDetails},
+    OplogExt,
+};
+use gitbutler_oxidize::ObjectIdExt as _;
+use gitbutler_project::FetchResult;
+use gitbutler_stack::{Stack, StackId};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Verify;
+use crate::upstream_integration::{
+    self, Resolution, ResolutionApproach, StackStatuses, UpstreamIntegrationContext,
+};
+use crate::VirtualBranchesExt;
+
+/// The path of the sync policy, relative to the root of the worktree.
+pub const POLICY_PATH: &str = ".gitbutler/sync.toml";
+
+/// How a stack is updated with the changes of the target branch.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncApproach {
+    /// Rebase the commits of the stack onto the target.
+    #[default]
+    Rebase,
+    /// Merge the target into the stack. Stacks with more than one branch are rebased instead.
+    Merge,
+    /// Unapply the stack from the workspace.
+    Unapply,
+}
+
+impl From<SyncApproach> for ResolutionApproach {
+    fn from(value: SyncApproach) -> Self {
+        match value {
+            SyncApproach::Rebase => ResolutionApproach::Rebase,
+            SyncApproach::Merge => ResolutionApproach::Merge,
+            SyncApproach::Unapply => ResolutionApproach::Unapply,
+        }
+    }
+}
+
+/// How to update stacks, as read from [`POLICY_PATH`].
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyncPolicy {
+    /// The approach for stacks without an override.
+    #[serde(default)]
+    pub default: SyncApproach,
+    /// Approaches for specific stacks, of which the first one that matches is used.
+    #[serde(default)]
+    pub overrides: Vec<PolicyOverride>,
+}
+
+/// The approach for all stacks that contain a branch whose name matches `branch`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyOverride {
+    /// A regular expression matched against the short names of the branches of a stack.
+    #[serde(with = "serde_regex")]
+    pub branch: Regex,
+    /// The approach for matching stacks.
+    pub approach: SyncApproach,
+}
+
+impl SyncPolicy {
+    /// Read the policy from the worktree at `worktree_dir`, or return the default policy if there is none.
+    pub fn load(worktree_dir: &Path) -> Result<Self> {
+        let path = worktree_dir.join(POLICY_PATH);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content)
+                .with_context(|| format!("Invalid sync policy in {POLICY_PATH}")),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("Could not read {}", path.display())),
+        }
+    }
+
+    /// Return the approach for a stack with the given `branches`, using `default` if there is no matching override
+    /// and it is set.
+    pub fn approach_for(&self, branches: &[String], default: Option<SyncApproach>) -> SyncApproach {
+        self.overrides
+            .iter()
+            .find(|o| branches.iter().any(|name| o.branch.is_match(name)))
+            .map(|o| o.approach)
+            .or(default)
+            .unwrap_or(self.default)
+    }
+}
+
+/// Options for [`sync()`].
+#[derive(Debug, Clone, Copy)]
+pub struct SyncOptions {
+    /// The approach for stacks without an override in the policy, instead of the policy's default.
+    pub approach: Option<SyncApproach>,
+    /// Whether to push branches whose remote is behind after updating.
+    pub push: bool,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions {
+            approach: None,
+            push: true,
+        }
+    }
+}
+
+/// What [`sync()`] did.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    /// Whether the target branch had new changes that were integrated.
+    pub integrated_upstream: bool,
+    /// Why nothing was changed, if that's the case. Branches aren't pushed then either.
+    pub aborted: Option<String>,
+    /// What happened to each stack that was applied at the start.
+    pub stacks: Vec<StackSyncReport>,
+}
+
+/// What [`sync()`] did with a single stack.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackSyncReport {
+    pub stack_id: StackId,
+    /// The short names of the branches in the stack, from the bottom to the top.
+    pub branches: Vec<String>,
+    /// How the stack was updated, or `None` if there was nothing to update.
+    pub approach: Option<ResolutionApproach>,
+    /// Whether updating the stack would have led to conflicts.
+    pub conflicted: bool,
+    /// The branches that were pushed.
+    pub pushed: Vec<String>,
+    /// Why pushing failed, if it did.
+    pub push_error: Option<String>,
+}
+
+/// Fetch all remotes, update all applied stacks with the changes of the target branch as configured in
+/// [`SyncPolicy`], and push all branches whose remote is behind.
+///
+/// Updating is atomic: if any stack would be conflicted afterwards, the workspace is restored from the oplog,
+/// and nothing is pushed. Failing pushes are recorded in the report instead of failing the whole operation.
+///
+/// `before_push` is called once the stacks are updated and before anything is pushed, which is where callers
+/// run their `pre-push` rules. Branches are pushed like [`push_stack()`](crate::stack::push_stack()) pushes them,
+/// so the `pre-push` hook runs and secrets are refused.
+pub fn sync(
+    ctx: &mut CommandContext,
+    options: SyncOptions,
+    before_push: impl FnOnce(&mut CommandContext) -> Result<()>,
+) -> Result<SyncReport> {
+    ensure_open_workspace_mode(ctx).context("Syncing requires open workspace mode")?;
+    if let FetchResult::Error { error, .. } = crate::fetch_from_remotes(ctx, Some("sync".into()))? {
+        bail!("Could not fetch from all remotes:\n{error}");
+    }
+
+    let policy = SyncPolicy::load(&ctx.project().path)?;
+    let state = ctx.project().virtual_branches();
+    let mut report = SyncReport {
+        integrated_upstream: false,
+        aborted: None,
+        stacks: state
+            .list_stacks_in_workspace()?
+            .iter()
+            .map(|stack| StackSyncReport {
+                stack_id: stack.id,
+                branches: branch_names(stack),
+                approach: None,
+                conflicted: false,
+                pushed: vec![],
+                push_error: None,
+            })
+            .collect(),
+    };
+
+    {
+        let mut guard = ctx.project().exclusive_worktree_access();
+        ctx.verify(guard.write_permission())?;
+
+        let statuses = {
+            let gix_repo = ctx.gix_repo()?;
+            let context =
+                UpstreamIntegrationContext::open(ctx, None, guard.write_permission(), &gix_repo)?;
+            upstream_integration::upstream_integration_statuses(&context)?
+        };
+        if let StackStatuses::UpdatesRequired {
+            worktree_conflicts,
+            statuses,
+        } = statuses
+        {
+            if !worktree_conflicts.is_empty() {
+                report.aborted = Some(format!(
+                    "Uncommitted changes would conflict with the target branch in: {}",
+                    worktree_conflicts
+                        .iter()
+                        .map(|path| path.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+                return Ok(report);
+            }
+
+            let mut resolutions = Vec::new();
+            for (stack_id, status) in &statuses {
+                let Some(stack_report) = report.stacks.iter_mut().find(|s| s.stack_id == *stack_id)
+                else {
+                    continue;
+                };
+                let preferred = policy.approach_for(&stack_report.branches, options.approach);
+                let approach = status.approach_or_default(preferred.into());
+                stack_report.approach = Some(approach);
+                resolutions.push(Resolution {
+                    branch_id: *stack_id,
+                    approach,
+                    delete_integrated_branches: false,
+                    force_integrated_branches: vec![],
+                });
+            }
+
+            let conflicts_before = conflicted_commits_by_stack(ctx)?;
+            let snapshot = ctx
+                .create_snapshot(
+                    SnapshotDetails::new(OperationKind::SyncWorkspace),
+                    guard.write_permission(),
+                )
+                .context("Could not create a snapshot to restore if syncing fails")?;
+            if let Err(err) = upstream_integration::integrate_upstream(
+                ctx,
+                &resolutions,
+                None,
+                guard.write_permission(),
+            ) {
+                ctx.restore_snapshot(snapshot, guard.write_permission())?;
+                return Err(err);
+            }
+
+            let mut conflicted = Vec::new();
+            for (stack_id, count) in conflicted_commits_by_stack(ctx)? {
+                let before = conflicts_before
+                    .iter()
+                    .find(|(id, _)| *id == stack_id)
+                    .map_or(0, |(_, count)| *count);
+                if count > before {
+                    conflicted.push(stack_id);
+                }
+            }
+            if !conflicted.is_empty() {
+                ctx.restore_snapshot(snapshot, guard.write_permission())?;
+                let mut names = Vec::new();
+                for stack_report in &mut report.stacks {
+                    if conflicted.contains(&stack_report.stack_id) {
+                        stack_report.conflicted = true;
+                        names.extend(stack_report.branches.last().cloned());
+                    }
+                }
+                report.aborted = Some(format!(
+                    "Updating would lead to conflicts in: {}",
+                    names.join(", ")
+                ));
+                return Ok(report);
+            }
+            report.integrated_upstream = true;
+        }
+    }
+
+    if options.push {
+        before_push(ctx)?;
+        for stack in state.list_stacks_in_workspace()? {
+            let Some(stack_report) = report.stacks.iter_mut().find(|s| s.stack_id == stack.id)
+            else {
+                continue;
+            };
+            let Some((top_branch, with_force)) = branches_to_push(ctx, &stack)? else {
+                continue;
+            };
+            match crate::stack::push_stack(ctx, stack.id, with_force, false, top_branch, true) {
+                Ok(result) => {
+                    stack_report.pushed = result
+                        .branch_to_remote
+                        .into_iter()
+                        .map(|(name, _)| name)
+                        .collect();
+                }
+                Err(err) => stack_report.push_error = Some(format!("{err:#}")),
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn branch_names(stack: &Stack) -> Vec<String> {
+    stack
+        .branches()
+        .into_iter()
+        .filter(|branch| !branch.archived)
+        .map(|branch| branch.name().to_owned())
+        .collect()
+}
+
+/// The amount of conflicted commits in each applied stack.
+fn conflicted_commits_by_stack(ctx: &CommandContext) -> Result<Vec<(StackId, usize)>> {
+    let mut counts = Vec::new();
+    for stack in ctx
+        .project()
+        .virtual_branches()
+        .list_stacks_in_workspace()?
+    {
+        let mut count = 0;
+        for branch in stack.branches() {
+            if branch.archived {
+                continue;
+            }
+            count += branch
+                .commits(ctx, &stack)?
+                .local_commits
+                .iter()
+                .filter(|commit| commit.is_conflicted())
+                .count();
+        }
+        counts.push((stack.id, count));
+    }
+    Ok(counts)
+}
+
+/// Return the topmost branch of `stack` whose remote branch is behind, along with whether
+/// pushing it requires a force-push, or `None` if no remote branch is behind.
+///
+/// Remote branches that are behind because the stack was rebased need to be force-pushed.
+/// Branches that were never pushed are left alone.
+fn branches_to_push(ctx: &CommandContext, stack: &Stack) -> Result<Option<(String, bool)>> {
+    let repo = ctx.repo();
+    let gix_repo = ctx.gix_repo()?;
+    let remote = ctx
+        .project()
+        .virtual_branches()
+        .get_default_target()?
+        .push_remote_name();
+
+    let mut top_branch = None;
+    let mut with_force = false;
+    for branch in stack.branches() {
+        if branch.archived {
+            continue;
+        }
+        let local = branch.head_oid(&gix_repo)?.to_git2();
+        let Ok(remote_ref) = repo.find_reference(&branch.remote_reference(&remote)) else {
+            continue;
+        };
+        let remote_head = remote_ref.peel_to_commit()?.id();
+        // Leave it alone if it's up to date, or if the remote has commits of its own.
+        if remote_head == local || repo.graph_descendant_of(remote_head, local)? {
+            continue;
+        }
+        with_force |= !repo.graph_descendant_of(local, remote_head)?;
+        top_branch = Some(branch.name().to_owned());
+    }
+    Ok(top_branch.map(|name| (name, with_force)))
+}
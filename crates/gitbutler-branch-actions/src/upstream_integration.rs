@@ -2,7 +2,7 @@ use crate::stack::branch_integrated;
 use crate::{r#virtual::IsCommitIntegrated, BranchManagerExt, VirtualBranchesExt as _};
 use anyhow::{anyhow, bail, Context, Result};
 use but_core::Reference;
-use but_rebase::{RebaseOutput, RebaseStep};
+use but_rebase::{RebaseOutput, RebaseStep, ReferenceSpec};
 use but_workspace::ref_info::Options;
 use but_workspace::stack_ext::StackExt;
 use gitbutler_command_context::CommandContext;
@@ -112,13 +112,27 @@ impl StackStatus {
         })
     }
 
-    fn resolution_acceptable(&self, approach: &ResolutionApproach) -> bool {
-        if self.tree_status == TreeStatus::Empty
+    fn is_fully_integrated(&self) -> bool {
+        self.tree_status == TreeStatus::Empty
             && self
                 .branch_statuses
                 .iter()
                 .all(|branch_status| branch_status.status == BranchStatus::Integrated)
-        {
+    }
+
+    /// Return `preferred` if it is acceptable for this stack, or the approach the frontend would default to otherwise.
+    pub(crate) fn approach_or_default(&self, preferred: ResolutionApproach) -> ResolutionApproach {
+        if self.resolution_acceptable(&preferred) {
+            preferred
+        } else if self.is_fully_integrated() {
+            ResolutionApproach::Delete
+        } else {
+            ResolutionApproach::Rebase
+        }
+    }
+
+    fn resolution_acceptable(&self, approach: &ResolutionApproach) -> bool {
+        if self.is_fully_integrated() {
             return matches!(
                 approach,
                 ResolutionApproach::Unapply | ResolutionApproach::Delete
@@ -309,6 +323,7 @@ fn get_stack_status(
             .map(|commit_id| RebaseStep::Pick {
                 commit_id: commit_id.to_gix(),
                 new_message: None,
+                base: None,
             })
             .collect();
         let mut rebase = but_rebase::Rebase::new(gix_repo, Some(rebase_base.to_gix()), None)?;
@@ -490,7 +505,7 @@ pub(crate) fn integrate_upstream(
         }
     }
 
-    let integration_results =
+    let (integration_results, workspace_merge) =
         compute_resolutions(&context, resolutions, base_branch_resolution_approach)?;
 
     {
@@ -580,7 +595,11 @@ pub(crate) fn integrate_upstream(
             update_uncommited_changes(ctx, old_workspace, new_workspace, permission)?;
         }
 
-        crate::integration::update_workspace_commit(&virtual_branches_state, ctx)?;
+        crate::integration::update_workspace_commit_to(
+            &virtual_branches_state,
+            ctx,
+            workspace_merge.map(|id| id.to_git2()),
+        )?;
     }
 
     Ok(IntegrationOutcome {
@@ -623,6 +642,7 @@ pub(crate) fn resolve_upstream_integration(
                 .map(|commit| RebaseStep::Pick {
                     commit_id: commit.to_gix(),
                     new_message: None,
+                    base: None,
                 })
                 .collect::<Vec<_>>();
             let mut rebase =
@@ -637,11 +657,13 @@ pub(crate) fn resolve_upstream_integration(
     }
 }
 
+/// Compute the result of each resolution, along with the workspace commit re-merged on top of the rebased stacks
+/// if it could be re-created in the same rebase.
 fn compute_resolutions(
     context: &UpstreamIntegrationContext,
     resolutions: &[Resolution],
     base_branch_resolution_approach: Option<BaseBranchResolutionApproach>,
-) -> Result<Vec<(StackId, IntegrationResult)>> {
+) -> Result<(Vec<(StackId, IntegrationResult)>, Option<gix::ObjectId>)> {
     let UpstreamIntegrationContext {
         repo,
         new_target,
@@ -650,147 +672,287 @@ fn compute_resolutions(
         ..
     } = context;
 
-    let results = resolutions
-        .iter()
-        .map(|resolution| {
-            let Some(branch_stack) = stacks_in_workspace
-                .iter()
-                .find(|branch| branch.id == resolution.branch_id)
-            else {
-                bail!("Failed to find virtual branch");
-            };
+    // If the base branch needs to resolve its divergence
+    // pick only the commits that are ahead of the old target head
+    let lower_bound = if base_branch_resolution_approach.is_some() {
+        target.sha
+    } else {
+        new_target.id()
+    };
 
-            match resolution.approach {
-                ResolutionApproach::Unapply => {
-                    Ok((branch_stack.id, IntegrationResult::UnapplyBranch))
-                }
-                ResolutionApproach::Delete => {
-                    Ok((branch_stack.id, IntegrationResult::DeleteBranch))
-                }
-                ResolutionApproach::Merge => {
-                    // Make a merge commit on top of the branch commits,
-                    // then rebase the tree ontop of that. If the tree ends
-                    // up conflicted, commit the tree.
-                    let target_commit =
-                        repo.find_commit(branch_stack.head_oid(context.gix_repo)?.to_git2())?;
-                    let top_branch = branch_stack.heads.last().context("top branch not found")?;
-
-                    // These two go into the merge commit message.
-                    let incoming_branch_name = target.branch.fullname();
-                    let target_branch_name = &top_branch.name();
-
-                    let new_head = gitbutler_merge_commits(
-                        repo,
-                        target_commit,
-                        new_target.clone(),
-                        target_branch_name,
-                        &incoming_branch_name,
-                    )?;
-
-                    Ok((
-                        branch_stack.id,
-                        IntegrationResult::UpdatedObjects {
-                            head: new_head.id(),
-                            tree: None,
-                            rebase_output: None,
-                            for_archival: vec![],
-                        },
-                    ))
-                }
-                ResolutionApproach::Rebase => {
-                    let gix_repo = gitbutler_command_context::gix_repo_for_merging(repo.path())?;
-                    let cache = gix_repo.commit_graph_if_enabled()?;
-                    let mut graph = gix_repo.revision_graph(cache.as_ref());
-                    let upstream_commit_oids =
-                        repo.l(new_target.id(), LogUntil::Commit(target.sha), true)?;
-                    let mut check_commit = IsCommitIntegrated::new_basic(
-                        &gix_repo,
-                        repo,
-                        &mut graph,
-                        git2_to_gix_object_id(target.sha),
-                        git2_to_gix_object_id(new_target.tree_id()),
-                        upstream_commit_oids,
-                    );
-
-                    // Rebase the commits, then try rebasing the tree. If
-                    // the tree ends up conflicted, commit the tree.
-
-                    // If the base branch needs to resolve its divergence
-                    // pick only the commits that are ahead of the old target head
-                    let lower_bound = if base_branch_resolution_approach.is_some() {
-                        target.sha
-                    } else {
-                        new_target.id()
-                    };
-
-                    let all_steps = branch_stack.as_rebase_steps(context.ctx, context.gix_repo)?;
-                    let branches_before = as_buckets(all_steps.clone());
-                    // Filter out any integrated commits
-                    let steps = all_steps
-                        .into_iter()
-                        .filter_map(|s| match s {
-                            RebaseStep::Pick {
-                                commit_id,
-                                new_message: _,
-                            } => {
-                                let commit = repo.find_commit(commit_id.to_git2()).ok()?;
-                                let is_integrated = check_commit.is_integrated(&commit).ok()?;
-                                let forced = forced_integrated(
-                                    &resolution.force_integrated_branches,
-                                    &branches_before,
-                                    &commit.id().to_gix(),
-                                );
-                                if is_integrated || forced {
-                                    None
-                                } else {
-                                    Some(s)
-                                }
-                            }
-                            _ => Some(s),
-                        })
-                        .collect::<Vec<_>>();
-
-                    let branches_after = as_buckets(steps.clone());
-
-                    // Branches that used to have commits but now don't are marked for archival
-                    let mut for_archival = vec![];
-                    for (ref_before, steps_before) in branches_before {
-                        if let Some((_, steps_after)) = branches_after
-                            .iter()
-                            .find(|(ref_after, _)| ref_after == &ref_before)
-                        {
-                            // if there were steps before and now there are none, this should be marked for archival
-                            if !steps_before.is_empty() && steps_after.is_empty() {
-                                for_archival.push(ref_before);
+    let mut results = Vec::with_capacity(resolutions.len());
+    // The stacks to rebase, along with their steps and the branches to archive, which are all rebased in one go.
+    let mut to_rebase = Vec::new();
+    for resolution in resolutions {
+        let Some(branch_stack) = stacks_in_workspace
+            .iter()
+            .find(|branch| branch.id == resolution.branch_id)
+        else {
+            bail!("Failed to find virtual branch");
+        };
+
+        match resolution.approach {
+            ResolutionApproach::Unapply => {
+                results.push((branch_stack.id, IntegrationResult::UnapplyBranch))
+            }
+            ResolutionApproach::Delete => {
+                results.push((branch_stack.id, IntegrationResult::DeleteBranch))
+            }
+            ResolutionApproach::Merge => {
+                // Make a merge commit on top of the branch commits,
+                // then rebase the tree ontop of that. If the tree ends
+                // up conflicted, commit the tree.
+                let target_commit =
+                    repo.find_commit(branch_stack.head_oid(context.gix_repo)?.to_git2())?;
+                let top_branch = branch_stack.heads.last().context("top branch not found")?;
+
+                // These two go into the merge commit message.
+                let incoming_branch_name = target.branch.fullname();
+                let target_branch_name = &top_branch.name();
+
+                let new_head = gitbutler_merge_commits(
+                    repo,
+                    target_commit,
+                    new_target.clone(),
+                    target_branch_name,
+                    &incoming_branch_name,
+                )?;
+
+                results.push((
+                    branch_stack.id,
+                    IntegrationResult::UpdatedObjects {
+                        head: new_head.id(),
+                        tree: None,
+                        rebase_output: None,
+                        for_archival: vec![],
+                    },
+                ));
+            }
+            ResolutionApproach::Rebase => {
+                let gix_repo = gitbutler_command_context::gix_repo_for_merging(repo.path())?;
+                let cache = gix_repo.commit_graph_if_enabled()?;
+                let mut graph = gix_repo.revision_graph(cache.as_ref());
+                let upstream_commit_oids =
+                    repo.l(new_target.id(), LogUntil::Commit(target.sha), true)?;
+                let mut check_commit = IsCommitIntegrated::new_basic(
+                    &gix_repo,
+                    repo,
+                    &mut graph,
+                    git2_to_gix_object_id(target.sha),
+                    git2_to_gix_object_id(new_target.tree_id()),
+                    upstream_commit_oids,
+                );
+
+                let all_steps = branch_stack.as_rebase_steps(context.ctx, context.gix_repo)?;
+                let branches_before = as_buckets(all_steps.clone());
+                // Filter out any integrated commits
+                let steps = all_steps
+                    .into_iter()
+                    .filter_map(|s| match s {
+                        RebaseStep::Pick {
+                            commit_id,
+                            new_message: _,
+                            base: _,
+                        } => {
+                            let commit = repo.find_commit(commit_id.to_git2()).ok()?;
+                            let is_integrated = check_commit.is_integrated(&commit).ok()?;
+                            let forced = forced_integrated(
+                                &resolution.force_integrated_branches,
+                                &branches_before,
+                                &commit.id().to_gix(),
+                            );
+                            if is_integrated || forced {
+                                None
+                            } else {
+                                Some(s)
                             }
                         }
+                        _ => Some(s),
+                    })
+                    .collect::<Vec<_>>();
+
+                let branches_after = as_buckets(steps.clone());
+
+                // Branches that used to have commits but now don't are marked for archival
+                let mut for_archival = vec![];
+                for (ref_before, steps_before) in branches_before {
+                    if let Some((_, steps_after)) = branches_after
+                        .iter()
+                        .find(|(ref_after, _)| ref_after == &ref_before)
+                    {
+                        // if there were steps before and now there are none, this should be marked for archival
+                        if !steps_before.is_empty() && steps_after.is_empty() {
+                            for_archival.push(ref_before);
+                        }
                     }
+                }
+
+                to_rebase.push((branch_stack.id, steps, for_archival));
+            }
+        }
+    }
+
+    // Stacks that are merged, unapplied or deleted change the parents of the workspace commit
+    // in ways the rebase doesn't know about.
+    let workspace_commit = if results.is_empty() {
+        rebasable_workspace_commit(context, &to_rebase)?
+    } else {
+        None
+    };
+    let (rebased, workspace_merge) = rebase_stacks(
+        context.gix_repo,
+        lower_bound.to_gix(),
+        to_rebase,
+        workspace_commit,
+    )?;
+    results.extend(rebased);
+    Ok((results, workspace_merge))
+}
+
+/// Return the workspace commit if it merges exactly the heads of all `stacks`, and each of these heads is
+/// the last commit picked for its stack, so the rebase can re-merge it with the rewritten heads.
+fn rebasable_workspace_commit(
+    context: &UpstreamIntegrationContext,
+    stacks: &[(StackId, Vec<RebaseStep>, Vec<Reference>)],
+) -> Result<Option<gix::ObjectId>> {
+    let mut heads = Vec::with_capacity(stacks.len());
+    for (stack_id, steps, _for_archival) in stacks {
+        let stack = context
+            .stacks_in_workspace
+            .iter()
+            .find(|stack| stack.id == *stack_id)
+            .context("BUG: rebased stacks are in the workspace")?;
+        let head = stack.head_oid(context.gix_repo)?;
+        let last_pick = steps.iter().rev().find_map(|step| match step {
+            RebaseStep::Pick { commit_id, .. } => Some(*commit_id),
+            _ => None,
+        });
+        if last_pick != Some(head) {
+            return Ok(None);
+        }
+        heads.push(head);
+    }
+    let workspace_commit = context.gix_repo.head_commit()?;
+    let mut parents: Vec<_> = workspace_commit
+        .parent_ids()
+        .map(|id| id.detach())
+        .collect();
+    parents.sort();
+    heads.sort();
+    Ok((!heads.is_empty() && parents == heads).then_some(workspace_commit.id))
+}
 
-                    let mut rebase = but_rebase::Rebase::new(
-                        context.gix_repo,
-                        Some(lower_bound.to_gix()),
-                        None,
-                    )?;
-                    rebase.rebase_noops(false);
-                    rebase.steps(steps)?;
-                    let output = rebase.rebase()?;
-                    let new_head = output.top_commit.to_git2();
-
-                    Ok((
-                        branch_stack.id,
-                        IntegrationResult::UpdatedObjects {
-                            head: new_head,
-                            tree: None,
-                            rebase_output: Some(output),
-                            for_archival,
-                        },
-                    ))
+/// Rebase the commits of all `stacks` onto `base` in a single pass, each stack starting a new sequence at `base`,
+/// and split the output of the rebase by stack.
+///
+/// If set, `workspace_commit` is picked last to re-merge it with the rewritten heads of all stacks,
+/// and the resulting merge is returned along with the stacks.
+fn rebase_stacks(
+    repo: &gix::Repository,
+    base: gix::ObjectId,
+    stacks: Vec<(StackId, Vec<RebaseStep>, Vec<Reference>)>,
+    workspace_commit: Option<gix::ObjectId>,
+) -> Result<(Vec<(StackId, IntegrationResult)>, Option<gix::ObjectId>)> {
+    let mut all_steps = Vec::new();
+    // For each stack, the references below its first commit, and the range of its steps within `all_steps`.
+    let mut layout = Vec::with_capacity(stacks.len());
+    for (stack_id, steps, for_archival) in stacks {
+        let first_pick = steps
+            .iter()
+            .position(|step| matches!(step, RebaseStep::Pick { .. }))
+            .unwrap_or(steps.len());
+        let mut steps = steps.into_iter();
+        // Branches without commits below the first commit of the stack are not part of the sequence,
+        // as they would otherwise point to the top of the previous stack.
+        let leading_references: Vec<_> = steps
+            .by_ref()
+            .take(first_pick)
+            .filter_map(|step| match step {
+                RebaseStep::Reference(reference) => Some(ReferenceSpec {
+                    reference,
+                    commit_id: base,
+                    previous_commit_id: base,
+                }),
+                _ => None,
+            })
+            .collect();
+        let start = all_steps.len();
+        for mut step in steps {
+            if all_steps.len() == start {
+                if let RebaseStep::Pick {
+                    base: step_base, ..
+                } = &mut step
+                {
+                    *step_base = Some(base);
                 }
             }
+            all_steps.push(step);
+        }
+        layout.push((
+            stack_id,
+            for_archival,
+            leading_references,
+            start..all_steps.len(),
+        ));
+    }
+
+    if let Some(commit_id) = workspace_commit {
+        all_steps.push(RebaseStep::Pick {
+            commit_id,
+            new_message: None,
+            base: None,
+        });
+    }
+
+    let output = if all_steps.is_empty() {
+        None
+    } else {
+        let mut rebase = but_rebase::Rebase::new(repo, Some(base), None)?;
+        rebase.rebase_noops(false);
+        rebase.steps(all_steps.iter().cloned())?;
+        Some(rebase.rebase()?)
+    };
+
+    let mut references = output
+        .as_ref()
+        .map(|output| output.references.clone())
+        .unwrap_or_default()
+        .into_iter();
+    let workspace_merge = workspace_commit
+        .and(output.as_ref())
+        .map(|output| output.top_commit);
+    let rebased = layout
+        .into_iter()
+        .map(|(stack_id, for_archival, mut stack_references, steps)| {
+            let reference_count = all_steps[steps.clone()]
+                .iter()
+                .filter(|step| matches!(step, RebaseStep::Reference(_)))
+                .count();
+            stack_references.extend(references.by_ref().take(reference_count));
+            // There is one mapping for each step, as all steps are rebased onto a base.
+            let commit_mapping = output
+                .as_ref()
+                .map(|output| output.commit_mapping[steps.clone()].to_vec())
+                .unwrap_or_default();
+            let top_commit = commit_mapping
+                .last()
+                .map_or(base, |(_base, _old, new)| *new);
+            Ok((
+                stack_id,
+                IntegrationResult::UpdatedObjects {
+                    head: top_commit.to_git2(),
+                    tree: None,
+                    rebase_output: Some(RebaseOutput {
+                        top_commit,
+                        references: stack_references,
+                        commit_mapping,
+                    }),
+                    for_archival,
+                },
+            ))
         })
         .collect::<Result<Vec<_>>>()?;
-
-    Ok(results)
+    Ok((rebased, workspace_merge))
 }
 
 // If the commit is in a bucket (branches_before) where the reference matches any of the
@@ -807,6 +969,7 @@ fn forced_integrated(
                 if let RebaseStep::Pick {
                     commit_id,
                     new_message: _,
+                    base: _,
                 } = step
                 {
                     if commit_id == target_commit_id {
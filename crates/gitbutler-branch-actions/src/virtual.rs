@@ -529,6 +529,7 @@ pub(crate) fn insert_blank_commit(
                 updated_steps.push(RebaseStep::Pick {
                     commit_id: blank_commit_oid.to_gix(),
                     new_message: None,
+                    base: None,
                 });
             }
         }
@@ -540,6 +541,7 @@ pub(crate) fn insert_blank_commit(
             RebaseStep::Pick {
                 commit_id: blank_commit_oid.to_gix(),
                 new_message: None,
+                base: None,
             },
         );
     }
@@ -618,6 +620,7 @@ pub(crate) fn update_commit_message(
         if let RebaseStep::Pick {
             commit_id: id,
             new_message,
+            ..
         } = step
         {
             if *id == commit_id.to_gix() {
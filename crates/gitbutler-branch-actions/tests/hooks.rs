@@ -5,10 +5,14 @@ mod tests {
         path::{Path, PathBuf},
     };
 
+    use but_workspace::DiffSpec;
     use git2::{Repository, StatusOptions};
     use gitbutler_branch_actions::hooks;
     use gitbutler_diff::Hunk;
-    use gitbutler_repo::hooks::{ErrorData, HookResult, MessageData, MessageHookResult};
+    use gitbutler_oxidize::OidExt;
+    use gitbutler_repo::hooks::{
+        ErrorData, HookResult, MessageData, MessageHookResult, SuccessData,
+    };
     use gitbutler_stack::{BranchOwnershipClaims, OwnershipClaim};
     use gitbutler_testsupport::{Case, Suite};
 
@@ -25,7 +29,7 @@ mod tests {
         git2_hooks::create_hook(ctx.repo(), git2_hooks::HOOK_PRE_COMMIT, hook);
         assert_eq!(
             hooks::pre_commit(ctx, &selected_hunks)?,
-            HookResult::Success
+            HookResult::Success(SuccessData::default())
         );
         Ok(())
     }
@@ -65,7 +69,10 @@ fi
         // fail if we pass no ownership claims. These claims are used to select what hunks
         // get committed.
         let ownership1 = BranchOwnershipClaims { claims: vec![] };
-        assert_eq!(hooks::pre_commit(ctx, &ownership1)?, HookResult::Success);
+        assert_eq!(
+            hooks::pre_commit(ctx, &ownership1)?,
+            HookResult::Success(SuccessData::default())
+        );
 
         // But when including the change in the ownerships the change will be staged, and
         // the hook therefore fails.
@@ -175,6 +182,131 @@ echo 'commit message' > $1
         Ok(())
     }
 
+    #[test]
+    fn pre_commit_hooks_run_like_git_commit() -> anyhow::Result<()> {
+        let suite = Suite::default();
+        let Case { ctx, .. } = &suite.new_case();
+
+        let hook = b"
+#!/bin/sh
+echo 'rewritten message' > $1
+";
+        git2_hooks::create_hook(ctx.repo(), git2_hooks::HOOK_COMMIT_MSG, hook);
+        assert_eq!(
+            hooks::run_pre_commit_hooks(ctx, None, &[], "commit message".to_owned())?,
+            "rewritten message\n",
+            "the message of the commit-msg hook is used"
+        );
+
+        let hook = b"
+#!/bin/sh
+echo 'rejected'
+exit 1
+";
+        git2_hooks::create_hook(ctx.repo(), git2_hooks::HOOK_PRE_COMMIT, hook);
+        let err = hooks::run_pre_commit_hooks(ctx, None, &[], "commit message".to_owned())
+            .expect_err("the pre-commit hook fails");
+        assert_eq!(err.to_string(), "The pre-commit hook failed:\nrejected\n");
+        Ok(())
+    }
+
+    #[test]
+    fn pre_commit_hook_sees_the_tree_to_commit() -> anyhow::Result<()> {
+        let suite = Suite::default();
+        let Case { ctx, .. } =
+            &suite.new_case_with_files(HashMap::from([(PathBuf::from("amended.txt"), "amended")]));
+        let repo = ctx.repo();
+        let workdir = repo.workdir().unwrap();
+        let commit_to_amend = repo.head()?.peel_to_commit()?.id();
+
+        std::fs::write(workdir.join("later.txt"), "later")?;
+        gitbutler_testsupport::commit_all(repo);
+        std::fs::write(workdir.join("new.txt"), "new")?;
+        let diff_specs = [DiffSpec {
+            previous_path: None,
+            path: "new.txt".into(),
+            hunk_headers: vec![],
+        }];
+
+        let hook = b"
+#!/bin/sh
+git ls-files
+exit 1
+";
+        git2_hooks::create_hook(repo, git2_hooks::HOOK_PRE_COMMIT, hook);
+        let files_seen_by_hook = |amend| -> anyhow::Result<Vec<String>> {
+            let HookResult::Failure(ErrorData { error }) =
+                hooks::pre_commit_with_diff_specs(ctx, amend, &diff_specs)?
+            else {
+                panic!("the hook always fails");
+            };
+            Ok(error.lines().map(ToOwned::to_owned).collect())
+        };
+
+        let files = files_seen_by_hook(None)?;
+        for expected in ["amended.txt", "later.txt", "new.txt"] {
+            assert!(
+                files.iter().any(|file| file == expected),
+                "a new commit is created on top of HEAD, so {expected} is in {files:?}"
+            );
+        }
+
+        let files = files_seen_by_hook(Some(commit_to_amend.to_gix()))?;
+        for expected in ["amended.txt", "new.txt"] {
+            assert!(
+                files.iter().any(|file| file == expected),
+                "the changes are added to the amended commit, so {expected} is in {files:?}"
+            );
+        }
+        assert!(
+            !files.iter().any(|file| file == "later.txt"),
+            "the amended commit doesn't contain files of later commits"
+        );
+
+        assert!(
+            !is_file_staged(repo, "new.txt")?,
+            "the index is restored after running the hook"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pre_commit_hooks_run_like_git_commit_amend() -> anyhow::Result<()> {
+        let suite = Suite::default();
+        let Case { ctx, .. } =
+            &suite.new_case_with_files(HashMap::from([(PathBuf::from("amended.txt"), "amended")]));
+        let repo = ctx.repo();
+        let commit_to_amend = repo.head()?.peel_to_commit()?.id().to_gix();
+        std::fs::write(repo.workdir().unwrap().join("later.txt"), "later")?;
+        gitbutler_testsupport::commit_all(repo);
+
+        let hook = b"
+#!/bin/sh
+if git ls-files --error-unmatch later.txt >/dev/null 2>&1; then
+  echo 'later.txt is committed'
+  exit 1
+fi
+";
+        git2_hooks::create_hook(repo, git2_hooks::HOOK_PRE_COMMIT, hook);
+        assert_eq!(
+            hooks::run_pre_commit_hooks(
+                ctx,
+                Some(commit_to_amend),
+                &[],
+                "amended message".to_owned()
+            )?,
+            "amended message",
+            "the hook sees the tree of the amended commit"
+        );
+        let err = hooks::run_pre_commit_hooks(ctx, None, &[], "commit message".to_owned())
+            .expect_err("a new commit on top of HEAD contains later.txt");
+        assert_eq!(
+            err.to_string(),
+            "The pre-commit hook failed:\nlater.txt is committed\n"
+        );
+        Ok(())
+    }
+
     fn is_file_staged(repo: &Repository, file_path: &str) -> Result<bool, git2::Error> {
         let mut opts = StatusOptions::new();
         opts.show(git2::StatusShow::Index);
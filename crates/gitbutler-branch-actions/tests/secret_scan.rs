@@ -0,0 +1,123 @@
+use std::path::Path;
+
+use anyhow::Result;
+use gitbutler_branch_actions::secret_scan::{scan_commits, AllowList};
+use regex::Regex;
+use tempfile::TempDir;
+
+/// A token in the format of a GitHub token, assembled at runtime so this file doesn't look like it contains one.
+fn github_token() -> String {
+    format!("ghp_{}", "a1B2c3D4e5F6".repeat(3))
+}
+
+#[test]
+fn finds_known_token_formats_in_added_lines() -> Result<()> {
+    let (repo, _tmp) = repo()?;
+    let first = commit(&repo, &[("config.env", "NAME=value\n")])?;
+    let second = commit(
+        &repo,
+        &[(
+            "config.env",
+            &format!("NAME=value\nTOKEN={}\n", github_token()),
+        )],
+    )?;
+
+    let findings = scan_commits(&repo, &[second, first], &AllowList::default())?;
+    assert_eq!(
+        findings.len(),
+        1,
+        "only the line added by the second commit"
+    );
+    let finding = &findings[0];
+    assert_eq!(finding.commit_id, second);
+    assert_eq!(finding.path, "config.env");
+    assert_eq!(finding.line, 2);
+    assert_eq!(finding.kind, "GitHub token");
+    assert_eq!(
+        finding.redacted, "ghp_…",
+        "the secret itself is never revealed"
+    );
+    Ok(())
+}
+
+#[test]
+fn finds_generic_secrets_only_if_they_look_random() -> Result<()> {
+    let (repo, _tmp) = repo()?;
+    let id = commit(
+        &repo,
+        &[(
+            "settings.py",
+            "password = \"correct-horse-horse\"\nclient_secret = \"q8Zr2LmX4vTn9PwK7sYd\"\n",
+        )],
+    )?;
+
+    let findings = scan_commits(&repo, &[id], &AllowList::default())?;
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].line, 2);
+    assert_eq!(findings[0].kind, "High-entropy secret");
+    Ok(())
+}
+
+#[test]
+fn allowed_secrets_are_not_reported() -> Result<()> {
+    let (repo, _tmp) = repo()?;
+    let token = github_token();
+    let id = commit(
+        &repo,
+        &[
+            (
+                "a.env",
+                &format!("TOKEN={token} # gitbutler:allow-secret\n"),
+            ),
+            ("fixtures/b.env", &format!("TOKEN={token}\n")),
+            ("c.env", &format!("TOKEN={token}\n")),
+        ],
+    )?;
+
+    let allow_list = AllowList {
+        secrets: vec![],
+        paths: vec![Regex::new("^fixtures/")?],
+    };
+    let findings = scan_commits(&repo, &[id], &allow_list)?;
+    assert_eq!(
+        findings.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(),
+        ["c.env"]
+    );
+
+    let allow_list = AllowList {
+        secrets: vec![Regex::new("^ghp_a1B2")?],
+        paths: vec![],
+    };
+    assert!(scan_commits(&repo, &[id], &allow_list)?.is_empty());
+    Ok(())
+}
+
+fn repo() -> Result<(git2::Repository, TempDir)> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init(tmp.path())?;
+    Ok((repo, tmp))
+}
+
+/// Write `files` to the worktree of `repo` and commit all of them on top of `HEAD`.
+fn commit(repo: &git2::Repository, files: &[(&str, &str)]) -> Result<git2::Oid> {
+    let workdir = repo.workdir().expect("non-bare");
+    let mut index = repo.index()?;
+    for (path, content) in files {
+        let full_path = workdir.join(path);
+        std::fs::create_dir_all(full_path.parent().expect("in worktree"))?;
+        std::fs::write(&full_path, content)?;
+        index.add_path(Path::new(path))?;
+    }
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let signature = git2::Signature::now("author", "author@example.com")?;
+    let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+    Ok(repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "commit",
+        &tree,
+        parent.iter().collect::<Vec<_>>().as_slice(),
+    )?)
+}
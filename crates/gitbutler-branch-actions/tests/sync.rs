@@ -0,0 +1,66 @@
+use anyhow::Result;
+use gitbutler_branch_actions::sync::{SyncApproach, SyncPolicy, POLICY_PATH};
+use tempfile::TempDir;
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn policy_defaults_to_rebase_without_a_file() -> Result<()> {
+    let tmp = TempDir::new()?;
+    let policy = SyncPolicy::load(tmp.path())?;
+    assert_eq!(
+        policy.approach_for(&names(&["feature"]), None),
+        SyncApproach::Rebase
+    );
+    assert_eq!(
+        policy.approach_for(&names(&["feature"]), Some(SyncApproach::Merge)),
+        SyncApproach::Merge,
+        "the approach passed by the caller replaces the default of the policy"
+    );
+    Ok(())
+}
+
+#[test]
+fn first_matching_override_wins_over_any_default() -> Result<()> {
+    let tmp = TempDir::new()?;
+    std::fs::create_dir_all(tmp.path().join(".gitbutler"))?;
+    std::fs::write(
+        tmp.path().join(POLICY_PATH),
+        r#"
+default = "unapply"
+
+[[overrides]]
+branch = "^release/"
+approach = "merge"
+
+[[overrides]]
+branch = "^release/old"
+approach = "rebase"
+"#,
+    )?;
+    let policy = SyncPolicy::load(tmp.path())?;
+
+    let release = names(&["base", "release/old-1"]);
+    assert_eq!(policy.approach_for(&release, None), SyncApproach::Merge);
+    assert_eq!(
+        policy.approach_for(&release, Some(SyncApproach::Rebase)),
+        SyncApproach::Merge,
+        "overrides are more specific than the approach passed by the caller"
+    );
+    assert_eq!(
+        policy.approach_for(&names(&["feature"]), None),
+        SyncApproach::Unapply
+    );
+    Ok(())
+}
+
+#[test]
+fn invalid_policy_is_an_error() -> Result<()> {
+    let tmp = TempDir::new()?;
+    std::fs::create_dir_all(tmp.path().join(".gitbutler"))?;
+    std::fs::write(tmp.path().join(POLICY_PATH), "default = \"squash\"\n")?;
+    assert!(SyncPolicy::load(tmp.path()).is_err());
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_branch_actions::upstream_integration::{Resolution, ResolutionApproach};
+use gitbutler_oxidize::ObjectIdExt;
+use gitbutler_testsupport::stack_details;
+
+use super::*;
+
+#[test]
+fn rebases_all_stacks_onto_the_new_target_in_one_pass() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &Test::default();
+
+    // make sure we have an undiscovered commit in the remote branch
+    {
+        fs::write(repo.path().join("file.txt"), "one").unwrap();
+        let first_commit_oid = repo.commit_all("first");
+        fs::write(repo.path().join("upstream.txt"), "upstream").unwrap();
+        repo.commit_all("second");
+        repo.push();
+        repo.reset_hard(Some(first_commit_oid));
+    }
+
+    gitbutler_branch_actions::set_base_branch(
+        ctx,
+        &"refs/remotes/origin/master".parse().unwrap(),
+        false,
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )
+    .unwrap();
+
+    let stack_1 = gitbutler_branch_actions::create_virtual_branch(
+        ctx,
+        &BranchCreateRequest::default(),
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    fs::write(repo.path().join("a.txt"), "a1")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_1.id, "a1", None)?;
+    fs::write(repo.path().join("a.txt"), "a2")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_1.id, "a2", None)?;
+
+    let stack_2 = gitbutler_branch_actions::create_virtual_branch(
+        ctx,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    fs::write(repo.path().join("b.txt"), "b")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_2.id, "b", None)?;
+
+    let resolutions = [stack_1.id, stack_2.id].map(|branch_id| Resolution {
+        branch_id,
+        approach: ResolutionApproach::Rebase,
+        delete_integrated_branches: false,
+        force_integrated_branches: vec![],
+    });
+    gitbutler_branch_actions::integrate_upstream(ctx, &resolutions, None)?;
+
+    let stacks = stack_details(ctx);
+    for (stack_id, expected) in [(stack_1.id, vec!["a2", "a1"]), (stack_2.id, vec!["b"])] {
+        let (_, details) = stacks.iter().find(|(id, _)| *id == stack_id).unwrap();
+        let commits = &details.branch_details[0].commits;
+        let messages: Vec<_> = commits.iter().map(|c| c.message.clone()).collect();
+        assert_eq!(messages, expected);
+        assert!(commits.iter().all(|c| !c.has_conflicts));
+
+        let bottom = repo.find_commit(commits.last().unwrap().id.to_git2())?;
+        assert_eq!(
+            bottom.parent(0)?.message(),
+            Some("second"),
+            "each stack starts on top of the new target, not on top of the stack rebased before it"
+        );
+    }
+
+    let workspace = ctx.repo().head()?.peel_to_commit()?;
+    let mut parents: Vec<_> = workspace.parent_ids().collect();
+    let mut heads: Vec<_> = stacks
+        .iter()
+        .map(|(_, details)| details.branch_details[0].commits[0].id.to_git2())
+        .collect();
+    parents.sort();
+    heads.sort();
+    assert_eq!(
+        parents, heads,
+        "the workspace commit is re-merged on top of the rebased stacks"
+    );
+
+    assert_eq!(
+        fs::read_to_string(repo.path().join("upstream.txt"))?,
+        "upstream"
+    );
+    assert_eq!(fs::read_to_string(repo.path().join("a.txt"))?, "a2");
+    assert_eq!(fs::read_to_string(repo.path().join("b.txt"))?, "b");
+    Ok(())
+}
@@ -68,12 +68,15 @@ mod apply_virtual_branch;
 mod create_virtual_branch_from_branch;
 mod init;
 mod insert_blank_commit;
+mod integrate_upstream;
 mod list;
 mod list_details;
 mod move_commit_to_vbranch;
 mod oplog;
+mod push_secrets;
 mod save_and_unapply_virtual_branch;
 mod set_base_branch;
+mod sync;
 mod unapply_without_saving_virtual_branch;
 mod undo_commit;
 mod update_commit_message;
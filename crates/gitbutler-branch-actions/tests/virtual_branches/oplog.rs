@@ -2,7 +2,7 @@ use std::{io::Write, path::Path, time::Duration};
 
 use gitbutler_branch::BranchCreateRequest;
 use gitbutler_branch_actions::list_commit_files;
-use gitbutler_oplog::OplogExt;
+use gitbutler_oplog::{entry::OperationKind, OplogExt, OplogRange};
 use gitbutler_oxidize::ObjectIdExt;
 use gitbutler_stack::VirtualBranchesHandle;
 use gitbutler_testsupport::stack_details;
@@ -456,3 +456,291 @@ fn first_snapshot_diff_works() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn exported_oplog_can_be_imported_into_another_project() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        ctx,
+        &"refs/remotes/origin/master".parse()?,
+        false,
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    let stack_entry = gitbutler_branch_actions::create_virtual_branch(
+        ctx,
+        &BranchCreateRequest::default(),
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    fs::write(repo.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "commit one", None)?;
+    fs::write(repo.path().join("file.txt"), "changed content")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "commit two", None)?;
+
+    let snapshots = ctx.list_snapshots(10, None, Vec::new())?;
+    assert_eq!(snapshots.len(), 3, "one vbranch, two commits");
+
+    let export_dir = tempfile::tempdir()?;
+    let range = OplogRange {
+        oldest: Some(snapshots[1].commit_id),
+        newest: None,
+    };
+    let manifest = ctx.export_oplog(range, export_dir.path())?;
+    assert_eq!(
+        manifest
+            .snapshots
+            .iter()
+            .map(|s| s.operation)
+            .collect::<Vec<_>>(),
+        [OperationKind::CreateCommit, OperationKind::CreateCommit],
+        "the oldest snapshot comes first, and the range is respected"
+    );
+    assert_eq!(
+        manifest.snapshots[1].original_commit_id,
+        snapshots[0].commit_id
+    );
+    assert_ne!(
+        manifest.snapshots[0].commit_id, snapshots[1].commit_id,
+        "the oldest snapshot is rewritten to not have a parent"
+    );
+    let source_repo = git2::Repository::open(&ctx.project().path)?;
+    assert!(
+        source_repo
+            .find_commit(manifest.snapshots[0].commit_id)
+            .is_err(),
+        "the rewritten snapshots aren't written into the exporting repository"
+    );
+    assert!(
+        source_repo
+            .find_reference("refs/gitbutler/oplog-export")
+            .is_err(),
+        "and neither is the exported reference"
+    );
+
+    let other = Test::default();
+    gitbutler_branch_actions::set_base_branch(
+        &other.ctx,
+        &"refs/remotes/origin/master".parse()?,
+        false,
+        other
+            .ctx
+            .project()
+            .exclusive_worktree_access()
+            .write_permission(),
+    )?;
+    let imported = other.ctx.import_oplog(export_dir.path())?;
+    assert_eq!(imported, manifest);
+    assert!(
+        other.ctx.list_snapshots(10, None, Vec::new())?.is_empty(),
+        "the local oplog isn't affected"
+    );
+    let tip = imported.snapshots.last().unwrap().commit_id;
+    let imported_snapshots = other.ctx.list_snapshots(10, Some(tip), Vec::new())?;
+    assert_eq!(
+        imported_snapshots
+            .iter()
+            .map(|s| s.details.as_ref().unwrap().operation)
+            .collect::<Vec<_>>(),
+        [OperationKind::CreateCommit],
+        "the older snapshot is reachable from the tip, which itself is skipped as traversal root"
+    );
+
+    {
+        let mut guard = other.project.exclusive_worktree_access();
+        other
+            .ctx
+            .restore_snapshot(imported.snapshots[0].commit_id, guard.write_permission())?;
+    }
+    assert_eq!(
+        fs::read_to_string(other.repo.path().join("file.txt"))?,
+        "content",
+        "the worktree is restored to the state before the second commit was created"
+    );
+    assert_eq!(
+        stack_details(&other.ctx).len(),
+        1,
+        "the branch of the exporting project is restored as well"
+    );
+    Ok(())
+}
+
+#[test]
+fn undo_and_redo_form_a_tree() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        ctx,
+        &"refs/remotes/origin/master".parse()?,
+        false,
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    let stack_entry = gitbutler_branch_actions::create_virtual_branch(
+        ctx,
+        &BranchCreateRequest::default(),
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    let git_repo = git2::Repository::open(&project.path)?;
+    fs::write(repo.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "commit one", None)?;
+    let head_after_commit_one = git_repo.head()?.peel_to_commit()?.id();
+    fs::write(repo.path().join("file.txt"), "changed content")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "commit two", None)?;
+    let head_after_commit_two = git_repo.head()?.peel_to_commit()?.id();
+
+    let tree = ctx.undo_tree()?;
+    assert_eq!(tree.nodes.len(), 3, "one vbranch, two commits");
+    assert_eq!(tree.current, Some(tree.nodes[2].snapshot_id));
+    assert!(tree.redo_stack.is_empty(), "nothing was undone yet");
+
+    let mut guard = project.exclusive_worktree_access();
+    let undone = ctx
+        .undo(guard.write_permission())?
+        .expect("there is something to undo");
+    assert_eq!(undone.snapshot_id, tree.nodes[2].snapshot_id);
+    assert_eq!(
+        git_repo.head()?.peel_to_commit()?.id(),
+        head_after_commit_one
+    );
+
+    let undone = ctx
+        .undo(guard.write_permission())?
+        .expect("undo goes further back");
+    assert_eq!(undone.snapshot_id, tree.nodes[1].snapshot_id);
+    let after_two_undos = ctx.undo_tree()?;
+    assert_eq!(after_two_undos.current, Some(tree.nodes[0].snapshot_id));
+    assert_eq!(
+        after_two_undos.redo_stack,
+        [tree.nodes[1].snapshot_id, tree.nodes[2].snapshot_id],
+        "both undone operations can be redone in order"
+    );
+    assert_eq!(
+        after_two_undos.nodes.len(),
+        3,
+        "undoing doesn't create new nodes"
+    );
+    assert_eq!(after_two_undos.nodes[2].undone_by.len(), 1);
+
+    ctx.redo(guard.write_permission())?
+        .expect("there is something to redo");
+    ctx.redo(guard.write_permission())?
+        .expect("the second undo can be redone");
+    assert_eq!(
+        git_repo.head()?.peel_to_commit()?.id(),
+        head_after_commit_two
+    );
+    assert!(
+        ctx.redo(guard.write_permission())?.is_none(),
+        "everything was redone"
+    );
+
+    ctx.undo(guard.write_permission())?
+        .expect("commit two is undone again");
+    drop(guard);
+    fs::write(repo.path().join("other.txt"), "other content")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "commit three", None)?;
+
+    let tree = ctx.undo_tree()?;
+    let new_node = tree.nodes.last().unwrap();
+    assert_eq!(
+        new_node.parent,
+        Some(tree.nodes[1].snapshot_id),
+        "the new operation branches off where commit two was undone"
+    );
+    assert_eq!(tree.current, Some(new_node.snapshot_id));
+    assert!(
+        tree.redo_stack.is_empty(),
+        "the undone commit two is on another branch of the tree"
+    );
+    Ok(())
+}
+
+#[test]
+fn restoring_a_single_stack_leaves_other_stacks_alone() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        ctx,
+        &"refs/remotes/origin/master".parse()?,
+        false,
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    let stack_a = gitbutler_branch_actions::create_virtual_branch(
+        ctx,
+        &BranchCreateRequest {
+            name: Some("a".into()),
+            ..Default::default()
+        },
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    fs::write(repo.path().join("a.txt"), "a")?;
+    let a_commit = gitbutler_branch_actions::create_commit(ctx, stack_a.id, "commit a", None)?;
+    let stack_b = gitbutler_branch_actions::create_virtual_branch(
+        ctx,
+        &BranchCreateRequest {
+            name: Some("b".into()),
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    fs::write(repo.path().join("b.txt"), "b")?;
+    let b_commit = gitbutler_branch_actions::create_commit(ctx, stack_b.id, "commit b", None)?;
+
+    let snapshots = ctx.list_snapshots(10, None, Vec::new())?;
+    let before_commit_a = &snapshots[2];
+    assert_eq!(
+        before_commit_a.details.as_ref().map(|d| d.operation),
+        Some(OperationKind::CreateCommit),
+        "taken right before `commit a` was created"
+    );
+
+    let vb_state = VirtualBranchesHandle::new(project.gb_dir());
+    let gix_repo = ctx.gix_repo()?;
+    let head_of = |stack_id| -> anyhow::Result<git2::Oid> {
+        Ok(vb_state.get_stack(stack_id)?.head_oid(&gix_repo)?.to_git2())
+    };
+
+    fs::write(repo.path().join("a.txt"), "a, but changed")?;
+    let mut guard = project.exclusive_worktree_access();
+    let outcome = ctx.restore_stack_snapshot(
+        before_commit_a.commit_id,
+        stack_a.id,
+        guard.write_permission(),
+    )?;
+    assert_eq!(
+        outcome.conflicting_paths,
+        ["a.txt"],
+        "the uncommitted change isn't owned by stack a, so it would be lost"
+    );
+    assert_eq!(outcome.snapshot_id, None, "nothing was restored");
+    assert_eq!(head_of(stack_a.id)?, a_commit);
+
+    fs::write(repo.path().join("a.txt"), "a")?;
+    let outcome = ctx.restore_stack_snapshot(
+        before_commit_a.commit_id,
+        stack_a.id,
+        guard.write_permission(),
+    )?;
+    assert!(outcome.conflicting_paths.is_empty());
+    assert!(outcome.snapshot_id.is_some());
+    drop(guard);
+
+    assert_ne!(
+        head_of(stack_a.id)?,
+        a_commit,
+        "stack a is back to the state before its commit"
+    );
+    assert_eq!(head_of(stack_b.id)?, b_commit, "stack b wasn't touched");
+    assert_eq!(fs::read_to_string(repo.path().join("b.txt"))?, "b");
+
+    let snapshots = ctx.list_snapshots(1, None, Vec::new())?;
+    assert_eq!(
+        snapshots[0].details.as_ref().map(|d| d.operation),
+        Some(OperationKind::RestoreStackFromSnapshot)
+    );
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_branch_actions::secret_scan::SecretsDetected;
+use gitbutler_error::error::Code;
+
+use super::*;
+
+#[test]
+fn refuses_to_push_commits_with_secrets() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        ctx,
+        &"refs/remotes/origin/master".parse()?,
+        false,
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+
+    let stack_entry = gitbutler_branch_actions::create_virtual_branch(
+        ctx,
+        &BranchCreateRequest::default(),
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+
+    // A token in the format of a GitHub token, assembled at runtime so this file doesn't look like it contains one.
+    let token = format!("ghp_{}", "a1B2c3D4e5F6".repeat(3));
+    fs::write(repo.path().join("config.env"), format!("TOKEN={token}\n"))?;
+    let commit_id =
+        gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "add config", None)?;
+
+    let branch_name = stack_entry.name().map(|s| s.to_string()).unwrap();
+    let err = gitbutler_branch_actions::stack::push_stack(
+        ctx,
+        stack_entry.id,
+        false,
+        false,
+        branch_name.clone(),
+        false,
+    )
+    .unwrap_err();
+
+    assert_eq!(err.downcast_ref::<Code>(), Some(&Code::SecretsDetected));
+    let secrets = err
+        .downcast_ref::<SecretsDetected>()
+        .expect("the findings are part of the error");
+    assert_eq!(secrets.findings.len(), 1);
+    let finding = &secrets.findings[0];
+    assert_eq!(finding.commit_id, commit_id);
+    assert_eq!(finding.path, "config.env");
+    assert_eq!(finding.line, 1);
+    assert!(
+        !finding.redacted.contains(&token),
+        "the secret itself isn't revealed"
+    );
+
+    assert!(
+        repo.local_repo
+            .find_reference(&format!("refs/remotes/origin/{branch_name}"))
+            .is_err(),
+        "nothing was pushed"
+    );
+    Ok(())
+}
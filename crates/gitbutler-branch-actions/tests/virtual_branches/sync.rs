@@ -0,0 +1,173 @@
+use std::cell::Cell;
+
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_branch_actions::sync::{self, SyncOptions};
+use gitbutler_oxidize::ObjectIdExt;
+use gitbutler_stack::{StackId, VirtualBranchesHandle};
+use gitbutler_testsupport::stack_details;
+
+use super::*;
+
+#[test]
+fn conflicts_in_any_stack_change_nothing() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+
+    // make sure we have an undiscovered commit in the remote branch that changes file.txt
+    let first_commit_oid = {
+        fs::write(repo.path().join("file.txt"), "one")?;
+        let first_commit_oid = repo.commit_all("first");
+        fs::write(repo.path().join("file.txt"), "upstream")?;
+        repo.commit_all("second");
+        repo.push();
+        repo.reset_hard(Some(first_commit_oid));
+        first_commit_oid
+    };
+
+    gitbutler_branch_actions::set_base_branch(
+        ctx,
+        &"refs/remotes/origin/master".parse()?,
+        false,
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+
+    let clean = gitbutler_branch_actions::create_virtual_branch(
+        ctx,
+        &BranchCreateRequest::default(),
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    fs::write(repo.path().join("a.txt"), "a")?;
+    gitbutler_branch_actions::create_commit(ctx, clean.id, "a", None)?;
+
+    let conflicting = gitbutler_branch_actions::create_virtual_branch(
+        ctx,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    fs::write(repo.path().join("file.txt"), "mine")?;
+    gitbutler_branch_actions::create_commit(ctx, conflicting.id, "mine", None)?;
+
+    let heads_before = heads(ctx);
+    let report = sync::sync(
+        ctx,
+        SyncOptions {
+            approach: None,
+            push: false,
+        },
+        |_| Ok(()),
+    )?;
+
+    assert!(!report.integrated_upstream);
+    assert!(report
+        .aborted
+        .as_deref()
+        .is_some_and(|reason| reason.starts_with("Updating would lead to conflicts in")));
+    let conflicted: Vec<_> = report
+        .stacks
+        .iter()
+        .filter(|stack| stack.conflicted)
+        .map(|stack| stack.stack_id)
+        .collect();
+    assert_eq!(conflicted, [conflicting.id]);
+
+    assert_eq!(
+        heads(ctx),
+        heads_before,
+        "the stack that could be rebased cleanly wasn't changed either"
+    );
+    assert_eq!(
+        VirtualBranchesHandle::new(ctx.project().gb_dir())
+            .get_default_target()?
+            .sha,
+        first_commit_oid,
+        "the target wasn't updated"
+    );
+    assert_eq!(fs::read_to_string(repo.path().join("file.txt"))?, "mine");
+    assert_eq!(fs::read_to_string(repo.path().join("a.txt"))?, "a");
+    Ok(())
+}
+
+#[test]
+fn pushes_branches_whose_remote_is_behind() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        ctx,
+        &"refs/remotes/origin/master".parse()?,
+        false,
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+
+    let pushed = gitbutler_branch_actions::create_virtual_branch(
+        ctx,
+        &BranchCreateRequest::default(),
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    let pushed_name = pushed.name().map(|s| s.to_string()).unwrap();
+    fs::write(repo.path().join("a.txt"), "a1")?;
+    gitbutler_branch_actions::create_commit(ctx, pushed.id, "a1", None)?;
+    gitbutler_branch_actions::stack::push_stack(
+        ctx,
+        pushed.id,
+        false,
+        false,
+        pushed_name.clone(),
+        false,
+    )?;
+    fs::write(repo.path().join("a.txt"), "a2")?;
+    gitbutler_branch_actions::create_commit(ctx, pushed.id, "a2", None)?;
+
+    let never_pushed = gitbutler_branch_actions::create_virtual_branch(
+        ctx,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+    let never_pushed_name = never_pushed.name().map(|s| s.to_string()).unwrap();
+    fs::write(repo.path().join("b.txt"), "b")?;
+    gitbutler_branch_actions::create_commit(ctx, never_pushed.id, "b", None)?;
+
+    let before_push_calls = Cell::new(0);
+    let report = sync::sync(ctx, SyncOptions::default(), |_| {
+        before_push_calls.set(before_push_calls.get() + 1);
+        Ok(())
+    })?;
+    assert_eq!(before_push_calls.get(), 1, "callers can run pre-push rules");
+    assert_eq!(report.aborted, None);
+
+    let stack_report = |id: StackId| report.stacks.iter().find(|s| s.stack_id == id).unwrap();
+    assert_eq!(stack_report(pushed.id).pushed, [pushed_name.clone()]);
+    assert_eq!(stack_report(pushed.id).push_error, None);
+    assert!(
+        stack_report(never_pushed.id).pushed.is_empty(),
+        "branches that were never pushed are left alone"
+    );
+
+    let head = heads(ctx)
+        .into_iter()
+        .find(|(id, _)| *id == pushed.id)
+        .map(|(_, head)| head)
+        .unwrap();
+    assert_eq!(
+        repo.local_repo
+            .refname_to_id(&format!("refs/remotes/origin/{pushed_name}"))?,
+        head
+    );
+    assert!(repo
+        .local_repo
+        .find_reference(&format!("refs/remotes/origin/{never_pushed_name}"))
+        .is_err());
+    Ok(())
+}
+
+/// The head commit of each stack in the workspace.
+fn heads(ctx: &CommandContext) -> Vec<(StackId, git2::Oid)> {
+    stack_details(ctx)
+        .into_iter()
+        .map(|(id, details)| (id, details.branch_details[0].commits[0].id.to_git2()))
+        .collect()
+}
@@ -1,8 +1,24 @@
 use nix::{
-    libc::{c_int, wait, EXIT_FAILURE, WEXITSTATUS, WIFEXITED, WIFSIGNALED, WTERMSIG},
+    libc::{c_int, kill, wait, EXIT_FAILURE, WEXITSTATUS, WIFEXITED, WIFSIGNALED, WTERMSIG},
+    sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal},
     unistd::{fork, setsid, ForkResult},
 };
-use std::{os::unix::process::CommandExt, process};
+use std::{
+    os::unix::process::CommandExt,
+    process,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+/// The PID of the child, which is also the ID of its process group as it leads its own session.
+static CHILD: AtomicI32 = AtomicI32::new(0);
+
+/// Pass `signal` on to the session of the child, as it doesn't receive signals sent to our process group.
+extern "C" fn forward_signal(signal: c_int) {
+    let child = CHILD.load(Ordering::SeqCst);
+    if child > 0 {
+        unsafe { kill(-child, signal) };
+    }
+}
 
 pub fn main() {
     let has_pipe_var = std::env::var("GITBUTLER_ASKPASS_PIPE")
@@ -16,6 +32,17 @@ pub fn main() {
 
     match unsafe { fork() }.unwrap() {
         ForkResult::Parent { child, .. } => {
+            CHILD.store(child.as_raw(), Ordering::SeqCst);
+            // Restart `wait()` after forwarding, instead of failing with `EINTR`.
+            let forward = SigAction::new(
+                SigHandler::Handler(forward_signal),
+                SaFlags::SA_RESTART,
+                SigSet::empty(),
+            );
+            for signal in [Signal::SIGTERM, Signal::SIGINT, Signal::SIGHUP] {
+                unsafe { sigaction(signal, &forward) }.expect("sigaction():");
+            }
+
             let mut status: c_int = 0;
 
             let waited_pid = unsafe { wait(&mut status as *mut _) };
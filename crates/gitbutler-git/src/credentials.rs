@@ -0,0 +1,336 @@
+//! Credentials for Git and SSH that are provided without asking the user, which is what
+//! makes fetching and pushing work where nobody can answer a prompt, like `but` on a server.
+//!
+//! Whenever Git or SSH ask for something through the askpass utility, the providers of a
+//! [`CredentialProviders`] chain are asked in order. Only if none of them has an answer,
+//! the prompt is passed on to the caller, which in the desktop app shows it to the user.
+
+use std::{collections::HashMap, io::Write, path::PathBuf, process::Stdio};
+
+/// The environment variable with the host of the HTTP(S) remotes that [`USERNAME_ENV`] and [`PASSWORD_ENV`] are for.
+pub const HOST_ENV: &str = "GITBUTLER_GIT_HOST";
+/// The environment variable with the username to use for HTTP(S) remotes on [`HOST_ENV`].
+pub const USERNAME_ENV: &str = "GITBUTLER_GIT_USERNAME";
+/// The environment variable with the password or access token to use for HTTP(S) remotes on [`HOST_ENV`].
+pub const PASSWORD_ENV: &str = "GITBUTLER_GIT_PASSWORD";
+/// The environment variable with the passphrase of the SSH key in use.
+pub const SSH_PASSPHRASE_ENV: &str = "GITBUTLER_SSH_PASSPHRASE";
+/// The environment variable with a credential helper, in the format of Git's `credential.helper`.
+pub const CREDENTIAL_HELPER_ENV: &str = "GITBUTLER_CREDENTIAL_HELPER";
+/// The environment variable with the path to the socket of an SSH agent.
+pub const SSH_AUTH_SOCK_ENV: &str = "GITBUTLER_SSH_AUTH_SOCK";
+
+/// What Git or SSH asked for, as deduced from the text of an askpass prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Prompt {
+    /// The username for an HTTP(S) remote, like `Username for 'https://github.com': `.
+    Username {
+        /// The URL of the remote.
+        url: String,
+    },
+    /// The password for an HTTP(S) remote, like `Password for 'https://user@github.com': `.
+    Password {
+        /// The URL of the remote, which includes the username if it is known.
+        url: String,
+    },
+    /// The passphrase of an SSH key, like `Enter passphrase for key '/home/user/.ssh/id_ed25519': `.
+    Passphrase {
+        /// The path to the key.
+        key: String,
+    },
+    /// Any other prompt, like confirming the key of an unknown host.
+    Other(String),
+}
+
+impl Prompt {
+    /// Deduce what `prompt` asks for.
+    pub fn parse(prompt: &str) -> Self {
+        let prompt = prompt.trim();
+        let quoted = |prefix: &str| {
+            prompt
+                .strip_prefix(prefix)
+                .and_then(|rest| rest.split('\'').next())
+                .map(ToOwned::to_owned)
+        };
+        if let Some(url) = quoted("Username for '") {
+            Prompt::Username { url }
+        } else if let Some(url) = quoted("Password for '") {
+            Prompt::Password { url }
+        } else if let Some(key) = quoted("Enter passphrase for key '") {
+            Prompt::Passphrase { key }
+        } else {
+            Prompt::Other(prompt.to_owned())
+        }
+    }
+}
+
+/// Something that knows credentials, and can provide them instead of the user.
+pub trait CredentialProvider: Send + Sync {
+    /// A name for the provider, used for logging.
+    fn name(&self) -> &'static str;
+
+    /// Environment variables to set for Git, to let it or SSH find credentials by themselves.
+    fn envs(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
+
+    /// Answer `prompt`, or return `None` if the next provider should be asked.
+    fn answer(&self, prompt: &Prompt) -> Option<String>;
+}
+
+/// Credentials from the environment of this process, see [`HOST_ENV`], [`USERNAME_ENV`], [`PASSWORD_ENV`]
+/// and [`SSH_PASSPHRASE_ENV`].
+#[derive(Debug, Default, Clone)]
+pub struct EnvironmentProvider {
+    /// The host that `username` and `password` are for, like `github.com` or `git.example.com:8443`.
+    /// Without it, they aren't given to any remote.
+    pub host: Option<String>,
+    /// The username for remotes on `host`.
+    pub username: Option<String>,
+    /// The password or access token for remotes on `host`.
+    pub password: Option<String>,
+    /// The passphrase of the SSH key in use.
+    pub ssh_passphrase: Option<String>,
+}
+
+impl EnvironmentProvider {
+    /// Read all credentials from the environment variables of this process.
+    pub fn from_env() -> Self {
+        let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+        EnvironmentProvider {
+            host: var(HOST_ENV),
+            username: var(USERNAME_ENV),
+            password: var(PASSWORD_ENV),
+            ssh_passphrase: var(SSH_PASSPHRASE_ENV),
+        }
+    }
+
+    /// Return `true` if `url` is a remote on the configured host, on any port unless the configured host has one.
+    fn is_configured_host(&self, url: &str) -> bool {
+        let (Some(configured), Ok(url)) = (self.host.as_deref(), gix::url::parse(url.into()))
+        else {
+            return false;
+        };
+        let Some(host) = url.host() else {
+            return false;
+        };
+        match configured.rsplit_once(':') {
+            Some((configured, port)) => {
+                configured.eq_ignore_ascii_case(host)
+                    && url.port.map(|p| p.to_string()).as_deref() == Some(port)
+            }
+            None => configured.eq_ignore_ascii_case(host),
+        }
+    }
+}
+
+impl CredentialProvider for EnvironmentProvider {
+    fn name(&self) -> &'static str {
+        "environment"
+    }
+
+    fn answer(&self, prompt: &Prompt) -> Option<String> {
+        match prompt {
+            Prompt::Username { url } => self
+                .is_configured_host(url)
+                .then(|| self.username.clone())
+                .flatten(),
+            Prompt::Password { url } => self
+                .is_configured_host(url)
+                .then(|| self.password.clone())
+                .flatten(),
+            Prompt::Passphrase { .. } => self.ssh_passphrase.clone(),
+            Prompt::Other(_) => None,
+        }
+    }
+}
+
+/// Credentials for HTTP(S) remotes from a credential helper, which is asked using
+/// [Git's protocol for helpers](https://git-scm.com/docs/gitcredentials#_custom_helpers).
+///
+/// This is useful for helpers that aren't configured in Git itself, as Git asks those before prompting.
+#[derive(Debug, Clone)]
+pub struct CredentialHelperProvider {
+    /// The helper, like `store --file /etc/gitbutler/credentials`, `/usr/bin/helper` or `!f() { … }; f`.
+    pub helper: String,
+    /// The directory to run the helper in.
+    pub cwd: PathBuf,
+}
+
+impl CredentialHelperProvider {
+    /// Use the helper in [`CREDENTIAL_HELPER_ENV`], if set, running it in `cwd`.
+    pub fn from_env(cwd: impl Into<PathBuf>) -> Option<Self> {
+        let helper = std::env::var(CREDENTIAL_HELPER_ENV)
+            .ok()
+            .filter(|helper| !helper.trim().is_empty())?;
+        Some(CredentialHelperProvider {
+            helper,
+            cwd: cwd.into(),
+        })
+    }
+
+    /// The command to run for `operation`, interpreted like Git interprets `credential.helper`.
+    fn command(&self, operation: &str) -> String {
+        let helper = self.helper.trim();
+        if let Some(script) = helper.strip_prefix('!') {
+            format!("{script} {operation}")
+        } else if std::path::Path::new(helper.split_whitespace().next().unwrap_or(helper))
+            .is_absolute()
+        {
+            format!("{helper} {operation}")
+        } else {
+            format!("git credential-{helper} {operation}")
+        }
+    }
+
+    /// Ask the helper for the credentials for `url` and return its answer as key-value pairs.
+    fn get(&self, url: &str) -> anyhow::Result<HashMap<String, String>> {
+        let url = gix::url::parse(url.into())?;
+        let mut input = format!("protocol={}\n", url.scheme.as_str());
+        if let Some(host) = url.host() {
+            match url.port {
+                Some(port) => input.push_str(&format!("host={host}:{port}\n")),
+                None => input.push_str(&format!("host={host}\n")),
+            }
+        }
+        if let Some(user) = url.user() {
+            input.push_str(&format!("username={user}\n"));
+        }
+        input.push('\n');
+
+        let mut child = std::process::Command::from(
+            gix::command::prepare(self.command("get"))
+                .with_shell()
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null()),
+        )
+        .current_dir(&self.cwd)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .spawn()?;
+        child
+            .stdin
+            .take()
+            .expect("configured")
+            .write_all(input.as_bytes())?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            anyhow::bail!("credential helper failed with {}", output.status);
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect())
+    }
+}
+
+impl CredentialProvider for CredentialHelperProvider {
+    fn name(&self) -> &'static str {
+        "credential-helper"
+    }
+
+    fn answer(&self, prompt: &Prompt) -> Option<String> {
+        let (url, key) = match prompt {
+            Prompt::Username { url } => (url, "username"),
+            Prompt::Password { url } => (url, "password"),
+            Prompt::Passphrase { .. } | Prompt::Other(_) => return None,
+        };
+        match self.get(url) {
+            Ok(mut answer) => answer.remove(key),
+            Err(err) => {
+                tracing::warn!(?err, "credential helper could not be asked");
+                None
+            }
+        }
+    }
+}
+
+/// Lets SSH authenticate with the keys of the SSH agent listening on `socket`,
+/// so it doesn't need to ask for passphrases.
+#[derive(Debug, Clone)]
+pub struct SshAgentProvider {
+    /// The path to the socket of the agent.
+    pub socket: PathBuf,
+}
+
+impl SshAgentProvider {
+    /// Use the agent whose socket is in [`SSH_AUTH_SOCK_ENV`], if set.
+    pub fn from_env() -> Option<Self> {
+        let socket = std::env::var_os(SSH_AUTH_SOCK_ENV).filter(|socket| !socket.is_empty())?;
+        Some(SshAgentProvider {
+            socket: socket.into(),
+        })
+    }
+}
+
+impl CredentialProvider for SshAgentProvider {
+    fn name(&self) -> &'static str {
+        "ssh-agent"
+    }
+
+    fn envs(&self) -> HashMap<String, String> {
+        HashMap::from([(
+            "SSH_AUTH_SOCK".to_owned(),
+            self.socket.to_string_lossy().into_owned(),
+        )])
+    }
+
+    fn answer(&self, _prompt: &Prompt) -> Option<String> {
+        None
+    }
+}
+
+/// A chain of [`CredentialProvider`]s, which are asked in the order they were added.
+#[derive(Default)]
+pub struct CredentialProviders {
+    providers: Vec<Box<dyn CredentialProvider>>,
+}
+
+impl std::fmt::Debug for CredentialProviders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.providers.iter().map(|p| p.name()))
+            .finish()
+    }
+}
+
+impl CredentialProviders {
+    /// All providers that are configured through the environment of this process,
+    /// with helpers running in `repo_path`.
+    pub fn from_env(repo_path: impl Into<PathBuf>) -> Self {
+        let mut providers = CredentialProviders::default().with(EnvironmentProvider::from_env());
+        if let Some(helper) = CredentialHelperProvider::from_env(repo_path) {
+            providers = providers.with(helper);
+        }
+        if let Some(agent) = SshAgentProvider::from_env() {
+            providers = providers.with(agent);
+        }
+        providers
+    }
+
+    /// Ask `provider` after all providers that were added before.
+    pub fn with(mut self, provider: impl CredentialProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    /// The environment variables of all providers.
+    pub fn envs(&self) -> HashMap<String, String> {
+        self.providers.iter().flat_map(|p| p.envs()).collect()
+    }
+
+    /// Return the answer of the first provider that can answer `prompt`.
+    pub fn answer(&self, prompt: &str) -> Option<String> {
+        let prompt = Prompt::parse(prompt);
+        self.providers.iter().find_map(|provider| {
+            let answer = provider.answer(&prompt)?;
+            tracing::info!(
+                provider = provider.name(),
+                ?prompt,
+                "answered askpass prompt"
+            );
+            Some(answer)
+        })
+    }
+}
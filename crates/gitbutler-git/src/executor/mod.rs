@@ -1,4 +1,4 @@
-use std::{collections::HashMap, path::Path, time::Duration};
+use std::{collections::HashMap, path::Path, sync::atomic::AtomicBool, time::Duration};
 
 #[cfg(any(test, feature = "tokio"))]
 pub mod tokio;
@@ -63,6 +63,22 @@ pub unsafe trait GitExecutor {
         envs: Option<HashMap<String, String>>,
     ) -> Result<(usize, String, String), Self::Error>;
 
+    /// Like [`Self::execute_raw`], but passes every line the command writes to
+    /// stderr to `on_stderr` as soon as it is written. Lines are terminated by
+    /// `\n` or by `\r`, which Git uses to update progress in place.
+    ///
+    /// As soon as `should_interrupt` is set, the command is killed along with
+    /// all processes it started, to the best of the implementation's abilities,
+    /// and `Err` is returned.
+    async fn execute_raw_observed<P: AsRef<Path>>(
+        &self,
+        args: &[&str],
+        cwd: P,
+        envs: Option<HashMap<String, String>>,
+        on_stderr: &mut dyn FnMut(&str),
+        should_interrupt: &AtomicBool,
+    ) -> Result<(usize, String, String), Self::Error>;
+
     /// Executes the given Git command with sane defaults.
     /// `git` is never passed as the first argument (arg 0).
     ///
@@ -74,21 +90,25 @@ pub unsafe trait GitExecutor {
         cwd: P,
         envs: Option<HashMap<String, String>>,
     ) -> Result<(usize, String, String), Self::Error> {
-        let mut args = args.as_ref().to_vec();
-
-        args.insert(0, "--no-pager");
-        // '-c' arguments must be inserted in reverse order; Git does not support
-        // shortflags for '-c' arguments, so they must be separated.
-        args.insert(0, "protocol.version=2");
-        args.insert(0, "-c");
-
-        let mut envs = envs.unwrap_or_default();
-        envs.insert("GIT_TERMINAL_PROMPT".into(), "0".into());
-        envs.insert("LC_ALL".into(), "C".into()); // Force English. We need this for parsing output.
-
+        let (args, envs) = with_defaults(args, envs);
         self.execute_raw(&args, cwd, Some(envs)).await
     }
 
+    /// Like [`Self::execute`], but observable and interruptible like
+    /// [`Self::execute_raw_observed`].
+    async fn execute_observed<P: AsRef<Path>>(
+        &self,
+        args: &[&str],
+        cwd: P,
+        envs: Option<HashMap<String, String>>,
+        on_stderr: &mut dyn FnMut(&str),
+        should_interrupt: &AtomicBool,
+    ) -> Result<(usize, String, String), Self::Error> {
+        let (args, envs) = with_defaults(args, envs);
+        self.execute_raw_observed(&args, cwd, Some(envs), on_stderr, should_interrupt)
+            .await
+    }
+
     /// Creates a named pipe server that is compatible with
     /// the `askpass` utility (see `bin/askpass.rs` and platform-specific
     /// adjacent sources).
@@ -143,6 +163,26 @@ pub unsafe trait GitExecutor {
     async fn stat<P: AsRef<Path>>(&self, path: P) -> Result<FileStat, Self::Error>;
 }
 
+/// Add the arguments and environment variables that [`GitExecutor::execute`] uses.
+fn with_defaults<'a>(
+    args: &[&'a str],
+    envs: Option<HashMap<String, String>>,
+) -> (Vec<&'a str>, HashMap<String, String>) {
+    let mut args = args.to_vec();
+
+    args.insert(0, "--no-pager");
+    // '-c' arguments must be inserted in reverse order; Git does not support
+    // shortflags for '-c' arguments, so they must be separated.
+    args.insert(0, "protocol.version=2");
+    args.insert(0, "-c");
+
+    let mut envs = envs.unwrap_or_default();
+    envs.insert("GIT_TERMINAL_PROMPT".into(), "0".into());
+    envs.insert("LC_ALL".into(), "C".into()); // Force English. We need this for parsing output.
+
+    (args, envs)
+}
+
 /// Stats for a file on the filesystem.
 ///
 /// This is returned by [`GitExecutor::stat`],
@@ -5,15 +5,25 @@ mod unix;
 #[cfg(windows)]
 mod windows;
 
+use futures::{select, FutureExt};
 use gix::bstr::ByteSlice;
-use std::{collections::HashMap, path::Path};
-use tokio::process::Command;
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::Stdio,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use tokio::{io::AsyncReadExt, process::Command};
 
 #[cfg(unix)]
 pub use self::unix::TokioAskpassServer;
 #[cfg(windows)]
 pub use self::windows::TokioAskpassServer;
 
+/// How often [`TokioExecutor`] checks if a command should be interrupted.
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 /// A Git executor implementation using the `git` command-line tool
 /// via [`tokio::process::Command`].
 pub struct TokioExecutor;
@@ -29,75 +39,92 @@ unsafe impl super::GitExecutor for TokioExecutor {
         cwd: P,
         envs: Option<HashMap<String, String>>,
     ) -> Result<(usize, String, String), Self::Error> {
-        let git_exe = gix::path::env::exe_invocation();
-        let mut cmd = Command::new(git_exe);
+        let mut cmd = command(args, cwd, envs);
+        let output = cmd.output().await?;
+        Ok(finish(&mut cmd, output))
+    }
 
-        cmd.kill_on_drop(true);
-        cmd.current_dir(cwd);
+    async fn execute_raw_observed<P: AsRef<Path>>(
+        &self,
+        args: &[&str],
+        cwd: P,
+        envs: Option<HashMap<String, String>>,
+        on_stderr: &mut dyn FnMut(&str),
+        should_interrupt: &AtomicBool,
+    ) -> Result<(usize, String, String), Self::Error> {
+        let mut cmd = command(args, cwd, envs);
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // Put Git into its own process group, so it can be killed along with everything it started.
+        #[cfg(unix)]
+        cmd.process_group(0);
 
-        #[cfg(not(windows))]
-        cmd.args(args);
+        let mut child = cmd.spawn()?;
+        let pid = child.id();
+        let mut stdout = child.stdout.take().expect("piped");
+        let mut stderr = child.stderr.take().expect("piped");
 
-        #[cfg(windows)]
-        {
-            // On Windows, we have to pass the arguments
-            // as-is using a special method since Windows
-            // seems to parse backslashes for some unknown
-            // reason.
-            for arg in args {
-                cmd.raw_arg(arg);
+        let read_stdout = async {
+            let mut buf = Vec::new();
+            stdout.read_to_end(&mut buf).await.map(|_| buf)
+        };
+        let read_stderr = async {
+            let mut buf = Vec::new();
+            let mut line_start = 0;
+            let mut chunk = [0; 4096];
+            loop {
+                let len = stderr.read(&mut chunk).await?;
+                if len == 0 {
+                    break;
+                }
+                buf.extend_from_slice(&chunk[..len]);
+                while let Some(line_len) = buf[line_start..]
+                    .iter()
+                    .position(|b| matches!(b, b'\r' | b'\n'))
+                {
+                    let line = &buf[line_start..line_start + line_len];
+                    if !line.is_empty() {
+                        on_stderr(&String::from_utf8_lossy(line));
+                    }
+                    line_start += line_len + 1;
+                }
             }
-
-            // On windows, CLI applications that aren't the `windows` subsystem
-            // will create and show a console window that pops up next to the
-            // main application window when run. We disable this behavior when
-            // running `git.exe` by setting the `CREATE_NO_WINDOW` flag.
-            cmd.creation_flags(0x0800_0000); // CREATE_NO_WINDOW
-        }
-
-        if let Some(envs) = envs {
-            #[cfg(not(windows))]
-            cmd.envs(envs);
-
-            // On Windows, we have to escape backslashes in
-            // environment variable values. Not sure why.
-            #[cfg(windows)]
-            {
-                cmd.envs(envs.iter().map(|(k, v)| {
-                    let v = v.replace('\\', "\\\\");
-                    (k, v)
-                }));
+            if line_start < buf.len() {
+                on_stderr(&String::from_utf8_lossy(&buf[line_start..]));
             }
+            Ok(buf)
+        };
+        let mut run = core::pin::pin!(async {
+            let (stdout, stderr, status) =
+                futures::try_join!(read_stdout, read_stderr, child.wait())?;
+            Ok::<_, std::io::Error>(std::process::Output {
+                status,
+                stdout,
+                stderr,
+            })
         }
-
-        let output = cmd.output().await?;
-
-        debug_log_sanitised_git_cmd(&mut cmd);
-
-        #[cfg(test)]
-        {
-            eprintln!(
-                "\n\n GIT STDOUT:\n\n{}\n\nGIT STDERR:\n\n{}\n\nGIT EXIT CODE: {}\n",
-                String::from_utf8_lossy(&output.stdout),
-                String::from_utf8_lossy(&output.stderr),
-                output.status.code().unwrap_or(127) as usize
-            );
+        .fuse());
+        let mut interrupted = core::pin::pin!(async {
+            while !should_interrupt.load(Ordering::Relaxed) {
+                tokio::time::sleep(INTERRUPT_POLL_INTERVAL).await;
+            }
         }
+        .fuse());
 
-        if !output.status.success() {
-            tracing::error!(
-                ?cmd,
-                stdout = output.stdout.as_bstr().to_string(),
-                stderr = output.stderr.as_bstr().to_string(),
-                "Git invocation failed"
-            );
+        select! {
+            output = run => Ok(finish(&mut cmd, output?)),
+            () = interrupted => {
+                #[cfg(unix)]
+                if let Some(pid) = pid {
+                    kill_process_group(pid);
+                }
+                #[cfg(not(unix))]
+                let _ = pid;
+                // Dropping the child kills Git itself, in case it didn't terminate yet.
+                Err(std::io::ErrorKind::Interrupted.into())
+            }
         }
-
-        Ok((
-            output.status.code().unwrap_or(127) as usize,
-            String::from_utf8_lossy(&output.stdout).trim().into(),
-            String::from_utf8_lossy(&output.stderr).trim().into(),
-        ))
     }
 
     async unsafe fn create_askpass_server(&self) -> Result<Self::ServerHandle, Self::Error> {
@@ -123,6 +150,98 @@ unsafe impl super::GitExecutor for TokioExecutor {
     }
 }
 
+/// Create the command to run Git with `args` in `cwd`, with `envs` set in addition to our own environment.
+fn command<P: AsRef<Path>>(
+    args: &[&str],
+    cwd: P,
+    envs: Option<HashMap<String, String>>,
+) -> Command {
+    let git_exe = gix::path::env::exe_invocation();
+    let mut cmd = Command::new(git_exe);
+
+    cmd.kill_on_drop(true);
+    cmd.current_dir(cwd);
+
+    #[cfg(not(windows))]
+    cmd.args(args);
+
+    #[cfg(windows)]
+    {
+        // On Windows, we have to pass the arguments
+        // as-is using a special method since Windows
+        // seems to parse backslashes for some unknown
+        // reason.
+        for arg in args {
+            cmd.raw_arg(arg);
+        }
+
+        // On windows, CLI applications that aren't the `windows` subsystem
+        // will create and show a console window that pops up next to the
+        // main application window when run. We disable this behavior when
+        // running `git.exe` by setting the `CREATE_NO_WINDOW` flag.
+        cmd.creation_flags(0x0800_0000); // CREATE_NO_WINDOW
+    }
+
+    if let Some(envs) = envs {
+        #[cfg(not(windows))]
+        cmd.envs(envs);
+
+        // On Windows, we have to escape backslashes in
+        // environment variable values. Not sure why.
+        #[cfg(windows)]
+        {
+            cmd.envs(envs.iter().map(|(k, v)| {
+                let v = v.replace('\\', "\\\\");
+                (k, v)
+            }));
+        }
+    }
+    cmd
+}
+
+/// Log the result of running `cmd`, and turn its `output` into `(exit_code, stdout, stderr)`.
+fn finish(cmd: &mut Command, output: std::process::Output) -> (usize, String, String) {
+    debug_log_sanitised_git_cmd(cmd);
+
+    #[cfg(test)]
+    {
+        eprintln!(
+            "\n\n GIT STDOUT:\n\n{}\n\nGIT STDERR:\n\n{}\n\nGIT EXIT CODE: {}\n",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr),
+            output.status.code().unwrap_or(127) as usize
+        );
+    }
+
+    if !output.status.success() {
+        tracing::error!(
+            ?cmd,
+            stdout = output.stdout.as_bstr().to_string(),
+            stderr = output.stderr.as_bstr().to_string(),
+            "Git invocation failed"
+        );
+    }
+
+    (
+        output.status.code().unwrap_or(127) as usize,
+        String::from_utf8_lossy(&output.stdout).trim().into(),
+        String::from_utf8_lossy(&output.stderr).trim().into(),
+    )
+}
+
+/// Terminate the process group of Git with the given `pid`. This includes the `setsid` helper,
+/// which passes the signal on to the session it started for SSH.
+#[cfg(unix)]
+fn kill_process_group(pid: u32) {
+    use nix::{
+        sys::signal::{killpg, Signal},
+        unistd::Pid,
+    };
+    if let Err(err) = killpg(Pid::from_raw(pid as i32), Signal::SIGTERM) {
+        tracing::warn!(?err, pid, "failed to terminate the process group of Git");
+    }
+}
+
 fn debug_log_sanitised_git_cmd(cmd: &mut Command) {
     cmd.env_remove("GITBUTLER_ASKPASS_SECRET")
         .env_remove("GITBUTLER_ASKPASS_PIPE")
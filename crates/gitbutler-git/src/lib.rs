@@ -16,15 +16,20 @@
 ))]
 compile_error!("BUG: in production code this flag should not be set, nor do we run test with `cargo test --release`. Benches must use `--features benches`");
 
+pub mod credentials;
 mod error;
 pub(crate) mod executor;
+pub mod local;
+mod progress;
 mod refspec;
 mod repository;
 
 #[cfg(feature = "tokio")]
 pub use self::executor::tokio;
 pub use self::{
+    credentials::{CredentialProvider, CredentialProviders},
     error::Error,
+    progress::{Progress, Stage},
     refspec::{Error as RefSpecError, RefSpec},
     repository::{fetch, push, sign_commit},
 };
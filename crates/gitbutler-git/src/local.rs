@@ -0,0 +1,492 @@
+//! An in-process transport for remotes that are repositories on this machine, like `file://` URLs and paths.
+//!
+//! Instead of spawning `git`, objects are copied directly between the object databases of both repositories,
+//! and refs are updated with the same rules `git fetch` and `git push` use. This makes it fast, cancellable
+//! and usable without the askpass binaries, which is why it's preferred for such remotes.
+//!
+//! Pushing to remotes that would run hooks or that have `receive.*` configuration isn't supported, as only
+//! `git receive-pack` can honour them. Use [`can_push()`] to learn if the Git executable is needed instead.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use gix::refs::transaction::PreviousValue;
+
+use crate::{
+    progress::{Progress, Stage},
+    RefSpec,
+};
+
+/// Errors of the in-process transport.
+///
+/// You probably don't want to use this type. Use [`crate::Error`] instead.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The operation was cancelled.
+    #[error("the operation was cancelled")]
+    Interrupted,
+    /// The remote isn't a repository on this machine.
+    #[error("remote '{0}' is not a repository on this machine")]
+    NotLocal(String),
+    /// The refspec can't be used with this transport.
+    #[error("unsupported refspec: {0}")]
+    UnsupportedRefSpec(String),
+    /// A ref would lose commits if it was updated, and the update wasn't forced.
+    #[error("updating {0} would not be a fast-forward")]
+    NotFastForward(String),
+    /// The ref to push to is checked out in the worktree of the remote repository.
+    #[error("refusing to update {0} as it is checked out in the remote repository")]
+    CheckedOut(String),
+    /// The remote repository has to receive the push with Git, for the given reason.
+    #[error("pushing to this remote requires git, as it has {0}")]
+    RequiresGit(String),
+    /// Reading from or writing to one of the repositories failed.
+    #[error(transparent)]
+    Git(Box<dyn std::error::Error + Send + Sync + 'static>),
+}
+
+fn git(err: impl Into<Box<dyn std::error::Error + Send + Sync + 'static>>) -> Error {
+    Error::Git(err.into())
+}
+
+/// Return the path of the repository that `remote` of the repository at `repo_path` refers to in `direction`,
+/// or `None` if it isn't a local repository, or it doesn't exist.
+pub fn remote_path(
+    repo_path: &Path,
+    remote: &str,
+    direction: gix::remote::Direction,
+) -> Option<PathBuf> {
+    let repo = gix::open(repo_path).ok()?;
+    let remote = repo.find_remote(remote).ok()?;
+    let url = remote.url(direction)?;
+    if url.scheme != gix::url::Scheme::File {
+        return None;
+    }
+    let path = gix::path::from_bstr(url.path.as_ref()).into_owned();
+    let path = if path.is_relative() {
+        repo.workdir().unwrap_or_else(|| repo.path()).join(path)
+    } else {
+        path
+    };
+    path.exists().then_some(path)
+}
+
+/// Return `true` if `remote` of the repository at `repo_path` is a local repository that [`push()`] can push to,
+/// which is the case if receiving the push doesn't run hooks and isn't configured with `receive.*` settings.
+pub fn can_push(repo_path: &Path, remote: &str) -> bool {
+    remote_path(repo_path, remote, gix::remote::Direction::Push)
+        .and_then(|path| gix::open(path).ok())
+        .is_some_and(|remote_repo| requires_git(&remote_repo).is_none())
+}
+
+/// The hooks `git receive-pack` runs when receiving a push.
+const RECEIVE_HOOKS: &[&str] = &[
+    "pre-receive",
+    "update",
+    "proc-receive",
+    "reference-transaction",
+    "push-to-checkout",
+    "post-receive",
+    "post-update",
+];
+
+/// Return why receiving a push in `remote_repo` requires `git receive-pack`, or `None` if it doesn't.
+fn requires_git(remote_repo: &gix::Repository) -> Option<String> {
+    let config = remote_repo.config_snapshot();
+    if config
+        .plumbing()
+        .sections_by_name("receive")
+        .is_some_and(|mut sections| sections.next().is_some())
+    {
+        return Some("receive.* configuration".to_owned());
+    }
+    if config.string("core.hooksPath").is_some() {
+        return Some("core.hooksPath configured".to_owned());
+    }
+    let hooks = remote_repo.common_dir().join("hooks");
+    RECEIVE_HOOKS
+        .iter()
+        .find(|hook| hooks.join(hook).is_file())
+        .map(|hook| format!("a {hook} hook"))
+}
+
+fn open_remote(
+    repo_path: &Path,
+    remote: &str,
+    direction: gix::remote::Direction,
+) -> Result<(gix::Repository, gix::Repository), Error> {
+    let remote_path = remote_path(repo_path, remote, direction)
+        .ok_or_else(|| Error::NotLocal(remote.to_owned()))?;
+    Ok((
+        gix::open(repo_path).map_err(git)?,
+        gix::open(remote_path).map_err(git)?,
+    ))
+}
+
+/// Fetches the given refspec from the local repository `remote` into the repository at `repo_path`,
+/// pruning refs that don't exist on the remote anymore and writing `FETCH_HEAD`, like `git fetch --prune` would.
+///
+/// `progress` is called as objects are copied, and the operation stops as soon as `should_interrupt` is set.
+pub fn fetch(
+    repo_path: &Path,
+    remote: &str,
+    refspec: RefSpec,
+    mut progress: impl FnMut(Progress),
+    should_interrupt: &AtomicBool,
+) -> Result<(), crate::Error<Error>> {
+    let (local, remote_repo) = open_remote(repo_path, remote, gix::remote::Direction::Fetch)?;
+    let (source, destination) = source_and_destination(&refspec)?;
+
+    let mut updates = Vec::new();
+    for reference in remote_repo.references().map_err(git)?.all().map_err(git)? {
+        let reference = reference.map_err(git)?;
+        let Some(id) = reference.try_id() else {
+            continue;
+        };
+        let name = reference.name().as_bstr().to_string();
+        if let Some(matched) = match_pattern(source, &name) {
+            updates.push((expand_pattern(destination, matched), id.detach(), name));
+        }
+    }
+    if updates.is_empty() && !source.contains('*') {
+        return Err(crate::Error::RefNotFound(source.to_owned()));
+    }
+
+    let tips: Vec<_> = updates.iter().map(|(_, id, _)| *id).collect();
+    copy_objects(
+        &remote_repo,
+        &local,
+        &tips,
+        Stage::ReceivingObjects,
+        &mut progress,
+        should_interrupt,
+    )?;
+
+    let fetched: HashSet<_> = updates.iter().map(|(name, ..)| name.clone()).collect();
+    for (name, new, _) in &updates {
+        let current = local.try_find_reference(name.as_str()).map_err(git)?;
+        let current = current.and_then(|r| r.try_id().map(|id| id.detach()));
+        if current == Some(*new) {
+            continue;
+        }
+        if let Some(current) = current {
+            if !refspec.update_non_fastforward && !is_ancestor(&local, current, *new)? {
+                return Err(Error::NotFastForward(name.clone()).into());
+            }
+        }
+        local
+            .reference(
+                name.as_str(),
+                *new,
+                PreviousValue::Any,
+                format!("fetch: {remote}"),
+            )
+            .map_err(git)?;
+    }
+
+    let url = local
+        .find_remote(remote)
+        .map_err(git)?
+        .url(gix::remote::Direction::Fetch)
+        .map(|url| url.to_bstring().to_string())
+        .unwrap_or_default();
+    // Like Git, only refs of refspecs without wildcards are meant to be merged.
+    let for_merge = !source.contains('*');
+    let fetch_head: String = updates
+        .iter()
+        .map(|(_, id, remote_name)| fetch_head_line(*id, remote_name, for_merge, &url))
+        .collect();
+    std::fs::write(local.path().join("FETCH_HEAD"), fetch_head).map_err(git)?;
+
+    // Prune refs that were fetched by this refspec before, but are gone on the remote.
+    if destination.contains('*') {
+        let mut stale = Vec::new();
+        for reference in local.references().map_err(git)?.all().map_err(git)? {
+            let reference = reference.map_err(git)?;
+            // Symbolic refs like `refs/remotes/origin/HEAD` point to a fetched ref, and are never fetched themselves.
+            if reference.try_id().is_none() {
+                continue;
+            }
+            let name = reference.name().as_bstr().to_string();
+            if match_pattern(destination, &name).is_some() && !fetched.contains(&name) {
+                stale.push(reference);
+            }
+        }
+        for reference in stale {
+            reference.delete().map_err(git)?;
+        }
+    }
+    Ok(())
+}
+
+/// Pushes the given refspec from the repository at `repo_path` to the local repository `remote`.
+///
+/// Refs that aren't fast-forwarded are only updated if `force` is set, and with `force_push_protection`,
+/// only if the remote ref still is where our remote-tracking branch says it is, like `git push --force-with-lease`.
+///
+/// `progress` is called as objects are copied, and the operation stops as soon as `should_interrupt` is set.
+pub fn push(
+    repo_path: &Path,
+    remote: &str,
+    refspec: RefSpec,
+    force: bool,
+    force_push_protection: bool,
+    mut progress: impl FnMut(Progress),
+    should_interrupt: &AtomicBool,
+) -> Result<(), crate::Error<Error>> {
+    let (local, remote_repo) = open_remote(repo_path, remote, gix::remote::Direction::Push)?;
+    if let Some(reason) = requires_git(&remote_repo) {
+        return Err(Error::RequiresGit(reason).into());
+    }
+    let (source, destination) = source_and_destination(&refspec)?;
+    if source.contains('*') || !destination.starts_with("refs/") {
+        return Err(Error::UnsupportedRefSpec(refspec.to_string()).into());
+    }
+    let new = local
+        .rev_parse_single(source)
+        .map_err(|_| crate::Error::RefNotFound(source.to_owned()))?
+        .detach();
+
+    let head_name = remote_repo.head_name().map_err(git)?;
+    if remote_repo.workdir().is_some()
+        && head_name.is_some_and(|head| head.as_bstr() == destination)
+    {
+        return Err(Error::CheckedOut(destination.to_owned()).into());
+    }
+
+    copy_objects(
+        &local,
+        &remote_repo,
+        &[new],
+        Stage::WritingObjects,
+        &mut progress,
+        should_interrupt,
+    )?;
+
+    let current = remote_repo
+        .try_find_reference(destination)
+        .map_err(git)?
+        .and_then(|r| r.try_id().map(|id| id.detach()));
+    let expected = match current {
+        None => PreviousValue::MustNotExist,
+        Some(current) if current == new => return Ok(()),
+        Some(current) => {
+            let is_forced = force || refspec.update_non_fastforward;
+            if !is_forced && !is_ancestor(&remote_repo, current, new)? {
+                return Err(Error::NotFastForward(destination.to_owned()).into());
+            }
+            if is_forced && force_push_protection {
+                let tracking = tracking_ref(remote, destination)
+                    .and_then(|name| local.try_find_reference(name.as_str()).ok().flatten())
+                    .and_then(|r| r.try_id().map(|id| id.detach()));
+                if tracking != Some(current) {
+                    return Err(crate::Error::ForcePushProtection(Error::NotFastForward(
+                        destination.to_owned(),
+                    )));
+                }
+            }
+            PreviousValue::MustExistAndMatch(gix::refs::Target::Object(current))
+        }
+    };
+    remote_repo
+        .reference(destination, new, expected, "push")
+        .map_err(git)?;
+
+    // Like Git, remember where the remote ref is now.
+    if let Some(tracking) = tracking_ref(remote, destination) {
+        local
+            .reference(tracking.as_str(), new, PreviousValue::Any, "update by push")
+            .map_err(git)?;
+    }
+    Ok(())
+}
+
+fn source_and_destination(refspec: &RefSpec) -> Result<(&str, &str), Error> {
+    match (refspec.source.as_deref(), refspec.destination.as_deref()) {
+        (Some(source), Some(destination))
+            if source.matches('*').count() == destination.matches('*').count() =>
+        {
+            Ok((source, destination))
+        }
+        (Some(source), None) if !source.contains('*') => Ok((source, source)),
+        _ => Err(Error::UnsupportedRefSpec(refspec.to_string())),
+    }
+}
+
+/// If `name` matches `pattern`, which may contain a single `*`, return the part of `name` matched by `*`.
+fn match_pattern<'a>(pattern: &str, name: &'a str) -> Option<&'a str> {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => name
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix(suffix)),
+        None => (pattern == name).then_some(""),
+    }
+}
+
+fn expand_pattern(pattern: &str, matched: &str) -> String {
+    pattern.replacen('*', matched, 1)
+}
+
+/// A line of `FETCH_HEAD` for `id` fetched from `remote_name` of the remote at `url`, as Git writes it.
+fn fetch_head_line(id: gix::ObjectId, remote_name: &str, for_merge: bool, url: &str) -> String {
+    let description = if let Some(branch) = remote_name.strip_prefix("refs/heads/") {
+        format!("branch '{branch}'")
+    } else if let Some(tag) = remote_name.strip_prefix("refs/tags/") {
+        format!("tag '{tag}'")
+    } else if let Some(branch) = remote_name.strip_prefix("refs/remotes/") {
+        format!("remote-tracking branch '{branch}'")
+    } else {
+        format!("'{remote_name}'")
+    };
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let merge = if for_merge { "" } else { "not-for-merge" };
+    format!("{id}\t{merge}\t{description} of {url}\n")
+}
+
+/// The name of the remote-tracking branch of `remote` for the remote ref `remote_ref`, if it is a branch.
+fn tracking_ref(remote: &str, remote_ref: &str) -> Option<String> {
+    remote_ref
+        .strip_prefix("refs/heads/")
+        .map(|branch| format!("refs/remotes/{remote}/{branch}"))
+}
+
+/// Return `true` if `ancestor` can be reached from `id`, which is the case if it's their merge-base.
+fn is_ancestor(
+    repo: &gix::Repository,
+    ancestor: gix::ObjectId,
+    id: gix::ObjectId,
+) -> Result<bool, Error> {
+    match repo.merge_base(ancestor, id) {
+        Ok(base) => Ok(base.detach() == ancestor),
+        Err(gix::repository::merge_base::Error::NotFound { .. }) => Ok(false),
+        Err(err) => Err(git(err)),
+    }
+}
+
+/// Copy all objects reachable from `tips` in `from` that are missing in `to`.
+///
+/// The walk stops at the commits the refs of `to` point to, as these are assumed to be complete along with their history,
+/// as it's the case for every repository that was only ever changed by Git. The same is assumed for every tree `to` has.
+/// Objects are written after everything they refer to, so whatever was copied before an interruption is complete as well.
+fn copy_objects(
+    from: &gix::Repository,
+    to: &gix::Repository,
+    tips: &[gix::ObjectId],
+    stage: Stage,
+    progress: &mut impl FnMut(Progress),
+    should_interrupt: &AtomicBool,
+) -> Result<(), Error> {
+    let mut objects = Vec::new();
+    let mut seen = HashSet::new();
+    let mut commits = Vec::new();
+    for tip in tips.iter().filter(|id| !to.has_object(id)) {
+        let mut object = from.find_object(*tip).map_err(git)?;
+        // Tags may point to other tags, which are copied along with the object they finally point to.
+        while object.kind == gix::object::Kind::Tag {
+            if seen.insert(object.id) {
+                objects.push(object.id);
+            }
+            let target = object.into_tag().target_id().map_err(git)?.detach();
+            object = from.find_object(target).map_err(git)?;
+        }
+        match object.kind {
+            gix::object::Kind::Commit => commits.push(object.id),
+            gix::object::Kind::Tree => collect_tree(from, to, object.id, &mut seen, &mut objects)?,
+            gix::object::Kind::Blob | gix::object::Kind::Tag => {
+                if !to.has_object(object.id) && seen.insert(object.id) {
+                    objects.push(object.id);
+                }
+            }
+        }
+    }
+    if !commits.is_empty() {
+        let mut hidden = Vec::new();
+        for reference in to.references().map_err(git)?.all().map_err(git)? {
+            let Some(id) = reference.map_err(git)?.try_id().map(|id| id.detach()) else {
+                continue;
+            };
+            // Refs may also point to trees or blobs, which don't hide anything.
+            if let Some(commit) = from
+                .find_object(id)
+                .ok()
+                .and_then(|object| object.peel_to_commit().ok())
+            {
+                hidden.push(commit.id);
+            }
+        }
+        for info in from
+            .rev_walk(commits)
+            .with_hidden(hidden)
+            .all()
+            .map_err(git)?
+        {
+            if should_interrupt.load(Ordering::Relaxed) {
+                return Err(Error::Interrupted);
+            }
+            let commit = from.find_commit(info.map_err(git)?.id).map_err(git)?;
+            if to.has_object(commit.id) || !seen.insert(commit.id) {
+                continue;
+            }
+            objects.push(commit.id);
+            let tree = commit.tree_id().map_err(git)?.detach();
+            collect_tree(from, to, tree, &mut seen, &mut objects)?;
+        }
+    }
+    if objects.is_empty() {
+        return Ok(());
+    }
+
+    let total = objects.len() as u64;
+    progress(Progress {
+        stage: Stage::CountingObjects,
+        done: total,
+        total: Some(total),
+        bytes: None,
+    });
+    let mut bytes = 0;
+    // Objects were collected before the objects they refer to, and commits before their parents.
+    for (done, id) in objects.into_iter().rev().enumerate() {
+        if should_interrupt.load(Ordering::Relaxed) {
+            return Err(Error::Interrupted);
+        }
+        let object = from.find_object(id).map_err(git)?;
+        to.write_buf(object.kind, &object.data).map_err(git)?;
+        bytes += object.data.len() as u64;
+        progress(Progress {
+            stage,
+            done: done as u64 + 1,
+            total: Some(total),
+            bytes: Some(bytes),
+        });
+    }
+    Ok(())
+}
+
+/// Collect `tree` and all objects it contains into `objects`, except for those `to` already has, or that were `seen` before.
+fn collect_tree(
+    from: &gix::Repository,
+    to: &gix::Repository,
+    tree: gix::ObjectId,
+    seen: &mut HashSet<gix::ObjectId>,
+    objects: &mut Vec<gix::ObjectId>,
+) -> Result<(), Error> {
+    if to.has_object(tree) || !seen.insert(tree) {
+        return Ok(());
+    }
+    objects.push(tree);
+    for entry in from.find_tree(tree).map_err(git)?.iter() {
+        let entry = entry.map_err(git)?;
+        let id = entry.oid().to_owned();
+        if entry.mode().is_tree() {
+            collect_tree(from, to, id, seen, objects)?;
+        } else if !entry.mode().is_commit() && !to.has_object(id) && seen.insert(id) {
+            // Submodule commits are never part of the repository that refers to them.
+            objects.push(id);
+        }
+    }
+    Ok(())
+}
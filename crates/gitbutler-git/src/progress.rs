@@ -0,0 +1,94 @@
+/// A stage of a fetch or push, in the order they usually happen in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+pub enum Stage {
+    /// Finding the objects that need to be transferred.
+    CountingObjects,
+    /// Compressing the objects to transfer.
+    CompressingObjects,
+    /// Receiving objects from the remote when fetching.
+    ReceivingObjects,
+    /// Reconstructing objects that were received as deltas.
+    ResolvingDeltas,
+    /// Sending objects to the remote when pushing.
+    WritingObjects,
+}
+
+/// The progress of a fetch or push within its current [`Stage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct Progress {
+    /// What is currently done.
+    pub stage: Stage,
+    /// How many objects of the stage are done.
+    pub done: u64,
+    /// How many objects there are in total in this stage, if known.
+    pub total: Option<u64>,
+    /// How many bytes were transferred so far, if known.
+    pub bytes: Option<u64>,
+}
+
+impl Progress {
+    /// Parse a line of the progress Git writes to stderr with `--progress`, like
+    /// `Receiving objects:  45% (9/20), 1.20 MiB | 1.00 MiB/s`, or `remote: Counting objects: 5, done.`
+    /// for progress of the remote.
+    ///
+    /// Return `None` if `line` isn't about progress. Git must run with `LC_ALL=C` for this to work.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let line = line.strip_prefix("remote:").map_or(line, str::trim_start);
+        let (title, rest) = line.split_once(':')?;
+        let stage = match title {
+            "Enumerating objects" | "Counting objects" => Stage::CountingObjects,
+            "Compressing objects" => Stage::CompressingObjects,
+            "Receiving objects" => Stage::ReceivingObjects,
+            "Resolving deltas" => Stage::ResolvingDeltas,
+            "Writing objects" => Stage::WritingObjects,
+            _ => return None,
+        };
+
+        let mut parts = rest.split(',').map(str::trim);
+        let counts = parts.next()?;
+        let (done, total) = match counts.split_once('(') {
+            // `45% (9/20)`
+            Some((_percent, counts)) => {
+                let (done, total) = counts.strip_suffix(')')?.split_once('/')?;
+                (done.parse().ok()?, Some(total.parse().ok()?))
+            }
+            // `5`, when the total isn't known.
+            None => (counts.parse().ok()?, None),
+        };
+        let bytes = parts
+            .next()
+            .and_then(|throughput| parse_bytes(throughput.split('|').next()?.trim()));
+        Some(Progress {
+            stage,
+            done,
+            total,
+            bytes,
+        })
+    }
+}
+
+/// Parse an amount of bytes the way Git writes it, like `300 bytes` or `1.20 MiB`.
+fn parse_bytes(amount: &str) -> Option<u64> {
+    let (value, unit) = amount.split_once(' ')?;
+    let value: f64 = value.parse().ok()?;
+    let factor: u64 = match unit {
+        "bytes" | "byte" => 1,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        "TiB" => 1 << 40,
+        _ => return None,
+    };
+    Some((value * factor as f64) as u64)
+}
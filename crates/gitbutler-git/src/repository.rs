@@ -1,10 +1,15 @@
-use std::{collections::HashMap, path::Path, time::Duration};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
 use futures::{select, FutureExt};
 use rand::Rng;
 
 use super::executor::{AskpassServer, GitExecutor, Pid, Socket};
-use crate::RefSpec;
+use crate::{CredentialProviders, Progress, RefSpec};
 
 /// The number of characters in the secret used for checking
 /// askpass invocations by ssh/git when connecting to our process.
@@ -51,6 +56,8 @@ pub enum RepositoryError<
     AskpassExecutableMismatch,
     #[error("Askpass Not found. Run `cargo build -p gitbutler-git` to get the binaries needed")]
     AskpassExecutableNotFound,
+    #[error("the git command was cancelled")]
+    Interrupted,
 }
 
 /// Higher level errors that can occur when interacting with the CLI.
@@ -60,12 +67,20 @@ pub type Error<E> = RepositoryError<
     <<<E as GitExecutor>::ServerHandle as AskpassServer>::SocketHandle as Socket>::Error,
 >;
 
+/// Run Git with `args`, answering its prompts with `credentials` or `on_prompt`.
+///
+/// Progress that Git writes to stderr is passed to `on_progress` instead of being returned,
+/// and Git is killed as soon as `should_interrupt` is set.
 #[cold]
+#[expect(clippy::too_many_arguments)]
 async fn execute_with_auth_harness<P, F, Fut, E, Extra>(
     repo_path: P,
     executor: &E,
     args: &[&str],
     envs: Option<HashMap<String, String>>,
+    credentials: &CredentialProviders,
+    mut on_progress: impl FnMut(Progress),
+    should_interrupt: &AtomicBool,
     mut on_prompt: F,
     extra: Extra,
 ) -> Result<(usize, String, String), Error<E>>
@@ -137,6 +152,9 @@ where
         .collect::<String>();
 
     let mut envs = envs.unwrap_or_default();
+    for (key, value) in credentials.envs() {
+        envs.entry(key).or_insert(value);
+    }
     envs.insert("GITBUTLER_ASKPASS_PIPE".into(), sock_server.to_string());
     envs.insert("GITBUTLER_ASKPASS_SECRET".into(), secret.clone());
     envs.insert("SSH_ASKPASS".into(), askpath_path);
@@ -193,12 +211,21 @@ where
         ),
     );
 
+    let mut on_stderr = |line: &str| {
+        if let Some(progress) = Progress::parse(line) {
+            on_progress(progress);
+        }
+    };
     let mut child_process = core::pin::pin! {
         async {
-            executor
-                .execute(args, repo_path, Some(envs))
-                .await
-                .map_err(Error::<E>::Exec)
+            let res = executor
+                .execute_observed(args, repo_path, Some(envs), &mut on_stderr, should_interrupt)
+                .await;
+            if should_interrupt.load(Ordering::Relaxed) {
+                return Err(Error::<E>::Interrupted);
+            }
+            let (status, stdout, stderr) = res.map_err(Error::<E>::Exec)?;
+            Ok((status, stdout, without_progress(&stderr)))
         }.fuse()
     };
 
@@ -263,8 +290,11 @@ where
                 // get the prompt
                 let prompt = sock.read_line().await.map_err(Error::<E>::AskpassIo)?;
 
-                // call the prompt handler
-                let response = on_prompt(prompt.clone(), extra.clone()).await;
+                // let the credential providers answer, and only then call the prompt handler
+                let response = match credentials.answer(&prompt) {
+                    Some(response) => Some(response),
+                    None => on_prompt(prompt.clone(), extra.clone()).await,
+                };
                 if let Some(response) = response {
                     sock.write_line(&response).await.map_err(Error::<E>::AskpassIo)?;
                 } else {
@@ -276,15 +306,22 @@ where
 }
 
 /// Fetches the given refspec from the given remote in the repository
-/// at the given path. Any prompts for the user are passed to the asynchronous
-/// callback `on_prompt` which should return the user's response or `None` if the
-/// operation should be aborted, in which case an `Err` value is returned from this
-/// function.
+/// at the given path. Prompts that none of the `credentials` can answer are
+/// passed to the asynchronous callback `on_prompt` which should return the
+/// user's response or `None` if the operation should be aborted, in which case
+/// an `Err` value is returned from this function.
+///
+/// `on_progress` is called whenever Git reports progress, and Git is killed along
+/// with SSH as soon as `should_interrupt` is set.
+#[expect(clippy::too_many_arguments)]
 pub async fn fetch<P, F, Fut, E, Extra>(
     repo_path: P,
     executor: E,
     remote: &str,
     refspec: RefSpec,
+    credentials: &CredentialProviders,
+    on_progress: impl FnMut(Progress),
+    should_interrupt: &AtomicBool,
     on_prompt: F,
     extra: Extra,
 ) -> Result<(), crate::Error<Error<E>>>
@@ -295,15 +332,25 @@ where
     Fut: std::future::Future<Output = Option<String>>,
     Extra: Send + Clone,
 {
-    let mut args = vec!["fetch", "--quiet", "--prune"];
+    let mut args = vec!["fetch", "--quiet", "--progress", "--prune"];
 
     let refspec = refspec.to_string();
 
     args.push(remote);
     args.push(&refspec);
 
-    let (status, stdout, stderr) =
-        execute_with_auth_harness(repo_path, &executor, &args, None, on_prompt, extra).await?;
+    let (status, stdout, stderr) = execute_with_auth_harness(
+        repo_path,
+        &executor,
+        &args,
+        None,
+        credentials,
+        on_progress,
+        should_interrupt,
+        on_prompt,
+        extra,
+    )
+    .await?;
 
     if status == 0 {
         Ok(())
@@ -334,9 +381,12 @@ where
 }
 
 /// Pushes a refspec to the given remote in the repository at the given path.
-/// Any prompts for the user are passed to the asynchronous callback `on_prompt`,
-/// which should return the user's response or `None` if the operation should be
-/// aborted, in which case an `Err` value is returned from this function.
+/// Prompts that none of the `credentials` can answer are passed to the asynchronous
+/// callback `on_prompt`, which should return the user's response or `None` if the
+/// operation should be aborted, in which case an `Err` value is returned from this function.
+///
+/// `on_progress` is called whenever Git reports progress, and Git is killed along
+/// with SSH as soon as `should_interrupt` is set.
 #[expect(clippy::too_many_arguments)]
 pub async fn push<P, F, Fut, E, Extra>(
     repo_path: P,
@@ -345,6 +395,9 @@ pub async fn push<P, F, Fut, E, Extra>(
     refspec: RefSpec,
     force: bool,
     force_push_protection: bool,
+    credentials: &CredentialProviders,
+    on_progress: impl FnMut(Progress),
+    should_interrupt: &AtomicBool,
     on_prompt: F,
     extra: Extra,
 ) -> Result<(), crate::Error<Error<E>>>
@@ -355,7 +408,7 @@ where
     Fut: std::future::Future<Output = Option<String>>,
     Extra: Send + Clone,
 {
-    let mut args = vec!["push", "--quiet", "--no-verify"];
+    let mut args = vec!["push", "--quiet", "--progress", "--no-verify"];
 
     let refspec = refspec.to_string();
 
@@ -371,8 +424,18 @@ where
         }
     }
 
-    let (status, stdout, stderr) =
-        execute_with_auth_harness(repo_path, &executor, &args, None, on_prompt, extra).await?;
+    let (status, stdout, stderr) = execute_with_auth_harness(
+        repo_path,
+        &executor,
+        &args,
+        None,
+        credentials,
+        on_progress,
+        should_interrupt,
+        on_prompt,
+        extra,
+    )
+    .await?;
 
     if status == 0 {
         return Ok(());
@@ -408,13 +471,14 @@ where
 /// Signs the given commit-ish in the repository at the given path.
 /// Returns the newly signed commit SHA.
 ///
-/// Any prompts for the user are passed to the asynchronous callback `on_prompt`,
-/// which should return the user's response or `None` if the operation should be
-/// aborted, in which case an `Err` value is returned from this function.
+/// Prompts that none of the `credentials` can answer are passed to the asynchronous
+/// callback `on_prompt`, which should return the user's response or `None` if the
+/// operation should be aborted, in which case an `Err` value is returned from this function.
 pub async fn sign_commit<P, E, F, Extra, Fut>(
     repo_path: P,
     executor: E,
     base_commitish: String,
+    credentials: &CredentialProviders,
     on_prompt: F,
     extra: Extra,
 ) -> Result<String, crate::Error<Error<E>>>
@@ -466,8 +530,18 @@ where
         "--allow-empty",
         "--allow-empty-message",
     ];
-    let (status, stdout, stderr) =
-        execute_with_auth_harness(&worktree_path, &executor, &args, None, on_prompt, extra).await?;
+    let (status, stdout, stderr) = execute_with_auth_harness(
+        &worktree_path,
+        &executor,
+        &args,
+        None,
+        credentials,
+        |_| {},
+        &AtomicBool::new(false),
+        on_prompt,
+        extra,
+    )
+    .await?;
     if status != 0 {
         return Err(Error::<E>::Failed {
             status,
@@ -517,6 +591,15 @@ where
     Ok(commit_hash)
 }
 
+/// Remove the progress from `stderr`, so only messages remain.
+fn without_progress(stderr: &str) -> String {
+    stderr
+        .split(['\r', '\n'])
+        .filter(|line| !line.is_empty() && Progress::parse(line).is_none())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 fn get_core_sshcommand(cwd: impl AsRef<Path>) -> anyhow::Result<Option<String>> {
     Ok(gix::open(cwd.as_ref())?
         .config_snapshot()
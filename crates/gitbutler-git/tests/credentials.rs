@@ -0,0 +1,159 @@
+use gitbutler_git::credentials::{
+    CredentialHelperProvider, CredentialProvider, CredentialProviders, EnvironmentProvider, Prompt,
+};
+
+#[test]
+fn parse_prompts() {
+    assert_eq!(
+        Prompt::parse("Username for 'https://github.com': "),
+        Prompt::Username {
+            url: "https://github.com".to_owned()
+        }
+    );
+    assert_eq!(
+        Prompt::parse("Password for 'https://user@github.com': "),
+        Prompt::Password {
+            url: "https://user@github.com".to_owned()
+        }
+    );
+    assert_eq!(
+        Prompt::parse("Enter passphrase for key '/home/user/.ssh/id_ed25519': "),
+        Prompt::Passphrase {
+            key: "/home/user/.ssh/id_ed25519".to_owned()
+        }
+    );
+    assert_eq!(
+        Prompt::parse("Are you sure you want to continue connecting (yes/no)? "),
+        Prompt::Other("Are you sure you want to continue connecting (yes/no)?".to_owned())
+    );
+}
+
+struct Fixed(&'static str);
+
+impl CredentialProvider for Fixed {
+    fn name(&self) -> &'static str {
+        "fixed"
+    }
+
+    fn answer(&self, prompt: &Prompt) -> Option<String> {
+        matches!(prompt, Prompt::Password { .. }).then(|| self.0.to_owned())
+    }
+}
+
+#[test]
+fn providers_are_asked_in_order() {
+    let providers = CredentialProviders::default()
+        .with(Fixed("first"))
+        .with(Fixed("second"));
+    assert_eq!(
+        providers
+            .answer("Password for 'https://github.com': ")
+            .as_deref(),
+        Some("first")
+    );
+    assert_eq!(
+        providers.answer("Username for 'https://github.com': "),
+        None,
+        "prompts nobody can answer are left to the caller"
+    );
+}
+
+#[test]
+fn environment_credentials_are_only_given_to_the_configured_host() {
+    let provider = |host: Option<&str>| EnvironmentProvider {
+        host: host.map(ToOwned::to_owned),
+        username: Some("user".to_owned()),
+        password: Some("token".to_owned()),
+        ssh_passphrase: Some("passphrase".to_owned()),
+    };
+    let password = |provider: &EnvironmentProvider, url: &str| {
+        provider.answer(&Prompt::Password {
+            url: url.to_owned(),
+        })
+    };
+
+    let github = provider(Some("github.com"));
+    assert_eq!(
+        github
+            .answer(&Prompt::Username {
+                url: "https://github.com".to_owned()
+            })
+            .as_deref(),
+        Some("user")
+    );
+    assert_eq!(
+        password(&github, "https://user@GitHub.com").as_deref(),
+        Some("token"),
+        "hosts are compared case-insensitively"
+    );
+    assert_eq!(
+        password(&github, "https://github.com:8443").as_deref(),
+        Some("token"),
+        "without a port, every port of the host is fine"
+    );
+    assert_eq!(
+        password(&github, "https://example.com"),
+        None,
+        "other hosts don't get the token"
+    );
+    assert_eq!(password(&github, "https://github.com.example.com"), None);
+
+    let with_port = provider(Some("git.example.com:8443"));
+    assert_eq!(
+        password(&with_port, "https://git.example.com:8443/repo.git").as_deref(),
+        Some("token")
+    );
+    assert_eq!(
+        password(&with_port, "https://git.example.com/repo.git"),
+        None
+    );
+
+    let unscoped = provider(None);
+    assert_eq!(
+        password(&unscoped, "https://github.com"),
+        None,
+        "without a host, the token isn't given to any remote"
+    );
+    assert_eq!(
+        unscoped
+            .answer(&Prompt::Passphrase {
+                key: "id_ed25519".to_owned()
+            })
+            .as_deref(),
+        Some("passphrase"),
+        "the passphrase is for a local key, not for a host"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn credential_helper_protocol() {
+    let tmp = std::env::temp_dir();
+    let helper = CredentialHelperProvider {
+        helper: r#"!f() { test "$1" = get || exit 1; while read -r line && [ -n "$line" ]; do case "$line" in host=*) host="${line#host=}";; username=*) user="${line#username=}";; esac; done; echo "username=${user:-helper-user}"; echo "password=secret-for-$host"; }; f"#.to_owned(),
+        cwd: tmp,
+    };
+    assert_eq!(
+        helper
+            .answer(&Prompt::Username {
+                url: "https://example.com:8443/repo.git".to_owned()
+            })
+            .as_deref(),
+        Some("helper-user")
+    );
+    assert_eq!(
+        helper
+            .answer(&Prompt::Password {
+                url: "https://me@example.com".to_owned()
+            })
+            .as_deref(),
+        Some("secret-for-example.com")
+    );
+    assert_eq!(
+        helper.answer(&Prompt::Passphrase {
+            key: "id_ed25519".to_owned()
+        }),
+        None,
+        "helpers only know about HTTP(S) credentials"
+    );
+}
@@ -1 +1,4 @@
+mod credentials;
+mod local;
+mod progress;
 mod refspec;
@@ -0,0 +1,245 @@
+use std::{path::Path, sync::atomic::AtomicBool};
+
+use gitbutler_git::{local, Error, RefSpec};
+use tempfile::TempDir;
+
+#[test]
+fn fetch_copies_objects_updates_and_prunes_refs() -> anyhow::Result<()> {
+    let (tmp, local_repo, remote_repo) = repos()?;
+    let main = commit(&remote_repo, "refs/heads/main", "a")?;
+    commit(&remote_repo, "refs/heads/gone", "b")?;
+
+    let mut progress = Vec::new();
+    fetch(tmp.path(), |p| progress.push(p))?;
+    assert_eq!(tracking(&local_repo, "main"), Some(main));
+    assert!(tracking(&local_repo, "gone").is_some());
+    assert!(local_repo.find_commit(main).is_ok(), "objects were copied");
+    assert!(!progress.is_empty());
+
+    local_repo.reference_symbolic(
+        "refs/remotes/origin/HEAD",
+        "refs/remotes/origin/main",
+        true,
+        "remote set-head",
+    )?;
+    remote_repo.find_reference("refs/heads/gone")?.delete()?;
+    let main = commit(&remote_repo, "refs/heads/main", "c")?;
+    fetch(tmp.path(), |_| {})?;
+    assert_eq!(tracking(&local_repo, "main"), Some(main));
+    assert_eq!(tracking(&local_repo, "gone"), None, "stale refs are pruned");
+    assert_eq!(
+        local_repo
+            .find_reference("refs/remotes/origin/HEAD")?
+            .symbolic_target(),
+        Some("refs/remotes/origin/main"),
+        "symbolic refs aren't pruned"
+    );
+    Ok(())
+}
+
+#[test]
+fn fetch_writes_fetch_head_like_git() -> anyhow::Result<()> {
+    let (tmp, local_repo, remote_repo) = repos()?;
+    let feature = commit(&remote_repo, "refs/heads/feature", "a")?;
+    let main = commit(&remote_repo, "refs/heads/main", "b")?;
+
+    fetch(tmp.path(), |_| {})?;
+    assert_eq!(
+        std::fs::read_to_string(local_repo.path().join("FETCH_HEAD"))?,
+        format!(
+            "{feature}\tnot-for-merge\tbranch 'feature' of ../remote\n\
+             {main}\tnot-for-merge\tbranch 'main' of ../remote\n"
+        ),
+        "refs of wildcard refspecs aren't for merging, and the URL loses its .git suffix"
+    );
+
+    local::fetch(
+        &tmp.path().join("local"),
+        "origin",
+        RefSpec::parse("refs/heads/main:refs/remotes/origin/main")?,
+        |_| {},
+        &AtomicBool::new(false),
+    )?;
+    assert_eq!(
+        std::fs::read_to_string(local_repo.path().join("FETCH_HEAD"))?,
+        format!("{main}\t\tbranch 'main' of ../remote\n")
+    );
+    Ok(())
+}
+
+#[test]
+fn objects_are_copied_as_pack() -> anyhow::Result<()> {
+    let (tmp, local_repo, remote_repo) = repos()?;
+    let first = commit(&remote_repo, "refs/heads/main", "a")?;
+    fetch(tmp.path(), |_| {})?;
+    let second = commit(&remote_repo, "refs/heads/main", "b")?;
+    fetch(tmp.path(), |_| {})?;
+
+    let objects = local_repo.path().join("objects");
+    let packs: Vec<_> = std::fs::read_dir(objects.join("pack"))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    let packs: Vec<_> = packs
+        .iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "pack"))
+        .collect();
+    assert_eq!(packs.len(), 2, "one pack per fetch");
+    for pack in packs {
+        assert_eq!(
+            objects_in_pack(pack)?,
+            3,
+            "each pack has one commit with its tree and blob, as the walk stops at objects that exist already"
+        );
+    }
+    assert!(
+        std::fs::read_dir(&objects)?
+            .filter_map(Result::ok)
+            .all(|entry| entry.file_name().len() != 2),
+        "no loose objects were written"
+    );
+
+    assert!(local_repo.find_commit(first).is_ok());
+    assert!(local_repo.find_commit(second).is_ok());
+    Ok(())
+}
+
+#[test]
+fn push_only_forces_if_asked_to() -> anyhow::Result<()> {
+    let (tmp, local_repo, remote_repo) = repos()?;
+    let first = commit(&local_repo, "refs/heads/feature", "a")?;
+    push(tmp.path(), first, false)?;
+    assert_eq!(
+        remote_repo.refname_to_id("refs/heads/feature")?,
+        first,
+        "new branches are created"
+    );
+    assert_eq!(tracking(&local_repo, "feature"), Some(first));
+
+    let second = commit(&local_repo, "refs/heads/feature", "b")?;
+    push(tmp.path(), second, false)?;
+    assert_eq!(remote_repo.refname_to_id("refs/heads/feature")?, second);
+
+    local_repo.reference("refs/heads/feature", first, true, "reset")?;
+    let rewritten = commit(&local_repo, "refs/heads/feature", "c")?;
+    let err = push(tmp.path(), rewritten, false).expect_err("not a fast-forward");
+    assert!(matches!(
+        err,
+        Error::Backend(local::Error::NotFastForward(_))
+    ));
+    push(tmp.path(), rewritten, true)?;
+    assert_eq!(remote_repo.refname_to_id("refs/heads/feature")?, rewritten);
+    Ok(())
+}
+
+#[test]
+fn push_requires_git_for_remotes_with_hooks_or_receive_config() -> anyhow::Result<()> {
+    let (tmp, local_repo, remote_repo) = repos()?;
+    let local_path = tmp.path().join("local");
+    let id = commit(&local_repo, "refs/heads/feature", "a")?;
+    assert!(local::can_push(&local_path, "origin"));
+
+    let hook = remote_repo.path().join("hooks").join("pre-receive");
+    std::fs::create_dir_all(hook.parent().unwrap())?;
+    std::fs::write(&hook, "#!/bin/sh\nexit 1\n")?;
+    assert!(!local::can_push(&local_path, "origin"));
+    let err = push(tmp.path(), id, false).expect_err("hooks can only be run by git");
+    assert!(matches!(err, Error::Backend(local::Error::RequiresGit(_))));
+    std::fs::remove_file(hook)?;
+
+    remote_repo
+        .config()?
+        .set_bool("receive.denyNonFastForwards", true)?;
+    assert!(!local::can_push(&local_path, "origin"));
+    let err = push(tmp.path(), id, true).expect_err("only git knows all receive.* settings");
+    assert!(matches!(err, Error::Backend(local::Error::RequiresGit(_))));
+    assert!(remote_repo.find_reference("refs/heads/feature").is_err());
+    Ok(())
+}
+
+#[test]
+fn cancelled_operations_change_nothing() -> anyhow::Result<()> {
+    let (tmp, local_repo, remote_repo) = repos()?;
+    let id = commit(&local_repo, "refs/heads/feature", "a")?;
+    let err = local::push(
+        &tmp.path().join("local"),
+        "origin",
+        RefSpec::parse(format!("{id}:refs/heads/feature"))?,
+        false,
+        false,
+        |_| {},
+        &AtomicBool::new(true),
+    )
+    .expect_err("cancelled");
+    assert!(matches!(err, Error::Backend(local::Error::Interrupted)));
+    assert!(remote_repo.find_reference("refs/heads/feature").is_err());
+    Ok(())
+}
+
+/// Create a repository with a bare repository as its `origin` remote.
+fn repos() -> anyhow::Result<(TempDir, git2::Repository, git2::Repository)> {
+    let tmp = tempfile::tempdir()?;
+    let remote_repo = git2::Repository::init_bare(tmp.path().join("remote.git"))?;
+    let local_repo = git2::Repository::init(tmp.path().join("local"))?;
+    local_repo.remote("origin", "../remote.git")?;
+    Ok((tmp, local_repo, remote_repo))
+}
+
+fn fetch(
+    tmp: &Path,
+    progress: impl FnMut(gitbutler_git::Progress),
+) -> Result<(), Error<local::Error>> {
+    local::fetch(
+        &tmp.join("local"),
+        "origin",
+        RefSpec::parse("+refs/heads/*:refs/remotes/origin/*").unwrap(),
+        progress,
+        &AtomicBool::new(false),
+    )
+}
+
+fn push(tmp: &Path, id: git2::Oid, force: bool) -> Result<(), Error<local::Error>> {
+    local::push(
+        &tmp.join("local"),
+        "origin",
+        RefSpec::parse(format!("{id}:refs/heads/feature")).unwrap(),
+        force,
+        false,
+        |_| {},
+        &AtomicBool::new(false),
+    )
+}
+
+/// The number of objects in `pack`, as stored in the last entry of the fan-out table of its version 2 index.
+fn objects_in_pack(pack: &Path) -> anyhow::Result<u32> {
+    let index = std::fs::read(pack.with_extension("idx"))?;
+    let last_fanout = 8 + 255 * 4;
+    Ok(u32::from_be_bytes(
+        index[last_fanout..last_fanout + 4].try_into()?,
+    ))
+}
+
+fn tracking(repo: &git2::Repository, branch: &str) -> Option<git2::Oid> {
+    repo.refname_to_id(&format!("refs/remotes/origin/{branch}"))
+        .ok()
+}
+
+/// Create a commit with a file whose content is `content` on top of `refname`, and point `refname` to it.
+fn commit(repo: &git2::Repository, refname: &str, content: &str) -> anyhow::Result<git2::Oid> {
+    let blob = repo.blob(content.as_bytes())?;
+    let mut tree = repo.treebuilder(None)?;
+    tree.insert("file", blob, 0o100644)?;
+    let tree = repo.find_tree(tree.write()?)?;
+    let signature = git2::Signature::now("author", "author@example.com")?;
+    let parent = repo
+        .find_reference(refname)
+        .ok()
+        .and_then(|r| r.peel_to_commit().ok());
+    Ok(repo.commit(
+        Some(refname),
+        &signature,
+        &signature,
+        content,
+        &tree,
+        parent.iter().collect::<Vec<_>>().as_slice(),
+    )?)
+}
@@ -0,0 +1,65 @@
+use gitbutler_git::{Progress, Stage};
+
+#[test]
+fn parse_progress_with_total_and_bytes() {
+    assert_eq!(
+        Progress::parse("Receiving objects:  45% (9/20), 1.50 MiB | 1.00 MiB/s"),
+        Some(Progress {
+            stage: Stage::ReceivingObjects,
+            done: 9,
+            total: Some(20),
+            bytes: Some(1_572_864),
+        })
+    );
+    assert_eq!(
+        Progress::parse("Writing objects: 100% (3/3), 300 bytes | 300.00 KiB/s, done."),
+        Some(Progress {
+            stage: Stage::WritingObjects,
+            done: 3,
+            total: Some(3),
+            bytes: Some(300),
+        })
+    );
+}
+
+#[test]
+fn parse_progress_of_remote() {
+    assert_eq!(
+        Progress::parse("remote: Counting objects: 100% (5/5), done."),
+        Some(Progress {
+            stage: Stage::CountingObjects,
+            done: 5,
+            total: Some(5),
+            bytes: None,
+        })
+    );
+    assert_eq!(
+        Progress::parse("remote: Enumerating objects: 12, done."),
+        Some(Progress {
+            stage: Stage::CountingObjects,
+            done: 12,
+            total: None,
+            bytes: None,
+        })
+    );
+}
+
+#[test]
+fn parse_other_lines() {
+    assert_eq!(
+        Progress::parse("Resolving deltas:  50% (1/2)").map(|p| p.stage),
+        Some(Stage::ResolvingDeltas)
+    );
+    assert_eq!(
+        Progress::parse("Delta compression using up to 8 threads"),
+        None
+    );
+    assert_eq!(
+        Progress::parse("remote: Total 5 (delta 0), reused 0 (delta 0)"),
+        None
+    );
+    assert_eq!(
+        Progress::parse("fatal: couldn't find remote ref main"),
+        None
+    );
+}
@@ -147,7 +147,9 @@ pub enum OperationKind {
     UpdateCommitMessage,
     MoveCommit,
     RestoreFromSnapshot,
+    RestoreStackFromSnapshot,
     ReorderCommit,
+    RebaseStack,
     InsertBlankCommit,
     MoveCommitFile,
     FileChanges,
@@ -161,6 +163,7 @@ pub enum OperationKind {
     AutoHandleChangesBefore,
     AutoHandleChangesAfter,
     SplitBranch,
+    ApplyWorkspaceRule,
     #[default]
     Unknown,
 }
@@ -201,7 +204,7 @@ impl FromStr for Version {
 
 /// Represents a key value pair stored in a snapshot, like `key: value\n`
 /// Using the git trailer format (<https://git-scm.com/docs/git-interpret-trailers>)
-#[derive(Debug, PartialEq, Clone, Serialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Trailer {
     /// Trailer key
@@ -0,0 +1,247 @@
+use std::{fs, path::Path, process::Command, str::FromStr};
+
+use anyhow::{bail, Context, Result};
+use gitbutler_project::Project;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entry::{OperationKind, SnapshotDetails, Trailer},
+    state::OplogHandle,
+};
+
+/// The name of the git bundle holding the exported snapshot commits, within the export directory.
+pub const BUNDLE_FILE_NAME: &str = "oplog.bundle";
+/// The name of the JSON file describing the exported snapshots, within the export directory.
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// The version of the manifest format, bumped whenever it changes incompatibly.
+const MANIFEST_FORMAT_VERSION: u32 = 1;
+/// The reference the bundle is created from, and which is contained in it.
+const EXPORT_REF: &str = "refs/gitbutler/oplog-export";
+/// The prefix of the references that keep imported snapshots reachable, one per import.
+const IMPORT_REF_PREFIX: &str = "refs/gitbutler/imported-oplog/";
+
+/// The snapshots to export, by following the oplog from `newest` back to `oldest`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OplogRange {
+    /// The oldest snapshot to include, or `None` to go back all the way to the very first snapshot.
+    pub oldest: Option<git2::Oid>,
+    /// The newest snapshot to include, or `None` to start at the current oplog head.
+    pub newest: Option<git2::Oid>,
+}
+
+/// Describes the snapshots contained in an exported oplog bundle, so they can be validated and replayed after import.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OplogManifest {
+    /// The version of the manifest format.
+    pub format_version: u32,
+    /// All exported snapshots, with the oldest one first, so they can be restored in order.
+    pub snapshots: Vec<ManifestEntry>,
+}
+
+/// A single snapshot in an [`OplogManifest`].
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    /// The id of the snapshot commit as contained in the bundle.
+    #[serde(with = "gitbutler_serde::oid")]
+    pub commit_id: git2::Oid,
+    /// The id of the snapshot commit in the oplog it was exported from.
+    ///
+    /// It differs from `commit_id` if the oldest exported snapshot had a parent, as exported snapshots never have one.
+    #[serde(with = "gitbutler_serde::oid")]
+    pub original_commit_id: git2::Oid,
+    /// The operation that was performed just before the snapshot was created.
+    pub operation: OperationKind,
+    /// The title of the snapshot.
+    pub title: String,
+    /// Additional text describing the snapshot.
+    pub body: Option<String>,
+    /// Additional key value pairs that describe the snapshot.
+    pub trailers: Vec<Trailer>,
+    /// Snapshot creation time in seconds since the Unix epoch.
+    pub created_at: i64,
+}
+
+pub(crate) fn export_oplog(
+    project: &Project,
+    range: OplogRange,
+    dir: &Path,
+) -> Result<OplogManifest> {
+    let repo = git2::Repository::open(&project.path)?;
+    let newest = match range.newest {
+        Some(id) => id,
+        None => OplogHandle::new(&project.gb_dir())
+            .oplog_head()?
+            .context("There are no snapshots to export")?,
+    };
+
+    let mut snapshots = Vec::new();
+    let mut next = Some(newest);
+    while let Some(id) = next {
+        let commit = repo
+            .find_commit(id)
+            .with_context(|| format!("Snapshot {id} could not be found"))?;
+        next = (range.oldest != Some(id))
+            .then(|| commit.parent_ids().next())
+            .flatten();
+        snapshots.push(commit);
+    }
+    if let Some(oldest) = range.oldest {
+        if snapshots.last().map(|c| c.id()) != Some(oldest) {
+            bail!("Snapshot {oldest} isn't older than snapshot {newest}");
+        }
+    }
+    snapshots.reverse();
+
+    // Rewrite the snapshots in a scratch repository that borrows the objects of the project,
+    // so the rewritten commits and the exported reference never end up in the project itself.
+    let scratch_dir = tempfile::tempdir()?;
+    let scratch = scratch_repository(&repo, scratch_dir.path())?;
+
+    // Rewrite the snapshots so the oldest one has no parent, which makes the bundle self-contained.
+    let mut entries = Vec::with_capacity(snapshots.len());
+    let mut parent: Option<git2::Commit> = None;
+    for snapshot in &snapshots {
+        let message = snapshot.message_raw().with_context(|| {
+            format!("Snapshot {} has a message that isn't UTF-8", snapshot.id())
+        })?;
+        let details = SnapshotDetails::from_str(message)
+            .with_context(|| format!("Commit {} isn't a snapshot", snapshot.id()))?;
+        let commit_id = scratch.commit(
+            None,
+            &snapshot.author(),
+            &snapshot.committer(),
+            message,
+            &scratch.find_tree(snapshot.tree_id())?,
+            parent.iter().collect::<Vec<_>>().as_slice(),
+        )?;
+        entries.push(ManifestEntry {
+            commit_id,
+            original_commit_id: snapshot.id(),
+            operation: details.operation,
+            title: details.title,
+            body: details.body,
+            trailers: details.trailers,
+            created_at: snapshot.time().seconds(),
+        });
+        parent = Some(scratch.find_commit(commit_id)?);
+    }
+    let tip = parent.context("BUG: there is at least one snapshot")?.id();
+
+    fs::create_dir_all(dir)?;
+    scratch.reference(EXPORT_REF, tip, true, "oplog export")?;
+    git(
+        scratch_dir.path(),
+        &[
+            "bundle".as_ref(),
+            "create".as_ref(),
+            dir.join(BUNDLE_FILE_NAME).as_os_str(),
+            EXPORT_REF.as_ref(),
+        ],
+    )?;
+
+    let manifest = OplogManifest {
+        format_version: MANIFEST_FORMAT_VERSION,
+        snapshots: entries,
+    };
+    gitbutler_fs::write(
+        dir.join(MANIFEST_FILE_NAME),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+    Ok(manifest)
+}
+
+pub(crate) fn import_oplog(project: &Project, dir: &Path) -> Result<OplogManifest> {
+    let manifest_path = dir.join(MANIFEST_FILE_NAME);
+    let manifest: OplogManifest = serde_json::from_slice(
+        &fs::read(&manifest_path)
+            .with_context(|| format!("Could not read {}", manifest_path.display()))?,
+    )?;
+    if manifest.format_version != MANIFEST_FORMAT_VERSION {
+        bail!(
+            "Oplog manifest has format version {}, but only version {MANIFEST_FORMAT_VERSION} is supported",
+            manifest.format_version
+        );
+    }
+    let tip = manifest
+        .snapshots
+        .last()
+        .context("The oplog manifest doesn't contain any snapshots")?
+        .commit_id;
+
+    let bundle_path = dir.join(BUNDLE_FILE_NAME);
+    git(
+        &project.path,
+        &[
+            "fetch".as_ref(),
+            "--no-tags".as_ref(),
+            bundle_path.as_os_str(),
+            format!("+{EXPORT_REF}:{IMPORT_REF_PREFIX}{tip}").as_ref(),
+        ],
+    )?;
+
+    let repo = git2::Repository::open(&project.path)?;
+    let mut expected_parent = None;
+    for entry in &manifest.snapshots {
+        let commit = repo
+            .find_commit(entry.commit_id)
+            .with_context(|| format!("Snapshot {} is missing in the bundle", entry.commit_id))?;
+        if commit.parent_ids().next() != expected_parent {
+            bail!(
+                "Snapshot {} isn't preceded by the previous snapshot in the manifest",
+                entry.commit_id
+            );
+        }
+        let details = commit
+            .message_raw()
+            .and_then(|msg| SnapshotDetails::from_str(msg).ok())
+            .with_context(|| format!("Commit {} isn't a snapshot", entry.commit_id))?;
+        if details.operation != entry.operation || details.trailers != entry.trailers {
+            bail!(
+                "Snapshot {} doesn't match its description in the manifest",
+                entry.commit_id
+            );
+        }
+        expected_parent = Some(entry.commit_id);
+    }
+    Ok(manifest)
+}
+
+/// Create a bare repository at `dir` which can read all objects of `repo` through its alternates,
+/// but writes new objects and references only to itself.
+fn scratch_repository(repo: &git2::Repository, dir: &Path) -> Result<git2::Repository> {
+    git2::Repository::init_bare(dir)?;
+    let objects_dir = repo.commondir().join("objects");
+    let objects_dir = objects_dir
+        .to_str()
+        .with_context(|| format!("{} isn't valid UTF-8", objects_dir.display()))?;
+    fs::write(
+        dir.join("objects").join("info").join("alternates"),
+        format!("{objects_dir}\n"),
+    )?;
+    // Open it again, as the object database of a repository only reads its alternates once.
+    Ok(git2::Repository::open_bare(dir)?)
+}
+
+/// Run `git` with `args` in `dir`, failing if it doesn't succeed.
+fn git(dir: &Path, args: &[&std::ffi::OsStr]) -> Result<()> {
+    let output = Command::new(gix::path::env::exe_invocation())
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .context("Could not run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed with {}: {}",
+            args.iter()
+                .map(|arg| arg.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
@@ -1,10 +1,18 @@
 pub mod entry;
+mod exchange;
+pub use exchange::{
+    ManifestEntry, OplogManifest, OplogRange, BUNDLE_FILE_NAME, MANIFEST_FILE_NAME,
+};
 mod oplog;
 pub use oplog::OplogExt;
 pub mod reflog;
 mod snapshot;
 pub use snapshot::SnapshotExt;
+mod stack_restore;
+pub use stack_restore::StackRestoreOutcome;
 mod state;
+mod undo;
+pub use undo::{UndoNode, UndoTree};
 
 /// The name of the file holding our state, useful for watching for changes.
 pub const OPLOG_FILE_NAME: &str = "operations-log.toml";
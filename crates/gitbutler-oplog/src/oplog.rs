@@ -1,11 +1,18 @@
 use std::{
     collections::{hash_map::Entry, HashMap},
     fs,
+    path::Path,
     str::{from_utf8, FromStr},
     time::Duration,
 };
 
-use crate::{entry::Version, reflog::ReflogCommits};
+use crate::{
+    entry::Version,
+    exchange::{self, OplogManifest, OplogRange},
+    reflog::ReflogCommits,
+    stack_restore::{self, StackRestoreOutcome},
+    undo::{self, UndoNode, UndoTree},
+};
 
 use super::{
     entry::{OperationKind, Snapshot, SnapshotDetails, Trailer},
@@ -28,7 +35,7 @@ use gitbutler_project::{
 };
 use gitbutler_repo::RepositoryExt;
 use gitbutler_repo::SignaturePurpose;
-use gitbutler_stack::{Stack, VirtualBranchesHandle, VirtualBranchesState};
+use gitbutler_stack::{Stack, StackId, VirtualBranchesHandle, VirtualBranchesState};
 use gix::prelude::ObjectIdExt;
 use gix::{bstr::ByteSlice, ObjectId};
 use tracing::instrument;
@@ -146,6 +153,53 @@ pub trait OplogExt {
 
     /// Gets the sha of the last snapshot commit if present.
     fn oplog_head(&self) -> Result<Option<git2::Oid>>;
+
+    /// Exports the snapshots in `range` into the directory `dir`, so the sequence of operations that led to
+    /// the newest snapshot can be reproduced on another machine with [`import_oplog`](Self::import_oplog).
+    ///
+    /// The directory will contain a git bundle with all snapshot commits, and a JSON manifest describing each
+    /// snapshot by its [`OperationKind`] and trailers. Returns the manifest that was written.
+    fn export_oplog(&self, range: OplogRange, dir: &Path) -> Result<OplogManifest>;
+
+    /// Imports the snapshots previously exported to `dir` with [`export_oplog`](Self::export_oplog), and validates
+    /// them against the manifest. The local oplog is left untouched.
+    ///
+    /// Returns the manifest with the oldest snapshot first, whose snapshots can then be restored in order
+    /// with [`restore_snapshot`](Self::restore_snapshot) to replay the operations.
+    fn import_oplog(&self, dir: &Path) -> Result<OplogManifest>;
+
+    /// Builds the [`UndoTree`] from all snapshots in the oplog, to learn which operations can be undone or redone.
+    fn undo_tree(&self) -> Result<UndoTree>;
+
+    /// Undoes the operation that led to the current state by restoring the snapshot taken right before it.
+    /// Calling it repeatedly undoes more and more operations.
+    ///
+    /// Returns the node of the undone operation, or `None` if there was nothing to undo.
+    fn undo(&self, perm: &mut WorktreeWritePermission) -> Result<Option<UndoNode>>;
+
+    /// Redoes the most recently undone operation that was performed on the current state, by restoring the
+    /// snapshot holding the state right after it.
+    ///
+    /// Returns the node of the redone operation, or `None` if there was nothing to redo.
+    fn redo(&self, perm: &mut WorktreeWritePermission) -> Result<Option<UndoNode>>;
+
+    /// Like [`restore_snapshot`](Self::restore_snapshot), but only restores the stack with `stack_id` to the state
+    /// it had in the snapshot with `snapshot_commit_id`, leaving all other stacks and unassigned changes untouched.
+    ///
+    /// This restores:
+    ///  - The heads of the stack as stored in `virtual_branches.toml`, along with their references.
+    ///  - The commits of the stack, which are recreated if they don't exist anymore.
+    ///  - The uncommitted changes assigned to the stack, along with their hunk assignments, at file granularity.
+    ///
+    /// If any file the restore would change is also changed by another stack or by changes not assigned to the stack,
+    /// nothing is restored and the conflicting paths are returned instead.
+    /// Otherwise, a snapshot of the state right before the restore is created.
+    fn restore_stack_snapshot(
+        &self,
+        snapshot_commit_id: git2::Oid,
+        stack_id: StackId,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<StackRestoreOutcome>;
 }
 
 impl OplogExt for CommandContext {
@@ -307,10 +361,44 @@ impl OplogExt for CommandContext {
         let oplog_state = OplogHandle::new(&self.project().gb_dir());
         oplog_state.oplog_head()
     }
+
+    #[instrument(skip(self), err(Debug))]
+    fn export_oplog(&self, range: OplogRange, dir: &Path) -> Result<OplogManifest> {
+        exchange::export_oplog(self.project(), range, dir)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    fn import_oplog(&self, dir: &Path) -> Result<OplogManifest> {
+        exchange::import_oplog(self.project(), dir)
+    }
+
+    fn undo_tree(&self) -> Result<UndoTree> {
+        undo::undo_tree(self)
+    }
+
+    #[instrument(skip(self, perm), err(Debug))]
+    fn undo(&self, perm: &mut WorktreeWritePermission) -> Result<Option<UndoNode>> {
+        undo::undo(self, perm)
+    }
+
+    #[instrument(skip(self, perm), err(Debug))]
+    fn redo(&self, perm: &mut WorktreeWritePermission) -> Result<Option<UndoNode>> {
+        undo::redo(self, perm)
+    }
+
+    #[instrument(skip(self, perm), err(Debug))]
+    fn restore_stack_snapshot(
+        &self,
+        snapshot_commit_id: git2::Oid,
+        stack_id: StackId,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<StackRestoreOutcome> {
+        stack_restore::restore_stack_snapshot(self, snapshot_commit_id, stack_id, perm)
+    }
 }
 
 /// Get a tree of the working dir (applied branches merged)
-fn get_workdir_tree(
+pub(crate) fn get_workdir_tree(
     wd_trees_cache: Option<&mut HashMap<gix::ObjectId, gix::ObjectId>>,
     commit_id: impl Into<gix::ObjectId>,
     repo: &gix::Repository,
@@ -355,7 +443,7 @@ fn get_workdir_tree(
     }
 }
 
-fn prepare_snapshot(
+pub(crate) fn prepare_snapshot(
     ctx: &CommandContext,
     _shared_access: &WorktreeReadPermission,
 ) -> Result<git2::Oid> {
@@ -394,6 +482,12 @@ fn prepare_snapshot(
     let r = &repo;
     let gix_repo = r.to_gix()?;
 
+    // hunk assignments are stored per stack, to allow restoring a single stack
+    let hunk_assignments = stack_restore::hunk_assignments(ctx).unwrap_or_else(|err| {
+        tracing::warn!("failed to read hunk assignments for snapshot - ignoring: {err}");
+        Vec::new()
+    });
+
     for mut stack in vb_state.list_stacks_in_workspace()? {
         head_tree_ids.push(stack.tree(ctx)?);
 
@@ -432,6 +526,18 @@ fn prepare_snapshot(
         let commits_tree_id = commits_tree_builder.write()?;
         branch_tree_builder.insert("commits", commits_tree_id, FileMode::Tree.into())?;
 
+        let stack_id = stack.id.to_string();
+        let stack_assignments: Vec<_> = hunk_assignments
+            .iter()
+            .filter(|a| a.stack_id.as_deref() == Some(stack_id.as_str()))
+            .collect();
+        let assignments_blob_id = repo.blob(&serde_json::to_vec(&stack_assignments)?)?;
+        branch_tree_builder.insert(
+            stack_restore::HUNK_ASSIGNMENTS_BLOB_NAME,
+            assignments_blob_id,
+            FileMode::Blob.into(),
+        )?;
+
         let branch_tree_id = branch_tree_builder.write()?;
         branches_tree_builder.insert(
             stack.id.to_string(),
@@ -483,7 +589,7 @@ fn prepare_snapshot(
     Ok(tree_id)
 }
 
-fn commit_snapshot(
+pub(crate) fn commit_snapshot(
     ctx: &Project,
     snapshot_tree_id: git2::Oid,
     details: SnapshotDetails,
@@ -831,7 +937,7 @@ fn serialize_commit(commit: &git2::Commit<'_>) -> Vec<u8> {
 
 /// we get the data from the blob entry and re-create a commit object from it,
 /// whose returned id should match the one we stored.
-fn deserialize_commit(
+pub(crate) fn deserialize_commit(
     repo: &git2::Repository,
     commit_entry: &git2::TreeEntry,
 ) -> Result<git2::Oid> {
@@ -0,0 +1,358 @@
+use std::{collections::BTreeSet, fs, path::Path, str::from_utf8};
+
+use anyhow::{Context, Result};
+use but_graph::virtual_branches_legacy_types;
+use gitbutler_command_context::CommandContext;
+use gitbutler_oxidize::{ObjectIdExt as _, OidExt};
+use gitbutler_project::access::WorktreeWritePermission;
+use gitbutler_repo::{RepositoryExt, SignaturePurpose};
+use gitbutler_stack::{Stack, StackId, VirtualBranchesHandle, VirtualBranchesState};
+use gix::{
+    bstr::{BStr, BString},
+    object::tree::EntryKind,
+};
+use serde::Serialize;
+
+use crate::{
+    entry::{OperationKind, SnapshotDetails, Trailer},
+    oplog::{commit_snapshot, deserialize_commit, get_workdir_tree, prepare_snapshot},
+};
+
+/// The name of the blob with the hunk assignments of a stack, stored next to its `tree` and `commits` in a snapshot.
+pub(crate) const HUNK_ASSIGNMENTS_BLOB_NAME: &str = "hunk_assignments.json";
+
+/// The result of [`restore_stack_snapshot()`](crate::OplogExt::restore_stack_snapshot).
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackRestoreOutcome {
+    /// The snapshot of the state right before the restore, or `None` if nothing was restored due to conflicts.
+    #[serde(with = "gitbutler_serde::oid_opt")]
+    pub snapshot_id: Option<git2::Oid>,
+    /// Paths that the stack would change, but which are also changed by other stacks or by unassigned changes.
+    /// If not empty, nothing was restored.
+    #[serde(with = "gitbutler_serde::bstring_vec_lossy")]
+    pub conflicting_paths: Vec<BString>,
+}
+
+/// Return all hunk assignments as currently stored in the database.
+pub(crate) fn hunk_assignments(ctx: &CommandContext) -> Result<Vec<but_db::HunkAssignment>> {
+    but_db::DbHandle::new_in_directory(ctx.project().gb_dir())?
+        .hunk_assignments()
+        .list_all()
+}
+
+pub(crate) fn restore_stack_snapshot(
+    ctx: &CommandContext,
+    snapshot_commit_id: git2::Oid,
+    stack_id: StackId,
+    exclusive_access: &mut WorktreeWritePermission,
+) -> Result<StackRestoreOutcome> {
+    let worktree_dir = ctx.project().path.as_path();
+    let repo = git2::Repository::open(worktree_dir)?;
+    let gix_repo = gitbutler_command_context::gix_repo_for_merging(worktree_dir)?;
+    repo.workspace_ref_from_head().context(
+        "We will not change a worktree which for some reason isn't on the workspace branch",
+    )?;
+
+    let snapshot_commit = repo.find_commit(snapshot_commit_id)?;
+    let snapshot_tree = snapshot_commit.tree()?;
+    let snapshot_stack = stack_from_snapshot(&repo, &snapshot_tree, stack_id)?;
+    let branch_tree = snapshot_tree
+        .get_path(
+            Path::new("virtual_branches")
+                .join(stack_id.to_string())
+                .as_path(),
+        )
+        .and_then(|entry| repo.find_tree(entry.id()))
+        .with_context(|| {
+            format!("Stack {stack_id} has no data in snapshot {snapshot_commit_id}")
+        })?;
+    let snapshot_assignments: Vec<but_db::HunkAssignment> = branch_tree
+        .get_name(HUNK_ASSIGNMENTS_BLOB_NAME)
+        .map(|entry| -> Result<_> {
+            Ok(serde_json::from_slice(
+                repo.find_blob(entry.id())?.content(),
+            )?)
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let target_tree_id = repo
+        .find_commit(vb_state.get_default_target()?.sha)?
+        .tree_id()
+        .to_gix();
+    let current_stack = vb_state
+        .try_stack(stack_id)?
+        .filter(|stack| stack.in_workspace);
+    let old_head_tree_id = match &current_stack {
+        Some(stack) => gix_repo
+            .find_commit(stack.head_oid(&gix_repo)?)?
+            .tree_id()?
+            .detach(),
+        None => target_tree_id,
+    };
+    let new_head_tree_id = branch_tree
+        .get_name("tree")
+        .context("failed to get tree entry of stack in snapshot")?
+        .id()
+        .to_gix();
+
+    let stack_id_str = stack_id.to_string();
+    let current_assignments = hunk_assignments(ctx)?;
+    let (own_assignments, other_assignments): (Vec<_>, Vec<_>) = current_assignments
+        .into_iter()
+        .partition(|a| a.stack_id.as_deref() == Some(stack_id_str.as_str()));
+
+    // All paths the restore might change in the worktree.
+    let mut paths_to_restore = changed_paths(&gix_repo, old_head_tree_id, new_head_tree_id)?;
+    let restored_assignment_paths: BTreeSet<BString> = snapshot_assignments
+        .iter()
+        .map(|a| a.path_bytes.clone().into())
+        .collect();
+    paths_to_restore.extend(restored_assignment_paths.iter().cloned());
+    paths_to_restore.extend(own_assignments.iter().map(|a| a.path_bytes.clone().into()));
+
+    // All paths that belong to others, be it uncommitted changes or commits of other stacks.
+    let own_paths: BTreeSet<BString> = own_assignments
+        .iter()
+        .map(|a| a.path_bytes.clone().into())
+        .collect();
+    let mut paths_of_others: BTreeSet<BString> = other_assignments
+        .iter()
+        .map(|a| a.path_bytes.clone().into())
+        .collect();
+    for change in but_core::diff::worktree_changes(&gix_repo)?.changes {
+        if !own_paths.contains(&change.path) {
+            paths_of_others.insert(change.path);
+        }
+    }
+    for stack in vb_state.list_stacks_in_workspace()? {
+        if stack.id == stack_id {
+            continue;
+        }
+        let head_tree_id = gix_repo
+            .find_commit(stack.head_oid(&gix_repo)?)?
+            .tree_id()?
+            .detach();
+        paths_of_others.extend(changed_paths(&gix_repo, target_tree_id, head_tree_id)?);
+    }
+
+    let conflicting_paths: Vec<BString> = paths_to_restore
+        .intersection(&paths_of_others)
+        .cloned()
+        .collect();
+    if !conflicting_paths.is_empty() {
+        return Ok(StackRestoreOutcome {
+            snapshot_id: None,
+            conflicting_paths,
+        });
+    }
+
+    let before_restore_snapshot_tree_id =
+        prepare_snapshot(ctx, exclusive_access.read_permission())?;
+
+    // Make sure all commits of the stack exist, they might have been garbage-collected in the meantime.
+    let commits_tree = branch_tree
+        .get_name("commits")
+        .and_then(|entry| repo.find_tree(entry.id()).ok())
+        .context("failed to get commits tree entry")?;
+    for commit_entry in commits_tree.iter() {
+        if let Some(commit_id) = commit_entry.name() {
+            let commit_oid = git2::Oid::from_str(commit_id)?;
+            if repo.find_commit(commit_oid).is_err()
+                && deserialize_commit(&repo, &commit_entry)? != commit_oid
+            {
+                anyhow::bail!("commit id mismatch: failed to recreate a commit from its parts");
+            }
+        }
+    }
+
+    // Restore the stack's metadata and references, without touching any other stack.
+    if let Some(current_stack) = &current_stack {
+        for branch in &current_stack.heads {
+            if !snapshot_stack
+                .heads
+                .iter()
+                .any(|b| b.name() == branch.name())
+            {
+                branch.delete_reference(&gix_repo).ok();
+            }
+        }
+    }
+    for branch in &snapshot_stack.heads {
+        branch.set_reference_to_head_value(&gix_repo)?;
+    }
+    let mut restored_stack = snapshot_stack;
+    restored_stack.in_workspace = true;
+    vb_state.set_stack(restored_stack)?;
+
+    let workspace_tree_id = update_workspace_commit(ctx, &repo, &gix_repo)?;
+
+    // Restore the uncommitted changes of the stack, and reflect the changes to its commits in the worktree.
+    let snapshot_worktree_id = get_workdir_tree(None, snapshot_commit_id.to_gix(), &gix_repo, ctx)?;
+    for path in &paths_to_restore {
+        let source_tree_id = if restored_assignment_paths.contains(path) {
+            snapshot_worktree_id
+        } else {
+            workspace_tree_id
+        };
+        checkout_path(&gix_repo, source_tree_id, worktree_dir, path.as_ref())?;
+    }
+
+    let mut assignments = other_assignments;
+    assignments.extend(snapshot_assignments);
+    but_db::DbHandle::new_in_directory(ctx.project().gb_dir())?
+        .hunk_assignments()
+        .set_all(assignments)?;
+
+    let details =
+        SnapshotDetails::new(OperationKind::RestoreStackFromSnapshot).with_trailers(vec![
+            Trailer {
+                key: "restored_from".to_string(),
+                value: snapshot_commit_id.to_string(),
+            },
+            Trailer {
+                key: "restored_stack".to_string(),
+                value: stack_id_str,
+            },
+        ]);
+    let snapshot_id = commit_snapshot(
+        ctx.project(),
+        before_restore_snapshot_tree_id,
+        details,
+        exclusive_access,
+    )?;
+    Ok(StackRestoreOutcome {
+        snapshot_id: Some(snapshot_id),
+        conflicting_paths: Vec::new(),
+    })
+}
+
+/// Read the stack with `stack_id` from the `virtual_branches.toml` stored in `snapshot_tree`.
+fn stack_from_snapshot(
+    repo: &git2::Repository,
+    snapshot_tree: &git2::Tree,
+    stack_id: StackId,
+) -> Result<Stack> {
+    let vb_toml_entry = snapshot_tree
+        .get_name("virtual_branches.toml")
+        .context("failed to get virtual_branches.toml blob")?;
+    let vb_toml_blob = repo
+        .find_blob(vb_toml_entry.id())
+        .context("failed to convert virtual_branches tree entry to blob")?;
+    let mut state: VirtualBranchesState = toml::from_str::<
+        virtual_branches_legacy_types::VirtualBranches,
+    >(from_utf8(vb_toml_blob.content())?)?
+    .into();
+    state
+        .branches
+        .remove(&stack_id)
+        .with_context(|| format!("Stack {stack_id} didn't exist when the snapshot was taken"))
+}
+
+/// Merge the heads of all stacks into a new workspace commit and point the workspace reference to it,
+/// returning its tree.
+///
+/// The message of the current workspace commit is kept, as it is rewritten by the next operation on the workspace anyway.
+fn update_workspace_commit(
+    ctx: &CommandContext,
+    repo: &git2::Repository,
+    gix_repo: &gix::Repository,
+) -> Result<gix::ObjectId> {
+    let (workspace_tree_id, stacks, target_commit) =
+        but_workspace::remerged_workspace_tree_v2(ctx, gix_repo)?;
+    let mut heads: Vec<git2::Commit<'_>> = stacks
+        .iter()
+        .filter_map(|stack| stack.head_oid(gix_repo).ok())
+        .filter_map(|h| repo.find_commit(h.to_git2()).ok())
+        .collect();
+    if heads.is_empty() {
+        heads = vec![repo.find_commit(target_commit.id())?];
+    }
+    let head_refs: Vec<&git2::Commit<'_>> = heads.iter().collect();
+
+    let mut workspace_ref = repo.workspace_ref_from_head()?;
+    let message = workspace_ref
+        .peel_to_commit()?
+        .message_raw()
+        .unwrap_or_default()
+        .to_owned();
+    let committer = gitbutler_repo::signature(SignaturePurpose::Committer)?;
+    let author = gitbutler_repo::signature(SignaturePurpose::Author)?;
+    let workspace_commit_id = repo.commit(
+        None,
+        &author,
+        &committer,
+        &message,
+        &repo.find_tree(workspace_tree_id)?,
+        head_refs.as_slice(),
+    )?;
+    workspace_ref.set_target(workspace_commit_id, "restore stack from snapshot")?;
+    Ok(workspace_tree_id.to_gix())
+}
+
+/// Return the paths of all files that differ between the trees `lhs` and `rhs`, including the source of renames.
+fn changed_paths(
+    repo: &gix::Repository,
+    lhs: gix::ObjectId,
+    rhs: gix::ObjectId,
+) -> Result<BTreeSet<BString>> {
+    let (changes, _) = but_core::diff::tree_changes(repo, Some(lhs), rhs)?;
+    let mut paths = BTreeSet::new();
+    for change in changes {
+        if let Some(previous_path) = change.previous_path() {
+            paths.insert(previous_path.to_owned());
+        }
+        paths.insert(change.path);
+    }
+    Ok(paths)
+}
+
+/// Write the file at `rela_path` in `tree_id` to the worktree, or delete it if it doesn't exist in the tree.
+fn checkout_path(
+    repo: &gix::Repository,
+    tree_id: gix::ObjectId,
+    worktree_dir: &Path,
+    rela_path: &BStr,
+) -> Result<()> {
+    let rela_path = gix::path::from_bstr(rela_path);
+    let path = worktree_dir.join(&rela_path);
+    let entry = repo.find_tree(tree_id)?.lookup_entry_by_path(&rela_path)?;
+    match entry {
+        Some(entry)
+            if matches!(
+                entry.mode().kind(),
+                EntryKind::Blob | EntryKind::BlobExecutable
+            ) =>
+        {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, &entry.object()?.data)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = if entry.mode().kind() == EntryKind::BlobExecutable {
+                    0o755
+                } else {
+                    0o644
+                };
+                fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+            }
+        }
+        Some(entry) => {
+            tracing::warn!(
+                "Not restoring {} as it is a {:?}, which isn't supported",
+                rela_path.display(),
+                entry.mode().kind()
+            );
+        }
+        None => {
+            if path.is_file() {
+                fs::remove_file(&path)?;
+            }
+        }
+    }
+    Ok(())
+}
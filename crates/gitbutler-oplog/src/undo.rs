@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use gitbutler_command_context::CommandContext;
+use gitbutler_project::access::WorktreeWritePermission;
+use serde::Serialize;
+
+use crate::{
+    entry::{OperationKind, Snapshot, SnapshotDetails},
+    OplogExt,
+};
+
+/// A node in the [`UndoTree`], representing the state of the workspace right after the operation of a snapshot.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoNode {
+    /// The id of the snapshot taken right before the operation. Restoring it undoes the operation.
+    #[serde(rename = "id", with = "gitbutler_serde::oid")]
+    pub snapshot_id: git2::Oid,
+    /// Snapshot details as persisted in the commit message, or `None` if the details couldn't be parsed.
+    pub details: Option<SnapshotDetails>,
+    /// The node whose state the operation was performed on, or `None` if it was performed on the oldest known state.
+    #[serde(with = "gitbutler_serde::oid_opt")]
+    pub parent: Option<git2::Oid>,
+    /// The snapshots created when the operation was undone, by restoring the state right before it, oldest first.
+    #[serde(with = "gitbutler_serde::oid_vec")]
+    pub undone_by: Vec<git2::Oid>,
+    /// The most recent snapshot holding the state right after the operation, which is restored to redo it.
+    #[serde(with = "gitbutler_serde::oid_opt")]
+    pub redo_snapshot_id: Option<git2::Oid>,
+}
+
+/// The branching history of operations, as recorded in the oplog.
+///
+/// Each operation moves the workspace from the state of its parent node to a new node. Undoing it moves back to
+/// the parent, and performing another operation after that starts a new branch, while the undone operation
+/// remains available for [redo](OplogExt::redo).
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoTree {
+    /// All nodes, with the oldest one first.
+    pub nodes: Vec<UndoNode>,
+    /// The node representing the current state, or `None` if all known operations were undone.
+    #[serde(with = "gitbutler_serde::oid_opt")]
+    pub current: Option<git2::Oid>,
+    /// The nodes that can be redone from `current`, in the order in which they would be redone.
+    /// At each step, the most recent child is chosen.
+    #[serde(with = "gitbutler_serde::oid_vec")]
+    pub redo_stack: Vec<git2::Oid>,
+}
+
+impl UndoTree {
+    /// Return the node for the snapshot with `snapshot_id`, if there is one.
+    pub fn node(&self, snapshot_id: git2::Oid) -> Option<&UndoNode> {
+        self.nodes.iter().find(|n| n.snapshot_id == snapshot_id)
+    }
+}
+
+pub(crate) fn undo_tree(ctx: &CommandContext) -> Result<UndoTree> {
+    let mut snapshots = ctx.list_snapshots(usize::MAX, None, Vec::new())?;
+    snapshots.reverse();
+
+    let mut nodes: Vec<UndoNode> = Vec::new();
+    // The node that was current at the time each snapshot was created, which is the state it holds.
+    let mut current_at_snapshot = HashMap::<git2::Oid, Option<git2::Oid>>::new();
+    let mut current = None;
+    for snapshot in snapshots {
+        current_at_snapshot.insert(snapshot.commit_id, current);
+        if let Some(node) = current.and_then(|id| nodes.iter_mut().find(|n| n.snapshot_id == id)) {
+            node.redo_snapshot_id = Some(snapshot.commit_id);
+        }
+        match restored_from(&snapshot) {
+            Some(restored_id) => {
+                if let Some(node) = nodes.iter_mut().find(|n| n.snapshot_id == restored_id) {
+                    node.undone_by.push(snapshot.commit_id);
+                }
+                current = current_at_snapshot.get(&restored_id).copied().flatten();
+            }
+            None => {
+                nodes.push(UndoNode {
+                    snapshot_id: snapshot.commit_id,
+                    details: snapshot.details,
+                    parent: current,
+                    undone_by: Vec::new(),
+                    redo_snapshot_id: None,
+                });
+                current = Some(snapshot.commit_id);
+            }
+        }
+    }
+
+    let mut redo_stack = Vec::new();
+    let mut cursor = current;
+    while let Some(child) = nodes
+        .iter()
+        .rev()
+        .find(|n| n.parent == cursor && n.redo_snapshot_id.is_some())
+    {
+        redo_stack.push(child.snapshot_id);
+        cursor = Some(child.snapshot_id);
+    }
+
+    Ok(UndoTree {
+        nodes,
+        current,
+        redo_stack,
+    })
+}
+
+pub(crate) fn undo(
+    ctx: &CommandContext,
+    perm: &mut WorktreeWritePermission,
+) -> Result<Option<UndoNode>> {
+    let tree = undo_tree(ctx)?;
+    let Some(node) = tree.current.and_then(|id| tree.node(id)) else {
+        return Ok(None);
+    };
+    ctx.restore_snapshot(node.snapshot_id, perm)?;
+    Ok(Some(node.clone()))
+}
+
+pub(crate) fn redo(
+    ctx: &CommandContext,
+    perm: &mut WorktreeWritePermission,
+) -> Result<Option<UndoNode>> {
+    let tree = undo_tree(ctx)?;
+    let Some(node) = tree.redo_stack.first().and_then(|id| tree.node(*id)) else {
+        return Ok(None);
+    };
+    let redo_snapshot_id = node
+        .redo_snapshot_id
+        .context("BUG: only nodes with a redo snapshot are on the redo stack")?;
+    ctx.restore_snapshot(redo_snapshot_id, perm)?;
+    Ok(Some(node.clone()))
+}
+
+/// Return the id of the snapshot that `snapshot` restored, if it is the result of a restore.
+fn restored_from(snapshot: &Snapshot) -> Option<git2::Oid> {
+    snapshot
+        .details
+        .as_ref()
+        .filter(|details| details.operation == OperationKind::RestoreFromSnapshot)?
+        .trailers
+        .iter()
+        .find(|t| t.key == "restored_from")?
+        .value
+        .parse()
+        .ok()
+}
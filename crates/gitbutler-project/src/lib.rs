@@ -8,7 +8,8 @@ use std::path::Path;
 
 use controller::Controller;
 pub use project::{
-    AddProjectOutcome, ApiProject, AuthKey, CodePushState, FetchResult, Project, ProjectId,
+    AddProjectOutcome, ApiProject, AuthKey, CodePushState, FetchResult, LlmProviderSettings,
+    Project, ProjectId,
 };
 pub use storage::UpdateRequest;
 
@@ -22,6 +22,37 @@ pub enum AuthKey {
     SystemExecutable,
 }
 
+/// The LLM that backs the AI features of a project.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum LlmProviderSettings {
+    /// OpenAI, through GitButler or with the user's own key.
+    #[default]
+    OpenAi,
+    /// A server with an OpenAI-compatible API, like vLLM, the llama.cpp server or Ollama.
+    #[serde(rename_all = "camelCase")]
+    OpenAiCompatible {
+        /// The URL the API paths are relative to, like `http://localhost:11434/v1`.
+        base_url: String,
+        /// The name of the model to use.
+        model: String,
+        /// The name of the environment variable that holds the API key, if the server needs one.
+        #[serde(default)]
+        api_key_env: Option<String>,
+    },
+    /// Anthropic's Messages API, with the user's own key or `ANTHROPIC_API_KEY`.
+    Anthropic {
+        /// The name of the model to use instead of the default one.
+        #[serde(default)]
+        model: Option<String>,
+    },
+    /// Replies with the given responses in order and never calls out, for tests.
+    Mock {
+        #[serde(default)]
+        responses: Vec<serde_json::Value>,
+    },
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ApiProject {
     pub name: String,
@@ -103,6 +134,9 @@ pub struct Project {
     pub snapshot_lines_threshold: Option<usize>,
     #[serde(default)]
     pub forge_override: Option<String>,
+    /// The LLM to use for AI features, or OpenAI if unset.
+    #[serde(default)]
+    pub llm_provider: Option<LlmProviderSettings>,
 }
 
 /// Instantiation
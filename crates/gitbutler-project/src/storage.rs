@@ -3,7 +3,9 @@ use std::path::PathBuf;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::{ApiProject, AuthKey, CodePushState, FetchResult, Project, ProjectId};
+use crate::{
+    ApiProject, AuthKey, CodePushState, FetchResult, LlmProviderSettings, Project, ProjectId,
+};
 
 const PROJECTS_FILE: &str = "projects.json";
 
@@ -33,6 +35,9 @@ pub struct UpdateRequest {
     pub forge_override: Option<String>,
     #[serde(default = "default_false")]
     pub unset_forge_override: bool,
+    pub llm_provider: Option<LlmProviderSettings>,
+    #[serde(default = "default_false")]
+    pub unset_llm_provider: bool,
 }
 
 fn default_false() -> bool {
@@ -115,6 +120,14 @@ impl Storage {
             project.forge_override = None;
         }
 
+        if let Some(llm_provider) = &update_request.llm_provider {
+            project.llm_provider = Some(llm_provider.clone());
+        }
+
+        if update_request.unset_llm_provider {
+            project.llm_provider = None;
+        }
+
         if let Some(preferred_key) = &update_request.preferred_key {
             project.preferred_key = preferred_key.clone();
         }
@@ -113,6 +113,7 @@ pub fn sign_commit_sync(
 
     // Run as sync
     let handle = std::thread::spawn(move || {
+        let credentials = crate::credentials::providers(&repo_path);
         tokio::runtime::Builder::new_multi_thread()
             .enable_all()
             .build()
@@ -121,6 +122,7 @@ pub fn sign_commit_sync(
                 &repo_path,
                 gitbutler_git::tokio::TokioExecutor,
                 base_commitish,
+                &credentials,
                 handle_git_prompt_commit_sign_sync,
                 branch_id,
             ))
@@ -0,0 +1,58 @@
+//! The credential providers used when fetching, pushing and signing with the Git executable.
+//!
+//! They are the same for the desktop app, `but` and `but-server`, so all of them can authenticate
+//! without a user who answers prompts, and only the desktop app asks the user if that fails.
+
+use std::path::Path;
+
+use anyhow::Result;
+use gitbutler_git::credentials::{CredentialProvider, CredentialProviders, Prompt};
+use gitbutler_secret::{secret, Sensitive};
+
+/// The username that is used along with stored tokens, which hosts like GitHub and GitLab accept for access tokens.
+const TOKEN_USERNAME: &str = "x-access-token";
+
+/// Return all providers to use for operations in the repository at `repo_path`, in the order they are asked in.
+pub fn providers(repo_path: &Path) -> CredentialProviders {
+    CredentialProviders::from_env(repo_path).with(TokenStoreProvider)
+}
+
+/// Store `token` as the access token for HTTP(S) remotes on `host`, or delete it if it is empty.
+pub fn store_token(host: &str, token: Sensitive<String>) -> Result<()> {
+    secret::persist(&token_handle(host), &token, secret::Namespace::Global)
+}
+
+fn token_handle(host: &str) -> String {
+    format!("git-token-{host}")
+}
+
+/// Access tokens stored per host with [`store_token()`], using the secret store of `gitbutler-secret`.
+pub struct TokenStoreProvider;
+
+impl TokenStoreProvider {
+    fn token(url: &str) -> Option<Sensitive<String>> {
+        let url = gix::url::parse(url.into()).ok()?;
+        let host = url.host()?;
+        match secret::retrieve(&token_handle(host), secret::Namespace::Global) {
+            Ok(token) => token,
+            Err(err) => {
+                tracing::warn!(?err, host, "could not read token from the secret store");
+                None
+            }
+        }
+    }
+}
+
+impl CredentialProvider for TokenStoreProvider {
+    fn name(&self) -> &'static str {
+        "token-store"
+    }
+
+    fn answer(&self, prompt: &Prompt) -> Option<String> {
+        match prompt {
+            Prompt::Username { url } => Self::token(url).map(|_| TOKEN_USERNAME.to_owned()),
+            Prompt::Password { url } => Self::token(url).map(|token| token.0),
+            Prompt::Passphrase { .. } | Prompt::Other(_) => None,
+        }
+    }
+}
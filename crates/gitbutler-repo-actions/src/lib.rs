@@ -1,4 +1,6 @@
 pub mod askpass;
+pub mod credentials;
+pub mod progress;
 
 mod repository;
 pub use repository::RepoActionsExt;
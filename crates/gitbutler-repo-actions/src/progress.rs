@@ -0,0 +1,152 @@
+//! Progress of the fetches and pushes that are currently running, and a way to cancel them.
+//!
+//! Every fetch or push is an [`Operation`] with an ID that is part of the [`ProgressEvent`]s
+//! passed to the sink installed with [`init()`], so whoever shows the progress can also [`cancel()`] it.
+//! The first event of each operation is sent as soon as it starts, so it can be cancelled even if it never
+//! gets to report any progress, like a push that hangs while connecting.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use gitbutler_git::{Progress, Stage};
+use gitbutler_id::id::Id;
+use gitbutler_project::ProjectId;
+use serde::Serialize;
+
+/// Report progress at most this often within a stage, as the in-process transport reports every single object.
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+static SINK: OnceLock<Box<dyn Fn(ProgressEvent) + Send + Sync>> = OnceLock::new();
+
+/// The flags to set to interrupt the operations that are currently running.
+static RUNNING: Mutex<BTreeMap<Id<Operation>, Arc<AtomicBool>>> = Mutex::new(BTreeMap::new());
+
+/// Pass the progress of all operations to `on_progress` from now on.
+///
+/// Only the first call has an effect. Without it, progress is only logged.
+pub fn init(on_progress: impl Fn(ProgressEvent) + Send + Sync + 'static) {
+    if SINK.set(Box::new(on_progress)).is_err() {
+        tracing::warn!("progress sink was already initialized");
+    }
+}
+
+/// Cancel all operations that are currently running, and return how many there were.
+pub fn cancel_all() -> usize {
+    let running = RUNNING.lock().expect("poisoned");
+    for should_interrupt in running.values() {
+        should_interrupt.store(true, Ordering::Relaxed);
+    }
+    running.len()
+}
+
+/// Cancel the operation with `id`, and return `true` if it was still running.
+pub fn cancel(id: Id<Operation>) -> bool {
+    let running = RUNNING.lock().expect("poisoned");
+    match running.get(&id) {
+        Some(should_interrupt) => {
+            should_interrupt.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// What an [`Operation`] does.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum OperationKind {
+    Fetch { remote: String },
+    Push { remote: String, branch: String },
+}
+
+/// The progress of an [`Operation`], as passed to the sink installed with [`init()`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressEvent {
+    pub operation_id: Id<Operation>,
+    pub project_id: ProjectId,
+    pub kind: OperationKind,
+    /// The progress so far, or `None` if the operation just started.
+    pub progress: Option<Progress>,
+}
+
+/// A fetch or push that is running, which is cancellable until it is dropped.
+pub struct Operation {
+    id: Id<Operation>,
+    project_id: ProjectId,
+    kind: OperationKind,
+    should_interrupt: Arc<AtomicBool>,
+    last_report: Mutex<Option<(Stage, Instant)>>,
+}
+
+impl Operation {
+    /// Start an operation of `kind` in the project with `project_id`, and send its first event without progress.
+    pub fn start(project_id: ProjectId, kind: OperationKind) -> Self {
+        let id = Id::generate();
+        let should_interrupt = Arc::new(AtomicBool::new(false));
+        RUNNING
+            .lock()
+            .expect("poisoned")
+            .insert(id, should_interrupt.clone());
+        let operation = Operation {
+            id,
+            project_id,
+            kind,
+            should_interrupt,
+            last_report: Mutex::new(None),
+        };
+        operation.send(None);
+        operation
+    }
+
+    /// The ID to [`cancel()`] the operation with.
+    pub fn id(&self) -> Id<Operation> {
+        self.id
+    }
+
+    /// The flag that is set once the operation is cancelled.
+    pub fn should_interrupt(&self) -> &AtomicBool {
+        &self.should_interrupt
+    }
+
+    /// Report `progress`, unless progress of the same stage was reported just now.
+    pub fn report(&self, progress: Progress) {
+        let now = Instant::now();
+        {
+            let mut last_report = self.last_report.lock().expect("poisoned");
+            let is_final = progress.total == Some(progress.done);
+            if let Some((stage, at)) = *last_report {
+                if stage == progress.stage && !is_final && now - at < REPORT_INTERVAL {
+                    return;
+                }
+            }
+            *last_report = Some((progress.stage, now));
+        }
+
+        self.send(Some(progress));
+    }
+
+    fn send(&self, progress: Option<Progress>) {
+        tracing::trace!(operation_id = %self.id, kind = ?self.kind, ?progress, "git progress");
+        if let Some(sink) = SINK.get() {
+            sink(ProgressEvent {
+                operation_id: self.id,
+                project_id: self.project_id,
+                kind: self.kind.clone(),
+                progress,
+            });
+        }
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        RUNNING.lock().expect("poisoned").remove(&self.id);
+    }
+}
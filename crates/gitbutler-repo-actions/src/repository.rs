@@ -9,9 +9,11 @@ use gitbutler_project::AuthKey;
 use gitbutler_reference::{Refname, RemoteRefname};
 use gitbutler_stack::{Stack, StackId};
 
-use crate::askpass;
+use crate::{
+    askpass, credentials,
+    progress::{Operation, OperationKind},
+};
 use gitbutler_repo::{
-    credentials,
     logging::{LogUntil, RepositoryExt as _},
     RepositoryExt,
 };
@@ -164,6 +166,31 @@ impl RepoActionsExt for CommandContext {
         refspec: Option<String>,
         askpass_broker: Option<Option<StackId>>,
     ) -> Result<()> {
+        let path = self.project().worktree_path();
+        let operation = Operation::start(
+            self.project().id,
+            OperationKind::Push {
+                remote: branch.remote().to_owned(),
+                branch: branch.branch().to_owned(),
+            },
+        );
+        // Repositories on this machine are pushed to in-process, which doesn't need credentials,
+        // unless they run hooks or have settings only Git can honour when receiving the push.
+        if gitbutler_git::local::can_push(&path, branch.remote()) {
+            let refspec =
+                refspec.unwrap_or_else(|| format!("{}:refs/heads/{}", head, branch.branch()));
+            return gitbutler_git::local::push(
+                &path,
+                branch.remote(),
+                gitbutler_git::RefSpec::parse(refspec)?,
+                with_force,
+                force_push_protection,
+                |progress| operation.report(progress),
+                operation.should_interrupt(),
+            )
+            .map_err(map_push_error);
+        }
+
         let use_git_executable = self.project().preferred_key == AuthKey::SystemExecutable;
         if !use_git_executable && force_push_protection {
             bail!("Force push protection is only supported when 'Using the Git executable'");
@@ -186,9 +213,9 @@ impl RepoActionsExt for CommandContext {
         // NOTE(qix-): work around a time-sensitive change that was necessary
         // NOTE(qix-): without having to refactor a large portion of the codebase.
         if use_git_executable {
-            let path = self.project().worktree_path();
             let remote = branch.remote().to_string();
             std::thread::spawn(move || {
+                let credentials = credentials::providers(&path);
                 tokio::runtime::Runtime::new()
                     .unwrap()
                     .block_on(gitbutler_git::push(
@@ -198,23 +225,18 @@ impl RepoActionsExt for CommandContext {
                         gitbutler_git::RefSpec::parse(refspec).unwrap(),
                         with_force,
                         force_push_protection,
+                        &credentials,
+                        |progress| operation.report(progress),
+                        operation.should_interrupt(),
                         handle_git_prompt_push,
                         askpass_broker,
                     ))
             })
             .join()
             .unwrap()
-            .map_err(|err| {
-                match err {
-                    gitbutler_git::Error::ForcePushProtection(_) => {
-                        anyhow!("The force push was blocked because the remote branch contains commits that would be overwritten")
-                            .context(Code::GitForcePushProtection)
-                    },
-                    _ => err.into()
-                }
-            })
+            .map_err(map_push_error)
         } else {
-            let auth_flows = credentials::help(self, branch.remote())?;
+            let auth_flows = gitbutler_repo::credentials::help(self, branch.remote())?;
             for (mut remote, callbacks) in auth_flows {
                 let mut update_refs_error: Option<git2::Error> = None;
                 for callback in callbacks {
@@ -276,15 +298,36 @@ impl RepoActionsExt for CommandContext {
     fn fetch(&self, remote_name: &str, askpass: Option<String>) -> Result<()> {
         let refspec = format!("+refs/heads/*:refs/remotes/{remote_name}/*");
 
+        let path = self.project().worktree_path();
+        let operation = Operation::start(
+            self.project().id,
+            OperationKind::Fetch {
+                remote: remote_name.to_owned(),
+            },
+        );
+        // Repositories on this machine are fetched from in-process, which doesn't need credentials.
+        if gitbutler_git::local::remote_path(&path, remote_name, gix::remote::Direction::Fetch)
+            .is_some()
+        {
+            return gitbutler_git::local::fetch(
+                &path,
+                remote_name,
+                gitbutler_git::RefSpec::parse(refspec)?,
+                |progress| operation.report(progress),
+                operation.should_interrupt(),
+            )
+            .map_err(Into::into);
+        }
+
         // NOTE(qix-): This is a nasty hack, however the codebase isn't structured
         // NOTE(qix-): in a way that allows us to really incorporate new backends
         // NOTE(qix-): without a lot of work. This is a temporary measure to
         // NOTE(qix-): work around a time-sensitive change that was necessary
         // NOTE(qix-): without having to refactor a large portion of the codebase.
         if self.project().preferred_key == AuthKey::SystemExecutable {
-            let path = self.project().worktree_path();
             let remote = remote_name.to_string();
             return std::thread::spawn(move || {
+                let credentials = credentials::providers(&path);
                 tokio::runtime::Runtime::new()
                     .unwrap()
                     .block_on(gitbutler_git::fetch(
@@ -292,6 +335,9 @@ impl RepoActionsExt for CommandContext {
                         gitbutler_git::tokio::TokioExecutor,
                         &remote,
                         gitbutler_git::RefSpec::parse(refspec).unwrap(),
+                        &credentials,
+                        |progress| operation.report(progress),
+                        operation.should_interrupt(),
                         handle_git_prompt_fetch,
                         askpass,
                     ))
@@ -301,7 +347,7 @@ impl RepoActionsExt for CommandContext {
             .map_err(Into::into);
         }
 
-        let auth_flows = credentials::help(self, remote_name)?;
+        let auth_flows = gitbutler_repo::credentials::help(self, remote_name)?;
         for (mut remote, callbacks) in auth_flows {
             for callback in callbacks {
                 let mut fetch_opts = git2::FetchOptions::new();
@@ -340,6 +386,19 @@ impl RepoActionsExt for CommandContext {
     }
 }
 
+fn map_push_error<E>(err: gitbutler_git::Error<E>) -> anyhow::Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    match err {
+        gitbutler_git::Error::ForcePushProtection(_) => {
+            anyhow!("The force push was blocked because the remote branch contains commits that would be overwritten")
+                .context(Code::GitForcePushProtection)
+        }
+        _ => err.into(),
+    }
+}
+
 async fn handle_git_prompt_push(
     prompt: String,
     askpass: Option<Option<StackId>>,
@@ -20,11 +20,18 @@ pub struct ErrorData {
     pub error: String,
 }
 
+#[derive(Serialize, PartialEq, Debug, Clone, Default)]
+pub struct SuccessData {
+    /// What the hook printed, if it is known and it printed anything.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
 /// Hook result indicating either success or failure.
 #[derive(Serialize, PartialEq, Debug, Clone)]
 #[serde(tag = "status", rename_all = "lowercase")]
 pub enum HookResult {
-    Success,
+    Success(SuccessData),
     NotConfigured,
     Failure(ErrorData),
 }
@@ -80,7 +87,7 @@ pub fn pre_commit(
     staging::stage(ctx, selected_hunks)?;
     Ok(
         match git2_hooks::hooks_pre_commit(ctx.repo(), Some(&["../.husky"]))? {
-            H::Ok { hook: _ } => HookResult::Success,
+            H::Ok { hook: _ } => HookResult::Success(SuccessData::default()),
             H::NoHookFound => HookResult::NotConfigured,
             H::RunNotSuccessful {
                 stdout,
@@ -113,7 +120,7 @@ pub fn pre_commit_with_tree(ctx: &CommandContext, tree_id: git2::Oid) -> Result<
 
     Ok(
         match git2_hooks::hooks_pre_commit(ctx.repo(), Some(&["../.husky"]))? {
-            H::Ok { hook: _ } => HookResult::Success,
+            H::Ok { hook: _ } => HookResult::Success(SuccessData::default()),
             H::NoHookFound => HookResult::NotConfigured,
             H::RunNotSuccessful {
                 stdout,
@@ -130,7 +137,7 @@ pub fn pre_commit_with_tree(ctx: &CommandContext, tree_id: git2::Oid) -> Result<
 
 pub fn post_commit(ctx: &CommandContext) -> Result<HookResult> {
     match git2_hooks::hooks_post_commit(ctx.repo(), Some(&["../.husky"]))? {
-        H::Ok { hook: _ } => Ok(HookResult::Success),
+        H::Ok { hook: _ } => Ok(HookResult::Success(SuccessData::default())),
         H::NoHookFound => Ok(HookResult::NotConfigured),
         H::RunNotSuccessful {
             stdout,
@@ -144,17 +151,64 @@ pub fn post_commit(ctx: &CommandContext) -> Result<HookResult> {
     }
 }
 
-// TODO: double-check this with what should happen according to Git; contribute to `git2-hooks` possibly.
-/// Since git2-hooks doesn't support pre-push yet, we implement it ourselves
-/// following the same pattern as the existing hooks
-/// Use `oid` and `remote_tracking_branch` to deduce the refspec information. Note that this isn't general, but should
-/// work for us.
+/// A single ref update of a push, as passed to the `pre-push` hook on its standard input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PushRefUpdate {
+    /// The full name of the local ref that is pushed, like `refs/heads/feature`.
+    pub local_ref: String,
+    /// The commit that is pushed.
+    pub local_oid: git2::Oid,
+    /// The full name of the ref on the remote that is updated, like `refs/heads/feature`.
+    pub remote_ref: String,
+    /// The commit the remote ref currently points to, as far as we know, or the null id if it doesn't exist yet.
+    pub remote_oid: git2::Oid,
+}
+
+impl PushRefUpdate {
+    /// Describe pushing `local_commit`, the head of the local branch named `local_branch`, to the branch
+    /// tracked by `remote_tracking_branch`.
+    ///
+    /// The remote commit is read from `remote_tracking_branch` in `repo`, which is what we last fetched.
+    pub fn new(
+        repo: &git2::Repository,
+        local_branch: &str,
+        local_commit: git2::Oid,
+        remote_tracking_branch: &gitbutler_reference::RemoteRefname,
+    ) -> Self {
+        let remote_oid = repo
+            .find_reference(&remote_tracking_branch.to_string())
+            .ok()
+            .and_then(|r| r.target())
+            .unwrap_or_else(git2::Oid::zero);
+        PushRefUpdate {
+            local_ref: format!("refs/heads/{local_branch}"),
+            local_oid: local_commit,
+            remote_ref: format!("refs/heads/{}", remote_tracking_branch.branch()),
+            remote_oid,
+        }
+    }
+}
+
+impl std::fmt::Display for PushRefUpdate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.local_ref, self.local_oid, self.remote_ref, self.remote_oid
+        )
+    }
+}
+
+/// Run the `pre-push` hook before pushing `updates` to the remote named `remote_name` at `remote_url`.
+///
+/// Like Git, the hook is called once with the remote name and URL as arguments, and one line per ref update on
+/// standard input. Since git2-hooks doesn't support pre-push yet, we implement it ourselves, looking for the hook
+/// in the same places as the other hooks.
 pub fn pre_push(
     repo: &git2::Repository,
     remote_name: &str,
     remote_url: &str,
-    local_commit: git2::Oid,
-    remote_tracking_branch: &gitbutler_reference::RemoteRefname,
+    updates: &[PushRefUpdate],
 ) -> Result<HookResult> {
     let hooks_path = repo.path().join("hooks").join("pre-push");
     let husky_path = repo
@@ -189,34 +243,35 @@ pub fn pre_push(
     })
     .current_dir(repo.workdir().unwrap_or_else(|| repo.path()))
     .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
     .spawn()?;
 
     {
-        let remote_commit = repo
-            .find_reference(&remote_tracking_branch.to_string())
-            .ok()
-            .and_then(|r| r.target())
-            .unwrap_or_else(git2::Oid::zero);
-        // THIS IS WRONG: but is correct the common case. This also is an issue when the ref is actually pushed,
-        // but we can fix it when moving everything to `gix`.
-        let local_tracking_branch_deduced =
-            format!("refs/heads/{}", remote_tracking_branch.branch());
-        let stdin = child.stdin.as_mut().expect("configured");
-        stdin.write_all(
-            format!("{local_tracking_branch_deduced} {local_commit} {remote_tracking_branch} {remote_commit}\n")
-                .as_bytes(),
-        )?;
+        let mut stdin = child.stdin.take().expect("configured");
+        let input: String = updates.iter().map(|update| format!("{update}\n")).collect();
+        // The hook may exit without reading its input, which is fine.
+        if let Err(err) = stdin.write_all(input.as_bytes()) {
+            if err.kind() != std::io::ErrorKind::BrokenPipe {
+                return Err(err.into());
+            }
+        }
     }
 
     let output = child.wait_with_output()?;
+    let stdout = output.stdout.to_str_lossy().into_owned();
+    let stderr = output.stderr.to_str_lossy().into_owned();
     if output.status.success() {
-        Ok(HookResult::Success)
+        let output = [stdout, stderr]
+            .into_iter()
+            .filter(|out| !out.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(HookResult::Success(SuccessData {
+            output: (!output.is_empty()).then_some(output),
+        }))
     } else {
-        let error = join_output(
-            output.stdout.to_str_lossy().into_owned(),
-            output.stderr.to_str_lossy().into_owned(),
-            output.status.code(),
-        );
+        let error = join_output(stdout, stderr, output.status.code());
         Ok(HookResult::Failure(ErrorData { error }))
     }
 }
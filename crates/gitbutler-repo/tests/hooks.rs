@@ -1,4 +1,4 @@
-use gitbutler_repo::hooks::{pre_push, HookResult};
+use gitbutler_repo::hooks::{pre_push, HookResult, PushRefUpdate, SuccessData};
 use gitbutler_testsupport::TestProject;
 use std::fs;
 #[cfg(unix)]
@@ -12,8 +12,12 @@ fn pre_push_hook_not_configured() -> anyhow::Result<()> {
         &test_project.local_repo,
         "origin",
         "https://github.com/test/repo.git",
-        git2::Oid::zero(),
-        &gitbutler_reference::RemoteRefname::new("origin", "does-not-matter"),
+        &[PushRefUpdate::new(
+            &test_project.local_repo,
+            "does-not-matter",
+            git2::Oid::zero(),
+            &gitbutler_reference::RemoteRefname::new("origin", "does-not-matter"),
+        )],
     );
     assert!(result.is_ok());
     assert_eq!(result?, HookResult::NotConfigured);
@@ -29,23 +33,46 @@ fn pre_push_hook_success() -> anyhow::Result<()> {
     fs::create_dir_all(&hooks_dir)?;
     let hook_path = hooks_dir.join("pre-push");
 
-    fs::write(&hook_path, "#!/bin/sh\ncat >hook.input\n")?;
+    fs::write(
+        &hook_path,
+        "#!/bin/sh\ncat >hook.input\necho all checks passed\n",
+    )?;
 
     #[cfg(unix)]
     fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))?;
 
+    let head = repo.head()?.target().expect("not detached");
     let result = pre_push(
         repo,
         "origin",
         "https://github.com/test/repo.git",
-        repo.head()?.target().expect("not detached"),
-        &gitbutler_reference::RemoteRefname::new("origin", "master"),
+        &[
+            PushRefUpdate::new(
+                repo,
+                "master",
+                head,
+                &gitbutler_reference::RemoteRefname::new("origin", "master"),
+            ),
+            PushRefUpdate::new(
+                repo,
+                "local-name",
+                head,
+                &gitbutler_reference::RemoteRefname::new("origin", "remote-name"),
+            ),
+        ],
     )?;
-    assert_eq!(result, HookResult::Success);
+    assert_eq!(
+        result,
+        HookResult::Success(SuccessData {
+            output: Some("all checks passed\n".into())
+        }),
+        "the output of successful hooks is kept"
+    );
 
     let input = std::fs::read_to_string(repo.workdir().expect("non-bare").join("hook.input"))
         .expect("test-hook to pipe its output");
-    let expected_pattern = "refs/heads/master ???????????????????????????????????????? refs/remotes/origin/master ????????????????????????????????????????\n";
+    let expected_pattern = "refs/heads/master ???????????????????????????????????????? refs/heads/master ????????????????????????????????????????\n\
+                            refs/heads/local-name ???????????????????????????????????????? refs/heads/remote-name 0000000000000000000000000000000000000000\n";
     let is_required_format = gix::glob::wildmatch(
         expected_pattern.into(),
         input.as_str().into(),
@@ -76,8 +103,12 @@ fn pre_push_hook_failure() -> anyhow::Result<()> {
         repo,
         "origin",
         "https://github.com/test/repo.git",
-        repo.head()?.target().expect("not detached"),
-        &gitbutler_reference::RemoteRefname::new("origin", "master"),
+        &[PushRefUpdate::new(
+            repo,
+            "master",
+            repo.head()?.target().expect("not detached"),
+            &gitbutler_reference::RemoteRefname::new("origin", "master"),
+        )],
     );
     match result.expect("success") {
         HookResult::Failure(error_data) => {
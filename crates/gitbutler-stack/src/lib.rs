@@ -13,7 +13,7 @@ pub use target::Target;
 
 mod heads;
 pub use heads::add_head;
-pub use stack::{PatchReferenceUpdate, TargetUpdate};
+pub use stack::{PatchReferenceUpdate, PushDetails, TargetUpdate};
 
 // This is here because CommitOrChangeId::ChangeId is deprecated, for some reason allow cant be done on the CommitOrChangeId struct
 #[expect(deprecated)]
@@ -1,4 +1,3 @@
-use but_action::OpenAiProvider;
 use but_api::error::Error;
 use but_core::ui::TreeChange;
 use but_settings::AppSettings;
@@ -63,7 +62,10 @@ pub fn auto_commit(
     let changes: Vec<but_core::TreeChange> =
         changes.into_iter().map(|change| change.into()).collect();
     let ctx = &mut CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
-    let openai = OpenAiProvider::with(Some(but_action::CredentialsKind::GitButlerProxied));
+    let llm = but_action::llm::for_project(
+        &project,
+        Some(but_action::CredentialsKind::GitButlerProxied),
+    )?;
 
     let emitter = std::sync::Arc::new(move |name: &str, payload: serde_json::Value| {
         app_handle.emit(name, payload).unwrap_or_else(|e| {
@@ -71,14 +73,8 @@ pub fn auto_commit(
         });
     });
 
-    match openai {
-        Some(openai) => but_action::auto_commit(emitter, ctx, &openai, changes).map_err(|e| Error::from(anyhow::anyhow!(e))),
-        None => {
-            Err(Error::from(anyhow::anyhow!(
-                "No valid credentials found for AI provider. Please configure your GitButler account credentials."
-            )))
-        }
-    }
+    but_action::auto_commit(emitter, ctx, llm.as_ref(), changes)
+        .map_err(|e| Error::from(anyhow::anyhow!(e)))
 }
 
 #[tauri::command(async)]
@@ -92,7 +88,10 @@ pub fn auto_branch_changes(
     let changes: Vec<but_core::TreeChange> =
         changes.into_iter().map(|change| change.into()).collect();
     let ctx = &mut CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
-    let openai = OpenAiProvider::with(Some(but_action::CredentialsKind::GitButlerProxied));
+    let llm = but_action::llm::for_project(
+        &project,
+        Some(but_action::CredentialsKind::GitButlerProxied),
+    )?;
 
     let emitter = std::sync::Arc::new(move |name: &str, payload: serde_json::Value| {
         app_handle.emit(name, payload).unwrap_or_else(|e| {
@@ -100,14 +99,8 @@ pub fn auto_branch_changes(
         });
     });
 
-    match openai {
-        Some(openai) => but_action::branch_changes(emitter, ctx, &openai, changes).map_err(|e| Error::from(anyhow::anyhow!(e))),
-        None => {
-            Err(Error::from(anyhow::anyhow!(
-                "No valid credentials found for AI provider. Please configure your GitButler account credentials."
-            )))
-        }
-    }
+    but_action::branch_changes(emitter, ctx, llm.as_ref(), changes)
+        .map_err(|e| Error::from(anyhow::anyhow!(e)))
 }
 
 #[tauri::command(async)]
@@ -121,7 +114,10 @@ pub fn absorb(
     let changes: Vec<but_core::TreeChange> =
         changes.into_iter().map(|change| change.into()).collect();
     let ctx = &mut CommandContext::open(&project, AppSettings::load_from_default_path_creating()?)?;
-    let openai = OpenAiProvider::with(Some(but_action::CredentialsKind::GitButlerProxied));
+    let llm = but_action::llm::for_project(
+        &project,
+        Some(but_action::CredentialsKind::GitButlerProxied),
+    )?;
 
     let emitter = std::sync::Arc::new(move |name: &str, payload: serde_json::Value| {
         app_handle.emit(name, payload).unwrap_or_else(|e| {
@@ -129,14 +125,8 @@ pub fn absorb(
         });
     });
 
-    match openai {
-        Some(openai) => but_action::absorb(emitter, ctx, &openai, changes).map_err(|e| Error::from(anyhow::anyhow!(e))),
-        None => {
-            Err(Error::from(anyhow::anyhow!(
-                "No valid credentials found for AI provider. Please configure your GitButler account credentials."
-            )))
-        }
-    }
+    but_action::absorb(emitter, ctx, llm.as_ref(), changes)
+        .map_err(|e| Error::from(anyhow::anyhow!(e)))
 }
 
 #[tauri::command(async)]
@@ -157,13 +147,18 @@ pub fn freestyle(
         });
     });
 
-    let openai = OpenAiProvider::with(Some(but_action::CredentialsKind::GitButlerProxied));
-    match openai {
-        Some(openai) => but_action::freestyle(project_id, message_id, emitter, ctx, &openai, chat_messages, model).map_err(|e| Error::from(anyhow::anyhow!(e))),
-        None => {
-            Err(Error::from(anyhow::anyhow!(
-                "No valid credentials found for AI provider. Please configure your GitButler account credentials."
-            )))
-        }
-    }
+    let llm = but_action::llm::for_project(
+        &project,
+        Some(but_action::CredentialsKind::GitButlerProxied),
+    )?;
+    but_action::freestyle(
+        project_id,
+        message_id,
+        emitter,
+        ctx,
+        llm.as_ref(),
+        chat_messages,
+        model,
+    )
+    .map_err(|e| Error::from(anyhow::anyhow!(e)))
 }
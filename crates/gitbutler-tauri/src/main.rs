@@ -153,6 +153,8 @@ fn main() {
                             .register_sender(&uuid::Uuid::new_v4(), send)
                     });
 
+                    git::broadcast_git_progress(broadcaster.clone());
+
                     let window2 = window.clone();
                     std::thread::spawn(move || {
                         while let Some(message) = recv.blocking_recv() {
@@ -214,6 +216,8 @@ fn main() {
                     git::git_test_push,
                     git::git_test_fetch,
                     git::git_index_size,
+                    git::git_cancel_operation,
+                    git::git_store_token,
                     zip::commands::get_logs_archive_path,
                     zip::commands::get_project_archive_path,
                     zip::commands::get_anonymous_graph_path,
@@ -265,6 +269,7 @@ fn main() {
                     virtual_branches::normalize_branch_name,
                     virtual_branches::upstream_integration_statuses,
                     virtual_branches::integrate_upstream,
+                    virtual_branches::sync_workspace,
                     virtual_branches::resolve_upstream_integration,
                     virtual_branches::find_commit,
                     stack::create_reference,
@@ -279,7 +284,11 @@ fn main() {
                     secret::secret_set_global,
                     undo::list_snapshots,
                     undo::restore_snapshot,
+                    undo::restore_stack_snapshot,
                     undo::snapshot_diff,
+                    undo::undo_tree,
+                    undo::undo_operation,
+                    undo::redo_operation,
                     config::get_gb_config,
                     config::set_gb_config,
                     config::store_author_globally_if_unset,
@@ -320,6 +329,7 @@ fn main() {
                     rules::delete_workspace_rule,
                     rules::update_workspace_rule,
                     rules::list_workspace_rules,
+                    rules::dry_run_workspace_rules,
                     workspace::head_info,
                     workspace::stacks,
                     workspace::stack_details,
@@ -55,6 +55,14 @@ pub(crate) mod state {
                         payload: serde_json::json!(&changes),
                         project_id,
                     },
+                    Change::RulesFile { project_id, error } => ChangeForFrontend {
+                        name: format!("project://{project_id}/rule-updates"),
+                        payload: serde_json::json!({
+                            "kind": "rules",
+                            "error": error
+                        }),
+                        project_id,
+                    },
                 }
             }
         }
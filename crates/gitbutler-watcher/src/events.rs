@@ -16,4 +16,10 @@ pub enum Change {
         project_id: ProjectId,
         changes: but_hunk_assignment::WorktreeChanges,
     },
+    /// The workspace rules checked into the repository were changed.
+    RulesFile {
+        project_id: ProjectId,
+        /// Why the rules file couldn't be loaded, in which case its rules are ignored.
+        error: Option<String>,
+    },
 }
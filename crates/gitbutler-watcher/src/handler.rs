@@ -1,4 +1,7 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::Change;
 use anyhow::{Context, Result};
@@ -72,6 +75,19 @@ impl Handler {
 
     #[instrument(skip(self, paths, ctx), fields(paths = paths.len()))]
     fn project_files_change(&self, paths: Vec<PathBuf>, ctx: &mut CommandContext) -> Result<()> {
+        if paths
+            .iter()
+            .any(|path| path == Path::new(but_rules::file::RULES_FILE_PATH))
+        {
+            // Rules are read on each evaluation, so the changed rules apply to the worktree changes emitted below.
+            let error = but_rules::file::load(&ctx.project().path)
+                .err()
+                .map(|err| format!("{err:#}"));
+            let _ = self.emit_app_event(Change::RulesFile {
+                project_id: ctx.project().id,
+                error,
+            });
+        }
         let _ = self.emit_worktree_changes(ctx);
 
         Ok(())
@@ -138,9 +154,13 @@ impl Handler {
             match file_name {
                 "FETCH_HEAD" => {
                     self.emit_app_event(Change::GitFetch(ctx.project().id))?;
+                    self.process_rules_for_trigger(ctx, but_rules::Trigger::UpstreamChange);
                 }
                 "logs/HEAD" => {
                     self.emit_app_event(Change::GitActivity(ctx.project().id))?;
+                    if head_moved_by_commit(ctx.repo()) {
+                        self.process_rules_for_trigger(ctx, but_rules::Trigger::CommitCreated);
+                    }
                 }
                 "index" => {
                     let _ = self.emit_worktree_changes(ctx);
@@ -160,6 +180,20 @@ impl Handler {
         }
         Ok(())
     }
+
+    /// Evaluate the workspace rules with `trigger`, and let the frontend know if changes were reassigned.
+    fn process_rules_for_trigger(&self, ctx: &mut CommandContext, trigger: but_rules::Trigger) {
+        if !ctx.app_settings().feature_flags.rules {
+            return;
+        }
+        match but_rules::process_rules_for_trigger(ctx, trigger) {
+            Ok(update_count) if update_count > 0 => {
+                let _ = self.emit_worktree_changes(ctx);
+            }
+            Ok(_) => {}
+            Err(err) => tracing::warn!(?err, "failed to process workspace rules"),
+        }
+    }
 }
 
 fn assignments_and_errors(
@@ -184,3 +218,38 @@ fn assignments_and_errors(
         assignments_error.map(|err| serde_error::Error::new(&*err)),
     ))
 }
+
+/// Return `true` if `HEAD` of `repo` last moved because a commit was created on top of it or amended,
+/// which triggers rules with [`but_rules::Trigger::CommitCreated`].
+///
+/// This is decided by the kind of operation recorded in the newest entry of the reflog of `HEAD`,
+/// and whether the commit `HEAD` moved to relates to the previous one the way this kind of operation implies.
+fn head_moved_by_commit(repo: &git2::Repository) -> bool {
+    let Ok(reflog) = repo.reflog("HEAD") else {
+        return false;
+    };
+    let Some(entry) = reflog.get(0) else {
+        return false;
+    };
+    let (previous_id, id) = (entry.id_old(), entry.id_new());
+    if previous_id == id {
+        return false;
+    }
+    let Ok(commit) = repo.find_commit(id) else {
+        return false;
+    };
+    let kind = entry
+        .message()
+        .and_then(|msg| msg.split_once(':'))
+        .map(|(kind, _)| kind);
+    match kind {
+        Some("commit" | "commit (merge)" | "cherry-pick" | "revert") => {
+            commit.parent_id(0).ok() == Some(previous_id)
+        }
+        Some("commit (initial)") => previous_id.is_zero() && commit.parent_count() == 0,
+        Some("commit (amend)") => repo
+            .find_commit(previous_id)
+            .is_ok_and(|previous| previous.parent_ids().eq(commit.parent_ids())),
+        _ => false,
+    }
+}